@@ -0,0 +1,191 @@
+use std::fs::File;
+use std::io::{self, BufReader, Read, Seek};
+use std::path::{Component, Path, PathBuf};
+use std::sync::Mutex;
+use anyhow::{Context, Result};
+use zip::result::ZipError;
+use zip::ZipArchive;
+use crate::settings::GameSettings;
+
+/// Where the game's assets are found, so they load the same no matter what directory the game runs from
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct AssetPaths {
+    root: PathBuf,
+}
+
+impl AssetPaths {
+    /// The assets directory from the settings, or the first `assets` directory next to the executable,
+    /// in the working directory, or in the package `cargo run` started the game from
+    pub fn from_settings(settings: &GameSettings) -> Self {
+        let exe = std::env::current_exe()
+            .inspect_err(|err| tracing::warn!("unable to find the game's executable; {err}"))
+            .ok();
+        let exe_dir = exe.as_deref().and_then(Path::parent);
+        let working_dir = std::env::current_dir().ok();
+        // read when the game runs, not when it's built, so it's only there for `cargo run`
+        let package_dir = std::env::var_os("CARGO_MANIFEST_DIR").map(PathBuf::from);
+
+        let search = [exe_dir, working_dir.as_deref(), package_dir.as_deref()];
+        Self { root: root_from(settings.assets_dir.as_deref(), exe_dir, search.into_iter().flatten()) }
+    }
+
+    /// Where the asset at `path` is, relative paths are inside the assets directory and absolute ones stay as they are
+    pub fn resolve(&self, path: impl AsRef<Path>) -> PathBuf {
+        self.root.join(path)
+    }
+}
+
+/// Somewhere assets can be read out of, by their path inside the assets directory
+pub trait AssetSource: Send + Sync {
+    fn read(&self, path: &Path) -> Result<Vec<u8>>;
+}
+
+/// Reads the assets out of the assets directory
+impl AssetSource for AssetPaths {
+    fn read(&self, path: &Path) -> Result<Vec<u8>> {
+        let path = self.resolve(path);
+        std::fs::read(&path).with_context(|| format!("failed to read {}", path.display()))
+    }
+}
+
+/// Reads the assets out of a zip archive, like a resource pack shipped as a single file,
+/// only the archive's index is read up front and each asset when it's asked for
+pub struct Zip<R> {
+    archive: Mutex<ZipArchive<R>>,
+}
+
+impl Zip<BufReader<File>> {
+    pub fn open(path: &Path) -> Result<Self> {
+        let file = File::open(path).with_context(|| format!("failed to open {}", path.display()))?;
+        Self::new(BufReader::new(file)).with_context(|| format!("invalid asset archive {}", path.display()))
+    }
+}
+
+impl<R: Read + Seek> Zip<R> {
+    pub fn new(reader: R) -> Result<Self> {
+        Ok(Self { archive: Mutex::new(ZipArchive::new(reader)?) })
+    }
+}
+
+impl<R: Read + Seek + Send> AssetSource for Zip<R> {
+    fn read(&self, path: &Path) -> Result<Vec<u8>> {
+        // the names in an archive are always split up with forward slashes
+        let name = path
+            .components()
+            .filter(|component| *component != Component::CurDir)
+            .map(|component| component.as_os_str().to_string_lossy())
+            .collect::<Vec<_>>()
+            .join("/");
+
+        let mut archive = self.archive.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+        let mut entry = match archive.by_name(&name) {
+            Ok(entry) => entry,
+            Err(ZipError::FileNotFound) => {
+                let err = io::Error::new(io::ErrorKind::NotFound, format!("there is no {name} in the asset archive"));
+                return Err(err.into());
+            }
+            Err(err) => return Err(err).with_context(|| format!("failed to read {name} from the asset archive")),
+        };
+
+        // the size in the entry's header comes from the archive, it isn't trusted with an allocation up front
+        let mut bytes = Vec::new();
+        entry.read_to_end(&mut bytes).with_context(|| format!("failed to read {name} from the asset archive"))?;
+        Ok(bytes)
+    }
+}
+
+/// Whether an [`AssetSource`] failed to read something because there's nothing there
+pub fn is_not_found(err: &anyhow::Error) -> bool {
+    err.downcast_ref::<io::Error>().is_some_and(|err| err.kind() == io::ErrorKind::NotFound)
+}
+
+/// Where the game's assets get read from, the assets directory from the settings,
+/// or the archive if that's what they point at
+pub fn source(settings: &GameSettings) -> Box<dyn AssetSource> {
+    let paths = AssetPaths::from_settings(settings);
+    if paths.root.is_file() {
+        match Zip::open(&paths.root) {
+            Ok(archive) => return Box::new(archive),
+            Err(err) => tracing::error!("unable to open the assets, looking for them in a directory instead; {err:#}"),
+        }
+    }
+
+    Box::new(paths)
+}
+
+/// The assets directory, a `configured` relative path is relative to `exe_dir`, the directory holding the executable,
+/// without one it's the `assets` directory in the first of `search` that has one
+fn root_from<'a>(configured: Option<&Path>, exe_dir: Option<&Path>, search: impl IntoIterator<Item = &'a Path>) -> PathBuf {
+    match (configured, exe_dir) {
+        (Some(dir), Some(exe_dir)) => exe_dir.join(dir),
+        (Some(dir), None) => dir.to_path_buf(),
+        (None, _) => search
+            .into_iter()
+            .map(|dir| dir.join("assets"))
+            .find(|dir| dir.is_dir())
+            .unwrap_or_else(|| PathBuf::from("assets")),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn paths_resolve_against_the_root() {
+        let exe_dir = std::env::temp_dir().join(format!("voxel-assets-{}", std::process::id()));
+
+        // relative to the executable, unless they are absolute
+        let package_dir = Path::new(env!("CARGO_MANIFEST_DIR"));
+        let relative = root_from(Some(Path::new("mods/assets")), Some(&exe_dir), [package_dir]);
+        assert_eq!(relative, exe_dir.join("mods/assets"));
+        let absolute = std::env::temp_dir().join("shared-assets");
+        assert_eq!(root_from(Some(&absolute), Some(&exe_dir), [package_dir]), absolute);
+
+        // the first directory that has them, in the order they are searched
+        let search = [exe_dir.as_path(), package_dir];
+        assert_eq!(root_from(None, Some(&exe_dir), [exe_dir.as_path()]), Path::new("assets"));
+        assert_eq!(root_from(None, Some(&exe_dir), search), package_dir.join("assets"));
+        std::fs::create_dir_all(exe_dir.join("assets")).unwrap();
+        assert_eq!(root_from(None, Some(&exe_dir), search), exe_dir.join("assets"));
+        std::fs::remove_dir_all(&exe_dir).unwrap();
+
+        let assets = AssetPaths { root: exe_dir.join("assets") };
+        assert_eq!(assets.resolve("cube/cube.obj"), exe_dir.join("assets/cube/cube.obj"));
+        assert_eq!(assets.resolve(&absolute), absolute);
+
+        let built = AssetPaths::from_settings(&GameSettings::default());
+        assert!(built.resolve("cube/cube.obj").is_file());
+    }
+
+    fn archive(files: &[(&str, &[u8])]) -> Zip<std::io::Cursor<Vec<u8>>> {
+        let mut writer = zip::ZipWriter::new(std::io::Cursor::new(Vec::new()));
+        for &(name, bytes) in files {
+            writer.start_file(name, zip::write::SimpleFileOptions::default()).unwrap();
+            std::io::Write::write_all(&mut writer, bytes).unwrap();
+        }
+        Zip::new(writer.finish().unwrap()).unwrap()
+    }
+
+    #[test]
+    fn archives_read_entries_by_path() {
+        let archive = archive(&[
+            ("cube/cube.mtl", b"newmtl cube\n"),
+            ("icon.png", b"not really a png"),
+        ]);
+
+        assert_eq!(archive.read(Path::new("cube/cube.mtl")).unwrap(), b"newmtl cube\n");
+        assert_eq!(archive.read(Path::new("./icon.png")).unwrap(), b"not really a png");
+        assert_eq!(archive.read(&Path::new("cube").join("cube.mtl")).unwrap(), b"newmtl cube\n");
+
+        let err = archive.read(Path::new("cube/cube.obj")).unwrap_err();
+        assert_eq!(err.to_string(), "there is no cube/cube.obj in the asset archive");
+        assert!(is_not_found(&err));
+
+        assert!(Zip::new(std::io::Cursor::new(b"not a zip file".to_vec())).is_err());
+        let directory = AssetPaths { root: Path::new(env!("CARGO_MANIFEST_DIR")).join("assets") };
+        assert!(directory.read(Path::new("cube/cube.mtl")).unwrap().starts_with(b"# Blender MTL File"));
+        assert!(is_not_found(&directory.read(Path::new("cube/missing.mtl")).unwrap_err()));
+        assert!(!is_not_found(&Zip::new(std::io::Cursor::new(Vec::new())).err().unwrap()));
+    }
+}
@@ -0,0 +1,228 @@
+use std::path::PathBuf;
+use std::sync::Arc;
+use ahash::HashMap;
+use glam::Vec3;
+use crate::game_state::coords::AbsoluteCoord;
+
+/// Past this many blocks away sounds are played at full volume
+pub const REFERENCE_DISTANCE: f32 = 1.0;
+
+/// Sounds further away than this many blocks can't be heard at all
+pub const MAX_DISTANCE: f32 = 32.0;
+
+/// How loud a sound `distance` blocks away from the listener is, in `[0, 1]`
+///
+/// this is the usual inverse distance falloff, additionally faded out linearly towards
+/// [`MAX_DISTANCE`] so sounds don't cut out abruptly when they get out of range
+pub fn attenuation(distance: f32) -> f32 {
+    if distance.is_nan() || distance >= MAX_DISTANCE {
+        return 0.0;
+    }
+
+    let inverse = REFERENCE_DISTANCE / distance.max(REFERENCE_DISTANCE);
+    let fade = 1.0 - (distance.max(0.0) / MAX_DISTANCE);
+    inverse * fade
+}
+
+/// Where a sound sits between the left (-1) and the right (1) ear
+pub fn pan(listener_right: Vec3, to_source: Vec3) -> f32 {
+    match to_source.try_normalize() {
+        Some(direction) => direction.dot(listener_right).clamp(-1.0, 1.0),
+        // a sound inside the listener's head is right in the middle
+        None => 0.0,
+    }
+}
+
+/// The left and right channel volumes for a panned sound,
+/// constant power so a sound doesn't get quieter while it moves across
+pub fn channel_volumes(volume: f32, pan: f32) -> [f32; 2] {
+    let angle = (pan.clamp(-1.0, 1.0) + 1.0) * std::f32::consts::FRAC_PI_4;
+    let (right, left) = angle.sin_cos();
+    [left * volume, right * volume]
+}
+
+#[derive(Debug, Copy, Clone, Hash, Eq, PartialEq)]
+pub struct SoundId(u32);
+
+/// An encoded sound file, decoded every time it gets played
+#[derive(Debug, Clone)]
+pub struct SoundData(
+    #[cfg_attr(not(feature = "audio"), expect(dead_code, reason = "only read by the audio backend"))]
+    Arc<[u8]>
+);
+
+impl SoundData {
+    /// Reads a sound file (wav, ogg vorbis, flac or mp3) without blocking
+    #[expect(dead_code, reason = "nothing makes sounds yet")]
+    pub async fn load<P: Into<PathBuf>>(path: P) -> std::io::Result<Self> {
+        voxel_runtime::fs::read(path).await.map(|bytes| Self(Arc::from(bytes)))
+    }
+}
+
+#[derive(Debug, Copy, Clone)]
+struct Listener {
+    position: AbsoluteCoord,
+    right: Vec3,
+}
+
+#[cfg(feature = "audio")]
+mod backend {
+    use std::io::Cursor;
+    use rodio::{Decoder, OutputStream, OutputStreamHandle, Source};
+    use rodio::source::ChannelVolume;
+    use crate::audio::SoundData;
+
+    pub struct Output {
+        // the stream stops playing once dropped
+        _stream: OutputStream,
+        handle: OutputStreamHandle,
+    }
+
+    impl Output {
+        pub fn new() -> Option<Self> {
+            match OutputStream::try_default() {
+                Ok((stream, handle)) => Some(Self { _stream: stream, handle }),
+                Err(err) => {
+                    tracing::error!("no audio output available, the game will be silent: {err}");
+                    None
+                }
+            }
+        }
+
+        pub fn play(&self, sound: &SoundData, [left, right]: [f32; 2]) {
+            let source = match Decoder::new(Cursor::new(sound.0.clone())) {
+                Ok(source) => source,
+                Err(err) => {
+                    tracing::warn!("unable to decode sound: {err}");
+                    return;
+                }
+            };
+
+            let source = ChannelVolume::new(source, vec![left, right]).convert_samples();
+            if let Err(err) = self.handle.play_raw(source) {
+                tracing::warn!("unable to play sound: {err}");
+            }
+        }
+    }
+}
+
+#[cfg(not(feature = "audio"))]
+mod backend {
+    use crate::audio::SoundData;
+
+    /// built without audio support, there is never an output to play on
+    pub enum Output {}
+
+    impl Output {
+        pub fn new() -> Option<Self> {
+            None
+        }
+
+        pub fn play(&self, _sound: &SoundData, _volumes: [f32; 2]) {
+            match *self {}
+        }
+    }
+}
+
+/// The game's audio output, silent if there is no output device or the `audio` feature is off
+pub struct Audio {
+    output: Option<backend::Output>,
+    sounds: HashMap<SoundId, SoundData>,
+    next_id: u32,
+    listener: Listener,
+}
+
+impl Audio {
+    pub fn new() -> Self {
+        Self {
+            output: backend::Output::new(),
+            sounds: HashMap::default(),
+            next_id: 0,
+            listener: Listener {
+                position: AbsoluteCoord::ZERO,
+                right: Vec3::X,
+            },
+        }
+    }
+
+    #[expect(dead_code, reason = "nothing makes sounds yet")]
+    pub fn register(&mut self, sound: SoundData) -> SoundId {
+        let id = SoundId(self.next_id);
+        self.next_id = self.next_id.checked_add(1).expect("too many sounds");
+        self.sounds.insert(id, sound);
+        id
+    }
+
+    /// Moves the ears positional sounds are heard from, usually the player's
+    pub fn set_listener(&mut self, position: AbsoluteCoord, right: Vec3) {
+        self.listener = Listener { position, right };
+    }
+
+    fn play_with_volumes(&self, id: SoundId, volumes: [f32; 2]) {
+        let Some(output) = &self.output else {
+            return;
+        };
+
+        match self.sounds.get(&id) {
+            Some(sound) => output.play(sound, volumes),
+            None => tracing::warn!("tried to play unregistered sound {id:?}"),
+        }
+    }
+
+    /// Plays a sound that isn't anywhere in the world, like a menu click
+    #[expect(dead_code, reason = "nothing makes sounds yet")]
+    pub fn play_sound(&self, id: SoundId) {
+        self.play_with_volumes(id, channel_volumes(1.0, 0.0))
+    }
+
+    /// Plays a sound coming from `position`, quieter with distance and panned towards its side
+    #[expect(dead_code, reason = "nothing makes sounds yet")]
+    pub fn play_at(&self, id: SoundId, position: AbsoluteCoord) {
+        let to_source = (position.xyz() - self.listener.position.xyz()).as_f32();
+        let volume = attenuation(to_source.length());
+        if volume == 0.0 {
+            return;
+        }
+
+        self.play_with_volumes(id, channel_volumes(volume, pan(self.listener.right, to_source)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use glam::vec3;
+    use super::*;
+
+    #[test]
+    fn attenuation_curve() {
+        assert_eq!(attenuation(0.0), 1.0);
+        assert!((attenuation(REFERENCE_DISTANCE) - (1.0 - REFERENCE_DISTANCE / MAX_DISTANCE)).abs() < 1e-6);
+        assert_eq!(attenuation(MAX_DISTANCE), 0.0);
+        assert_eq!(attenuation(MAX_DISTANCE * 4.0), 0.0);
+        assert_eq!(attenuation(f32::NAN), 0.0);
+
+        let samples = (0..=64).map(|i| attenuation(i as f32 * 0.5)).collect::<Vec<_>>();
+        assert!(samples.windows(2).all(|pair| pair[0] >= pair[1]), "{samples:?}");
+        assert!(samples.iter().all(|volume| (0.0..=1.0).contains(volume)));
+
+        // twice as far is at most half as loud
+        assert!(attenuation(8.0) <= attenuation(4.0) / 2.0);
+    }
+
+    #[test]
+    fn panning() {
+        let right = Vec3::X;
+        assert_eq!(pan(right, vec3(5.0, 0.0, 0.0)), 1.0);
+        assert_eq!(pan(right, vec3(-5.0, 0.0, 0.0)), -1.0);
+        assert_eq!(pan(right, vec3(0.0, 0.0, 5.0)), 0.0);
+        assert_eq!(pan(right, Vec3::ZERO), 0.0);
+
+        let [left, right] = channel_volumes(1.0, 0.0);
+        assert!((left - right).abs() < 1e-6);
+        assert!((left * left + right * right - 1.0).abs() < 1e-6);
+
+        let [left, right] = channel_volumes(0.5, 1.0);
+        assert!(left.abs() < 1e-6);
+        assert!((right - 0.5).abs() < 1e-6);
+    }
+}
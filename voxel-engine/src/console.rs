@@ -0,0 +1,176 @@
+use std::str::FromStr;
+use thiserror::Error;
+use voxel_maths::fixed_point::FixedPoint;
+use voxel_maths::i48_int::i48;
+use crate::game_state::coords::{AbsoluteBlockCoord, AbsoluteCoord};
+use crate::game_state::GameState;
+use crate::settings::{Fov, GameSettingsHandle};
+
+/// A command typed into the console
+#[derive(Debug, Clone, PartialEq)]
+pub enum Command {
+    /// `tp <x> <y> <z>`
+    Teleport(AbsoluteCoord),
+    /// `fov <degrees>`
+    Fov(Fov),
+    /// `speed <blocks a second>`
+    Speed(FixedPoint),
+    /// `setblock <x> <y> <z> <block name>`
+    SetBlock(AbsoluteBlockCoord, String),
+}
+
+#[derive(Debug, Error, PartialEq)]
+pub enum CommandError {
+    #[error("no command given")]
+    Empty,
+    #[error("unknown command `{0}`")]
+    UnknownCommand(String),
+    #[error("usage: {0}")]
+    Usage(&'static str),
+    #[error("invalid {name} `{value}`")]
+    InvalidArgument {
+        name: &'static str,
+        value: String,
+    },
+    #[error("there is no block called `{0}`")]
+    UnknownBlock(String),
+    #[error("that part of the world isn't loaded")]
+    NotLoaded,
+}
+
+fn parse_arg<T: FromStr>(name: &'static str, value: &str) -> Result<T, CommandError> {
+    value.parse().map_err(|_| CommandError::InvalidArgument {
+        name,
+        value: value.to_owned(),
+    })
+}
+
+impl Command {
+    pub fn parse(line: &str) -> Result<Self, CommandError> {
+        let mut words = line.split_whitespace();
+        let name = words.next().ok_or(CommandError::Empty)?;
+        let args = words.collect::<Vec<_>>();
+
+        match (name, &*args) {
+            ("tp", [x, y, z]) => Ok(Self::Teleport(AbsoluteCoord::from_xyz(
+                parse_arg("x", x)?,
+                parse_arg("y", y)?,
+                parse_arg("z", z)?,
+            ))),
+            ("tp", _) => Err(CommandError::Usage("tp <x> <y> <z>")),
+
+            ("fov", [degrees]) => {
+                let fov = parse_arg::<u8>("fov", degrees)
+                    .ok()
+                    .and_then(Fov::new)
+                    .ok_or_else(|| CommandError::InvalidArgument { name: "fov", value: degrees.to_string() })?;
+                Ok(Self::Fov(fov))
+            }
+            ("fov", _) => Err(CommandError::Usage("fov <degrees>")),
+
+            ("speed", [speed]) => match parse_arg::<FixedPoint>("speed", speed)? {
+                speed if speed.is_negative() => Err(CommandError::InvalidArgument { name: "speed", value: speed.to_string() }),
+                speed => Ok(Self::Speed(speed)),
+            },
+            ("speed", _) => Err(CommandError::Usage("speed <blocks a second>")),
+
+            ("setblock", [x, y, z, block]) => Ok(Self::SetBlock(
+                AbsoluteBlockCoord::from_xyz(
+                    parse_arg::<i48>("x", x)?,
+                    parse_arg("y", y)?,
+                    parse_arg::<i48>("z", z)?,
+                ),
+                block.to_string(),
+            )),
+            ("setblock", _) => Err(CommandError::Usage("setblock <x> <y> <z> <block>")),
+
+            (name, _) => Err(CommandError::UnknownCommand(name.to_owned())),
+        }
+    }
+
+    pub fn run(self, game: &mut GameState, settings: &GameSettingsHandle) -> Result<(), CommandError> {
+        match self {
+            Self::Teleport(position) => game.teleport_player(position),
+            Self::Fov(fov) => settings.edit(|settings| settings.fov = fov),
+            Self::Speed(speed) => game.set_walk_speed(speed),
+            Self::SetBlock(position, name) => {
+                let world = game.world_mut();
+                let block = world.blocks().by_name(&name).ok_or(CommandError::UnknownBlock(name))?;
+                world.set_block(position, block).ok_or(CommandError::NotLoaded)?;
+            }
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use voxel_maths::i48;
+    use crate::game_state::entities::EntityId;
+    use crate::game_state::entity::Entity;
+    use crate::settings::GameSettings;
+    use super::*;
+
+    fn fixed(x: i64) -> FixedPoint {
+        FixedPoint::from_int(i48::new(x).unwrap())
+    }
+
+    #[test]
+    fn parse_teleport() {
+        assert_eq!(
+            Command::parse("tp 1 -2.5   300"),
+            Ok(Command::Teleport(AbsoluteCoord::from_xyz(fixed(1), FixedPoint::from_f32(-2.5), fixed(300))))
+        );
+
+        assert_eq!(Command::parse("tp 1 2"), Err(CommandError::Usage("tp <x> <y> <z>")));
+        assert_eq!(Command::parse("tp 1 2 3 4"), Err(CommandError::Usage("tp <x> <y> <z>")));
+        assert_eq!(
+            Command::parse("tp 1 up 3"),
+            Err(CommandError::InvalidArgument { name: "y", value: "up".to_owned() })
+        );
+    }
+
+    #[test]
+    fn parse_fov() {
+        assert_eq!(Command::parse("fov 90"), Ok(Command::Fov(Fov::new(90).unwrap())));
+
+        let invalid = |value: &str| Err(CommandError::InvalidArgument { name: "fov", value: value.to_owned() });
+        assert_eq!(Command::parse("fov 10"), invalid("10"));
+        assert_eq!(Command::parse("fov 500"), invalid("500"));
+        assert_eq!(Command::parse("fov wide"), invalid("wide"));
+        assert_eq!(Command::parse("fov"), Err(CommandError::Usage("fov <degrees>")));
+    }
+
+    #[test]
+    fn parse_other_commands() {
+        assert_eq!(Command::parse(""), Err(CommandError::Empty));
+        assert_eq!(Command::parse("fly"), Err(CommandError::UnknownCommand("fly".to_owned())));
+        assert_eq!(Command::parse("speed 4.5"), Ok(Command::Speed(FixedPoint::from_f32(4.5))));
+        assert!(matches!(Command::parse("speed -1"), Err(CommandError::InvalidArgument { name: "speed", .. })));
+        assert_eq!(
+            Command::parse("setblock 1 2 3 stone"),
+            Ok(Command::SetBlock(AbsoluteBlockCoord::from_xyz(i48!(1), 2, i48!(3)), "stone".to_owned()))
+        );
+        assert!(matches!(Command::parse("setblock 1 256 3 stone"), Err(CommandError::InvalidArgument { name: "y", .. })));
+    }
+
+    #[test]
+    fn run_commands() {
+        let mut game = GameState::new();
+        let settings = GameSettingsHandle::in_memory(GameSettings::default());
+        let mut run = |line: &str| Command::parse(line).and_then(|command| command.run(&mut game, &settings));
+
+        run("tp 10 20 -30").unwrap();
+        run("fov 100").unwrap();
+        run("speed 3").unwrap();
+        assert_eq!(run("setblock 0 0 0 nothing"), Err(CommandError::UnknownBlock("nothing".to_owned())));
+        assert_eq!(run("setblock 100000 0 0 air"), Err(CommandError::NotLoaded));
+
+        let target = AbsoluteCoord::from_xyz(fixed(10), fixed(20), fixed(-30));
+        assert_eq!(game.player().position(), target);
+        assert_eq!(game.entities().get(EntityId::PLAYER).unwrap().position, target);
+        assert_eq!(game.walk_speed(), fixed(3));
+        assert_eq!(settings.load().fov, Fov::new(100).unwrap());
+    }
+}
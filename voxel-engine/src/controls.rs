@@ -1,10 +1,12 @@
 use std::mem::MaybeUninit;
+use std::time::{Duration, Instant};
 use thiserror::Error;
 use std::hash::Hash;
-use ahash::{HashSet, HashSetExt};
+use ahash::{HashMap, HashSet, HashSetExt};
 use glam::{vec2, Vec2};
-use winit::event::{DeviceEvent, ElementState, MouseButton, RawKeyEvent};
-use winit::keyboard::{KeyCode, PhysicalKey};
+use winit::event::{DeviceEvent, ElementState, MouseButton};
+use winit::keyboard::{Key, KeyCode, PhysicalKey};
+use crate::settings::{AimCurve, StickSettings};
 
 pub trait Button: Copy + Send + Sync + Hash + Eq + 'static  {}
 
@@ -13,9 +15,13 @@ impl<T: Copy + Send + Sync + Hash + Eq + 'static> Button for T {}
 #[derive(Debug)]
 pub struct ButtonInput<T> {
     pressed: HashSet<T>,
+    /// when each pressed input went down
+    pressed_at: HashMap<T, Instant>,
 
     just_pressed: HashSet<T>,
-    just_released: HashSet<T>
+    just_released: HashSet<T>,
+    /// how long each input in `just_released` was held for, if that's known
+    held_for: HashMap<T, Duration>,
 }
 
 
@@ -23,17 +29,20 @@ impl<T: Button> ButtonInput<T> {
     pub fn new() -> Self {
         Self {
             pressed: HashSet::new(),
+            pressed_at: HashMap::default(),
 
             just_pressed: HashSet::new(),
-            just_released: HashSet::new()
+            just_released: HashSet::new(),
+            held_for: HashMap::default(),
         }
     }
 
-    /// Registers a press for the given `input`.
-    pub fn press(&mut self, input: T) {
+    /// Registers a press for the given `input` that happened `at`.
+    pub fn press(&mut self, input: T, at: Instant) {
         // Returns `true` if the `input` wasn't pressed.
         if self.pressed.insert(input) {
             self.just_pressed.insert(input);
+            self.pressed_at.insert(input, at);
         }
     }
 
@@ -45,18 +54,33 @@ impl<T: Button> ButtonInput<T> {
         self.just_pressed.contains(&input)
     }
 
-    pub fn release(&mut self, input: T) {
+    pub fn just_released(&self, input: T) -> bool {
+        self.just_released.contains(&input)
+    }
+
+    /// Whether `input` was just released after being held for no longer than `max_hold`
+    pub fn just_tapped(&self, input: T, max_hold: Duration) -> bool {
+        self.held_for.get(&input).is_some_and(|&held| held <= max_hold)
+    }
+
+    /// Registers a release for the given `input` that happened `at`.
+    pub fn release(&mut self, input: T, at: Instant) {
         // Returns `true` if the `input` was pressed.
         if self.pressed.remove(&input) {
             self.just_released.insert(input);
+            if let Some(pressed_at) = self.pressed_at.remove(&input) {
+                self.held_for.insert(input, at.saturating_duration_since(pressed_at));
+            }
         }
     }
 
 
     /// Registers a release for all currently pressed inputs.
     pub fn release_all(&mut self) {
-        // Move all items from pressed into just_released
+        // Move all items from pressed into just_released,
+        // they weren't let go of on purpose so they don't count as taps
         self.just_released.extend(self.pressed.drain());
+        self.pressed_at.clear();
     }
 
     /// Clears the `pressed`, `just_pressed`, and `just_released` data for every input.
@@ -64,8 +88,10 @@ impl<T: Button> ButtonInput<T> {
     /// See also [`ButtonInput::clear`] for simulating elapsed time steps.
     pub fn reset_all(&mut self) {
         self.pressed.clear();
+        self.pressed_at.clear();
         self.just_pressed.clear();
         self.just_released.clear();
+        self.held_for.clear();
     }
 
 
@@ -75,6 +101,7 @@ impl<T: Button> ButtonInput<T> {
     pub fn clear(&mut self) {
         self.just_pressed.clear();
         self.just_released.clear();
+        self.held_for.clear();
     }
 }
 
@@ -159,6 +186,12 @@ impl<T: Button> Keybinding<T> {
         self.held_down(inputs)
             && self.keys().any(move |key| inputs.just_pressed(key))
     }
+
+    /// A key of the binding was tapped while the rest of it was held down, or let go of at the same time
+    fn tapped(&self, inputs: &ButtonInput<T>, max_hold: Duration) -> bool {
+        self.keys().any(|key| inputs.just_tapped(key, max_hold))
+            && self.keys().all(|key| inputs.pressed(key) || inputs.just_released(key))
+    }
 }
 
 #[derive(Debug)]
@@ -246,9 +279,56 @@ macro_rules! define_key_mappings {
 #[expect(dead_code, reason = "mouse controls soon")]
 pub enum MouseAndKeyboardButton {
     Mouse(MouseButton),
+    /// Keys are bound by where they sit on the keyboard, not what's printed on them,
+    /// so WASD stays in the same place on an AZERTY keyboard (where it reads ZQSD).
+    /// Use [`KeyLabels`] to show a key the way the user's layout names it
     Keyboard(KeyCode)
 }
 
+/// What the keys are called on the user's keyboard layout, learned from the keys they press
+#[derive(Debug, Default)]
+pub struct KeyLabels {
+    learned: HashMap<KeyCode, String>,
+}
+
+impl KeyLabels {
+    /// Remembers what `logical_key` says the key at `code` types on the current layout
+    pub fn learn(&mut self, code: KeyCode, logical_key: &Key) {
+        if let Key::Character(text) = logical_key {
+            // shift or caps lock shouldn't change what the key is called
+            self.learned.insert(code, text.to_uppercase());
+        }
+    }
+
+    /// A name for the key at `code` to show the user,
+    /// the one on their layout if it was pressed before, otherwise [`fallback_key_label`]
+    #[cfg_attr(not(test), expect(dead_code, reason = "there is no settings screen to show bindings on yet"))]
+    pub fn label(&self, code: KeyCode) -> String {
+        match self.learned.get(&code) {
+            Some(label) => label.clone(),
+            None => fallback_key_label(code),
+        }
+    }
+}
+
+/// Names a key after where it is on a US keyboard, for when the layout's name for it isn't known
+pub fn fallback_key_label(code: KeyCode) -> String {
+    let name = format!("{code:?}");
+
+    if let Some(key) = name.strip_prefix("Key").or_else(|| name.strip_prefix("Digit")) {
+        return key.to_owned()
+    }
+
+    for side in ["Left", "Right"] {
+        match name.strip_suffix(side) {
+            Some(key) if !key.is_empty() => return format!("{side} {key}"),
+            _ => {}
+        }
+    }
+
+    name
+}
+
 #[expect(unused_macros, reason = "mouse controls soon")]
 macro_rules! mouse {
     ($mouse_button: ident) => {
@@ -303,12 +383,49 @@ impl<T: Button> Keybindings<T> {
     fn triggered(&self, mapping: KeyMapping) -> bool {
         self.map.get(mapping).triggered(&self.inputs)
     }
+
+    fn was_tapped(&self, mapping: KeyMapping, max_hold: Duration) -> bool {
+        self.map.get(mapping).tapped(&self.inputs, max_hold)
+    }
 }
 
 
 #[derive(Debug)]
 struct MouseMotion {
-    accumulated: Vec2
+    accumulated: Vec2,
+    curve: AimCurve,
+}
+
+impl AimCurve {
+    pub fn apply(self, delta: Vec2) -> Vec2 {
+        match self {
+            AimCurve::Linear => delta,
+            AimCurve::Power { exponent, cap } => {
+                let length = delta.length();
+                if length == 0.0 {
+                    return delta
+                }
+
+                delta * length.powf(exponent - 1.0).min(cap)
+            }
+        }
+    }
+}
+
+impl StickSettings {
+    /// Where a stick at `stick` (with a length of at most 1) points, after the deadzone and sensitivity
+    ///
+    /// The deadzone is a circle rather than a square around the center, so diagonals aren't cut off,
+    /// and what's past it is stretched back out to start from zero
+    pub fn apply(self, stick: Vec2) -> Vec2 {
+        let length = stick.length();
+        if length <= self.deadzone {
+            return Vec2::ZERO
+        }
+
+        let pushed = ((length - self.deadzone) / (1.0 - self.deadzone)).min(1.0);
+        stick / length * pushed * self.sensitivity
+    }
 }
 
 #[derive(Debug)]
@@ -336,13 +453,19 @@ impl InputMethod for MouseAndKeyboardInput {
     }
 
     fn cursor_delta(&self) -> Vec2 {
-        self.mouse.accumulated
+        self.mouse.curve.apply(self.mouse.accumulated)
     }
 }
 
+/// Every input goes through here, each kind of it from exactly one source so nothing counts twice:
+/// - keys come from the window's `KeyboardInput`, only while it has focus and with the repeat flag
+/// - mouse motion comes from `DeviceEvent::MouseMotion`, raw and still there with the cursor grabbed
+///
+/// The window's `CursorMoved` and the devices' `Key` events are ignored
 #[derive(Debug)]
 pub struct Controls {
-    mkb: MouseAndKeyboardInput
+    mkb: MouseAndKeyboardInput,
+    key_labels: KeyLabels,
 }
 
 impl Default for Controls {
@@ -354,9 +477,11 @@ impl Default for Controls {
                     map: KeyMap::default()
                 },
                 mouse: MouseMotion { 
-                    accumulated: Vec2::ZERO
+                    accumulated: Vec2::ZERO,
+                    curve: AimCurve::default(),
                 },
-            }
+            },
+            key_labels: KeyLabels::default(),
         }
     }
 }
@@ -367,30 +492,62 @@ impl Controls {
         self.mkb.mouse.accumulated = Vec2::ZERO
     }
 
+    /// Shapes the mouse motion of each frame before it's handed out by `cursor_delta`
+    pub fn set_cursor_sensitivity_curve(&mut self, curve: AimCurve) {
+        self.mkb.mouse.curve = curve
+    }
+
+    #[expect(dead_code, reason = "there is no settings screen to show bindings on yet")]
+    pub fn key_labels(&self) -> &KeyLabels {
+        &self.key_labels
+    }
+
+    /// Picks up what a key is called from a key press the window got
+    pub fn learn_key_label(&mut self, physical_key: PhysicalKey, logical_key: &Key) {
+        if let PhysicalKey::Code(code) = physical_key {
+            self.key_labels.learn(code, logical_key)
+        }
+    }
+
+    /// Whether the action was let go of this frame after being held no longer than `max_hold`,
+    /// tells a quick tap apart from holding it down
+    #[cfg_attr(not(test), expect(dead_code, reason = "nothing tells taps from holds yet"))]
+    pub fn was_tapped(&self, mapping: KeyMapping, max_hold: Duration) -> bool {
+        self.mkb.keys.was_tapped(mapping, max_hold)
+    }
+
     pub fn lost_focus(&mut self) {
         let input = &mut self.mkb.keys.inputs;
         input.reset_all();
         input.release_all()
     }
 
-    fn update_mkb_buttons(&mut self, code: MouseAndKeyboardButton, state: ElementState) {
+    fn update_mkb_buttons(&mut self, code: MouseAndKeyboardButton, state: ElementState, at: Instant) {
         let inputs = &mut self.mkb.keys.inputs;
         match state {
-            ElementState::Pressed =>  inputs.press(code),
-            ElementState::Released => inputs.release(code),
+            ElementState::Pressed =>  inputs.press(code, at),
+            ElementState::Released => inputs.release(code, at),
         }
     }
 
-    pub fn update(&mut self, window_event: &DeviceEvent) {
-        match *window_event {
-            DeviceEvent::Key(RawKeyEvent { physical_key: PhysicalKey::Code(code), state, .. }) =>
-                {
-                    self.update_mkb_buttons(MouseAndKeyboardButton::Keyboard(code), state)
-                },
-            DeviceEvent::MouseMotion { delta: (x, y) } => {
-                self.mkb.mouse.accumulated += vec2(x as f32, y as f32);
-            }
-            _ => {}
+    /// A key the window got, `repeat` is set for the presses the OS sends while a key is held down,
+    /// those don't press it again
+    pub fn key_input(&mut self, physical_key: PhysicalKey, state: ElementState, repeat: bool, at: Instant) {
+        let PhysicalKey::Code(code) = physical_key else {
+            return;
+        };
+
+        if repeat && state == ElementState::Pressed {
+            return;
+        }
+
+        self.update_mkb_buttons(MouseAndKeyboardButton::Keyboard(code), state, at)
+    }
+
+    /// Raw input from the devices themselves, only the mouse motion is taken from here
+    pub fn device_input(&mut self, event: &DeviceEvent) {
+        if let DeviceEvent::MouseMotion { delta: (x, y) } = *event {
+            self.mkb.mouse.accumulated += vec2(x as f32, y as f32);
         }
     }
 }
@@ -407,4 +564,179 @@ impl InputMethod for Controls {
     fn cursor_delta(&self) -> Vec2 {
         self.mkb.cursor_delta()
     }
-}
\ No newline at end of file
+}
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn key(controls: &mut Controls, code: KeyCode, state: ElementState, at: Instant) {
+        controls.key_input(PhysicalKey::Code(code), state, false, at)
+    }
+
+    #[test]
+    fn taps_are_short_presses() {
+        const MAX_HOLD: Duration = Duration::from_millis(200);
+
+        let start = Instant::now();
+        let mut controls = Controls::default();
+        let mut hold = |held: Duration| {
+            key(&mut controls, KeyCode::Space, ElementState::Pressed, start);
+            assert!(!controls.was_tapped(KeyMapping::Jump, MAX_HOLD));
+            key(&mut controls, KeyCode::Space, ElementState::Released, start + held);
+            let tapped = controls.was_tapped(KeyMapping::Jump, MAX_HOLD);
+            controls.new_frame();
+            // only counts for the frame it was let go in
+            assert!(!controls.was_tapped(KeyMapping::Jump, MAX_HOLD));
+            tapped
+        };
+
+        assert!(hold(Duration::ZERO));
+        assert!(hold(Duration::from_millis(199)));
+        assert!(hold(MAX_HOLD));
+        assert!(!hold(Duration::from_millis(201)));
+        assert!(!hold(Duration::from_secs(3)));
+
+        // losing focus lets go of everything, but not as a tap
+        key(&mut controls, KeyCode::Space, ElementState::Pressed, start);
+        controls.lost_focus();
+        assert!(!controls.was_tapped(KeyMapping::Jump, MAX_HOLD));
+    }
+
+    #[test]
+    fn os_key_repeats_are_not_presses() {
+        let now = Instant::now();
+        let mut controls = Controls::default();
+        key(&mut controls, KeyCode::Escape, ElementState::Pressed, now);
+        assert!(controls.triggered(KeyMapping::MainMenu));
+        controls.new_frame();
+
+        controls.key_input(PhysicalKey::Code(KeyCode::Escape), ElementState::Pressed, true, now);
+        assert!(!controls.triggered(KeyMapping::MainMenu));
+        assert!(controls.held_down(KeyMapping::MainMenu));
+
+        // a repeat of a key that isn't down (focus came back while it was held) doesn't press it either
+        controls.key_input(PhysicalKey::Code(KeyCode::KeyW), ElementState::Pressed, true, now);
+        assert!(!controls.held_down(KeyMapping::WalkForwards));
+
+        key(&mut controls, KeyCode::Escape, ElementState::Released, now);
+        assert!(!controls.held_down(KeyMapping::MainMenu));
+    }
+
+    #[test]
+    fn held_keys_are_only_just_pressed_once() {
+        let start = Instant::now();
+        let mut controls = Controls::default();
+        let mut just_pressed = 0;
+        for frame in 0..10 {
+            let at = start + Duration::from_millis(30) * frame;
+            // some platforms don't flag their repeats, those have to be caught too
+            for repeat in [frame > 0, false, true] {
+                controls.key_input(PhysicalKey::Code(KeyCode::Escape), ElementState::Pressed, repeat, at);
+            }
+
+            just_pressed += usize::from(controls.triggered(KeyMapping::MainMenu));
+            assert!(controls.held_down(KeyMapping::MainMenu));
+            controls.new_frame();
+        }
+        assert_eq!(just_pressed, 1);
+
+        // the hold is timed from the first press, not the last repeat
+        key(&mut controls, KeyCode::Escape, ElementState::Released, start + Duration::from_millis(300));
+        assert!(!controls.was_tapped(KeyMapping::MainMenu, Duration::from_millis(100)));
+
+        let mut inputs = ButtonInput::new();
+        for _ in 0..3 {
+            inputs.press(KeyCode::KeyA, start);
+        }
+        assert!(inputs.just_pressed(KeyCode::KeyA));
+        inputs.clear();
+        inputs.press(KeyCode::KeyA, start);
+        assert!(!inputs.just_pressed(KeyCode::KeyA));
+    }
+
+    #[test]
+    fn devices_only_move_the_mouse() {
+        let mut controls = Controls::default();
+        controls.device_input(&DeviceEvent::Key(winit::event::RawKeyEvent {
+            physical_key: PhysicalKey::Code(KeyCode::KeyW),
+            state: ElementState::Pressed,
+        }));
+        assert!(!controls.held_down(KeyMapping::WalkForwards));
+
+        controls.device_input(&DeviceEvent::MouseMotion { delta: (3.0, -2.0) });
+        controls.device_input(&DeviceEvent::MouseMotion { delta: (1.0, 1.0) });
+        assert_eq!(controls.cursor_delta(), vec2(4.0, -1.0));
+        controls.new_frame();
+        assert_eq!(controls.cursor_delta(), Vec2::ZERO);
+    }
+
+    #[test]
+    fn aim_curves() {
+        let deltas = [vec2(0.5, 0.0), vec2(3.0, -4.0), vec2(0.0, 40.0), Vec2::ZERO];
+        for delta in deltas {
+            assert_eq!(AimCurve::Linear.apply(delta), delta);
+        }
+
+        let power = AimCurve::Power { exponent: 1.5, cap: 4.0 };
+        let gain = |delta: Vec2| power.apply(delta).length() / delta.length();
+        assert!(gain(vec2(1.0, 0.0)) < gain(vec2(3.0, -4.0)));
+        assert!(gain(vec2(3.0, -4.0)) < gain(vec2(0.0, 9.0)));
+        // same direction, just further
+        assert!(power.apply(vec2(3.0, -4.0)).normalize().abs_diff_eq(vec2(0.6, -0.8), 1e-6));
+        // the cap kicks in at 16
+        assert_eq!(power.apply(vec2(0.0, 40.0)), vec2(0.0, 160.0));
+        assert_eq!(power.apply(Vec2::ZERO), Vec2::ZERO);
+
+        let mut controls = Controls::default();
+        controls.set_cursor_sensitivity_curve(power);
+        controls.device_input(&DeviceEvent::MouseMotion { delta: (0.0, 40.0) });
+        assert_eq!(controls.cursor_delta(), vec2(0.0, 160.0));
+    }
+
+    #[test]
+    fn radial_deadzone() {
+        let stick = StickSettings { deadzone: 0.2, sensitivity: 1.0 };
+        assert_eq!(stick.apply(vec2(0.19, 0.0)), Vec2::ZERO);
+        // a diagonal inside the circle, even with both axes past the deadzone on their own
+        assert_eq!(stick.apply(vec2(0.13, 0.13)), Vec2::ZERO);
+
+        // just outside starts from nothing instead of jumping to the deadzone
+        let barely = stick.apply(vec2(0.0, -0.21));
+        assert!(barely.y < 0.0 && barely.y > -0.02);
+        assert_eq!(barely.x, 0.0);
+
+        assert!(stick.apply(vec2(0.6, 0.0)).abs_diff_eq(vec2(0.5, 0.0), 1e-6));
+        assert!(stick.apply(vec2(0.6, 0.8)).abs_diff_eq(vec2(0.6, 0.8), 1e-6));
+
+        let sensitive = StickSettings { sensitivity: 2.0, ..stick };
+        assert!(sensitive.apply(vec2(0.6, 0.0)).abs_diff_eq(vec2(1.0, 0.0), 1e-6));
+    }
+
+    #[test]
+    fn fallback_labels() {
+        assert_eq!(fallback_key_label(KeyCode::KeyW), "W");
+        assert_eq!(fallback_key_label(KeyCode::Digit7), "7");
+        assert_eq!(fallback_key_label(KeyCode::Space), "Space");
+        assert_eq!(fallback_key_label(KeyCode::ShiftLeft), "Left Shift");
+        assert_eq!(fallback_key_label(KeyCode::ControlRight), "Right Control");
+        assert_eq!(fallback_key_label(KeyCode::ArrowLeft), "Left Arrow");
+        assert_eq!(fallback_key_label(KeyCode::F11), "F11");
+        assert_eq!(fallback_key_label(KeyCode::Escape), "Escape");
+    }
+
+    #[test]
+    fn labels_follow_the_layout() {
+        let mut labels = KeyLabels::default();
+        assert_eq!(labels.label(KeyCode::KeyW), "W");
+
+        // the key where W is on a US keyboard types z on AZERTY
+        labels.learn(KeyCode::KeyW, &Key::Character("z".into()));
+        assert_eq!(labels.label(KeyCode::KeyW), "Z");
+        labels.learn(KeyCode::Digit2, &Key::Character("é".into()));
+        assert_eq!(labels.label(KeyCode::Digit2), "É");
+
+        // named keys keep their fallback
+        labels.learn(KeyCode::Space, &Key::Named(winit::keyboard::NamedKey::Space));
+        assert_eq!(labels.label(KeyCode::Space), "Space");
+    }
+}
@@ -0,0 +1,277 @@
+use std::borrow::Cow;
+use ahash::HashMap;
+use glam::IVec3;
+use crate::game_state::world::{Block, MAX_LIGHT};
+
+/// The six sides of a block
+#[derive(Debug, Copy, Clone, Hash, Eq, PartialEq)]
+pub enum BlockFace {
+    PosX,
+    NegX,
+    PosY,
+    NegY,
+    PosZ,
+    NegZ,
+}
+
+impl BlockFace {
+    pub const ALL: [Self; 6] = [Self::PosX, Self::NegX, Self::PosY, Self::NegY, Self::PosZ, Self::NegZ];
+
+    /// One block in the direction the face points
+    pub const fn offset(self) -> IVec3 {
+        match self {
+            Self::PosX => IVec3::X,
+            Self::NegX => IVec3::NEG_X,
+            Self::PosY => IVec3::Y,
+            Self::NegY => IVec3::NEG_Y,
+            Self::PosZ => IVec3::Z,
+            Self::NegZ => IVec3::NEG_Z,
+        }
+    }
+
+    /// The face on the other side of the block, the one the neighbor touches this face with
+    pub const fn opposite(self) -> Self {
+        match self {
+            Self::PosX => Self::NegX,
+            Self::NegX => Self::PosX,
+            Self::PosY => Self::NegY,
+            Self::NegY => Self::PosY,
+            Self::PosZ => Self::NegZ,
+            Self::NegZ => Self::PosZ,
+        }
+    }
+}
+
+/// The texture atlas layer drawn on each face of a block
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub struct FaceTextures([u32; 6]);
+
+impl FaceTextures {
+    pub const fn all(layer: u32) -> Self {
+        Self([layer; 6])
+    }
+
+    #[cfg_attr(not(test), expect(dead_code, reason = "the default registry only has air, nothing has different faces yet"))]
+    pub const fn top_bottom_sides(top: u32, bottom: u32, sides: u32) -> Self {
+        Self([sides, sides, top, bottom, sides, sides])
+    }
+
+    #[cfg_attr(not(test), expect(dead_code, reason = "chunk meshes are colored by block id, they don't sample the block texture atlas yet"))]
+    pub const fn get(self, face: BlockFace) -> u32 {
+        self.0[face as usize]
+    }
+}
+
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct BlockProperties {
+    pub name: Cow<'static, str>,
+    /// whether entities collide with it
+    pub solid: bool,
+    /// whether the blocks behind it show through, so faces against it still get drawn
+    pub transparent: bool,
+    /// whether the player can point at it to break or use it
+    pub selectable: bool,
+    /// whether entities swim through it instead of walking
+    pub fluid: bool,
+    /// how much light it gives off, from 0 for none up to [`MAX_LIGHT`]
+    pub light: u8,
+    pub textures: FaceTextures,
+}
+
+impl BlockProperties {
+    /// What an id nothing was registered for behaves as, a plain solid block
+    /// with the missing texture so holes in the registry show up instead of hiding
+    pub const UNKNOWN: Self = Self::solid("unknown", FaceTextures::all(0));
+
+    pub const fn solid(name: &'static str, textures: FaceTextures) -> Self {
+        Self {
+            name: Cow::Borrowed(name),
+            solid: true,
+            transparent: false,
+            selectable: true,
+            fluid: false,
+            light: 0,
+            textures,
+        }
+    }
+
+    /// A block entities swim through, like water
+    #[cfg_attr(not(test), expect(dead_code, reason = "the default registry only has air, nothing registers water yet"))]
+    pub const fn fluid(name: &'static str, textures: FaceTextures) -> Self {
+        Self {
+            name: Cow::Borrowed(name),
+            solid: false,
+            transparent: true,
+            selectable: false,
+            fluid: true,
+            light: 0,
+            textures,
+        }
+    }
+}
+
+static UNKNOWN: BlockProperties = BlockProperties::UNKNOWN;
+
+const AIR: BlockProperties = BlockProperties {
+    name: Cow::Borrowed("air"),
+    solid: false,
+    transparent: true,
+    selectable: false,
+    fluid: false,
+    light: 0,
+    textures: FaceTextures::all(0),
+};
+
+/// Every kind of block there is, air is always [`Block::AIR`]
+///
+/// blocks get ids in the order they are registered, so the order has to stay the same
+/// for saved worlds to load with the right blocks
+#[derive(Debug, Clone)]
+pub struct BlockRegistry {
+    blocks: Vec<BlockProperties>,
+    by_name: HashMap<Cow<'static, str>, Block>,
+}
+
+impl Default for BlockRegistry {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl BlockRegistry {
+    pub fn new() -> Self {
+        let mut registry = Self {
+            blocks: Vec::new(),
+            by_name: HashMap::default(),
+        };
+
+        let air = registry.register(AIR);
+        debug_assert_eq!(air, Block::AIR);
+        registry
+    }
+
+    /// Adds a block and returns the id it got
+    ///
+    /// # Panics
+    /// if a block with the same name exists, or every id is taken
+    pub fn register(&mut self, properties: BlockProperties) -> Block {
+        let id = u16::try_from(self.blocks.len()).expect("ran out of block ids");
+        let block = Block::from_id(id);
+
+        let previous = self.by_name.insert(properties.name.clone(), block);
+        assert!(previous.is_none(), "block {:?} was registered twice", properties.name);
+
+        self.blocks.push(properties);
+        block
+    }
+
+    pub fn by_name(&self, name: &str) -> Option<Block> {
+        self.by_name.get(name).copied()
+    }
+
+    /// The properties of `block`, [`BlockProperties::UNKNOWN`] if it was never registered
+    pub fn get(&self, block: Block) -> &BlockProperties {
+        self.blocks
+            .get(usize::from(block.id()))
+            .unwrap_or(&UNKNOWN)
+    }
+
+    pub fn is_solid(&self, block: Block) -> bool {
+        self.get(block).solid
+    }
+
+    pub fn is_transparent(&self, block: Block) -> bool {
+        self.get(block).transparent
+    }
+
+    pub fn is_selectable(&self, block: Block) -> bool {
+        self.get(block).selectable
+    }
+
+    pub fn is_fluid(&self, block: Block) -> bool {
+        self.get(block).fluid
+    }
+
+    /// How much light `block` gives off
+    pub fn light(&self, block: Block) -> u8 {
+        self.get(block).light.min(MAX_LIGHT)
+    }
+
+    /// Whether the face of `block` touching `neighbor` can be seen
+    pub fn face_visible(&self, block: Block, neighbor: Block) -> bool {
+        // the faces between two of the same see through block (like glass) would only be clutter
+        self.is_transparent(neighbor) && block != neighbor
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn glass() -> BlockProperties {
+        BlockProperties {
+            transparent: true,
+            ..BlockProperties::solid("glass", FaceTextures::all(3))
+        }
+    }
+
+    #[test]
+    fn registered_blocks_keep_their_properties() {
+        let mut registry = BlockRegistry::new();
+        let grass = registry.register(BlockProperties::solid("grass", FaceTextures::top_bottom_sides(1, 2, 4)));
+        let glass = registry.register(glass());
+
+        assert_eq!(grass, Block::from_id(1));
+        assert_eq!(registry.by_name("grass"), Some(grass));
+        assert_eq!(registry.by_name("glass"), Some(glass));
+        assert_eq!(registry.by_name("air"), Some(Block::AIR));
+        assert_eq!(registry.by_name("dirt"), None);
+
+        assert!(registry.is_solid(grass) && !registry.is_transparent(grass) && registry.is_selectable(grass));
+        assert!(registry.is_solid(glass) && registry.is_transparent(glass));
+        assert!(!registry.is_solid(Block::AIR) && !registry.is_selectable(Block::AIR));
+
+        let water = registry.register(BlockProperties::fluid("water", FaceTextures::all(5)));
+        assert!(registry.is_fluid(water) && !registry.is_solid(water) && !registry.is_selectable(water));
+        assert!(!registry.is_fluid(grass) && !registry.is_fluid(Block::AIR));
+
+        let textures = registry.get(grass).textures;
+        assert_eq!(textures.get(BlockFace::PosY), 1);
+        assert_eq!(textures.get(BlockFace::NegY), 2);
+        assert!([BlockFace::PosX, BlockFace::NegX, BlockFace::PosZ, BlockFace::NegZ]
+            .into_iter()
+            .all(|face| textures.get(face) == 4));
+    }
+
+    #[test]
+    fn unknown_blocks_are_plain_solid_blocks() {
+        let registry = BlockRegistry::new();
+        let unknown = Block::from_id(1234);
+
+        assert_eq!(registry.get(unknown), &BlockProperties::UNKNOWN);
+        assert!(registry.is_solid(unknown) && registry.is_selectable(unknown));
+        assert!(!registry.is_transparent(unknown));
+    }
+
+    #[test]
+    fn faces_show_against_see_through_blocks() {
+        let mut registry = BlockRegistry::new();
+        let stone = registry.register(BlockProperties::solid("stone", FaceTextures::all(1)));
+        let glass = registry.register(glass());
+
+        assert!(registry.face_visible(stone, Block::AIR));
+        assert!(registry.face_visible(stone, glass));
+        assert!(registry.face_visible(glass, Block::AIR));
+        assert!(!registry.face_visible(stone, stone));
+        assert!(!registry.face_visible(glass, glass));
+        assert!(!registry.face_visible(glass, stone));
+    }
+
+    #[test]
+    #[should_panic(expected = "registered twice")]
+    fn names_are_unique() {
+        let mut registry = BlockRegistry::new();
+        registry.register(glass());
+        registry.register(glass());
+    }
+}
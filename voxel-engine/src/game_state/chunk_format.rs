@@ -0,0 +1,342 @@
+//! The on disk format of chunks and the region files that group them
+//!
+//! A chunk is stored as a palette of the distinct blocks in it followed by an index into
+//! the palette for every block, packed into as few bits as the palette needs.
+//! Most chunks are a handful of block types, so this is a fraction of the raw array.
+//!
+//! Every number is little endian.
+
+use std::path::{Path, PathBuf};
+use anyhow::{Context, Result};
+use bytemuck::{Pod, Zeroable};
+use thiserror::Error;
+use crate::game_state::coords::ChunkCoord;
+use crate::game_state::palette::{bits_for, packed_words, PalettedBlocks};
+use crate::game_state::world::{Block, Chunk, BLOCKS_PER_CHUNK};
+
+const CHUNK_MAGIC: [u8; 4] = *b"VXCK";
+const REGION_MAGIC: [u8; 4] = *b"VXRG";
+
+/// bumped whenever the layout changes, older versions have to stay readable
+const VERSION: u16 = 1;
+
+/// the width and depth of a region in chunks
+pub const REGION_SIZE: i32 = 32;
+
+#[derive(Debug, Error)]
+pub enum ChunkFormatError {
+    #[error("the data ends before the chunk does")]
+    Truncated,
+    #[error("{0} extra bytes after the chunk")]
+    TrailingBytes(usize),
+    #[error("not a chunk or region, the magic bytes are {0:?}")]
+    BadMagic([u8; 4]),
+    #[error("format version {0} is newer than this build understands")]
+    UnsupportedVersion(u16),
+    #[error("a palette of {len} blocks can't be packed into {bits} bits per block")]
+    BadPalette { len: u32, bits: u8 },
+    #[error("block {index} points past the end of the palette")]
+    IndexOutOfPalette { index: usize },
+}
+
+#[derive(Debug, Copy, Clone, Pod, Zeroable)]
+#[repr(C)]
+struct ChunkHeader {
+    magic: [u8; 4],
+    version: u16,
+    bits_per_index: u8,
+    _padding: u8,
+    palette_len: u32,
+}
+
+#[derive(Debug, Copy, Clone, Pod, Zeroable)]
+#[repr(C)]
+struct RegionHeader {
+    magic: [u8; 4],
+    version: u16,
+    _padding: u16,
+    chunk_count: u32,
+}
+
+#[derive(Debug, Copy, Clone, Pod, Zeroable)]
+#[repr(C)]
+struct RegionEntry {
+    x: i32,
+    z: i32,
+    len: u32,
+}
+
+struct Reader<'a> {
+    bytes: &'a [u8],
+}
+
+impl<'a> Reader<'a> {
+    fn take(&mut self, len: usize) -> Result<&'a [u8], ChunkFormatError> {
+        let (taken, rest) = self.bytes
+            .split_at_checked(len)
+            .ok_or(ChunkFormatError::Truncated)?;
+        self.bytes = rest;
+        Ok(taken)
+    }
+
+    fn read<T: Pod>(&mut self) -> Result<T, ChunkFormatError> {
+        self.take(size_of::<T>()).map(bytemuck::pod_read_unaligned)
+    }
+
+    fn array<const N: usize>(&mut self) -> Result<[u8; N], ChunkFormatError> {
+        self.read()
+    }
+
+    fn finish(self) -> Result<(), ChunkFormatError> {
+        match self.bytes.len() {
+            0 => Ok(()),
+            extra => Err(ChunkFormatError::TrailingBytes(extra)),
+        }
+    }
+}
+
+fn check_header(magic: [u8; 4], expected: [u8; 4], version: u16) -> Result<(), ChunkFormatError> {
+    if magic != expected {
+        return Err(ChunkFormatError::BadMagic(magic));
+    }
+
+    match u16::from_le(version) {
+        VERSION => Ok(()),
+        version => Err(ChunkFormatError::UnsupportedVersion(version)),
+    }
+}
+
+pub fn encode_chunk(chunk: &Chunk) -> Vec<u8> {
+    // compressed from scratch, leaving out blocks that were in the chunk once but not anymore
+    let blocks = PalettedBlocks::from_blocks(chunk.blocks().iter());
+    let (palette, words) = (blocks.palette(), blocks.words());
+
+    let header = ChunkHeader {
+        magic: CHUNK_MAGIC,
+        version: VERSION.to_le(),
+        bits_per_index: blocks.bits_per_block(),
+        _padding: 0,
+        palette_len: blocks.palette_len().to_le(),
+    };
+
+    let mut bytes = Vec::with_capacity(
+        size_of::<ChunkHeader>() + palette.len() * size_of::<u16>() + size_of_val(words)
+    );
+    bytes.extend_from_slice(bytemuck::bytes_of(&header));
+    for block in palette {
+        bytes.extend_from_slice(&block.id().to_le_bytes());
+    }
+    for word in words {
+        bytes.extend_from_slice(&word.to_le_bytes());
+    }
+
+    bytes
+}
+
+pub fn decode_chunk(bytes: &[u8]) -> Result<Chunk, ChunkFormatError> {
+    let mut reader = Reader { bytes };
+    let chunk = read_chunk(&mut reader)?;
+    reader.finish()?;
+    Ok(chunk)
+}
+
+fn read_chunk(reader: &mut Reader) -> Result<Chunk, ChunkFormatError> {
+    let header = reader.read::<ChunkHeader>()?;
+    check_header(header.magic, CHUNK_MAGIC, header.version)?;
+
+    let palette_len = u32::from_le(header.palette_len);
+    let bits = header.bits_per_index;
+    if !(1..=BLOCKS_PER_CHUNK as u32).contains(&palette_len) || bits != bits_for(palette_len) {
+        return Err(ChunkFormatError::BadPalette { len: palette_len, bits });
+    }
+
+    let palette = reader
+        .take(palette_len as usize * size_of::<u16>())?
+        .chunks_exact(size_of::<u16>())
+        .map(|id| Block::from_id(u16::from_le_bytes([id[0], id[1]])))
+        .collect::<Vec<_>>();
+
+    let words = (0..packed_words(bits))
+        .map(|_| reader.array().map(u64::from_le_bytes))
+        .collect::<Result<Box<[u64]>, _>>()?;
+
+    PalettedBlocks::from_parts(palette, words)
+        .map(Chunk::from_blocks)
+        .map_err(|index| ChunkFormatError::IndexOutOfPalette { index })
+}
+
+/// The region a chunk is saved in
+pub fn region_of(chunk: ChunkCoord) -> (i32, i32) {
+    (chunk.chunk_x().div_euclid(REGION_SIZE), chunk.chunk_z().div_euclid(REGION_SIZE))
+}
+
+pub fn region_path(save_dir: &Path, (x, z): (i32, i32)) -> PathBuf {
+    save_dir.join(format!("r.{x}.{z}.region"))
+}
+
+pub fn encode_region<'a>(chunks: impl IntoIterator<Item = (ChunkCoord, &'a Chunk)>) -> Vec<u8> {
+    let mut count = 0_u32;
+    let mut body = Vec::new();
+    for (coord, chunk) in chunks {
+        let encoded = encode_chunk(chunk);
+        let entry = RegionEntry {
+            x: coord.chunk_x().to_le(),
+            z: coord.chunk_z().to_le(),
+            len: u32::try_from(encoded.len()).expect("an encoded chunk is well under 4GiB").to_le(),
+        };
+        body.extend_from_slice(bytemuck::bytes_of(&entry));
+        body.extend_from_slice(&encoded);
+        count += 1;
+    }
+
+    let header = RegionHeader {
+        magic: REGION_MAGIC,
+        version: VERSION.to_le(),
+        _padding: 0,
+        chunk_count: count.to_le(),
+    };
+
+    let mut bytes = Vec::with_capacity(size_of::<RegionHeader>() + body.len());
+    bytes.extend_from_slice(bytemuck::bytes_of(&header));
+    bytes.extend_from_slice(&body);
+    bytes
+}
+
+pub fn decode_region(bytes: &[u8]) -> Result<Vec<(ChunkCoord, Chunk)>, ChunkFormatError> {
+    let mut reader = Reader { bytes };
+    let header = reader.read::<RegionHeader>()?;
+    check_header(header.magic, REGION_MAGIC, header.version)?;
+
+    let chunks = (0..u32::from_le(header.chunk_count))
+        .map(|_| {
+            let entry = reader.read::<RegionEntry>()?;
+            let coord = ChunkCoord::from_xz(i32::from_le(entry.x), i32::from_le(entry.z));
+            let chunk = decode_chunk(reader.take(u32::from_le(entry.len) as usize)?)?;
+            Ok((coord, chunk))
+        })
+        .collect::<Result<Vec<_>, _>>()?;
+
+    reader.finish()?;
+    Ok(chunks)
+}
+
+pub async fn save_region<'a>(
+    path: impl Into<PathBuf>,
+    chunks: impl IntoIterator<Item = (ChunkCoord, &'a Chunk)>
+) -> Result<()> {
+    let path = path.into();
+    let bytes = encode_region(chunks);
+    voxel_runtime::fs::write(&path, bytes)
+        .await
+        .with_context(|| format!("failed to write region {}", path.display()))
+}
+
+pub async fn load_region(path: impl Into<PathBuf>) -> Result<Vec<(ChunkCoord, Chunk)>> {
+    let path = path.into();
+    let bytes = voxel_runtime::fs::read(&path)
+        .await
+        .with_context(|| format!("failed to read region {}", path.display()))?;
+
+    decode_region(&bytes).with_context(|| format!("region {} is corrupt", path.display()))
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::game_state::coords::BlockCoord;
+    use super::*;
+
+    fn layered_chunk() -> Chunk {
+        let mut chunk = Chunk::empty();
+        for x in 0..16 {
+            for z in 0..16 {
+                for y in 0..64 {
+                    let block = match y {
+                        0 => 7,
+                        1..60 => 1,
+                        60..63 => 2,
+                        _ => 3,
+                    };
+                    chunk.set(BlockCoord::from_xyz(x, y, z), Block::from_id(block));
+                }
+            }
+        }
+        chunk.set(BlockCoord::from_xyz(4, 63, 9), Block::from_id(u16::MAX));
+        chunk
+    }
+
+    #[test]
+    fn chunks_round_trip() {
+        let mut noisy = Chunk::empty();
+        for (i, block) in (0..BLOCKS_PER_CHUNK).step_by(7).enumerate() {
+            let [x, y, z] = [block % 16, block / 256, block / 16 % 16].map(|c| c as u8);
+            noisy.set(BlockCoord::from_xyz(x, y, z), Block::from_id((i % 300) as u16));
+        }
+
+        for chunk in [Chunk::empty(), layered_chunk(), noisy] {
+            let decoded = decode_chunk(&encode_chunk(&chunk)).unwrap();
+            assert!(decoded.blocks() == chunk.blocks());
+        }
+    }
+
+    #[test]
+    fn low_entropy_chunks_are_small() {
+        let naive = BLOCKS_PER_CHUNK * size_of::<Block>();
+
+        let empty = encode_chunk(&Chunk::empty());
+        assert_eq!(empty.len(), size_of::<ChunkHeader>() + size_of::<u16>());
+
+        // 6 block types fit in 3 bits, 21 to a word
+        let layered = encode_chunk(&layered_chunk());
+        assert_eq!(layered[6], 3);
+        assert!(layered.len() * 5 < naive, "{} vs {naive}", layered.len());
+    }
+
+    #[test]
+    fn corrupt_chunks_are_rejected() {
+        let bytes = encode_chunk(&layered_chunk());
+
+        assert!(matches!(decode_chunk(&bytes[..bytes.len() - 1]), Err(ChunkFormatError::Truncated)));
+        assert!(matches!(decode_chunk(&[bytes.as_slice(), &[0]].concat()), Err(ChunkFormatError::TrailingBytes(1))));
+
+        let mut bad_magic = bytes.clone();
+        bad_magic[0] = b'X';
+        assert!(matches!(decode_chunk(&bad_magic), Err(ChunkFormatError::BadMagic(_))));
+
+        let mut future = bytes.clone();
+        future[4] = 2;
+        assert!(matches!(decode_chunk(&future), Err(ChunkFormatError::UnsupportedVersion(2))));
+
+        let mut wrong_bits = bytes.clone();
+        wrong_bits[6] = 4;
+        assert!(matches!(decode_chunk(&wrong_bits), Err(ChunkFormatError::BadPalette { .. })));
+
+        // 6 entries in the palette, so index 7 doesn't exist
+        let mut out_of_palette = bytes;
+        let first_word = size_of::<ChunkHeader>() + 6 * size_of::<u16>();
+        out_of_palette[first_word] = 0b111;
+        assert!(matches!(decode_chunk(&out_of_palette), Err(ChunkFormatError::IndexOutOfPalette { index: 0 })));
+    }
+
+    #[test]
+    fn regions_round_trip_through_the_disk() {
+        let chunks = [
+            (ChunkCoord::from_xz(-32, -1), layered_chunk()),
+            (ChunkCoord::from_xz(-1, -32), Chunk::empty()),
+        ];
+        assert!(chunks.iter().all(|(coord, _)| region_of(*coord) == (-1, -1)));
+
+        let dir = std::env::temp_dir().join(format!("voxel-region-test-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = region_path(&dir, (-1, -1));
+
+        voxel_runtime::block_on(save_region(&path, chunks.iter().map(|(coord, chunk)| (*coord, chunk)))).unwrap();
+        let loaded = voxel_runtime::block_on(load_region(&path)).unwrap();
+        std::fs::remove_dir_all(&dir).unwrap();
+
+        assert_eq!(loaded.len(), chunks.len());
+        for ((coord, chunk), (loaded_coord, loaded_chunk)) in chunks.iter().zip(&loaded) {
+            assert_eq!(coord, loaded_coord);
+            assert!(chunk.blocks() == loaded_chunk.blocks());
+        }
+    }
+}
@@ -1,11 +1,19 @@
+use std::fmt::{Debug, Formatter};
 use std::ops::{Add, AddAssign};
-use glam::{u8vec3, U8Vec3};
+use glam::{u8vec3, IVec3, U8Vec3};
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
 use voxel_maths::fixed_point::FixedPoint;
 use voxel_maths::{i48, FixedPointVec3}; 
 use voxel_maths::i48_int::i48;
+use crate::game_state::blocks::BlockFace;
+use crate::game_state::world::{block_y, CHUNK_SIZE};
 
 
-#[derive(Copy, Clone, Hash, Eq, PartialEq)]
+#[derive(Debug, Copy, Clone, Hash, Eq, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[cfg_attr(feature = "serde", serde(from = "[i32; 2]", into = "[i32; 2]"))]
 #[repr(C, align(8))]
 pub struct ChunkCoord {
     x: i32,
@@ -27,6 +35,136 @@ impl ChunkCoord {
     pub fn z(&self) -> i48 {
         i48::from(self.z) * i48!(16)
     }
+
+    /// The x coordinate counted in chunks rather than blocks
+    #[inline(always)]
+    pub fn chunk_x(&self) -> i32 {
+        self.x
+    }
+
+    /// The z coordinate counted in chunks rather than blocks
+    #[inline(always)]
+    pub fn chunk_z(&self) -> i32 {
+        self.z
+    }
+
+    /// The chunk `dx` chunks along x and `dz` along z, `None` past the edge of the world
+    pub fn offset(self, dx: i32, dz: i32) -> Option<Self> {
+        Some(Self::from_xz(self.x.checked_add(dx)?, self.z.checked_add(dz)?))
+    }
+
+    /// The chunk next to this one on its `face` side, `None` past the edge of the world
+    /// and above or below it, where a chunk already reaches the top and bottom of the world
+    pub fn neighbor(self, face: BlockFace) -> Option<Self> {
+        match face.offset() {
+            IVec3 { y: 0, x, z } => self.offset(x, z),
+            _ => None,
+        }
+    }
+
+    /// How many chunks apart two chunks are, counting diagonal steps as one
+    pub fn chebyshev_distance(self, other: Self) -> u32 {
+        let dx = (self.x as i64 - other.x as i64).unsigned_abs();
+        let dz = (self.z as i64 - other.z as i64).unsigned_abs();
+        dx.max(dz) as u32
+    }
+
+    /// Every chunk at most `radius` chunks away from `self` (as in [`ChunkCoord::chebyshev_distance`]),
+    /// nearest first so whatever gets loaded from it fills in around the player
+    pub fn spiral(self, radius: u32) -> ChunkSpiral {
+        ChunkSpiral {
+            center: self,
+            radius,
+            ring: 0,
+            index: 0,
+        }
+    }
+}
+
+/// See [`ChunkCoord::spiral`]
+///
+/// walks square rings around the center outwards, ring `k` being the `8k` chunks
+/// exactly `k` away, so the order never moves further away and nothing gets allocated
+#[derive(Debug, Clone)]
+pub struct ChunkSpiral {
+    center: ChunkCoord,
+    radius: u32,
+    ring: u32,
+    /// position along the current ring
+    index: u32,
+}
+
+impl ChunkSpiral {
+    fn ring_len(ring: u32) -> u64 {
+        match ring {
+            0 => 1,
+            ring => 8 * ring as u64,
+        }
+    }
+
+    /// The offset from the center of the chunk `index` steps along `ring`,
+    /// starting in the -x -z corner and going counterclockwise
+    fn ring_offset(ring: u32, index: u32) -> (i64, i64) {
+        if ring == 0 {
+            return (0, 0);
+        }
+
+        let ring = ring as i64;
+        let side_len = 2 * ring;
+        let (side, along) = (index as i64 / side_len, index as i64 % side_len);
+        match side {
+            0 => (-ring + along, -ring),
+            1 => (ring, -ring + along),
+            2 => (ring - along, ring),
+            _ => (-ring, ring - along),
+        }
+    }
+
+    /// The whole square minus the square of the rings already done and the part of this one,
+    /// `None` if the square is too big to count
+    fn remaining(&self) -> Option<u64> {
+        if self.ring > self.radius {
+            return Some(0);
+        }
+
+        let square = |side: u64| side.checked_mul(side);
+        let total = square(2 * u64::from(self.radius) + 1)?;
+        let inner_rings = match self.ring {
+            0 => 0,
+            ring => square(2 * u64::from(ring) - 1)?,
+        };
+        total.checked_sub(inner_rings.checked_add(u64::from(self.index))?)
+    }
+}
+
+impl Iterator for ChunkSpiral {
+    type Item = ChunkCoord;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        while self.ring <= self.radius {
+            let (dx, dz) = Self::ring_offset(self.ring, self.index);
+
+            self.index += 1;
+            if self.index as u64 == Self::ring_len(self.ring) {
+                self.index = 0;
+                self.ring += 1;
+            }
+
+            // the world ends at the edge of an i32, there's nothing past it to yield
+            let x = i32::try_from(self.center.x as i64 + dx);
+            let z = i32::try_from(self.center.z as i64 + dz);
+            if let (Ok(x), Ok(z)) = (x, z) {
+                return Some(ChunkCoord::from_xz(x, z));
+            }
+        }
+
+        None
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        // chunks past the edge of the world get skipped, so this is only an upper bound
+        (0, self.remaining().and_then(|remaining| usize::try_from(remaining).ok()))
+    }
 }
 
 #[derive(Copy, Clone, Hash, Eq, PartialEq)]
@@ -38,6 +176,7 @@ pub struct ChunkRelativeXZ {
 }
 
 impl ChunkRelativeXZ {
+    #[expect(dead_code, reason = "blocks are only ever placed at the corner of a chunk through BlockCoord::ZERO")]
     pub const ZERO: Self = Self::from_xz(0, 0);
 
     pub const fn from_xz(x: u8, z: u8) -> Self {
@@ -57,18 +196,24 @@ impl ChunkRelativeXZ {
     }
 }
 
+/// The type a block's height is stored as, wide enough for every block from the bottom of the world
+/// up to [`WORLD_HEIGHT`](crate::game_state::world::WORLD_HEIGHT)
+pub type BlockY = u8;
+
 #[derive(Copy, Clone, Hash, Eq, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[cfg_attr(feature = "serde", serde(try_from = "[u8; 3]", into = "[u8; 3]"))]
 #[repr(C, align(2))]
 pub struct BlockCoord {
     xz: ChunkRelativeXZ,
-    y: u8
+    y: BlockY
 }
 
 impl BlockCoord {
     pub const ZERO: Self = Self::from_xyz(0, 0, 0);
 
     #[inline(always)]
-    pub const fn from_xyz(x: u8, y: u8, z: u8) -> Self {
+    pub const fn from_xyz(x: u8, y: BlockY, z: u8) -> Self {
         Self {
             xz: ChunkRelativeXZ::from_xz(x, z),
             y
@@ -86,10 +231,22 @@ impl BlockCoord {
     }
 
     #[inline(always)]
-    pub fn y(self) -> u8 {
+    pub fn y(self) -> BlockY {
         self.y
     }
 
+    /// The block next to this one on its `face` side, with how many chunks over along x and z it is,
+    /// `(0, 0)` when it's in the same chunk, `None` above or below the world
+    pub fn neighbor(self, face: BlockFace) -> Option<(Self, (i32, i32))> {
+        const SIZE: i32 = CHUNK_SIZE as i32;
+
+        let position = self.xyz().as_ivec3() + face.offset();
+        let y = block_y(i64::from(position.y))?;
+        let (x, z) = (position.x.rem_euclid(SIZE), position.z.rem_euclid(SIZE));
+        let chunk = (position.x.div_euclid(SIZE), position.z.div_euclid(SIZE));
+        Some((Self::from_xyz(x as u8, y, z as u8), chunk))
+    }
+
     #[inline(always)]
     pub fn xyz(self) -> U8Vec3 {
         u8vec3(
@@ -101,6 +258,8 @@ impl BlockCoord {
 }
 
 #[derive(Copy, Clone, Hash, Eq, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[cfg_attr(feature = "serde", serde(try_from = "(i48, BlockY, i48)", into = "(i48, BlockY, i48)"))]
 pub struct AbsoluteBlockCoord {
     chunk: ChunkCoord,
     block_coord: BlockCoord
@@ -109,8 +268,13 @@ pub struct AbsoluteBlockCoord {
 impl AbsoluteBlockCoord {
     pub const ZERO: Self = Self::from_xyz(i48!(0), 0, i48!(0));
     
-    pub const fn from_xyz(x: i48, y: u8, z: i48) -> Self {
-        const { assert!(size_of::<Self>() < size_of::<(i48, u8, i48)>()) }
+    /// The block at `block` inside of `chunk`
+    pub const fn new(chunk: ChunkCoord, block: BlockCoord) -> Self {
+        Self { chunk, block_coord: block }
+    }
+
+    pub const fn from_xyz(x: i48, y: BlockY, z: i48) -> Self {
+        const { assert!(size_of::<Self>() < size_of::<(i48, BlockY, i48)>()) }
         
         const fn separate(coord: i48) -> (i32, u8) {
             let num = coord.as_i64();
@@ -147,17 +311,45 @@ impl AbsoluteBlockCoord {
     }
 
     #[inline(always)]
-    pub fn y(&self) -> u8 {
+    pub fn y(&self) -> BlockY {
         self.block_coord.y
     }
 
     #[inline(always)]
-    pub fn xyz(&self) -> (i48, u8, i48) {
+    pub fn xyz(&self) -> (i48, BlockY, i48) {
         (self.x(), self.y(), self.z())
     }
+
+    /// The block `dx`, `dy` and `dz` blocks away, `None` above or below the world or past its edge
+    pub fn offset(&self, dx: i64, dy: i64, dz: i64) -> Option<Self> {
+        let (x, y, z) = self.xyz();
+        let [x, z] = [(x, dx), (z, dz)].map(|(coord, by)| coord.as_i64().checked_add(by).and_then(i48::new));
+        let y = i64::from(y).checked_add(dy).and_then(block_y)?;
+        Self::try_from((x?, y, z?)).ok()
+    }
+
+    /// The block next to this one on its `face` side, see [`AbsoluteBlockCoord::offset`]
+    pub fn neighbor(&self, face: BlockFace) -> Option<Self> {
+        let offset = face.offset().as_i64vec3();
+        self.offset(offset.x, offset.y, offset.z)
+    }
+
+    /// The chunk the block is in
+    #[inline(always)]
+    pub fn chunk(&self) -> ChunkCoord {
+        self.chunk
+    }
+
+    /// Where the block is inside of its chunk
+    #[inline(always)]
+    pub fn block(&self) -> BlockCoord {
+        self.block_coord
+    }
 }
 
-#[derive(Copy, Clone, PartialEq)]
+#[derive(Debug, Copy, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[cfg_attr(feature = "serde", serde(from = "[FixedPoint; 3]", into = "[FixedPoint; 3]"))]
 pub struct AbsoluteCoord {
     x: FixedPoint,
     y: FixedPoint,
@@ -223,4 +415,279 @@ impl AddAssign for AbsoluteCoord {
     fn add_assign(&mut self, rhs: Self) {
         *self = (*self) + rhs
     }
-}
\ No newline at end of file
+}
+
+// the compact forms the coordinates are saved as,
+// anything loaded from a save file is checked before it becomes a coordinate
+
+/// A saved coordinate that can't exist
+#[derive(Debug, Error)]
+pub enum InvalidCoord {
+    #[error("block x = {x}, z = {z} is outside of its chunk, both have to be below 16")]
+    OutsideChunk { x: u8, z: u8 },
+    #[error("block x = {x}, z = {z} is in a chunk too far out to be addressed")]
+    OutsideWorld { x: i48, z: i48 },
+}
+
+impl From<[i32; 2]> for ChunkCoord {
+    fn from([x, z]: [i32; 2]) -> Self {
+        Self::from_xz(x, z)
+    }
+}
+
+impl From<ChunkCoord> for [i32; 2] {
+    fn from(chunk: ChunkCoord) -> Self {
+        [chunk.chunk_x(), chunk.chunk_z()]
+    }
+}
+
+impl TryFrom<[u8; 3]> for BlockCoord {
+    type Error = InvalidCoord;
+
+    fn try_from([x, y, z]: [u8; 3]) -> Result<Self, Self::Error> {
+        if x >= 16 || z >= 16 {
+            return Err(InvalidCoord::OutsideChunk { x, z });
+        }
+
+        Ok(Self::from_xyz(x, y, z))
+    }
+}
+
+impl From<BlockCoord> for [u8; 3] {
+    fn from(block: BlockCoord) -> Self {
+        block.xyz().to_array()
+    }
+}
+
+impl Debug for BlockCoord {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("BlockCoord")
+            .field("x", &self.x())
+            .field("y", &self.y())
+            .field("z", &self.z())
+            .finish()
+    }
+}
+
+impl Debug for AbsoluteBlockCoord {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("AbsoluteBlockCoord")
+            .field("x", &self.x())
+            .field("y", &self.y())
+            .field("z", &self.z())
+            .finish()
+    }
+}
+
+impl TryFrom<(i48, BlockY, i48)> for AbsoluteBlockCoord {
+    type Error = InvalidCoord;
+
+    fn try_from((x, y, z): (i48, BlockY, i48)) -> Result<Self, Self::Error> {
+        // from_xyz clamps chunks that don't fit in an i32, a save file should never have one
+        let fits = |coord: i48| i32::try_from(coord.as_i64().div_euclid(16)).is_ok();
+        if !fits(x) || !fits(z) {
+            return Err(InvalidCoord::OutsideWorld { x, z });
+        }
+
+        Ok(Self::from_xyz(x, y, z))
+    }
+}
+
+impl From<AbsoluteBlockCoord> for (i48, BlockY, i48) {
+    fn from(coord: AbsoluteBlockCoord) -> Self {
+        coord.xyz()
+    }
+}
+
+impl From<[FixedPoint; 3]> for AbsoluteCoord {
+    fn from([x, y, z]: [FixedPoint; 3]) -> Self {
+        Self::from_xyz(x, y, z)
+    }
+}
+
+impl From<AbsoluteCoord> for [FixedPoint; 3] {
+    fn from(coord: AbsoluteCoord) -> Self {
+        [coord.x(), coord.y(), coord.z()]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use ahash::HashSet;
+    use super::*;
+
+    #[test]
+    fn spiral_covers_the_square() {
+        let center = ChunkCoord::from_xz(-7, 12);
+        for radius in [0, 1, 2, 5] {
+            let chunks = center.spiral(radius).collect::<Vec<_>>();
+            let unique = chunks.iter().copied().collect::<HashSet<_>>();
+
+            let side = 2 * radius as usize + 1;
+            assert_eq!(chunks.len(), side * side);
+            assert_eq!(unique.len(), chunks.len(), "a chunk was yielded twice");
+            assert!(chunks.iter().all(|chunk| chunk.chebyshev_distance(center) <= radius));
+            assert_eq!(chunks[0], center);
+        }
+    }
+
+    #[test]
+    fn spiral_is_nearest_first() {
+        let center = ChunkCoord::ZERO;
+        let distances = center
+            .spiral(6)
+            .map(|chunk| chunk.chebyshev_distance(center))
+            .collect::<Vec<_>>();
+
+        assert!(distances.is_sorted(), "{distances:?}");
+        assert_eq!(distances.last(), Some(&6));
+    }
+
+    #[test]
+    fn spiral_size_hint() {
+        let mut spiral = ChunkCoord::ZERO.spiral(2);
+        assert_eq!(spiral.size_hint(), (0, Some(25)));
+        spiral.nth(3);
+        assert_eq!(spiral.size_hint(), (0, Some(21)));
+        spiral.nth(20);
+        assert_eq!(spiral.size_hint(), (0, Some(0)));
+        assert_eq!(spiral.next(), None);
+        assert_eq!(spiral.size_hint(), (0, Some(0)));
+
+        // counted without walking the rings, and too many to count is no upper bound at all
+        assert_eq!(ChunkCoord::ZERO.spiral(1 << 20).size_hint(), (0, Some(((1 << 21) + 1) * ((1 << 21) + 1))));
+        assert_eq!(ChunkCoord::ZERO.spiral(u32::MAX).size_hint(), (0, None));
+    }
+
+    #[test]
+    fn spiral_stops_at_the_edge_of_the_world() {
+        let corner = ChunkCoord::from_xz(i32::MAX, i32::MIN);
+        let chunks = corner.spiral(1).collect::<Vec<_>>();
+        // only a quarter of the square is inside the world
+        assert_eq!(chunks.len(), 4);
+        assert!(chunks.contains(&ChunkCoord::from_xz(i32::MAX - 1, i32::MIN + 1)));
+    }
+
+    #[test]
+    #[cfg(feature = "serde")]
+    fn coords_round_trip() {
+        fn round_trip<T>(value: T)
+        where
+            T: Serialize + for<'de> Deserialize<'de> + PartialEq + Copy,
+        {
+            let json = serde_json::to_string(&value).unwrap();
+            assert!(serde_json::from_str::<T>(&json).unwrap() == value, "{json}");
+
+            let bytes = bincode::serialize(&value).unwrap();
+            assert!(bincode::deserialize::<T>(&bytes).unwrap() == value);
+        }
+
+        for chunk in [ChunkCoord::ZERO, ChunkCoord::from_xz(i32::MIN, i32::MAX), ChunkCoord::from_xz(-3, 8)] {
+            round_trip(chunk);
+        }
+        for block in [BlockCoord::ZERO, BlockCoord::from_xyz(15, 255, 15), BlockCoord::from_xyz(3, 70, 9)] {
+            round_trip(block);
+        }
+        for block in [AbsoluteBlockCoord::ZERO, AbsoluteBlockCoord::from_xyz(i48!(-17), 64, i48!(1_000_003))] {
+            round_trip(block);
+        }
+        round_trip(AbsoluteCoord::from_xyz(
+            FixedPoint::from_f32(-12.625),
+            FixedPoint::from_f32(70.5),
+            FixedPoint::MAX
+        ));
+    }
+
+    #[test]
+    #[cfg(feature = "serde")]
+    fn coords_serialize_compactly() {
+        assert_eq!(serde_json::to_string(&ChunkCoord::from_xz(-3, 8)).unwrap(), "[-3,8]");
+        assert_eq!(serde_json::to_string(&BlockCoord::from_xyz(1, 2, 3)).unwrap(), "[1,2,3]");
+
+        let block = AbsoluteBlockCoord::from_xyz(i48!(-17), 64, i48!(40));
+        assert_eq!(serde_json::to_string(&block).unwrap(), "[-17,64,40]");
+        assert_eq!(bincode::serialize(&block).unwrap().len(), 8 + 1 + 8);
+    }
+
+    #[test]
+    #[cfg(feature = "serde")]
+    fn invalid_coords_are_rejected() {
+        assert!(serde_json::from_str::<BlockCoord>("[16,0,0]").is_err());
+        assert!(serde_json::from_str::<BlockCoord>("[0,0,200]").is_err());
+        assert!(serde_json::from_str::<BlockCoord>("[0,256,0]").is_err());
+
+        let too_far = format!("[{},0,0]", (i32::MAX as i64 + 1) * 16);
+        assert!(serde_json::from_str::<AbsoluteBlockCoord>(&too_far).is_err());
+        let edge = format!("[{},0,0]", i32::MIN as i64 * 16);
+        assert!(serde_json::from_str::<AbsoluteBlockCoord>(&edge).is_ok());
+    }
+
+    #[test]
+    fn neighbors_cross_chunk_boundaries() {
+        // inside the chunk
+        let block = BlockCoord::from_xyz(7, 64, 7);
+        for face in BlockFace::ALL {
+            let (neighbor, chunk) = block.neighbor(face).unwrap();
+            assert_eq!(chunk, (0, 0));
+            assert_eq!(neighbor.xyz().as_ivec3() - block.xyz().as_ivec3(), face.offset());
+            assert_eq!(neighbor.neighbor(face.opposite()), Some((block, (0, 0))));
+        }
+
+        // off the edges of the chunk and onto the other side of the next one
+        let corner = BlockCoord::from_xyz(15, 0, 0);
+        assert_eq!(corner.neighbor(BlockFace::PosX), Some((BlockCoord::from_xyz(0, 0, 0), (1, 0))));
+        assert_eq!(corner.neighbor(BlockFace::NegZ), Some((BlockCoord::from_xyz(15, 0, 15), (0, -1))));
+        assert_eq!(corner.neighbor(BlockFace::NegY), None);
+        assert_eq!(BlockCoord::from_xyz(0, 255, 0).neighbor(BlockFace::PosY), None);
+
+        // the chunk it says to move to is the one the absolute coordinate ends up in
+        let absolute = AbsoluteBlockCoord::from_xyz(i48!(-17), 3, i48!(32));
+        for face in BlockFace::ALL {
+            let (block, (dx, dz)) = absolute.block().neighbor(face).unwrap();
+            let neighbor = absolute.neighbor(face).unwrap();
+            assert_eq!(neighbor.block(), block);
+            assert_eq!(Some(neighbor.chunk()), absolute.chunk().offset(dx, dz));
+            if (dx, dz) != (0, 0) {
+                assert_eq!(absolute.chunk().neighbor(face), Some(neighbor.chunk()));
+            }
+        }
+
+        let chunk = ChunkCoord::from_xz(i32::MAX, 4);
+        assert_eq!(chunk.neighbor(BlockFace::NegX), Some(ChunkCoord::from_xz(i32::MAX - 1, 4)));
+        assert_eq!(chunk.neighbor(BlockFace::PosZ), Some(ChunkCoord::from_xz(i32::MAX, 5)));
+        assert_eq!(chunk.neighbor(BlockFace::PosX), None);
+        assert_eq!(chunk.neighbor(BlockFace::PosY), None);
+    }
+
+    #[test]
+    fn offsets_compose() {
+        let start = AbsoluteBlockCoord::from_xyz(i48!(-1), 100, i48!(15));
+        let steps = [(1, 0, 0), (-20, 5, 3), (0, -100, 17), (33, 50, -35)];
+
+        let mut walked = start;
+        for (dx, dy, dz) in steps {
+            walked = walked.offset(dx, dy, dz).unwrap();
+        }
+        let total = steps.iter().fold((0, 0, 0), |(x, y, z), (dx, dy, dz)| (x + dx, y + dy, z + dz));
+        assert_eq!(Some(walked), start.offset(total.0, total.1, total.2));
+        assert_eq!(walked.xyz(), (i48!(13), 55, i48!(0)));
+        assert_eq!(start.offset(0, 0, 0), Some(start));
+        assert_eq!(walked.offset(-total.0, -total.1, -total.2), Some(start));
+
+        // faces are single steps
+        let stepped = [BlockFace::PosX, BlockFace::PosY, BlockFace::PosY, BlockFace::NegZ]
+            .into_iter()
+            .try_fold(start, |block, face| block.neighbor(face));
+        assert_eq!(stepped, start.offset(1, 2, -1));
+
+        // nothing past the top, bottom or edges of the world
+        assert_eq!(start.offset(0, 156, 0), None);
+        assert_eq!(start.offset(0, -101, 0), None);
+        assert_eq!(start.offset(i64::MAX, 0, 0), None);
+        assert_eq!(start.offset(0, i64::MAX, 0), None);
+        assert_eq!(start.offset(i64::MIN, i64::MIN, i64::MIN), None);
+        let edge = AbsoluteBlockCoord::from_xyz(i48::new(i32::MAX as i64 * 16 + 15).unwrap(), 0, i48!(0));
+        assert_eq!(edge.neighbor(BlockFace::PosX), None);
+        assert_eq!(edge.neighbor(BlockFace::NegX).map(|block| block.x()), i48::new(i32::MAX as i64 * 16 + 14));
+    }
+}
@@ -0,0 +1,155 @@
+use std::f32::consts::TAU;
+use std::time::Duration;
+use glam::{vec3, Vec3};
+use crate::game_state::light::Light;
+use crate::settings::DayNightSettings;
+
+/// Where in the day the world is, as a fraction of a day starting at midnight
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct TimeOfDay(f32);
+
+impl TimeOfDay {
+    #[cfg_attr(not(test), expect(dead_code, reason = "nothing sets the time yet"))]
+    pub const MIDNIGHT: Self = Self(0.0);
+    pub const DAWN: Self = Self(0.25);
+    #[cfg_attr(not(test), expect(dead_code, reason = "nothing sets the time yet"))]
+    pub const NOON: Self = Self(0.5);
+    #[cfg_attr(not(test), expect(dead_code, reason = "nothing sets the time yet"))]
+    pub const DUSK: Self = Self(0.75);
+
+    /// `fraction` wraps around, so 1.25 is the next day's dawn
+    pub fn new(fraction: f32) -> Self {
+        Self(fraction.rem_euclid(1.0))
+    }
+
+    #[cfg_attr(not(test), expect(dead_code, reason = "nothing sets the time yet"))]
+    pub fn fraction(self) -> f32 {
+        self.0
+    }
+
+    /// The direction towards the sun, which rises in +x, is overhead at noon and sets in -x
+    pub fn sun_direction(self) -> Vec3 {
+        let angle = (self.0 - Self::DAWN.0) * TAU;
+        vec3(angle.cos(), angle.sin(), 0.0)
+    }
+
+    /// How bright the day is, 0 through the night up to 1 once the sun is well up
+    fn daylight(self) -> f32 {
+        let height = self.sun_direction().y;
+        ((height + 0.1) / 0.35).clamp(0.0, 1.0)
+    }
+
+    /// How close the sun is to the horizon, 1 right on it
+    fn twilight(self) -> f32 {
+        1.0 - (self.sun_direction().y.abs() / 0.3).min(1.0)
+    }
+
+    /// The color of the sun's light, a dim blue moonlight at night and warm around sunrise and sunset
+    pub fn sun_color(self) -> Vec3 {
+        const NIGHT: Vec3 = vec3(0.1, 0.12, 0.22);
+        const DAY: Vec3 = vec3(1.0, 1.0, 0.95);
+        const TWILIGHT: Vec3 = vec3(1.0, 0.6, 0.35);
+
+        NIGHT.lerp(DAY, self.daylight()).lerp(TWILIGHT, self.twilight() * self.daylight())
+    }
+
+    /// The sun (or the moon at night) as the light it casts
+    pub fn sun(self) -> Light {
+        Light::Directional { direction: self.sun_direction(), color: self.sun_color(), intensity: 1.0 }
+    }
+
+    /// The color of the sky, what the screen is cleared to
+    pub fn sky_color(self) -> Vec3 {
+        const NIGHT: Vec3 = vec3(0.01, 0.01, 0.04);
+        const DAY: Vec3 = vec3(0.45, 0.7, 1.0);
+        const TWILIGHT: Vec3 = vec3(0.9, 0.45, 0.25);
+
+        NIGHT.lerp(DAY, self.daylight()).lerp(TWILIGHT, self.twilight() * 0.6)
+    }
+}
+
+pub struct DayCycle {
+    time: TimeOfDay,
+    settings: DayNightSettings,
+}
+
+impl Default for DayCycle {
+    fn default() -> Self {
+        Self {
+            // start in the morning so the world isn't dark right away
+            time: TimeOfDay::new(0.3),
+            settings: DayNightSettings::default(),
+        }
+    }
+}
+
+impl DayCycle {
+    pub fn time(&self) -> TimeOfDay {
+        self.time
+    }
+
+    #[cfg_attr(not(test), expect(dead_code, reason = "nothing sets the time yet"))]
+    pub fn set_time(&mut self, time: TimeOfDay) {
+        self.time = time
+    }
+
+    pub fn set_settings(&mut self, settings: DayNightSettings) {
+        self.settings = settings
+    }
+
+    pub fn advance(&mut self, step: Duration) {
+        if self.settings.paused {
+            return;
+        }
+
+        let day_length = Duration::from_secs(self.settings.day_length.get().into());
+        self.time = TimeOfDay::new(self.time.0 + step.div_duration_f32(day_length));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::num::NonZero;
+    use super::*;
+
+    fn assert_close(a: Vec3, b: Vec3) {
+        assert!(a.abs_diff_eq(b, 1e-5), "{a} != {b}");
+    }
+
+    #[test]
+    fn sun_direction_at_key_times() {
+        assert_close(TimeOfDay::DAWN.sun_direction(), Vec3::X);
+        assert_close(TimeOfDay::NOON.sun_direction(), Vec3::Y);
+        assert_close(TimeOfDay::DUSK.sun_direction(), Vec3::NEG_X);
+        assert_close(TimeOfDay::MIDNIGHT.sun_direction(), Vec3::NEG_Y);
+        assert_close(TimeOfDay::new(1.5).sun_direction(), Vec3::Y);
+    }
+
+    #[test]
+    fn the_sky_follows_the_sun() {
+        let brightness = |time: TimeOfDay| time.sky_color().element_sum();
+
+        assert!(brightness(TimeOfDay::NOON) > brightness(TimeOfDay::DAWN));
+        assert!(brightness(TimeOfDay::DAWN) > brightness(TimeOfDay::MIDNIGHT));
+        // sunrise and sunset are reddish
+        let dusk = TimeOfDay::DUSK.sky_color();
+        assert!(dusk.x > dusk.z, "{dusk}");
+        assert!(TimeOfDay::MIDNIGHT.sun_color().element_sum() < TimeOfDay::NOON.sun_color().element_sum());
+    }
+
+    #[test]
+    fn advancing_respects_the_settings() {
+        let mut cycle = DayCycle::default();
+        cycle.set_time(TimeOfDay::DAWN);
+        cycle.set_settings(DayNightSettings { day_length: NonZero::new(100).unwrap(), paused: false });
+
+        cycle.advance(Duration::from_secs(25));
+        assert!((cycle.time().fraction() - 0.5).abs() < 1e-6);
+        cycle.advance(Duration::from_secs(75));
+        assert!((cycle.time().fraction() - 0.25).abs() < 1e-6);
+
+        cycle.set_settings(DayNightSettings { paused: true, ..DayNightSettings::default() });
+        cycle.advance(Duration::from_secs(1000));
+        assert!((cycle.time().fraction() - 0.25).abs() < 1e-6);
+    }
+}
@@ -0,0 +1,276 @@
+use glam::Quat;
+use voxel_maths::Transform;
+use crate::game_state::coords::AbsoluteCoord;
+
+/// A handle to an entity in [`Entities`]
+///
+/// slots get reused after a despawn, but every reuse bumps the generation
+/// so an old id never aliases whatever got spawned in its place
+#[derive(Debug, Copy, Clone, Hash, Eq, PartialEq)]
+pub struct EntityId {
+    index: u32,
+    generation: u32,
+}
+
+impl EntityId {
+    /// The player is always the first thing spawned into a world
+    pub const PLAYER: Self = Self { index: 0, generation: 0 };
+}
+
+#[derive(Debug, Copy, Clone, Hash, Eq, PartialEq)]
+pub enum EntityKind {
+    Player,
+    /// a static object drawn with the default model
+    Prop,
+}
+
+impl EntityKind {
+    /// Whether this kind of entity is drawn in the world,
+    /// the player is the camera so it isn't
+    pub fn is_rendered(self) -> bool {
+        match self {
+            EntityKind::Player => false,
+            EntityKind::Prop => true,
+        }
+    }
+}
+
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct EntityData {
+    pub kind: EntityKind,
+    pub position: AbsoluteCoord,
+    pub rotation: Quat,
+    /// where the entity was at the end of the previous simulation step
+    previous_position: AbsoluteCoord,
+    previous_rotation: Quat,
+}
+
+impl EntityData {
+    pub fn new(kind: EntityKind, position: AbsoluteCoord) -> Self {
+        Self {
+            kind,
+            position,
+            rotation: Quat::IDENTITY,
+            previous_position: position,
+            previous_rotation: Quat::IDENTITY,
+        }
+    }
+
+    pub fn with_rotation(self, rotation: Quat) -> Self {
+        Self {
+            rotation,
+            previous_rotation: rotation,
+            ..self
+        }
+    }
+
+    /// Moves the entity without interpolating from where it was
+    pub fn teleport(&mut self, position: AbsoluteCoord) {
+        self.position = position;
+        self.previous_position = position;
+    }
+
+    /// Marks the current state as the start of the next simulation step
+    fn store_previous(&mut self) {
+        self.previous_position = self.position;
+        self.previous_rotation = self.rotation;
+    }
+
+    pub fn transform(&self) -> Transform {
+        Transform {
+            position: self.position.xyz().as_f32().into(),
+            rotation: self.rotation,
+        }
+    }
+
+    pub fn previous_transform(&self) -> Transform {
+        Transform {
+            position: self.previous_position.xyz().as_f32().into(),
+            rotation: self.previous_rotation,
+        }
+    }
+
+    /// The transform to draw the entity with, `alpha` being how far the frame is
+    /// between the previous simulation step (0) and the current one (1)
+    pub fn render_transform(&self, alpha: f32) -> Transform {
+        self.previous_transform().lerp(self.transform(), alpha)
+    }
+}
+
+#[derive(Debug)]
+struct Slot {
+    generation: u32,
+    entity: Option<EntityData>,
+}
+
+#[derive(Debug, Default)]
+pub struct Entities {
+    slots: Vec<Slot>,
+    free: Vec<u32>,
+    len: usize,
+}
+
+impl Entities {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn spawn(&mut self, entity: EntityData) -> EntityId {
+        self.len += 1;
+
+        if let Some(index) = self.free.pop() {
+            let slot = &mut self.slots[index as usize];
+            debug_assert!(slot.entity.is_none());
+            slot.entity = Some(entity);
+            return EntityId { index, generation: slot.generation };
+        }
+
+        let index = u32::try_from(self.slots.len()).expect("too many entities");
+        self.slots.push(Slot {
+            generation: 0,
+            entity: Some(entity)
+        });
+
+        EntityId { index, generation: 0 }
+    }
+
+    fn slot_mut(&mut self, id: EntityId) -> Option<&mut Slot> {
+        self.slots.get_mut(id.index as usize).filter(|slot| slot.generation == id.generation)
+    }
+
+    /// Removes the entity, returning it if `id` was still alive
+    ///
+    /// the player is never removed, everything else expects it to be there
+    #[cfg_attr(not(test), expect(dead_code, reason = "nothing despawns entities yet"))]
+    pub fn despawn(&mut self, id: EntityId) -> Option<EntityData> {
+        if id == EntityId::PLAYER {
+            return None;
+        }
+
+        let slot = self.slot_mut(id)?;
+        let entity = slot.entity.take()?;
+
+        // if the generation would wrap around just retire the slot instead
+        if let Some(generation) = slot.generation.checked_add(1) {
+            slot.generation = generation;
+            self.free.push(id.index);
+        }
+
+        self.len -= 1;
+        Some(entity)
+    }
+
+    pub fn get(&self, id: EntityId) -> Option<&EntityData> {
+        self.slots
+            .get(id.index as usize)
+            .filter(|slot| slot.generation == id.generation)?
+            .entity
+            .as_ref()
+    }
+
+    pub fn get_mut(&mut self, id: EntityId) -> Option<&mut EntityData> {
+        self.slot_mut(id)?.entity.as_mut()
+    }
+
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    /// Has to be called before every simulation step so rendering can interpolate from it
+    pub fn store_previous_states(&mut self) {
+        self.slots
+            .iter_mut()
+            .filter_map(|slot| slot.entity.as_mut())
+            .for_each(EntityData::store_previous)
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = (EntityId, &EntityData)> {
+        self.slots.iter().zip(0..).filter_map(|(slot, index)| {
+            let id = EntityId { index, generation: slot.generation };
+            slot.entity.as_ref().map(|entity| (id, entity))
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use voxel_maths::fixed_point::FixedPoint;
+    use voxel_maths::FixedPointVec3;
+    use super::*;
+
+    fn prop_at(x: f32) -> EntityData {
+        EntityData::new(
+            EntityKind::Prop,
+            AbsoluteCoord::from_xyz(FixedPoint::from_f32(x), FixedPoint::ZERO, FixedPoint::ZERO)
+        )
+    }
+
+    fn with_player() -> Entities {
+        let mut entities = Entities::new();
+        let player = entities.spawn(EntityData::new(EntityKind::Player, AbsoluteCoord::ZERO));
+        assert_eq!(player, EntityId::PLAYER);
+        entities
+    }
+
+    #[test]
+    fn spawn_and_despawn() {
+        let mut entities = with_player();
+        let a = entities.spawn(prop_at(1.0));
+        let b = entities.spawn(prop_at(2.0));
+
+        assert_eq!(entities.len(), 3);
+        assert_eq!(entities.get(b), Some(&prop_at(2.0)));
+
+        assert_eq!(entities.despawn(a), Some(prop_at(1.0)));
+        assert_eq!(entities.despawn(a), None);
+        assert_eq!(entities.get(a), None);
+        assert_eq!(entities.len(), 2);
+
+        let ids = entities.iter().map(|(id, _)| id).collect::<Vec<_>>();
+        assert_eq!(ids, [EntityId::PLAYER, b]);
+    }
+
+    #[test]
+    fn the_player_cant_be_despawned() {
+        let mut entities = with_player();
+        entities.spawn(prop_at(1.0));
+
+        assert_eq!(entities.despawn(EntityId::PLAYER), None);
+        assert_eq!(entities.len(), 2);
+        assert_eq!(entities.get(EntityId::PLAYER).map(|player| player.kind), Some(EntityKind::Player));
+    }
+
+    #[test]
+    fn reused_slots_dont_alias() {
+        let mut entities = with_player();
+        let old = entities.spawn(prop_at(1.0));
+        entities.despawn(old);
+
+        let new = entities.spawn(prop_at(3.0));
+        assert_ne!(old, new);
+        assert_eq!(entities.get(old), None);
+        assert!(entities.get_mut(old).is_none());
+        assert_eq!(entities.despawn(old), None);
+        assert_eq!(entities.get(new), Some(&prop_at(3.0)));
+    }
+
+    #[test]
+    fn render_transform_interpolates_steps() {
+        let mut entity = prop_at(0.0).with_rotation(Quat::IDENTITY);
+        entity.store_previous();
+        entity.position += AbsoluteCoord::from_xyz_vec(FixedPointVec3::Y);
+        entity.rotation = Quat::from_rotation_x(1.0);
+
+        assert_eq!(entity.render_transform(0.0), entity.previous_transform());
+        let end = entity.render_transform(1.0);
+        assert!(end.position.abs_diff_eq(entity.transform().position, 1e-6));
+        assert!(end.rotation.abs_diff_eq(entity.transform().rotation, 1e-6));
+        assert_eq!(
+            entity.render_transform(0.5),
+            entity.previous_transform().lerp(entity.transform(), 0.5)
+        );
+
+        entity.teleport(AbsoluteCoord::ZERO);
+        assert_eq!(entity.render_transform(0.0).position, entity.transform().position);
+    }
+}
@@ -1,18 +1,92 @@
-use glam::vec3;
-use voxel_maths::FixedPointVec3;
+use glam::{Quat, Vec3};
+use voxel_maths::fixed_point::FixedPoint;
+use voxel_maths::{i48, FixedPointVec3};
 use crate::game_state::coords::AbsoluteCoord;
+use crate::game_state::physics::PLAYER_EYE_HEIGHT;
 
 #[derive(Copy, Clone)]
 pub struct Camera {
     /// measured in radians
     pub yaw: f32,
     /// measured in radians
-    pub pitch: f32
+    pub pitch: f32,
+    /// a free look orientation that takes over from `yaw` and `pitch` when set,
+    /// unlike them it can roll
+    pub orientation: Option<Quat>,
+}
+
+impl Camera {
+    /// Where a camera with no rotation looks
+    pub const FORWARD: Vec3 = Vec3::X;
+
+    /// The orientation `yaw`, `pitch` and `roll` describe, all measured in radians,
+    /// roll turns the camera around the way it looks
+    pub fn euler_to_quat(yaw: f32, pitch: f32, roll: f32) -> Quat {
+        // a positive yaw turns from +x towards +z, the opposite way a rotation around +y does
+        Quat::from_rotation_y(-yaw) * Quat::from_rotation_z(pitch) * Quat::from_rotation_x(roll)
+    }
+
+    /// Switches the camera to free look, looking where it looks now but rolled by `roll`
+    #[cfg_attr(not(test), expect(dead_code, reason = "there is no spectator or cinematic camera yet"))]
+    pub fn free_look(self, roll: f32) -> Self {
+        Self {
+            orientation: Some(Self::euler_to_quat(self.yaw, self.pitch, roll)),
+            ..self
+        }
+    }
 }
 
 pub struct Player {
     pub(super) camera: Camera,
+    /// where the player's feet are
     pub(super) position: AbsoluteCoord,
+    /// used up by sprinting, the player can't sprint with none left
+    pub(super) stamina: FixedPoint,
+    /// how fast the player is moving up, in blocks a second, negative while falling
+    pub(super) vertical_speed: FixedPoint,
+    /// whether the player was standing on something at the end of the last step
+    pub(super) on_ground: bool,
+}
+
+impl Player {
+    #[cfg_attr(not(test), expect(dead_code, reason = "there is no HUD to show the stamina on yet"))]
+    pub fn stamina(&self) -> FixedPoint {
+        self.stamina
+    }
+}
+
+/// How stamina gets used up by sprinting and comes back otherwise
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub struct StaminaRules {
+    /// the most stamina the player can have, and what they start with
+    pub max: FixedPoint,
+    /// how much sprinting uses up a second
+    pub drain: FixedPoint,
+    /// how much comes back a second while not sprinting
+    pub regen: FixedPoint,
+}
+
+impl Default for StaminaRules {
+    fn default() -> Self {
+        // 5 seconds of sprinting, which takes 8 to come back
+        Self {
+            max: FixedPoint::from_int(i48!(1)),
+            drain: FixedPoint::from_f32(0.2),
+            regen: FixedPoint::from_f32(0.125),
+        }
+    }
+}
+
+impl StaminaRules {
+    /// The stamina left after `dt` seconds of `sprinting` or not, starting with `stamina`
+    pub fn next(&self, stamina: FixedPoint, sprinting: bool, dt: FixedPoint) -> FixedPoint {
+        let stamina = match sprinting {
+            true => stamina - self.drain * dt,
+            false => stamina + self.regen * dt,
+        };
+
+        stamina.clamp(FixedPoint::ZERO, self.max)
+    }
 }
 
 pub trait Entity {
@@ -26,30 +100,37 @@ pub trait Entity {
 
     fn camera_direction(&self) -> FixedPointVec3 {
         let camera = self.camera();
+        if let Some(orientation) = camera.orientation {
+            return FixedPointVec3::from_f32(orientation * Camera::FORWARD)
+        }
 
-        let (sin_yaw, cos_yaw) = camera.yaw.sin_cos();
-        let (sin_pitch, cos_pitch) = camera.pitch.sin_cos();
+        let (sin_yaw, cos_yaw) = FixedPoint::from_f32(camera.yaw).sin_cos();
+        let (sin_pitch, cos_pitch) = FixedPoint::from_f32(camera.pitch).sin_cos();
 
         let x = cos_pitch * cos_yaw;
         let y = sin_pitch;
         let z = cos_pitch * sin_yaw;
 
-        FixedPointVec3::from_f32(vec3(x, y, z))
+        FixedPointVec3::new(x, y, z)
+    }
+
+    /// Which way is up on screen, only a free look camera can roll away from +y
+    fn camera_up(&self) -> Vec3 {
+        self.camera().orientation.map_or(Vec3::Y, |orientation| orientation * Vec3::Y)
     }
 
     // visualization of axis
     // https://sotrh.github.io/learn-wgpu/assets/img/left_right_hand.ccabf5d0.gif
 
+    /// Walking follows the yaw even in free look
     fn forwards(&self) -> FixedPointVec3 {
-        let (yaw_sin, yaw_cos) = self.camera().yaw.sin_cos();
-        let forward = vec3(yaw_cos, 0.0, yaw_sin).normalize();
-        FixedPointVec3::from_f32(forward)
+        let (yaw_sin, yaw_cos) = FixedPoint::from_f32(self.camera().yaw).sin_cos();
+        FixedPointVec3::new(yaw_cos, FixedPoint::ZERO, yaw_sin)
     }
 
     fn right(&self) -> FixedPointVec3 {
-        let (yaw_sin, yaw_cos) = self.camera().yaw.sin_cos();
-        let right = vec3(-yaw_sin, 0.0, yaw_cos).normalize();
-        FixedPointVec3::from_f32(right)
+        let (yaw_sin, yaw_cos) = FixedPoint::from_f32(self.camera().yaw).sin_cos();
+        FixedPointVec3::new(-yaw_sin, FixedPoint::ZERO, yaw_cos)
     }
 }
 
@@ -61,4 +142,53 @@ impl Entity for Player {
     fn position(&self) -> AbsoluteCoord {
         self.position
     }
-}
\ No newline at end of file
+
+    fn eye(&self) -> AbsoluteCoord {
+        self.position + AbsoluteCoord::from_xyz(FixedPoint::ZERO, PLAYER_EYE_HEIGHT, FixedPoint::ZERO)
+    }
+}
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn player(camera: Camera) -> Player {
+        Player {
+            camera,
+            position: AbsoluteCoord::ZERO,
+            stamina: FixedPoint::ZERO,
+            vertical_speed: FixedPoint::ZERO,
+            on_ground: false,
+        }
+    }
+
+    #[test]
+    fn free_look_agrees_with_euler_angles() {
+        for yaw in [0.0, 0.5, 1.7, 3.1, 4.5, 6.0] {
+            for pitch in [-1.5, -0.7, 0.0, 0.3, 1.5] {
+                let euler = player(Camera { yaw, pitch, orientation: None });
+                let free = player(euler.camera.free_look(0.0));
+
+                let (euler_direction, free_direction) = (euler.camera_direction().as_f32(), free.camera_direction().as_f32());
+                assert!(
+                    euler_direction.abs_diff_eq(free_direction, 1e-3),
+                    "yaw {yaw} pitch {pitch}: {euler_direction} != {free_direction}"
+                );
+                // without roll the up vector stays upright, it only tilts with the pitch
+                assert!(free.camera_up().dot(free_direction).abs() < 1e-3);
+                assert!(free.camera_up().y >= 0.0);
+                assert!(free.camera_up().cross(free_direction).y.abs() < 1e-3);
+            }
+        }
+    }
+
+    #[test]
+    fn rolling_turns_the_up_vector() {
+        let camera = Camera { yaw: 0.0, pitch: 0.0, orientation: None };
+        assert_eq!(player(camera).camera_up(), Vec3::Y);
+
+        let rolled = player(camera.free_look(std::f32::consts::FRAC_PI_2));
+        // still looking down +x
+        assert!(rolled.camera_direction().as_f32().abs_diff_eq(Vec3::X, 1e-3));
+        assert!(rolled.camera_up().abs_diff_eq(Vec3::Z, 1e-5));
+    }
+}
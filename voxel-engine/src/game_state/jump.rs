@@ -0,0 +1,121 @@
+use std::time::Duration;
+
+/// How forgiving jumping is about when jump gets pressed
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub struct JumpTiming {
+    /// how long a jump pressed in the air is remembered, so pressing it just before landing still jumps
+    pub buffer: Duration,
+    /// how long after walking off a ledge the player can still jump, as if they were on it
+    pub coyote: Duration,
+}
+
+impl Default for JumpTiming {
+    fn default() -> Self {
+        Self {
+            buffer: Duration::from_millis(100),
+            coyote: Duration::from_millis(100),
+        }
+    }
+}
+
+/// Works out when the player jumps from when jump was pressed and when they were last on the ground,
+/// every time is simulation time so the same steps always jump at the same time
+#[derive(Debug, Copy, Clone, Default)]
+pub struct JumpBuffer {
+    timing: JumpTiming,
+    /// when jump was pressed, until it gets used up or too old
+    pressed: Option<Duration>,
+    /// the last time the player was standing on something, until they jump off of it
+    grounded: Option<Duration>,
+}
+
+impl JumpBuffer {
+    pub fn new(timing: JumpTiming) -> Self {
+        Self { timing, pressed: None, grounded: None }
+    }
+
+    /// Jump was pressed at `now`
+    pub fn press(&mut self, now: Duration) {
+        self.pressed = Some(now)
+    }
+
+    /// Whether the player jumps at `now`, with `grounded` being whether they're standing on something,
+    /// called once a step after [`JumpBuffer::press`]
+    pub fn update(&mut self, now: Duration, grounded: bool) -> bool {
+        if grounded {
+            self.grounded = Some(now);
+        }
+
+        let within = |at: Option<Duration>, window: Duration| at.is_some_and(|at| now.saturating_sub(at) <= window);
+        if !within(self.pressed, self.timing.buffer) {
+            self.pressed = None;
+            return false;
+        }
+
+        if !within(self.grounded, self.timing.coyote) {
+            return false;
+        }
+
+        // both get used up, otherwise the coyote time would be another jump in mid air
+        self.pressed = None;
+        self.grounded = None;
+        true
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const STEP: Duration = Duration::from_millis(10);
+
+    /// Steps from `from` to `to` ms, on the ground whenever `grounded` says so, returning when the player jumped
+    fn run(buffer: &mut JumpBuffer, from: u64, to: u64, grounded: impl Fn(u64) -> bool) -> Option<u64> {
+        (from..to)
+            .step_by(STEP.as_millis() as usize)
+            .find(|&ms| buffer.update(Duration::from_millis(ms), grounded(ms)))
+    }
+
+    #[test]
+    fn jumps_pressed_just_before_landing_happen_on_landing() {
+        let mut buffer = JumpBuffer::default();
+        run(&mut buffer, 0, 100, |_| false);
+        // 80ms before landing at 200ms
+        buffer.press(Duration::from_millis(120));
+        assert_eq!(run(&mut buffer, 120, 300, |ms| ms >= 200), Some(200));
+
+        // too early to count
+        let mut buffer = JumpBuffer::default();
+        buffer.press(Duration::from_millis(50));
+        assert_eq!(run(&mut buffer, 50, 300, |ms| ms >= 200), None);
+
+        // the window is configurable
+        let mut buffer = JumpBuffer::new(JumpTiming { buffer: Duration::from_millis(200), ..JumpTiming::default() });
+        buffer.press(Duration::from_millis(50));
+        assert_eq!(run(&mut buffer, 50, 300, |ms| ms >= 200), Some(200));
+    }
+
+    #[test]
+    fn jumps_just_after_leaving_the_ground_still_happen() {
+        let mut buffer = JumpBuffer::default();
+        // walked off a ledge at 100ms
+        assert_eq!(run(&mut buffer, 0, 150, |ms| ms < 100), None);
+        buffer.press(Duration::from_millis(150));
+        assert!(buffer.update(Duration::from_millis(150), false));
+
+        // but only once
+        buffer.press(Duration::from_millis(160));
+        assert_eq!(run(&mut buffer, 160, 300, |_| false), None);
+
+        // and not long after
+        let mut buffer = JumpBuffer::new(JumpTiming { coyote: Duration::from_millis(30), ..JumpTiming::default() });
+        assert_eq!(run(&mut buffer, 0, 150, |ms| ms < 100), None);
+        buffer.press(Duration::from_millis(150));
+        assert!(!buffer.update(Duration::from_millis(150), false));
+
+        // standing on the ground it's just a jump
+        let mut buffer = JumpBuffer::default();
+        buffer.press(Duration::ZERO);
+        assert!(buffer.update(Duration::ZERO, true));
+    }
+}
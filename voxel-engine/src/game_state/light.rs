@@ -0,0 +1,45 @@
+use glam::Vec3;
+
+/// Something lighting the world, `color` gets scaled by `intensity` before it reaches anything
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum Light {
+    /// infinitely far away, lighting everything from the same direction like the sun
+    Directional {
+        /// towards the light
+        direction: Vec3,
+        color: Vec3,
+        intensity: f32,
+    },
+    /// shining out in every direction from `position`, fading out to nothing at `range`
+    Point {
+        position: Vec3,
+        range: f32,
+        color: Vec3,
+        intensity: f32,
+    },
+    /// a point light that only shines within `cone` radians of `direction`
+    #[cfg_attr(not(test), expect(dead_code, reason = "nothing places spot lights yet"))]
+    Spot {
+        position: Vec3,
+        /// the way the light shines
+        direction: Vec3,
+        range: f32,
+        cone: f32,
+        color: Vec3,
+        intensity: f32,
+    },
+}
+
+impl Light {
+    pub fn color(&self) -> Vec3 {
+        match *self {
+            Light::Directional { color, .. } | Light::Point { color, .. } | Light::Spot { color, .. } => color,
+        }
+    }
+
+    pub fn intensity(&self) -> f32 {
+        match *self {
+            Light::Directional { intensity, .. } | Light::Point { intensity, .. } | Light::Spot { intensity, .. } => intensity,
+        }
+    }
+}
@@ -0,0 +1,476 @@
+//! Light levels that spread through the world block by block, from the sky and from blocks that give off light
+//!
+//! every block has a sky light and a block light level, from 0 (dark) up to [`MAX_LIGHT`].
+//! Light gets a level dimmer with every block it spreads through and stops at opaque blocks,
+//! except sky light at full brightness, which shines straight down without fading.
+//! Light only spreads through loaded chunks, and spreads on into a chunk once it gets loaded.
+
+use std::collections::VecDeque;
+use std::sync::Arc;
+use crate::game_state::blocks::{BlockFace, BlockRegistry};
+use crate::game_state::coords::{AbsoluteBlockCoord, BlockCoord, BlockY, ChunkCoord};
+use crate::game_state::world::{Chunk, World, BLOCKS_PER_CHUNK, CHUNK_HEIGHT, CHUNK_SIZE, MAX_LIGHT};
+
+/// How lit a block is, by the sky and by blocks around it
+#[derive(Debug, Copy, Clone, Default, Hash, Eq, PartialEq)]
+pub struct BlockLight(u8);
+
+impl BlockLight {
+    pub const DARK: Self = Self(0);
+
+    pub const fn new(sky: u8, block: u8) -> Self {
+        debug_assert!(sky <= MAX_LIGHT && block <= MAX_LIGHT);
+        Self(sky << 4 | block)
+    }
+
+    pub const fn sky(self) -> u8 {
+        self.0 >> 4
+    }
+
+    pub const fn block(self) -> u8 {
+        self.0 & 0xF
+    }
+
+    /// How bright the block is, whichever of the two lights it more
+    pub const fn level(self) -> u8 {
+        match self.sky() > self.block() {
+            true => self.sky(),
+            false => self.block(),
+        }
+    }
+
+    fn get(self, channel: Channel) -> u8 {
+        match channel {
+            Channel::Sky => self.sky(),
+            Channel::Block => self.block(),
+        }
+    }
+
+    fn with(self, channel: Channel, level: u8) -> Self {
+        match channel {
+            Channel::Sky => Self::new(level, self.block()),
+            Channel::Block => Self::new(self.sky(), level),
+        }
+    }
+}
+
+/// The light of every block in a chunk, in the same order as the chunk's blocks
+#[derive(Clone, Default)]
+pub struct ChunkLight {
+    /// `None` while the whole chunk is dark
+    levels: Option<Box<[BlockLight; BLOCKS_PER_CHUNK]>>,
+}
+
+impl ChunkLight {
+    /// The sky shining straight down every column, as far as `columns` says it does
+    pub fn sky(columns: &SkyColumns) -> Self {
+        let mut light = Self::default();
+        for (index, level) in light.levels_mut().iter_mut().enumerate() {
+            let (x, z, y) = (index % CHUNK_SIZE, index / CHUNK_SIZE % CHUNK_SIZE, index / (CHUNK_SIZE * CHUNK_SIZE));
+            if y >= columns[x][z] {
+                *level = BlockLight::new(MAX_LIGHT, 0);
+            }
+        }
+        light
+    }
+
+    #[inline]
+    pub fn get(&self, index: usize) -> BlockLight {
+        self.levels.as_ref().map_or(BlockLight::DARK, |levels| levels[index])
+    }
+
+    pub fn set(&mut self, index: usize, light: BlockLight) {
+        if self.levels.is_none() && light == BlockLight::DARK {
+            return;
+        }
+
+        self.levels_mut()[index] = light;
+    }
+
+    fn levels_mut(&mut self) -> &mut [BlockLight; BLOCKS_PER_CHUNK] {
+        self.levels.get_or_insert_with(|| {
+            vec![BlockLight::DARK; BLOCKS_PER_CHUNK]
+                .into_boxed_slice()
+                .try_into()
+                .unwrap_or_else(|_| unreachable!("the vec has exactly BLOCKS_PER_CHUNK blocks"))
+        })
+    }
+}
+
+/// The two kinds of light, which spread separately
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+enum Channel {
+    Sky,
+    Block,
+}
+
+impl Channel {
+    const ALL: [Self; 2] = [Self::Sky, Self::Block];
+}
+
+/// How bright the block at `coord` is without any light spreading to it
+fn source(world: &World, coord: AbsoluteBlockCoord, channel: Channel) -> u8 {
+    let Some(block) = world.get_block(coord) else {
+        return 0;
+    };
+
+    match channel {
+        Channel::Block => world.blocks().light(block),
+        // the sky shines into the top of the world
+        Channel::Sky => match usize::from(coord.y()) == CHUNK_HEIGHT - 1 && world.blocks().is_transparent(block) {
+            true => MAX_LIGHT,
+            false => 0,
+        },
+    }
+}
+
+/// How bright light at `level` is once it spreads into the next block over towards `face`
+fn spread(channel: Channel, level: u8, face: BlockFace) -> u8 {
+    match (channel, face) {
+        (Channel::Sky, BlockFace::NegY) if level == MAX_LIGHT => MAX_LIGHT,
+        _ => level.saturating_sub(1),
+    }
+}
+
+/// Spreads light out from every block in `queue` to the blocks around it that are darker
+fn spread_light(world: &mut World, channel: Channel, queue: &mut VecDeque<AbsoluteBlockCoord>) {
+    while let Some(coord) = queue.pop_front() {
+        let Some(level) = world.get_light(coord).map(|light| light.get(channel)) else {
+            continue;
+        };
+
+        for face in BlockFace::ALL {
+            let spread = spread(channel, level, face);
+            let Some(neighbor) = coord.neighbor(face).filter(|_| spread > 0) else {
+                continue;
+            };
+
+            // unloaded chunks stop the light as much as opaque blocks do
+            let lets_through = world.get_block(neighbor).is_some_and(|block| world.blocks().is_transparent(block));
+            let Some(light) = world.get_light(neighbor).filter(|light| lets_through && light.get(channel) < spread) else {
+                continue;
+            };
+
+            world.set_light(neighbor, light.with(channel, spread));
+            queue.push_back(neighbor);
+        }
+    }
+}
+
+/// Darkens everything that was lit by the blocks in `removed`, which were at the level they're paired with,
+/// the blocks around the darkened ones that are lit some other way get added to `relight` to spread back in
+fn remove_light(
+    world: &mut World,
+    channel: Channel,
+    removed: &mut VecDeque<(AbsoluteBlockCoord, u8)>,
+    relight: &mut VecDeque<AbsoluteBlockCoord>
+) {
+    while let Some((coord, level)) = removed.pop_front() {
+        for (neighbor, face) in BlockFace::ALL.into_iter().filter_map(|face| Some((coord.neighbor(face)?, face))) {
+            darken(world, channel, (neighbor, level, face), removed, relight);
+        }
+    }
+}
+
+/// Darkens `block` if its light came from light at `level` that was removed next to it,
+/// on the other side of it from `face`, see [`remove_light`]
+fn darken(
+    world: &mut World,
+    channel: Channel,
+    (block, level, face): (AbsoluteBlockCoord, u8, BlockFace),
+    removed: &mut VecDeque<(AbsoluteBlockCoord, u8)>,
+    relight: &mut VecDeque<AbsoluteBlockCoord>
+) {
+    let Some(light) = world.get_light(block).filter(|light| light.get(channel) > 0) else {
+        return;
+    };
+
+    // anything brighter than the removed light could have made it, or giving off the light itself,
+    // was lit from somewhere else
+    let (block_level, own) = (light.get(channel), source(world, block, channel));
+    if block_level > spread(channel, level, face) || block_level == own {
+        relight.push_back(block);
+        return;
+    }
+
+    world.set_light(block, light.with(channel, own));
+    removed.push_back((block, block_level));
+    if own > 0 {
+        relight.push_back(block);
+    }
+}
+
+/// Fixes up the light around `coord` after the block there got replaced
+pub fn block_changed(world: &mut World, coord: AbsoluteBlockCoord) {
+    let Some(block) = world.get_block(coord) else {
+        return;
+    };
+    let lets_through = world.blocks().is_transparent(block);
+
+    for channel in Channel::ALL {
+        let Some(light) = world.get_light(coord) else {
+            return;
+        };
+
+        // whatever the old block lit or let light through to goes dark,
+        // then gets lit back up by whatever is still around it
+        let (old, own) = (light.get(channel), source(world, coord, channel));
+        world.set_light(coord, light.with(channel, own));
+
+        let mut relight = VecDeque::new();
+        if old > 0 {
+            remove_light(world, channel, &mut VecDeque::from([(coord, old)]), &mut relight);
+        }
+        if own > 0 {
+            relight.push_back(coord);
+        }
+        if lets_through {
+            relight.extend(BlockFace::ALL.into_iter().filter_map(|face| coord.neighbor(face)));
+        }
+
+        spread_light(world, channel, &mut relight);
+    }
+}
+
+/// The x and z of every column of blocks in a chunk
+fn chunk_columns() -> impl Iterator<Item = (u8, u8)> {
+    (0..CHUNK_SIZE as u8).flat_map(|x| (0..CHUNK_SIZE as u8).map(move |z| (x, z)))
+}
+
+/// The blocks along the `face` side of a chunk, each paired with the block next to it on the other side,
+/// in the chunk over that way
+fn chunk_side(face: BlockFace) -> impl Iterator<Item = (BlockCoord, BlockCoord)> {
+    let last = CHUNK_SIZE as u8 - 1;
+    (0..CHUNK_HEIGHT).map(|y| y as BlockY).flat_map(move |y| {
+        (0..CHUNK_SIZE as u8).map(move |along| {
+            let ((x, z), (next_x, next_z)) = match face {
+                BlockFace::PosX => ((last, along), (0, along)),
+                BlockFace::NegX => ((0, along), (last, along)),
+                BlockFace::PosZ => ((along, last), (along, 0)),
+                _ => ((along, 0), (along, last)),
+            };
+            (BlockCoord::from_xyz(x, y, z), BlockCoord::from_xyz(next_x, y, next_z))
+        })
+    })
+}
+
+/// How far down the sky shines in every column of a chunk, the lowest y it reaches, x first
+pub type SkyColumns = [[usize; CHUNK_SIZE]; CHUNK_SIZE];
+
+/// How far the sky shines straight down every column of a chunk before something opaque stops it
+pub fn sky_columns(chunk: &Chunk, blocks: &BlockRegistry) -> SkyColumns {
+    // nothing to stop it, which is all of the empty chunks up in the sky
+    if chunk.blocks().palette().iter().all(|&block| blocks.is_transparent(block)) {
+        return [[0; CHUNK_SIZE]; CHUNK_SIZE];
+    }
+
+    std::array::from_fn(|x| std::array::from_fn(|z| {
+        (0..CHUNK_HEIGHT)
+            .rev()
+            .find(|&y| !blocks.is_transparent(chunk.get(BlockCoord::from_xyz(x as u8, y as BlockY, z as u8))))
+            .map_or(0, |opaque| opaque + 1)
+    }))
+}
+
+/// Spreads the light of a chunk that just got loaded at `coord`, lit from the sky by [`ChunkLight::sky`],
+/// `replaced` being the chunk that was there before it
+///
+/// light spreads out from the sky and the blocks in it and from the chunks around it into it, and back out into them,
+/// whatever the replaced chunk lit around it goes dark first
+pub fn chunk_loaded(world: &mut World, coord: ChunkCoord, columns: &SkyColumns, replaced: Option<&Chunk>) {
+    let Some(chunk) = world.chunk(coord).map(Arc::clone) else {
+        return;
+    };
+    let blocks = Arc::clone(world.blocks());
+    let horizontal = [BlockFace::PosX, BlockFace::NegX, BlockFace::PosZ, BlockFace::NegZ];
+
+    for channel in Channel::ALL {
+        let mut relight = VecDeque::new();
+        if let Some(replaced) = replaced {
+            // the chunk itself got lit from scratch, only the chunks around it could have been lit by it
+            let mut removed = VecDeque::new();
+            for face in horizontal {
+                let Some(next) = coord.neighbor(face).filter(|&next| world.chunk(next).is_some()) else {
+                    continue;
+                };
+                for (inside, outside) in chunk_side(face) {
+                    let level = replaced.light(inside).get(channel);
+                    if level > 0 {
+                        darken(world, channel, (AbsoluteBlockCoord::new(next, outside), level, face), &mut removed, &mut relight);
+                    }
+                }
+            }
+            remove_light(world, channel, &mut removed, &mut relight);
+        }
+
+        // the sky only spreads sideways into the columns next to it that it doesn't reach as far down,
+        // the rest of the chunk is either dark or already in the open sky
+        let spreading = match channel {
+            Channel::Sky => chunk_columns()
+                .flat_map(|(x, z)| horizontal.map(|face| (x, z, face)))
+                .flat_map(|(x, z, face)| {
+                    let next = BlockCoord::from_xyz(x, 0, z).neighbor(face).filter(|&(_, chunk)| chunk == (0, 0));
+                    let below = next.map_or(0, |(next, _)| columns[usize::from(next.x())][usize::from(next.z())]);
+                    (columns[usize::from(x)][usize::from(z)]..below).map(move |y| BlockCoord::from_xyz(x, y as BlockY, z))
+                })
+                .collect::<Vec<_>>(),
+            Channel::Block if !chunk.blocks().palette().iter().any(|&block| blocks.light(block) > 0) => Vec::new(),
+            Channel::Block => chunk
+                .iter_non_air()
+                .filter(|&(_, block)| blocks.light(block) > 0)
+                .map(|(block, _)| block)
+                .collect(),
+        };
+        for block in spreading.into_iter().map(|block| AbsoluteBlockCoord::new(coord, block)) {
+            let own = source(world, block, channel);
+            if let Some(light) = world.get_light(block).filter(|light| own > light.get(channel)) {
+                world.set_light(block, light.with(channel, own));
+            }
+            relight.push_back(block);
+        }
+
+        // and light going across the sides, whichever way it goes,
+        // looking at the chunk as it is now that the light in it has changed
+        let chunk = Arc::clone(world.chunk(coord).expect("the chunk was just loaded"));
+        for face in horizontal {
+            let Some((next_coord, next)) = coord.neighbor(face).and_then(|next| Some((next, Arc::clone(world.chunk(next)?)))) else {
+                continue;
+            };
+
+            for (inside, outside) in chunk_side(face) {
+                let (inside_level, outside_level) = (chunk.light(inside).get(channel), next.light(outside).get(channel));
+                if spread(channel, outside_level, face.opposite()) > inside_level && blocks.is_transparent(chunk.get(inside)) {
+                    relight.push_back(AbsoluteBlockCoord::new(next_coord, outside));
+                }
+                if spread(channel, inside_level, face) > outside_level && blocks.is_transparent(next.get(outside)) {
+                    relight.push_back(AbsoluteBlockCoord::new(coord, inside));
+                }
+            }
+        }
+
+        spread_light(world, channel, &mut relight);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::game_state::blocks::{BlockProperties, FaceTextures};
+    use crate::game_state::test_support::{block_at, world_with_blocks, STONE};
+    use crate::game_state::world::Block;
+    use super::*;
+
+    const TORCH: Block = Block::from_id(2);
+
+    /// stone and then a torch, so they get the ids of [`STONE`] and [`TORCH`]
+    fn stone_and_torch() -> BlockRegistry {
+        let mut blocks = BlockRegistry::new();
+        blocks.register(BlockProperties::solid("stone", FaceTextures::all(1)));
+        blocks.register(BlockProperties {
+            solid: false,
+            transparent: true,
+            light: MAX_LIGHT,
+            ..BlockProperties::solid("torch", FaceTextures::all(2))
+        });
+        blocks
+    }
+
+    /// Checks the light in `world` is what lighting the same chunks from scratch comes up with
+    fn assert_lit_from_scratch(world: &World, chunks: &[ChunkCoord]) {
+        let mut scratch = World::with_blocks(BlockRegistry::clone(world.blocks()));
+        for &chunk in chunks {
+            scratch.insert_chunk(chunk, Chunk::clone(world.chunk(chunk).unwrap()));
+        }
+
+        for &chunk in chunks {
+            let (lit, fresh) = (world.chunk(chunk).unwrap(), scratch.chunk(chunk).unwrap());
+            for (block, _) in lit.iter() {
+                assert_eq!(lit.light(block), fresh.light(block), "{block:?} in {chunk:?}");
+            }
+        }
+    }
+
+    #[test]
+    fn light_falls_off_with_distance() {
+        let (center, east) = (ChunkCoord::ZERO, ChunkCoord::from_xz(1, 0));
+        let mut world = world_with_blocks(stone_and_torch(), &[center, east]);
+        world.dirty_chunks_mut().drain().for_each(drop);
+        world.set_block(block_at(8, 100, 8), TORCH);
+
+        // a level dimmer for every block away, into the chunk next to it too
+        for (x, y, z) in (0..32).flat_map(|x| (85..=115).flat_map(move |y| (0..16).map(move |z| (x, y, z)))) {
+            let distance = (x - 8_i64).abs() + (i64::from(y) - 100).abs() + (z - 8_i64).abs();
+            let expected = u8::try_from(15 - distance).unwrap_or(0);
+            assert_eq!(world.get_light(block_at(x, y, z)).unwrap().block(), expected, "at {x}, {y}, {z}");
+        }
+        assert!(world.dirty_chunks_mut().is_dirty(east));
+
+        // but not into chunks that aren't loaded, and the sky lights everything open
+        assert_eq!(world.get_light(block_at(-1, 100, 8)), None);
+        assert_eq!(world.get_light(block_at(8, 0, 8)).unwrap().sky(), MAX_LIGHT);
+        assert_eq!(world.get_light(block_at(9, 100, 8)).unwrap().level(), MAX_LIGHT);
+
+        // and taking the torch away takes all of it away
+        world.set_block(block_at(8, 100, 8), Block::AIR);
+        assert!(world.chunk(center).unwrap().iter().all(|(block, _)| world.chunk(center).unwrap().light(block).block() == 0));
+        assert_lit_from_scratch(&world, &[center, east]);
+    }
+
+    #[test]
+    fn opaque_blocks_stop_light() {
+        // a stone roof over half of the chunk, the sky only gets under it from the open side
+        let mut roofed = Chunk::empty();
+        for (x, z) in (0..8).flat_map(|x| (0..16).map(move |z| (x, z))) {
+            roofed.set(BlockCoord::from_xyz(x, 50, z), STONE);
+        }
+        let mut world = world_with_blocks(stone_and_torch(), &[]);
+        world.insert_chunk(ChunkCoord::ZERO, roofed);
+        for (x, y) in (0..8).flat_map(|x| [0, 20, 49].map(move |y| (x, y))) {
+            assert_eq!(world.get_light(block_at(x, y, 5)).unwrap().sky(), 7 + x as u8, "at {x}, {y}");
+        }
+        assert_eq!(world.get_light(block_at(8, 20, 5)).unwrap().sky(), MAX_LIGHT);
+        assert_eq!(world.get_light(block_at(3, 50, 5)), Some(BlockLight::DARK));
+
+        // a hole in the roof lets the sky straight down again
+        world.set_block(block_at(3, 50, 5), Block::AIR);
+        assert_eq!(world.get_light(block_at(3, 0, 5)).unwrap().sky(), MAX_LIGHT);
+        assert_eq!(world.get_light(block_at(2, 0, 5)).unwrap().sky(), MAX_LIGHT - 1);
+
+        // a torch behind a wall lights around it the long way
+        world.set_block(block_at(4, 20, 10), TORCH);
+        for (y, z) in (18..=22).flat_map(|y| (8..=12).map(move |z| (y, z))) {
+            world.set_block(block_at(5, y, z), STONE);
+        }
+        assert_eq!(world.get_light(block_at(6, 20, 10)).unwrap().block(), MAX_LIGHT - 8);
+        assert_eq!(world.get_light(block_at(6, 20, 13)).unwrap().block(), MAX_LIGHT - 5);
+        assert_eq!(world.get_light(block_at(5, 20, 10)), Some(BlockLight::DARK));
+
+        assert_lit_from_scratch(&world, &[ChunkCoord::ZERO]);
+    }
+
+    #[test]
+    fn light_spreads_into_chunks_as_they_load() {
+        let (center, east) = (ChunkCoord::ZERO, ChunkCoord::from_xz(1, 0));
+        let mut world = world_with_blocks(stone_and_torch(), &[center]);
+        world.set_block(block_at(14, 100, 8), TORCH);
+        for (y, z) in (60..70).flat_map(|y| (0..16).map(move |z| (y, z))) {
+            world.set_block(block_at(15, y, z), STONE);
+        }
+
+        let mut stone = Chunk::empty();
+        stone.set(BlockCoord::from_xyz(0, 99, 8), STONE);
+        world.insert_chunk(east, stone.clone());
+        assert_eq!(world.get_light(block_at(17, 100, 8)).unwrap().block(), MAX_LIGHT - 3);
+        assert_eq!(world.get_light(block_at(16, 99, 8)), Some(BlockLight::DARK));
+        assert_lit_from_scratch(&world, &[center, east]);
+
+        // light spreads back out of a chunk that gets replaced
+        stone.set(BlockCoord::from_xyz(2, 100, 8), TORCH);
+        world.insert_chunk(east, stone);
+        assert_eq!(world.get_light(block_at(15, 100, 8)).unwrap().block(), MAX_LIGHT - 1);
+        assert_lit_from_scratch(&world, &[center, east]);
+
+        world.insert_chunk(east, Chunk::empty());
+        assert_eq!(world.get_light(block_at(15, 100, 8)).unwrap().block(), MAX_LIGHT - 1);
+        assert_lit_from_scratch(&world, &[center, east]);
+    }
+}
@@ -1,30 +1,177 @@
-use std::cell::Cell;
-use std::time::Instant;
-use glam::Vec2;
+use std::time::Duration;
+use glam::{vec3a, Quat, Vec2, Vec3, Vec3A};
 use voxel_maths::fixed_point::FixedPoint;
-use voxel_maths::FixedPointVec3;
+use voxel_maths::{i48, FixedPointVec3};
+use voxel_runtime::time::Clock;
 use crate::controls::{Controls, InputMethod, KeyMapping};
-use crate::game_state::coords::AbsoluteCoord;
-use crate::game_state::entity::{Camera, Entity, Player};
+use crate::game_state::blocks::BlockFace;
+use crate::game_state::coords::{AbsoluteBlockCoord, AbsoluteCoord};
+use crate::game_state::day_cycle::DayCycle;
+use crate::game_state::entities::{Entities, EntityData, EntityId, EntityKind};
+use crate::game_state::entity::{Camera, Entity, Player, StaminaRules};
+use crate::game_state::jump::{JumpBuffer, JumpTiming};
+use crate::game_state::light::Light;
+use crate::game_state::particles::{ParticleEmitter, ParticleSystem};
+use crate::game_state::physics::MovementPhysics;
+use crate::game_state::step_up::StepUp;
+use crate::game_state::timestep::FixedTimestep;
+use crate::game_state::world::World;
+use crate::settings::GameSettings;
 
 pub mod entity;
 
+pub mod entities;
+
+pub mod timestep;
+
+pub mod particles;
+
+pub mod world;
+
+pub mod palette;
+
+pub mod lighting;
+
+pub mod blocks;
+
+pub mod day_cycle;
+
+pub mod light;
+
+pub mod raycast;
+
+pub mod jump;
+
+pub mod step_up;
+
+pub mod physics;
+
+#[cfg_attr(not(test), expect(dead_code, reason = "there are no world saves yet"))]
+pub mod chunk_format;
+
 pub mod coords;
 
+#[cfg(test)]
+pub(crate) mod test_support;
+
 pub struct GameState {
     player: Player,
+    entities: Entities,
+    /// measures the time between frames, stopped while the game is paused
+    clock: Clock,
+    timestep: FixedTimestep,
+    /// the total time covered by the simulation steps run so far
+    simulation_time: Duration,
+    particles: ParticleSystem,
+    world: World,
+    day_cycle: DayCycle,
+    /// a light standing in for the sun, which lights the world when there is none
+    light: Option<Light>,
+    target: Target,
+    /// what the player is holding, drawn with the model of that kind of entity in front of the camera
+    held_item: Option<EntityKind>,
+    /// how fast the player walks, in blocks a second
+    walk_speed: FixedPoint,
+    stamina_rules: StaminaRules,
+    jump: JumpBuffer,
+    step_up: StepUp,
+}
+
+/// The block the player is looking at, and what it was worked out from
+/// so it only gets worked out again once one of those changes
+#[derive(Default)]
+struct Target {
+    /// the eye, the direction the camera faces and the world's revision
+    from: Option<(AbsoluteCoord, FixedPointVec3, u64)>,
+    hit: Option<(AbsoluteBlockCoord, BlockFace)>,
+}
+
+/// What the world starts out with
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Default)]
+pub enum Scene {
+    /// a grid of cubes around the player
+    #[default]
+    Props,
+    /// just the player
+    Empty,
+}
+
+/// How fast the player walks until it's changed from the console, e^2 has always felt right
+fn default_walk_speed() -> FixedPoint {
+    FixedPoint::from_int(i48!(2)).exp()
+}
+
+/// The grid of cubes the world starts out with
+fn default_props() -> impl Iterator<Item = EntityData> {
+    const NUM_INSTANCES_PER_ROW: u32 = 10;
+    const SPACE_BETWEEN: f32 = 3.0;
+
+    (0..NUM_INSTANCES_PER_ROW).flat_map(|z| {
+        (0..NUM_INSTANCES_PER_ROW).map(move |x| {
+            let x = SPACE_BETWEEN * (x as f32 - NUM_INSTANCES_PER_ROW as f32 / 2.0);
+            let z = SPACE_BETWEEN * (z as f32 - NUM_INSTANCES_PER_ROW as f32 / 2.0);
+
+            let position = vec3a(x, 0.0, z);
+
+            let rotation = if position.cmpeq(Vec3A::ZERO).all() {
+                Quat::from_axis_angle(Vec3::Z, 0.0)
+            } else {
+                Quat::from_axis_angle(position.normalize().into(), 45.0_f32.to_radians())
+            };
+
+            EntityData::new(
+                EntityKind::Prop,
+                AbsoluteCoord::from_xyz_vec(FixedPointVec3::from_f32a(position))
+            ).with_rotation(rotation)
+        })
+    })
 }
 
 impl GameState {
+    #[cfg(test)]
     pub fn new() -> Self {
+        Self::from_scene(Scene::default())
+    }
+
+    pub fn from_scene(scene: Scene) -> Self {
+        let stamina_rules = StaminaRules::default();
+        let player = Player {
+            camera: Camera {
+                yaw: 0.0,
+                pitch: 0.0,
+                orientation: None,
+            },
+            position: AbsoluteCoord::ZERO,
+            stamina: stamina_rules.max,
+            vertical_speed: FixedPoint::ZERO,
+            on_ground: false,
+        };
+
+        let mut entities = Entities::new();
+        let player_id = entities.spawn(EntityData::new(EntityKind::Player, player.position));
+        debug_assert_eq!(player_id, EntityId::PLAYER);
+        if scene == Scene::Props {
+            for prop in default_props() {
+                entities.spawn(prop);
+            }
+        }
+
         Self {
-            player: Player {
-                camera: Camera {
-                    yaw: 0.0,
-                    pitch: 0.0,
-                },
-                position: AbsoluteCoord::ZERO
-            } 
+            player,
+            entities,
+            clock: Clock::new(),
+            timestep: FixedTimestep::default(),
+            simulation_time: Duration::ZERO,
+            particles: ParticleSystem::default(),
+            world: World::new(),
+            day_cycle: DayCycle::default(),
+            light: None,
+            target: Target::default(),
+            held_item: None,
+            walk_speed: default_walk_speed(),
+            stamina_rules,
+            jump: JumpBuffer::new(JumpTiming::default()),
+            step_up: StepUp::default(),
         }
     }
     
@@ -32,14 +179,61 @@ impl GameState {
         &self.player
     }
 
-    fn run_player_movement(&mut self, controls: &Controls) {
-        thread_local! {
-            static LAST: Cell<Instant> = Cell::new(Instant::now());
-        }
+    pub fn entities(&self) -> &Entities {
+        &self.entities
+    }
 
-        let now = Instant::now();
-        let delta_frame = (now - LAST.replace(now)).as_secs_f32();
+    #[cfg_attr(not(test), expect(dead_code, reason = "nothing spawns or moves entities yet"))]
+    pub fn entities_mut(&mut self) -> &mut Entities {
+        &mut self.entities
+    }
+
+    pub fn particles(&self) -> &ParticleSystem {
+        &self.particles
+    }
+
+    #[expect(dead_code, reason = "nothing reads the world yet")]
+    pub fn world(&self) -> &World {
+        &self.world
+    }
 
+    pub fn world_mut(&mut self) -> &mut World {
+        &mut self.world
+    }
+
+    /// Moves the player's feet straight to `position`, without sliding there over the next frame
+    /// and without keeping whatever speed they were falling at
+    pub fn teleport_player(&mut self, position: AbsoluteCoord) {
+        self.player.position = position;
+        self.player.vertical_speed = FixedPoint::ZERO;
+        self.entities
+            .get_mut(EntityId::PLAYER)
+            .expect("the player can't be despawned")
+            .teleport(position);
+    }
+
+    #[cfg_attr(not(test), expect(dead_code, reason = "nothing shows the speed yet"))]
+    pub fn walk_speed(&self) -> FixedPoint {
+        self.walk_speed
+    }
+
+    pub fn set_walk_speed(&mut self, speed: FixedPoint) {
+        self.walk_speed = speed
+    }
+
+    /// Changes how long the player can sprint for, what stamina they have left stays under the new max
+    #[cfg_attr(not(test), expect(dead_code, reason = "nothing changes how long sprinting lasts yet"))]
+    pub fn set_stamina_rules(&mut self, rules: StaminaRules) {
+        self.stamina_rules = rules;
+        self.player.stamina = self.player.stamina.min(rules.max);
+    }
+
+    #[cfg_attr(not(test), expect(dead_code, reason = "nothing breaks blocks yet"))]
+    pub fn spawn_particles(&mut self, emitter: &ParticleEmitter) {
+        self.particles.emit(emitter)
+    }
+
+    fn run_camera_movement(&mut self, controls: &Controls, delta_frame: f32) {
         let delta_mouse = controls.cursor_delta();
         
         const MAX_YAW_DIF: f32 = std::f32::consts::FRAC_2_PI - (0.1_f32.to_radians());
@@ -55,63 +249,398 @@ impl GameState {
             camera.yaw = (camera.yaw + yaw).rem_euclid(const { 2.0 * std::f32::consts::PI });
             camera.pitch = (camera.pitch + pitch).clamp(MIN_PITCH, MAX_PITCH);
         }
+    }
 
-        // FIXME not actually fixed point
-        let delta_frame = FixedPoint::from_f32(delta_frame);
+    fn run_player_movement(&mut self, controls: &dyn InputMethod, delta_step: FixedPoint) {
+        let two = FixedPoint::from_int(i48!(2));
+        let mut speed = self.walk_speed;
 
-        let mut delta = FixedPointVec3::ZERO;
-        
-        // this float is fine, its in a very fine grained and rigid range
-        let mut speed = 2.0_f32.exp();
-        
-        if controls.held_down(KeyMapping::Sprint) {
-            speed *= 2.0
-        }
-        
-        if controls.held_down(KeyMapping::Jump) {
-            delta += FixedPointVec3::Y
+        // only running forwards is sprinting, standing still or walking any other way doesn't use up stamina
+        let forwards = controls.held_down(KeyMapping::WalkForwards) && !controls.held_down(KeyMapping::WalkBackwards);
+        let sprinting = controls.held_down(KeyMapping::Sprint) && forwards && self.player.stamina > FixedPoint::ZERO;
+        self.player.stamina = self.stamina_rules.next(self.player.stamina, sprinting, delta_step);
+        if sprinting {
+            speed *= two
         }
 
-        if controls.held_down(KeyMapping::Sneak) {
-            speed /= 2.0;
-            delta -= FixedPointVec3::Y
+        let jump = controls.held_down(KeyMapping::Jump);
+        let sneak = controls.held_down(KeyMapping::Sneak);
+        if sneak {
+            speed /= two
         }
 
-        let speed = FixedPoint::from_f32(speed);
-        
         let forward = self.player.forwards();
         let right = self.player.right();
+        let mut walk = FixedPointVec3::ZERO;
         
         if controls.held_down(KeyMapping::WalkForwards) {
-            delta += forward
+            walk += forward
         }
 
         if controls.held_down(KeyMapping::WalkBackwards) {
-            delta -= forward
+            walk -= forward
         }
 
         if controls.held_down(KeyMapping::WalkRight) {
-            delta += right
+            walk += right
         }
 
         if controls.held_down(KeyMapping::WalkLeft) {
-            delta -= right
+            walk -= right
         }
 
-        
-        let pos_delta = delta.normalize_or_zero() * speed * delta_frame;
-        self.player.position += AbsoluteCoord::from_xyz_vec(pos_delta);
+        // with nothing loaded to stand on the player flies, jump and sneak going up and down
+        if !physics::is_loaded(&self.world, self.player.position) {
+            let mut delta = walk;
+            if jump {
+                delta += FixedPointVec3::Y
+            }
+            if sneak {
+                delta -= FixedPointVec3::Y
+            }
 
-        
-        if controls.triggered(KeyMapping::MainMenu) {
-            self.player.position = AbsoluteCoord::ZERO
+            let pos_delta = delta.normalize_or_zero() * speed * delta_step;
+            self.player.position += AbsoluteCoord::from_xyz_vec(pos_delta);
+            return;
+        }
+
+        // holding jump keeps jumping whenever the player lands
+        let now = self.simulation_time;
+        if jump {
+            self.jump.press(now)
+        }
+        if self.jump.update(now, self.player.on_ground) {
+            self.player.vertical_speed = physics::JUMP_SPEED
+        }
+
+        let physics = MovementPhysics::at(&self.world, self.player.position);
+        self.player.vertical_speed = physics.fall(self.player.vertical_speed, jump, delta_step);
+
+        let mut delta = walk.normalize_or_zero() * speed * physics.speed * delta_step;
+        delta.y = self.player.vertical_speed * delta_step;
+        let (feet, collisions) = physics::move_player(&self.world, self.player.position, delta, &self.step_up);
+
+        self.player.position = feet;
+        self.player.on_ground = collisions.ground;
+        if collisions.ground || collisions.ceiling {
+            self.player.vertical_speed = FixedPoint::ZERO
+        }
+    }
+
+    /// Advances the simulation by exactly one fixed step
+    fn tick(&mut self, controls: &Controls) {
+        let step = self.timestep.step();
+        self.step(controls, timestep::to_seconds(step));
+    }
+
+    /// Advances the simulation by one step of `dt` seconds,
+    /// the same state stepped with the same input always ends up in the same place
+    ///
+    /// time only goes forwards, a negative `dt` is a step of no time at all
+    pub fn step(&mut self, input: &dyn InputMethod, dt: FixedPoint) {
+        self.entities.store_previous_states();
+
+        let dt = dt.max(FixedPoint::ZERO);
+        let step = timestep::to_duration(dt);
+        self.run_player_movement(input, dt);
+        self.particles.update(dt);
+        self.day_cycle.advance(step);
+
+        self.simulation_time += step;
+
+        let player = self.entities.get_mut(EntityId::PLAYER).expect("the player can't be despawned");
+        player.position = self.player.position;
+    }
+
+    /// How far the current frame is between the last simulation step and the next one,
+    /// used to smooth out rendering when the frame rate and the tick rate differ
+    pub fn interpolation_alpha(&self) -> f32 {
+        self.timestep.alpha()
+    }
+
+    /// How much time the simulation has advanced, only moves in whole steps
+    pub fn simulation_time(&self) -> Duration {
+        self.simulation_time
+    }
+
+    pub fn day_cycle(&self) -> &DayCycle {
+        &self.day_cycle
+    }
+
+    /// How far away the player can point at blocks from, in blocks
+    pub const REACH: f32 = 5.0;
+
+    /// The block the player is looking at (the one under the crosshair) and the face they're looking at,
+    /// as of the end of the last frame
+    pub fn target_block(&self) -> Option<(AbsoluteBlockCoord, BlockFace)> {
+        self.target.hit
+    }
+
+    fn update_target(&mut self) {
+        let from = (self.player.eye(), self.player.camera_direction(), self.world.revision());
+        if self.target.from == Some(from) {
+            return;
+        }
+
+        let (eye, direction, _) = from;
+        self.target = Target {
+            from: Some(from),
+            hit: raycast::raycast(&self.world, eye, direction.as_f32(), Self::REACH),
+        };
+    }
+
+    pub fn held_item(&self) -> Option<EntityKind> {
+        self.held_item
+    }
+
+    #[expect(dead_code, reason = "there is no inventory to pick what to hold from yet")]
+    pub fn set_held_item(&mut self, item: Option<EntityKind>) {
+        self.held_item = item
+    }
+
+    /// What lights the world right now
+    pub fn light(&self) -> Light {
+        self.light.unwrap_or_else(|| self.day_cycle.time().sun())
+    }
+
+    /// Lights the world with `light` instead of the sun, `None` goes back to the sun
+    #[cfg_attr(not(test), expect(dead_code, reason = "nothing places lights yet"))]
+    pub fn set_light(&mut self, light: Option<Light>) {
+        self.light = light
+    }
+
+    /// Picks up whatever settings the simulation depends on, they can change at any time
+    pub fn apply_settings(&mut self, settings: &GameSettings) {
+        self.day_cycle.set_settings(settings.day_night);
+    }
+
+    /// Stops time for the game while it can't be seen, frames after it comes back
+    /// don't try to catch up on the time it spent paused
+    pub fn set_paused(&mut self, paused: bool) {
+        match paused {
+            true => self.clock.pause(),
+            false => self.clock.resume(),
         }
     }
 
-    /// # Returns
-    /// true if the event was `consumed`
-    /// false otherwise
     pub fn frame_update(&mut self, controls: &Controls) {
-        self.run_player_movement(controls)
+        let delta_frame = self.clock.tick();
+
+        // looking around stays tied to the frame rate so it never feels laggy
+        self.run_camera_movement(controls, delta_frame.as_secs_f32());
+
+        for _ in 0..self.timestep.advance(delta_frame) {
+            self.tick(controls);
+        }
+
+        // edge triggered actions are handled once per frame,
+        // a frame can run zero or many ticks
+        if controls.triggered(KeyMapping::MainMenu) {
+            self.teleport_player(AbsoluteCoord::ZERO);
+        }
+
+        self.update_target();
     }
-}
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Holds down the same actions every step
+    struct Held(&'static [KeyMapping]);
+
+    impl InputMethod for Held {
+        fn held_down(&self, mapping: KeyMapping) -> bool {
+            self.0.contains(&mapping)
+        }
+
+        fn triggered(&self, _: KeyMapping) -> bool {
+            false
+        }
+
+        fn cursor_delta(&self) -> Vec2 {
+            Vec2::ZERO
+        }
+    }
+
+    #[test]
+    fn steps_are_deterministic() {
+        let inputs = [
+            Held(&[KeyMapping::WalkForwards]),
+            Held(&[KeyMapping::WalkForwards, KeyMapping::Sprint, KeyMapping::Jump]),
+            Held(&[]),
+            Held(&[KeyMapping::WalkLeft, KeyMapping::Sneak]),
+        ];
+        let dts = ["0.015625", "0.05", "0.001", "0.25"].map(|dt| dt.parse::<FixedPoint>().unwrap());
+
+        let mut a = GameState::new();
+        let mut b = GameState::new();
+        for (input, dt) in inputs.iter().cycle().zip(dts.iter().cycle()).take(100) {
+            a.step(input, *dt);
+            b.step(input, *dt);
+        }
+
+        assert_ne!(a.player().position, AbsoluteCoord::ZERO);
+        assert_eq!(a.player().position, b.player().position);
+        assert_eq!(a.simulation_time(), b.simulation_time());
+    }
+
+    #[test]
+    fn simulation_time_adds_up_exactly() {
+        let mut game = GameState::new();
+        let sixty_fourth = "0.015625".parse::<FixedPoint>().unwrap();
+        for _ in 0..64 {
+            game.step(&Held(&[]), sixty_fourth);
+        }
+        assert_eq!(game.simulation_time(), Duration::from_secs(1));
+
+        // going backwards is standing still
+        let position = game.player().position;
+        game.step(&Held(&[KeyMapping::WalkForwards]), -sixty_fourth);
+        assert_eq!(game.simulation_time(), Duration::from_secs(1));
+        assert_eq!(game.player().position, position);
+    }
+
+    #[test]
+    fn sprinting_runs_out_of_stamina() {
+        let mut game = GameState::new();
+        let quarter = FixedPoint::from_f32(0.25);
+        game.set_walk_speed(FixedPoint::from_int(i48!(1)));
+        game.set_stamina_rules(StaminaRules {
+            max: FixedPoint::from_int(i48!(1)),
+            drain: FixedPoint::from_f32(0.5),
+            regen: quarter,
+        });
+        let stamina = |game: &GameState| game.player().stamina().as_f32();
+        let step = |game: &mut GameState, input: &'static [KeyMapping]| {
+            let before = game.player().position.x();
+            game.step(&Held(input), quarter);
+            (game.player().position.x() - before).as_f32()
+        };
+
+        // sprinting on the spot or backwards isn't sprinting
+        assert_eq!(step(&mut game, &[KeyMapping::Sprint]), 0.0);
+        step(&mut game, &[KeyMapping::Sprint, KeyMapping::WalkBackwards]);
+        assert_eq!(stamina(&game), 1.0);
+
+        // twice as fast while it lasts
+        let sprint = &[KeyMapping::WalkForwards, KeyMapping::Sprint];
+        for _ in 0..8 {
+            assert!((step(&mut game, sprint) - 0.5).abs() < 1e-3);
+        }
+        assert_eq!(stamina(&game), 0.0);
+        assert!((step(&mut game, sprint) - 0.25).abs() < 1e-3);
+        // and it comes back while not sprinting
+        assert_eq!(stamina(&game), 0.0625);
+        step(&mut game, &[KeyMapping::WalkForwards]);
+        assert_eq!(stamina(&game), 0.125);
+        assert!((step(&mut game, sprint) - 0.5).abs() < 1e-3);
+        assert_eq!(stamina(&game), 0.0);
+
+        for _ in 0..100 {
+            step(&mut game, &[]);
+        }
+        assert_eq!(stamina(&game), 1.0);
+    }
+
+    #[test]
+    fn the_player_falls_jumps_and_steps_up() {
+        use crate::game_state::coords::{BlockCoord, ChunkCoord};
+        use crate::game_state::world::{Block, Chunk};
+
+        // a floor at y = 9, a ledge a block high along z = 8 from x = 10, and a wall 2 blocks higher on it at x = 13
+        let stone = Block::from_id(1);
+        let mut chunk = Chunk::empty();
+        for x in 0..16 {
+            for z in 0..16 {
+                chunk.set(BlockCoord::from_xyz(x, 9, z), stone);
+            }
+        }
+        for x in 10..16 {
+            chunk.set(BlockCoord::from_xyz(x, 10, 8), stone);
+        }
+        for y in [11, 12] {
+            chunk.set(BlockCoord::from_xyz(13, y, 8), stone);
+        }
+
+        let mut game = GameState::from_scene(Scene::Empty);
+        game.world_mut().insert_chunk(ChunkCoord::ZERO, chunk);
+        let at = |x: f32, y: f32| AbsoluteCoord::from_xyz_vec(FixedPointVec3::from_f32(Vec3::new(x, y, 8.5)));
+        let dt = "0.015625".parse::<FixedPoint>().unwrap();
+        let steps = |game: &mut GameState, input: &'static [KeyMapping], count: usize| {
+            (0..count).map(|_| {
+                game.step(&Held(input), dt);
+                game.player().position
+            }).collect::<Vec<_>>()
+        };
+
+        // falls onto the floor and stays there
+        game.teleport_player(at(5.5, 14.0));
+        steps(&mut game, &[], 64);
+        assert_eq!(game.player().position, at(5.5, 10.0));
+        assert!(game.player.on_ground);
+
+        // jumping gets higher than a block and comes back down, again and again while jump is held
+        let heights = steps(&mut game, &[KeyMapping::Jump], 128).iter().map(|feet| feet.y().as_f32()).collect::<Vec<_>>();
+        assert!(heights.iter().all(|&y| (10.0..12.5).contains(&y)));
+        assert!(heights.iter().any(|&y| y > 11.0));
+        let landings = heights.windows(2).filter(|pair| pair[0] > 10.0 && pair[1] == 10.0).count();
+        assert!(landings >= 2, "{heights:?}");
+        steps(&mut game, &[], 64);
+        assert_eq!(game.player().position, at(5.5, 10.0));
+
+        // walking into the ledge steps onto it, but the wall stops the player flush against it
+        steps(&mut game, &[KeyMapping::WalkForwards], 64);
+        let flush = FixedPoint::from_int(i48!(13)) - physics::PLAYER_HALF_WIDTH;
+        assert_eq!(game.player().position, AbsoluteCoord::from_xyz(flush, at(0.0, 11.0).y(), at(0.0, 0.0).z()));
+        assert!(game.player.on_ground);
+    }
+
+    #[test]
+    fn targets_the_block_in_front() {
+        use crate::game_state::coords::ChunkCoord;
+        use crate::game_state::world::{Block, Chunk};
+
+        let mut game = GameState::from_scene(Scene::Empty);
+        game.world_mut().insert_chunk(ChunkCoord::ZERO, Chunk::empty());
+        let block = AbsoluteBlockCoord::from_xyz(i48!(4), 20, i48!(2));
+        game.world_mut().set_block(block, Block::from_id(1));
+
+        // looking from the middle of a block 3 blocks away along x, which a level camera with no yaw faces
+        let eye = Vec3::new(1.5, 20.5, 2.5) - Vec3::Y * physics::PLAYER_EYE_HEIGHT.as_f32();
+        game.teleport_player(AbsoluteCoord::from_xyz_vec(FixedPointVec3::from_f32(eye)));
+        game.update_target();
+        assert_eq!(game.target_block(), Some((block, BlockFace::NegX)));
+
+        // looking straight down there's nothing
+        game.player.camera.pitch = -89_f32.to_radians();
+        game.update_target();
+        assert_eq!(game.target_block(), None);
+
+        // the target follows the world too
+        game.player.camera.pitch = 0.0;
+        game.update_target();
+        let closer = AbsoluteBlockCoord::from_xyz(i48!(3), 20, i48!(2));
+        game.world_mut().set_block(closer, Block::from_id(1));
+        game.update_target();
+        assert_eq!(game.target_block(), Some((closer, BlockFace::NegX)));
+        game.world_mut().set_block(closer, Block::AIR);
+        game.update_target();
+        assert_eq!(game.target_block(), Some((block, BlockFace::NegX)));
+    }
+
+    #[test]
+    fn lights_stand_in_for_the_sun() {
+        let mut game = GameState::new();
+        assert_eq!(game.light(), game.day_cycle().time().sun());
+
+        let lamp = Light::Point { position: Vec3::Y, range: 10.0, color: Vec3::ONE, intensity: 3.0 };
+        game.set_light(Some(lamp));
+        game.step(&Held(&[]), FixedPoint::from_f32(0.05));
+        assert_eq!(game.light(), lamp);
+
+        game.set_light(None);
+        assert!(matches!(game.light(), Light::Directional { .. }));
+    }
+}
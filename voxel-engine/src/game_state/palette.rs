@@ -0,0 +1,254 @@
+//! The blocks of a chunk, compressed into a palette of the distinct blocks in it
+//! and an index into the palette for every block
+//!
+//! the indices are packed into as few bits as the palette needs, so a chunk of a handful of
+//! block types takes a few bits a block instead of a whole [`Block`]
+
+use ahash::HashMap;
+use crate::game_state::world::{Block, BLOCKS_PER_CHUNK};
+
+/// The fewest bits that can tell `palette_len` blocks apart, 0 when there's only one
+pub const fn bits_for(palette_len: u32) -> u8 {
+    (u32::BITS - (palette_len - 1).leading_zeros()) as u8
+}
+
+/// indices never straddle two words, so a word holds `64 / bits` of them
+pub const fn packed_words(bits: u8) -> usize {
+    match bits {
+        0 => 0,
+        bits => BLOCKS_PER_CHUNK.div_ceil(64 / bits as usize),
+    }
+}
+
+/// A chunk's worth of blocks, in the same order as [`Chunk`](crate::game_state::world::Chunk) stores them
+///
+/// blocks only ever get added to the palette, one that got replaced everywhere keeps its place
+/// until the blocks get compressed from scratch, like when the chunk gets saved and loaded again
+#[derive(Debug, Clone)]
+pub struct PalettedBlocks {
+    palette: Vec<Block>,
+    bits: u8,
+    words: Box<[u64]>,
+}
+
+impl PalettedBlocks {
+    /// Every block the same one
+    pub fn filled(block: Block) -> Self {
+        Self { palette: vec![block], bits: 0, words: Box::default() }
+    }
+
+    /// Exactly [`BLOCKS_PER_CHUNK`] blocks, with a palette of only the blocks in them
+    pub fn from_blocks(blocks: impl IntoIterator<Item = Block>) -> Self {
+        let mut palette = Vec::new();
+        let mut palette_index = HashMap::default();
+        let indices = blocks.into_iter().map(|block| {
+            *palette_index.entry(block).or_insert_with(|| {
+                palette.push(block);
+                (palette.len() - 1) as u64
+            })
+        }).collect::<Vec<_>>();
+        assert_eq!(indices.len(), BLOCKS_PER_CHUNK, "a chunk has to be given all of its blocks");
+
+        let mut blocks = Self { palette, bits: 0, words: Box::default() };
+        blocks.bits = bits_for(blocks.palette_len());
+        blocks.words = vec![0; packed_words(blocks.bits)].into_boxed_slice();
+        for (index, palette_index) in indices.into_iter().enumerate() {
+            blocks.write(index, palette_index);
+        }
+        blocks
+    }
+
+    /// Blocks packed the same way as they are here, `Err` with the first block pointing past the end of the palette
+    pub fn from_parts(palette: Vec<Block>, words: Box<[u64]>) -> Result<Self, usize> {
+        assert!((1..=BLOCKS_PER_CHUNK).contains(&palette.len()), "a palette holds one to a chunk's worth of blocks");
+        let mut blocks = Self { palette, bits: 0, words };
+        blocks.bits = bits_for(blocks.palette_len());
+        assert_eq!(blocks.words.len(), packed_words(blocks.bits), "the words don't fit the palette");
+
+        match (0..BLOCKS_PER_CHUNK).find(|&index| blocks.read(index) as usize >= blocks.palette.len()) {
+            Some(index) => Err(index),
+            None => Ok(blocks),
+        }
+    }
+
+    /// Every block that has been in the blocks, in the order they were first added
+    pub fn palette(&self) -> &[Block] {
+        &self.palette
+    }
+
+    /// How many blocks are in the palette, never more than there are blocks in a chunk
+    pub fn palette_len(&self) -> u32 {
+        const { assert!(BLOCKS_PER_CHUNK <= u32::MAX as usize) }
+        debug_assert!(self.palette.len() <= BLOCKS_PER_CHUNK, "the palette outgrew the chunk");
+        self.palette.len() as u32
+    }
+
+    /// How many bits every block's index into the palette takes up
+    pub fn bits_per_block(&self) -> u8 {
+        self.bits
+    }
+
+    /// The packed indices, `64 / bits` to a word starting from the least significant bits
+    pub fn words(&self) -> &[u64] {
+        &self.words
+    }
+
+    #[inline]
+    pub fn get(&self, index: usize) -> Block {
+        self.palette[self.read(index) as usize]
+    }
+
+    /// Replaces a block, returning the old one
+    ///
+    /// a block that isn't in the palette yet gets added to it, repacking every index
+    /// into more bits when the palette outgrows the ones it has
+    pub fn set(&mut self, index: usize, block: Block) -> Block {
+        let old = self.get(index);
+        if old == block {
+            return old;
+        }
+
+        let palette_index = match self.palette.iter().position(|&entry| entry == block) {
+            Some(palette_index) => palette_index,
+            None => {
+                self.palette.push(block);
+                let bits = bits_for(self.palette_len());
+                if bits != self.bits {
+                    self.repack(bits);
+                }
+                self.palette.len() - 1
+            }
+        };
+
+        self.write(index, palette_index as u64);
+        old
+    }
+
+    /// Every block in order
+    pub fn iter(&self) -> impl ExactSizeIterator<Item = Block> + '_ {
+        (0..BLOCKS_PER_CHUNK).map(|index| self.get(index))
+    }
+
+    /// The word the index of the block at `index` is in, and how far into it
+    #[inline(always)]
+    fn locate(&self, index: usize) -> (usize, usize) {
+        let per_word = 64 / self.bits as usize;
+        (index / per_word, index % per_word * self.bits as usize)
+    }
+
+    #[inline(always)]
+    fn read(&self, index: usize) -> u64 {
+        if self.bits == 0 {
+            return 0;
+        }
+
+        let (word, shift) = self.locate(index);
+        (self.words[word] >> shift) & ((1 << self.bits) - 1)
+    }
+
+    fn write(&mut self, index: usize, palette_index: u64) {
+        if self.bits == 0 {
+            return;
+        }
+
+        let (word, shift) = self.locate(index);
+        let mask = ((1 << self.bits) - 1) << shift;
+        self.words[word] = (self.words[word] & !mask) | (palette_index << shift);
+    }
+
+    fn repack(&mut self, bits: u8) {
+        let old = std::mem::replace(self, Self {
+            palette: Vec::new(),
+            bits,
+            words: vec![0; packed_words(bits)].into_boxed_slice(),
+        });
+
+        for index in 0..BLOCKS_PER_CHUNK {
+            self.write(index, old.read(index));
+        }
+        self.palette = old.palette;
+    }
+}
+
+/// Equal when every block is, however they happen to be packed
+impl PartialEq for PalettedBlocks {
+    fn eq(&self, other: &Self) -> bool {
+        self.iter().eq(other.iter())
+    }
+}
+
+impl Eq for PalettedBlocks {}
+
+#[cfg(test)]
+mod tests {
+    use crate::game_state::test_support::STONE;
+    use super::*;
+
+    const DIRT: Block = Block::from_id(2);
+
+    #[test]
+    fn indices_grow_with_the_palette() {
+        let mut blocks = PalettedBlocks::filled(Block::AIR);
+        assert_eq!(blocks.bits_per_block(), 0);
+        assert!(blocks.words().is_empty());
+
+        blocks.set(10, STONE);
+        assert_eq!(blocks.bits_per_block(), 1);
+
+        // 3 block types take 2 bits each, an 8th of what a whole block would
+        blocks.set(20, DIRT);
+        assert_eq!(blocks.bits_per_block(), 2);
+        assert_eq!(size_of_val(blocks.words()), BLOCKS_PER_CHUNK * size_of::<Block>() / 8);
+        blocks.set(30, Block::from_id(3));
+        assert_eq!(blocks.bits_per_block(), 2);
+
+        // and a 5th one doesn't fit in 2 bits anymore
+        blocks.set(40, Block::from_id(4));
+        assert_eq!(blocks.bits_per_block(), 3);
+        assert_eq!(blocks.palette(), [Block::AIR, STONE, DIRT, Block::from_id(3), Block::from_id(4)]);
+
+        // blocks that are already in the palette don't grow it
+        blocks.set(50, STONE);
+        blocks.set(10, Block::AIR);
+        assert_eq!(blocks.bits_per_block(), 3);
+        assert_eq!(blocks.palette().len(), 5);
+    }
+
+    #[test]
+    fn blocks_survive_repacking() {
+        let mut blocks = PalettedBlocks::filled(Block::AIR);
+        let mut expected = vec![Block::AIR; BLOCKS_PER_CHUNK];
+
+        // grows all the way up to 9 bits, with blocks on both ends of words of every width
+        for id in 1..300_u16 {
+            for index in [usize::from(id) * 211 % BLOCKS_PER_CHUNK, BLOCKS_PER_CHUNK - usize::from(id)] {
+                assert_eq!(blocks.set(index, Block::from_id(id)), expected[index]);
+                expected[index] = Block::from_id(id);
+            }
+            assert_eq!(blocks.bits_per_block(), bits_for(u32::from(id) + 1));
+            assert!(blocks.iter().eq(expected.iter().copied()), "wrong blocks after adding block {id}");
+        }
+
+        // setting one block doesn't touch the ones packed next to it
+        blocks.set(0, STONE);
+        blocks.set(1, DIRT);
+        assert_eq!((blocks.get(0), blocks.get(1), blocks.get(2)), (STONE, DIRT, expected[2]));
+
+        let compressed = PalettedBlocks::from_blocks(blocks.iter());
+        assert!(compressed.iter().eq(blocks.iter()));
+        assert_eq!(compressed.palette_len(), blocks.palette_len());
+    }
+
+    #[test]
+    fn parts_pointing_past_the_palette_are_rejected() {
+        let blocks = PalettedBlocks::from_blocks((0..BLOCKS_PER_CHUNK).map(|index| Block::from_id((index % 3) as u16)));
+        let parts = |palette: &[Block]| PalettedBlocks::from_parts(palette.to_vec(), blocks.words().into());
+
+        assert!(parts(blocks.palette()).unwrap().iter().eq(blocks.iter()));
+        assert_eq!(parts(&[STONE, DIRT, Block::AIR]).unwrap().get(1), DIRT);
+
+        // 2 bits can point at a 4th entry that isn't there
+        let words = vec![u64::MAX; packed_words(2)].into_boxed_slice();
+        assert_eq!(PalettedBlocks::from_parts(vec![STONE, DIRT, Block::AIR], words).map(|_| ()), Err(0));
+    }
+}
@@ -0,0 +1,232 @@
+use std::collections::VecDeque;
+use glam::{vec3, Vec4};
+use voxel_maths::fixed_point::FixedPoint;
+use voxel_maths::FixedPointVec3;
+use crate::game_state::coords::AbsoluteCoord;
+
+/// blocks per second squared
+const GRAVITY: FixedPointVec3 = FixedPointVec3::new(
+    FixedPoint::ZERO,
+    FixedPoint::from_f32(-9.81),
+    FixedPoint::ZERO
+);
+
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct Particle {
+    pub position: AbsoluteCoord,
+    /// blocks per second
+    pub velocity: FixedPointVec3,
+    /// seconds the particle has been alive for
+    pub age: FixedPoint,
+    /// seconds the particle lives for
+    pub lifetime: FixedPoint,
+    pub color: Vec4,
+}
+
+impl Particle {
+    fn is_alive(&self) -> bool {
+        self.age < self.lifetime
+    }
+
+    fn update(&mut self, dt: FixedPoint) {
+        // semi implicit euler, velocity first
+        self.velocity += GRAVITY * dt;
+        self.position += AbsoluteCoord::from_xyz_vec(self.velocity * dt);
+        self.age += dt;
+    }
+
+    /// The color to draw the particle with, it fades out over its lifetime
+    pub fn faded_color(&self) -> Vec4 {
+        let remaining = 1.0 - (self.age.as_f32() / self.lifetime.as_f32());
+        let mut color = self.color;
+        color.w *= remaining.clamp(0.0, 1.0);
+        color
+    }
+}
+
+/// Describes a burst of particles flying out of a single point
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct ParticleEmitter {
+    pub position: AbsoluteCoord,
+    pub count: u16,
+    /// blocks per second
+    pub speed: f32,
+    /// seconds
+    pub lifetime: FixedPoint,
+    pub color: Vec4,
+}
+
+impl ParticleEmitter {
+    /// The particles of the burst, spread evenly over a sphere
+    pub fn particles(&self) -> impl Iterator<Item = Particle> {
+        // fibonacci sphere, golden angle steps around the y axis
+        let golden_angle = std::f32::consts::PI * (3.0 - 5.0_f32.sqrt());
+        let count = self.count as f32;
+        let emitter = *self;
+
+        (0..self.count).map(move |i| {
+            let y = 1.0 - 2.0 * (i as f32 + 0.5) / count;
+            let radius = (1.0 - y * y).sqrt();
+            let (sin, cos) = (golden_angle * i as f32).sin_cos();
+            let direction = vec3(cos * radius, y, sin * radius);
+
+            Particle {
+                position: emitter.position,
+                velocity: FixedPointVec3::from_f32(direction * emitter.speed),
+                age: FixedPoint::ZERO,
+                lifetime: emitter.lifetime,
+                color: emitter.color,
+            }
+        })
+    }
+}
+
+#[derive(Debug)]
+pub struct ParticleSystem {
+    /// oldest first
+    particles: VecDeque<Particle>,
+    capacity: usize,
+}
+
+impl ParticleSystem {
+    pub const DEFAULT_CAPACITY: usize = 4096;
+
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            particles: VecDeque::with_capacity(capacity),
+            capacity,
+        }
+    }
+
+    /// Adds a particle, making room by evicting the oldest one if needed
+    pub fn spawn(&mut self, particle: Particle) {
+        if self.capacity == 0 {
+            return;
+        }
+
+        if self.particles.len() == self.capacity {
+            self.particles.pop_front();
+        }
+
+        self.particles.push_back(particle)
+    }
+
+    pub fn emit(&mut self, emitter: &ParticleEmitter) {
+        for particle in emitter.particles() {
+            self.spawn(particle)
+        }
+    }
+
+    /// Advances every particle by one simulation step and drops the expired ones
+    pub fn update(&mut self, dt: FixedPoint) {
+        for particle in &mut self.particles {
+            particle.update(dt);
+        }
+
+        self.particles.retain(Particle::is_alive);
+    }
+
+    pub fn len(&self) -> usize {
+        self.particles.len()
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = &Particle> {
+        self.particles.iter()
+    }
+}
+
+impl Default for ParticleSystem {
+    fn default() -> Self {
+        Self::new(Self::DEFAULT_CAPACITY)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn particle(lifetime: f32) -> Particle {
+        Particle {
+            position: AbsoluteCoord::ZERO,
+            velocity: FixedPointVec3::from_f32(vec3(1.0, 0.0, 0.0)),
+            age: FixedPoint::ZERO,
+            lifetime: FixedPoint::from_f32(lifetime),
+            color: Vec4::ONE,
+        }
+    }
+
+    #[test]
+    fn particles_fall() {
+        let mut system = ParticleSystem::new(8);
+        system.spawn(particle(10.0));
+
+        let dt = FixedPoint::from_f32(0.5);
+        for _ in 0..2 {
+            system.update(dt);
+        }
+
+        let particle = system.iter().next().unwrap();
+        let position = particle.position.xyz().as_f32();
+        let velocity = particle.velocity.as_f32();
+
+        // v1 = -4.905, p1 = -2.4525
+        // v2 = -9.81,  p2 = -2.4525 - 4.905
+        assert!((velocity.y - -9.81).abs() < 1e-3, "{velocity}");
+        assert!((position.y - -7.3575).abs() < 1e-3, "{position}");
+        // nothing slows the particle down sideways
+        assert!((position.x - 1.0).abs() < 1e-3, "{position}");
+        assert!((particle.faded_color().w - 0.9).abs() < 1e-3);
+    }
+
+    #[test]
+    fn particles_expire() {
+        let mut system = ParticleSystem::new(8);
+        system.spawn(particle(1.0));
+        system.spawn(particle(2.0));
+
+        let dt = FixedPoint::from_f32(0.25);
+        for _ in 0..3 {
+            system.update(dt);
+        }
+        assert_eq!(system.len(), 2);
+
+        system.update(dt);
+        assert_eq!(system.len(), 1);
+
+        for _ in 0..4 {
+            system.update(dt);
+        }
+        assert_eq!(system.len(), 0);
+    }
+
+    #[test]
+    fn oldest_particles_are_evicted() {
+        let mut system = ParticleSystem::new(3);
+        for lifetime in 1..=5 {
+            system.spawn(particle(lifetime as f32));
+        }
+
+        let lifetimes = system.iter().map(|p| p.lifetime.as_f32()).collect::<Vec<_>>();
+        assert_eq!(lifetimes, [3.0, 4.0, 5.0]);
+    }
+
+    #[test]
+    fn bursts_spread_out() {
+        let emitter = ParticleEmitter {
+            position: AbsoluteCoord::ZERO,
+            count: 32,
+            speed: 2.0,
+            lifetime: FixedPoint::from_f32(1.0),
+            color: Vec4::ONE,
+        };
+
+        let mut system = ParticleSystem::new(64);
+        system.emit(&emitter);
+        assert_eq!(system.len(), 32);
+
+        for particle in system.iter() {
+            let speed = particle.velocity.as_f32().length();
+            assert!((speed - 2.0).abs() < 1e-3);
+        }
+    }
+}
@@ -0,0 +1,265 @@
+use voxel_maths::fixed_point::FixedPoint;
+use voxel_maths::{i48, FixedPointVec3};
+use voxel_maths::i48_int::i48;
+use crate::game_state::coords::{AbsoluteBlockCoord, AbsoluteCoord};
+use crate::game_state::step_up::{StepUp, PLAYER_HEIGHT};
+use crate::game_state::world::{block_y, World};
+
+/// How far the player's sides are from the middle of their feet, in blocks
+pub const PLAYER_HALF_WIDTH: FixedPoint = FixedPoint::from_f32(0.3);
+
+/// How far above their feet the player sees from, in blocks
+pub const PLAYER_EYE_HEIGHT: FixedPoint = FixedPoint::from_f32(1.625);
+
+/// How fast a jump sends the player up, in blocks a second, enough to get a bit over a block high
+pub const JUMP_SPEED: FixedPoint = FixedPoint::from_int(i48!(9));
+
+/// How the player moves through whatever they're in, every speed is in blocks a second
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub struct MovementPhysics {
+    /// how fast falling speeds up, in blocks a second squared
+    pub gravity: FixedPoint,
+    /// how much of the gravity gets pushed back against, also in blocks a second squared
+    pub buoyancy: FixedPoint,
+    /// the fastest the player can fall
+    pub terminal_velocity: FixedPoint,
+    /// multiplies how fast the player walks
+    pub speed: FixedPoint,
+    /// how fast holding jump moves the player up, `None` where jumping is left to the ground
+    pub swim_speed: Option<FixedPoint>,
+}
+
+impl MovementPhysics {
+    pub const AIR: Self = Self {
+        gravity: FixedPoint::from_int(i48!(32)),
+        buoyancy: FixedPoint::ZERO,
+        terminal_velocity: FixedPoint::from_int(i48!(78)),
+        speed: FixedPoint::from_int(i48!(1)),
+        swim_speed: None,
+    };
+
+    pub const FLUID: Self = Self {
+        gravity: FixedPoint::from_int(i48!(8)),
+        buoyancy: FixedPoint::from_int(i48!(6)),
+        terminal_velocity: FixedPoint::from_int(i48!(2)),
+        speed: FixedPoint::from_f32(0.5),
+        swim_speed: Some(FixedPoint::from_int(i48!(3))),
+    };
+
+    /// What the player moves through with their feet at `feet`, fluid if any of them is in it
+    pub fn at(world: &World, feet: AbsoluteCoord) -> &'static Self {
+        match in_fluid(world, feet) {
+            true => &Self::FLUID,
+            false => &Self::AIR,
+        }
+    }
+
+    /// How fast the player moves up after falling for `dt` seconds from moving up at `vertical`,
+    /// holding `jump` swims up if there's something to swim in
+    pub fn fall(&self, vertical: FixedPoint, jump: bool, dt: FixedPoint) -> FixedPoint {
+        let mut vertical = vertical - (self.gravity - self.buoyancy) * dt;
+        if let (true, Some(swim_speed)) = (jump, self.swim_speed) && vertical < swim_speed {
+            vertical = swim_speed
+        }
+
+        if vertical < -self.terminal_velocity {
+            return -self.terminal_velocity
+        }
+        vertical
+    }
+}
+
+/// Every block the player overlaps standing with their feet at `feet`, as x, y and z,
+/// including the ones above or below the world
+fn overlapped(feet: AbsoluteCoord) -> impl Iterator<Item = (i64, i64, i64)> {
+    // blocks the player only touches the side of aren't ones they're in
+    let just_under = |coord: FixedPoint| (coord - FixedPoint::from_bits(1)).int().as_i64();
+    let span = |coord: FixedPoint, below: FixedPoint, above: FixedPoint| {
+        (coord - below).int().as_i64()..=just_under(coord + above)
+    };
+
+    let height = FixedPoint::from_int(i48::from(PLAYER_HEIGHT));
+    let ys = span(feet.y(), FixedPoint::ZERO, height);
+    let xs = span(feet.x(), PLAYER_HALF_WIDTH, PLAYER_HALF_WIDTH);
+    let zs = span(feet.z(), PLAYER_HALF_WIDTH, PLAYER_HALF_WIDTH);
+
+    ys.flat_map(move |y| {
+        let zs = zs.clone();
+        xs.clone().flat_map(move |x| zs.clone().map(move |z| (x, y, z)))
+    })
+}
+
+/// Whether any of the player, standing with their feet at `feet`, is inside a fluid block
+pub fn in_fluid(world: &World, feet: AbsoluteCoord) -> bool {
+    overlapped(feet)
+        .filter_map(|(x, y, z)| Some(AbsoluteBlockCoord::from_xyz(i48::new(x)?, block_y(y)?, i48::new(z)?)))
+        .any(|block| world.get_block(block).is_some_and(|block| world.blocks().is_fluid(block)))
+}
+
+/// Whether the player, standing with their feet at `feet`, is inside of anything they can't walk through
+pub fn collides(world: &World, feet: AbsoluteCoord) -> bool {
+    overlapped(feet).any(|block| !is_clear(world, block))
+}
+
+/// Whether nothing is in the way at a block, the sky above the world is clear
+/// while below the world, past its edge and chunks that aren't loaded block everything
+fn is_clear(world: &World, (x, y, z): (i64, i64, i64)) -> bool {
+    let Some(y) = block_y(y) else {
+        return y > 0;
+    };
+    let (Some(x), Some(z)) = (i48::new(x), i48::new(z)) else {
+        return false;
+    };
+
+    world
+        .get_block(AbsoluteBlockCoord::from_xyz(x, y, z))
+        .is_some_and(|block| !world.blocks().is_solid(block))
+}
+
+/// Whether the chunk the player is standing in is loaded,
+/// there's nothing to stand on or bump into until it is
+pub fn is_loaded(world: &World, feet: AbsoluteCoord) -> bool {
+    let column = AbsoluteBlockCoord::from_xyz(feet.x().int(), 0, feet.z().int());
+    world.chunk(column.chunk()).is_some()
+}
+
+/// Which ways [`move_player`] got stopped
+#[derive(Debug, Copy, Clone, Default, Eq, PartialEq)]
+pub struct Collisions {
+    /// landed on something
+    pub ground: bool,
+    /// bumped their head
+    pub ceiling: bool,
+    /// walked into something too high to step onto
+    pub wall: bool,
+}
+
+/// Moves the player's feet from `feet` by `delta`, up or down first and then along x and z,
+/// stopping them flush against whatever they run into
+///
+/// once they're standing on the ground, walking into a ledge lifts them onto it with `step_up`.
+/// a player that's already stuck inside of something moves freely so they can get back out
+pub fn move_player(world: &World, feet: AbsoluteCoord, delta: FixedPointVec3, step_up: &StepUp) -> (AbsoluteCoord, Collisions) {
+    let mut collisions = Collisions::default();
+    if collides(world, feet) {
+        return (feet + AbsoluteCoord::from_xyz_vec(delta), collisions);
+    }
+
+    // moving less than a block at a time the player can't skip past a block without touching it
+    let half_block = FixedPoint::from_f32(0.5);
+    let one = FixedPoint::from_int(i48!(1));
+    let height = FixedPoint::from_int(i48::from(PLAYER_HEIGHT));
+
+    let mut position = <[FixedPoint; 3]>::from(feet);
+    let delta = [delta.x, delta.y, delta.z];
+    for axis in [1, 0, 2] {
+        // how far the player reaches below and above their feet along this axis
+        let (below, above) = match axis {
+            1 => (FixedPoint::ZERO, height),
+            _ => (PLAYER_HALF_WIDTH, PLAYER_HALF_WIDTH),
+        };
+
+        let mut left = delta[axis];
+        while left != FixedPoint::ZERO {
+            let piece = left.clamp(-half_block, half_block);
+            left -= piece;
+
+            let mut to = position;
+            to[axis] += piece;
+            if !collides(world, to.into()) {
+                position = to;
+                continue;
+            }
+
+            let lifted = (axis != 1 && collisions.ground).then(|| step_up.walk(world, position.into(), to.into()));
+            if let Some(Some(lifted)) = lifted {
+                position = lifted.into();
+                continue;
+            }
+
+            // the block run into is the one the leading side moved into
+            position[axis] = match piece < FixedPoint::ZERO {
+                true => FixedPoint::from_int((to[axis] - below).int()) + one + below,
+                false => FixedPoint::from_int((to[axis] + above - FixedPoint::from_bits(1)).int()) - above,
+            };
+            match (axis, piece < FixedPoint::ZERO) {
+                (1, true) => collisions.ground = true,
+                (1, false) => collisions.ceiling = true,
+                _ => collisions.wall = true,
+            }
+            break;
+        }
+    }
+
+    (position.into(), collisions)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::game_state::blocks::{BlockProperties, BlockRegistry, FaceTextures};
+    use crate::game_state::coords::ChunkCoord;
+    use crate::game_state::test_support::at;
+    use crate::game_state::world::Chunk;
+    use super::*;
+
+    /// A pool of water 4 blocks across and 2 deep, from (4, 10, 4) to (7, 11, 7)
+    fn pool() -> World {
+        let mut blocks = BlockRegistry::new();
+        let water = blocks.register(BlockProperties::fluid("water", FaceTextures::all(1)));
+        let mut world = World::with_blocks(blocks);
+        world.insert_chunk(ChunkCoord::ZERO, Chunk::empty());
+        for x in 4..8 {
+            for y in 10..12 {
+                for z in 4..8 {
+                    world.set_block(AbsoluteBlockCoord::from_xyz(i48::from(x as u8), y, i48::from(z as u8)), water);
+                }
+            }
+        }
+        world
+    }
+
+    #[test]
+    fn fluids_are_found_anywhere_around_the_player() {
+        let world = pool();
+        assert!(in_fluid(&world, at(5.5, 10.0, 5.5)));
+        // only their head in it, from below
+        assert!(in_fluid(&world, at(5.5, 8.5, 5.5)));
+        // reaching over the edge
+        assert!(in_fluid(&world, at(3.8, 10.0, 5.5)));
+
+        // standing next to it, touching it isn't being in it
+        assert!(!in_fluid(&world, at(3.7, 10.0, 5.5)));
+        assert!(!in_fluid(&world, at(5.5, 8.0, 5.5)));
+        assert!(!in_fluid(&world, at(5.5, 12.0, 5.5)));
+        // out in unloaded chunks
+        assert!(!in_fluid(&world, at(-5.5, 10.0, 5.5)));
+    }
+
+    #[test]
+    fn fluids_slow_falling_down() {
+        let world = pool();
+        let dt = FixedPoint::from_f32(0.0625);
+        let int = |int: i64| FixedPoint::from_int(i48::new(int).unwrap());
+
+        let air = MovementPhysics::at(&world, at(5.5, 13.0, 5.5));
+        let water = MovementPhysics::at(&world, at(5.5, 10.5, 5.5));
+        assert_eq!(air, &MovementPhysics::AIR);
+        assert_eq!(water, &MovementPhysics::FLUID);
+
+        // from standing still
+        assert_eq!(air.fall(FixedPoint::ZERO, false, dt), int(-2));
+        assert_eq!(water.fall(FixedPoint::ZERO, false, dt), FixedPoint::from_f32(-0.125));
+
+        // already falling as fast as it gets
+        assert_eq!(air.fall(int(-100), false, dt), int(-78));
+        assert_eq!(water.fall(int(-100), false, dt), int(-2));
+        // jumping into the pool slows right down
+        assert_eq!(water.fall(int(-20), false, dt), int(-2));
+
+        // jump swims up, but only in a fluid
+        assert_eq!(water.fall(FixedPoint::ZERO, true, dt), int(3));
+        assert_eq!(air.fall(FixedPoint::ZERO, true, dt), int(-2));
+        assert_eq!(water.fall(int(5), true, dt), int(5) - FixedPoint::from_f32(0.125));
+        assert!(water.speed < air.speed);
+    }
+}
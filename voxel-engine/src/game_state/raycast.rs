@@ -0,0 +1,100 @@
+use glam::Vec3;
+use voxel_maths::i48_int::i48;
+use crate::game_state::blocks::BlockFace;
+use crate::game_state::coords::{AbsoluteBlockCoord, AbsoluteCoord};
+use crate::game_state::world::{block_y, World};
+
+/// The first selectable block within `reach` blocks of `origin` looking along `direction`,
+/// and the face the ray went in through
+///
+/// the block `origin` is inside of is skipped, it has no face to hit from in there.
+/// blocks in chunks that aren't loaded are passed through
+pub fn raycast(world: &World, origin: AbsoluteCoord, direction: Vec3, reach: f32) -> Option<(AbsoluteBlockCoord, BlockFace)> {
+    let direction = direction.try_normalize()?;
+
+    // walked relative to the block the ray starts in, so it stays precise however far out that is
+    let start = [origin.x(), origin.y(), origin.z()].map(|coord| coord.int().as_i64());
+    let inside = Vec3::from_array([origin.x(), origin.y(), origin.z()].map(|coord| coord.fract().as_f32()));
+
+    let step = direction.signum().to_array().map(|sign| sign as i64);
+    // how far along the ray the next block boundary on each axis is, and the distance between them
+    let mut next = [0, 1, 2].map(|axis| match direction[axis] {
+        0.0 => f32::INFINITY,
+        d if d > 0.0 => (1.0 - inside[axis]) / d,
+        d => inside[axis] / -d,
+    });
+    let between = direction.recip().abs().to_array();
+
+    let mut offset = [0_i64; 3];
+    loop {
+        let axis = (0..3).min_by(|&a, &b| next[a].total_cmp(&next[b]))?;
+        if next[axis] > reach {
+            return None;
+        }
+
+        offset[axis] += step[axis];
+        next[axis] += between[axis];
+
+        let [x, y, z] = [0, 1, 2].map(|axis| start[axis] + offset[axis]);
+        let Some(y) = block_y(y) else {
+            continue;
+        };
+        let coord = AbsoluteBlockCoord::from_xyz(i48::new(x)?, y, i48::new(z)?);
+        if world.get_block(coord).is_some_and(|block| world.blocks().is_selectable(block)) {
+            // the ray goes into the block through the face pointing back the way it came
+            let face = match (axis, step[axis] > 0) {
+                (0, true) => BlockFace::NegX,
+                (0, false) => BlockFace::PosX,
+                (1, true) => BlockFace::NegY,
+                (1, false) => BlockFace::PosY,
+                (_, true) => BlockFace::NegZ,
+                (_, false) => BlockFace::PosZ,
+            };
+            return Some((coord, face));
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use voxel_maths::i48;
+    use crate::game_state::coords::ChunkCoord;
+    use crate::game_state::test_support::{at, STONE};
+    use crate::game_state::world::Chunk;
+    use super::*;
+
+    #[test]
+    fn hits_the_first_block_along_the_ray() {
+        let mut world = World::new();
+        world.insert_chunk(ChunkCoord::ZERO, Chunk::empty());
+        world.insert_chunk(ChunkCoord::from_xz(-1, 0), Chunk::empty());
+        let block = AbsoluteBlockCoord::from_xyz(i48!(5), 10, i48!(3));
+        world.set_block(block, STONE);
+        world.set_block(AbsoluteBlockCoord::from_xyz(i48!(7), 10, i48!(3)), STONE);
+
+        // straight along +x, from the middle of a block 4 away
+        let eye = at(1.5, 10.5, 3.5);
+        assert_eq!(raycast(&world, eye, Vec3::X, 5.0), Some((block, BlockFace::NegX)));
+        // too far to reach
+        assert_eq!(raycast(&world, eye, Vec3::X, 3.0), None);
+        // looking away
+        assert_eq!(raycast(&world, eye, Vec3::NEG_X, 10.0), None);
+
+        // from above at an angle, landing on its top
+        let hit = raycast(&world, at(4.2, 13.0, 3.5), vec_to(at(4.2, 13.0, 3.5), at(5.5, 11.0, 3.5)), 6.0);
+        assert_eq!(hit, Some((block, BlockFace::PosY)));
+
+        // from the far side, across the chunk border at x = 0
+        let other = AbsoluteBlockCoord::from_xyz(i48!(-2), 10, i48!(3));
+        world.set_block(other, STONE);
+        assert_eq!(raycast(&world, at(3.5, 10.5, 3.5), Vec3::NEG_X, 8.0), Some((other, BlockFace::PosX)));
+
+        // air and unloaded chunks get passed through
+        assert_eq!(raycast(&world, at(20.5, 10.5, 3.5), Vec3::Z, 10.0), None);
+        assert_eq!(raycast(&world, eye, Vec3::ZERO, 10.0), None);
+    }
+
+    fn vec_to(from: AbsoluteCoord, to: AbsoluteCoord) -> Vec3 {
+        to.xyz().as_f32() - from.xyz().as_f32()
+    }
+}
@@ -0,0 +1,113 @@
+use voxel_maths::fixed_point::FixedPoint;
+use voxel_maths::i48_int::i48;
+use crate::game_state::coords::AbsoluteCoord;
+use crate::game_state::physics::collides;
+use crate::game_state::world::World;
+
+/// How many blocks tall the player is, everything has to be clear that high for them to stand somewhere
+pub const PLAYER_HEIGHT: u8 = 2;
+
+/// Lifts the player onto ledges they walk into instead of stopping them, as long as the ledge is low enough
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub struct StepUp {
+    /// the highest ledge that gets stepped onto, in blocks
+    pub max_height: u8,
+}
+
+impl Default for StepUp {
+    fn default() -> Self {
+        Self { max_height: 1 }
+    }
+}
+
+impl StepUp {
+    /// Where the feet end up after walking from `from` to `to` on the same level,
+    /// lifted onto the ledge if there is one in the way, `None` if the way is blocked
+    pub fn walk(&self, world: &World, from: AbsoluteCoord, to: AbsoluteCoord) -> Option<AbsoluteCoord> {
+        let lift = self.lift(world, from, to)?;
+        if lift == 0 {
+            return Some(to);
+        }
+
+        // standing right on top of the ledge, wherever in the block the feet were
+        let y = FixedPoint::from_int(to.y().int().checked_add(i48::from(lift))?);
+        Some(AbsoluteCoord::from_xyz(to.x(), y, to.z()))
+    }
+
+    /// How many blocks up the feet have to go to walk from `from` to `to`, `None` if it's further than [`StepUp::max_height`]
+    fn lift(&self, world: &World, from: AbsoluteCoord, to: AbsoluteCoord) -> Option<u8> {
+        let up = |at: AbsoluteCoord, up: u8| {
+            if up == 0 {
+                return Some(at);
+            }
+            let y = FixedPoint::from_int(at.y().int().checked_add(i48::from(up))?);
+            Some(AbsoluteCoord::from_xyz(at.x(), y, at.z()))
+        };
+        let fits = |at: Option<AbsoluteCoord>| at.is_some_and(|at| !collides(world, at));
+
+        (0..=self.max_height).find(|&lift| {
+            // the player has to fit where they are lifted to, and on their way up to it
+            fits(up(to, lift)) && (1..=lift).all(|height| fits(up(from, height)))
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use voxel_maths::i48;
+    use crate::game_state::coords::{AbsoluteBlockCoord, ChunkCoord};
+    use crate::game_state::test_support::{at, STONE};
+    use crate::game_state::world::Chunk;
+    use super::*;
+
+    /// A flat floor at y = 9 with stone stacked `heights` high on it, along x = 5, 6, 7...
+    fn world_with(heights: &[u8]) -> World {
+        let mut world = World::new();
+        world.insert_chunk(ChunkCoord::ZERO, Chunk::empty());
+        for x in 0..16 {
+            world.set_block(AbsoluteBlockCoord::from_xyz(i48::from(x as u8), 9, i48!(3)), STONE);
+        }
+        for (x, &height) in (5_u8..).zip(heights) {
+            for y in 10..10 + height {
+                world.set_block(AbsoluteBlockCoord::from_xyz(i48::from(x), y, i48!(3)), STONE);
+            }
+        }
+        world
+    }
+
+    #[test]
+    fn steps_onto_single_blocks() {
+        let world = world_with(&[1]);
+        let step_up = StepUp::default();
+
+        // the player's side reaching onto the block lifts them on top of it, the same distance in
+        assert_eq!(step_up.walk(&world, at(4.5, 10.0, 3.5), at(4.8, 10.0, 3.5)), Some(at(4.8, 11.0, 3.5)));
+        // nothing in the way
+        assert_eq!(step_up.walk(&world, at(3.5, 10.0, 3.5), at(4.5, 10.0, 3.5)), Some(at(4.5, 10.0, 3.5)));
+        // and back down is left to falling
+        assert_eq!(step_up.walk(&world, at(5.5, 11.0, 3.5), at(6.1, 11.0, 3.5)), Some(at(6.1, 11.0, 3.5)));
+
+        // no room to stand on top of it
+        let mut low_ceiling = world_with(&[1]);
+        low_ceiling.set_block(AbsoluteBlockCoord::from_xyz(i48!(5), 12, i48!(3)), STONE);
+        assert_eq!(step_up.walk(&low_ceiling, at(4.5, 10.0, 3.5), at(4.8, 10.0, 3.5)), None);
+        // or to get up there
+        let mut bump = world_with(&[1]);
+        bump.set_block(AbsoluteBlockCoord::from_xyz(i48!(4), 12, i48!(3)), STONE);
+        assert_eq!(step_up.walk(&bump, at(4.5, 10.0, 3.5), at(4.8, 10.0, 3.5)), None);
+    }
+
+    #[test]
+    fn walls_stop_the_player() {
+        let world = world_with(&[2]);
+        assert_eq!(StepUp::default().walk(&world, at(4.5, 10.0, 3.5), at(4.8, 10.0, 3.5)), None);
+
+        // unless they can step that high
+        let high = StepUp { max_height: 2 };
+        assert_eq!(high.walk(&world, at(4.5, 10.0, 3.5), at(4.8, 10.0, 3.5)), Some(at(4.8, 12.0, 3.5)));
+        assert_eq!(StepUp { max_height: 0 }.walk(&world_with(&[1]), at(4.5, 10.0, 3.5), at(4.8, 10.0, 3.5)), None);
+
+        // walking out of the loaded world
+        assert_eq!(StepUp::default().walk(&world, at(15.5, 10.0, 3.5), at(15.8, 10.0, 3.5)), None);
+    }
+}
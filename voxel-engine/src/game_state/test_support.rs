@@ -0,0 +1,33 @@
+//! Blocks, coordinates and worlds the game state's tests build on
+
+use voxel_maths::fixed_point::FixedPoint;
+use voxel_maths::i48_int::i48;
+use crate::game_state::blocks::BlockRegistry;
+use crate::game_state::coords::{AbsoluteBlockCoord, AbsoluteCoord, ChunkCoord};
+use crate::game_state::world::{Block, Chunk, World};
+
+/// a plain solid block, unless a test registers something else in its place
+pub const STONE: Block = Block::from_id(1);
+
+pub fn at(x: f32, y: f32, z: f32) -> AbsoluteCoord {
+    AbsoluteCoord::from_xyz(FixedPoint::from_f32(x), FixedPoint::from_f32(y), FixedPoint::from_f32(z))
+}
+
+pub fn block_at(x: i64, y: u8, z: i64) -> AbsoluteBlockCoord {
+    AbsoluteBlockCoord::from_xyz(i48::new(x).unwrap(), y, i48::new(z).unwrap())
+}
+
+/// A world with empty `chunks` loaded, and none of them left dirty
+pub fn world_with(chunks: &[ChunkCoord]) -> World {
+    world_with_blocks(BlockRegistry::new(), chunks)
+}
+
+/// [`world_with`], for the blocks in `blocks`
+pub fn world_with_blocks(blocks: BlockRegistry, chunks: &[ChunkCoord]) -> World {
+    let mut world = World::with_blocks(blocks);
+    for &chunk in chunks {
+        world.insert_chunk(chunk, Chunk::empty());
+    }
+    world.dirty_chunks_mut().drain().for_each(drop);
+    world
+}
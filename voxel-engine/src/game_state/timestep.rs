@@ -0,0 +1,140 @@
+use std::time::Duration;
+use voxel_maths::fixed_point::FixedPoint;
+
+const NANOS_PER_SEC: u128 = 1_000_000_000;
+const FRACTION_BITS: u32 = 16;
+
+/// A duration in fixed point seconds, rounded down to the nearest fraction a [`FixedPoint`] can hold,
+/// worked out with integers only so the same duration always makes the same step
+pub fn to_seconds(duration: Duration) -> FixedPoint {
+    let bits = (duration.as_nanos() << FRACTION_BITS) / NANOS_PER_SEC;
+    FixedPoint::from_bits(i64::try_from(bits).unwrap_or(i64::MAX))
+}
+
+/// The other way around from [`to_seconds`], rounded down to the nanosecond, negative seconds are no time at all
+pub fn to_duration(seconds: FixedPoint) -> Duration {
+    let bits = u128::try_from(seconds.to_bits()).unwrap_or(0);
+    let nanos = (bits * NANOS_PER_SEC) >> FRACTION_BITS;
+    Duration::from_nanos(u64::try_from(nanos).unwrap_or(u64::MAX))
+}
+
+/// Accumulates real time and hands it out in fixed sized simulation steps,
+/// whatever doesn't fill a whole step is carried over to the next frame
+#[derive(Debug, Copy, Clone)]
+pub struct FixedTimestep {
+    step: Duration,
+    accumulated: Duration,
+    max_steps: u32,
+}
+
+impl FixedTimestep {
+    pub const DEFAULT_STEP: Duration = Duration::from_nanos(1_000_000_000 / 60);
+
+    /// how many steps a single frame may run before time starts getting dropped,
+    /// without this a slow step makes the next frame slower, which makes more steps, which...
+    pub const DEFAULT_MAX_STEPS: u32 = 8;
+
+    pub const fn new(step: Duration) -> Self {
+        assert!(!step.is_zero(), "a fixed timestep can't be zero");
+
+        Self {
+            step,
+            accumulated: Duration::ZERO,
+            max_steps: Self::DEFAULT_MAX_STEPS,
+        }
+    }
+
+    pub const fn step(&self) -> Duration {
+        self.step
+    }
+
+    /// Adds `elapsed` real time and returns how many fixed steps should be run
+    pub fn advance(&mut self, elapsed: Duration) -> u32 {
+        self.accumulated += elapsed;
+
+        let mut steps = 0;
+        while self.accumulated >= self.step {
+            if steps == self.max_steps {
+                // drop the backlog, the simulation slows down instead of spiraling
+                self.accumulated = Duration::ZERO;
+                break;
+            }
+
+            self.accumulated -= self.step;
+            steps += 1;
+        }
+
+        steps
+    }
+
+    /// The time that didn't make up a full step
+    pub const fn remainder(&self) -> Duration {
+        self.accumulated
+    }
+
+    /// How far between the last and the next step the current frame is, in `[0, 1)`
+    pub fn alpha(&self) -> f32 {
+        self.remainder().as_secs_f32() / self.step.as_secs_f32()
+    }
+}
+
+impl Default for FixedTimestep {
+    fn default() -> Self {
+        Self::new(Self::DEFAULT_STEP)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const STEP: Duration = Duration::from_millis(10);
+
+    #[test]
+    fn long_frame_runs_multiple_steps() {
+        let mut timestep = FixedTimestep::new(STEP);
+
+        assert_eq!(timestep.advance(Duration::from_millis(35)), 3);
+        assert_eq!(timestep.remainder(), Duration::from_millis(5));
+        assert!((timestep.alpha() - 0.5).abs() < 1e-6);
+
+        // the remainder is carried over
+        assert_eq!(timestep.advance(Duration::from_millis(5)), 1);
+        assert_eq!(timestep.remainder(), Duration::ZERO);
+    }
+
+    #[test]
+    fn short_frames_accumulate() {
+        let mut timestep = FixedTimestep::new(STEP);
+
+        for _ in 0..3 {
+            assert_eq!(timestep.advance(Duration::from_millis(3)), 0);
+        }
+
+        assert_eq!(timestep.advance(Duration::from_millis(3)), 1);
+        assert_eq!(timestep.remainder(), Duration::from_millis(2));
+    }
+
+    #[test]
+    fn durations_convert_without_floats() {
+        let sixty_fourth = "0.015625".parse::<FixedPoint>().unwrap();
+        assert_eq!(to_seconds(Duration::from_micros(15_625)), sixty_fourth);
+        assert_eq!(to_duration(sixty_fourth), Duration::from_micros(15_625));
+        assert_eq!(to_duration(FixedPoint::from_bits(1)), Duration::from_nanos(15_258));
+
+        // the default step doesn't fit exactly, it comes out less than one fraction short
+        let step = to_duration(to_seconds(FixedTimestep::DEFAULT_STEP));
+        assert!(FixedTimestep::DEFAULT_STEP - step < to_duration(FixedPoint::from_bits(1)));
+
+        assert_eq!(to_duration(-sixty_fourth), Duration::ZERO);
+        assert_eq!(to_seconds(Duration::MAX), FixedPoint::MAX);
+    }
+
+    #[test]
+    fn huge_frame_is_clamped() {
+        let mut timestep = FixedTimestep::new(STEP);
+
+        assert_eq!(timestep.advance(Duration::from_secs(10)), FixedTimestep::DEFAULT_MAX_STEPS);
+        assert_eq!(timestep.remainder(), Duration::ZERO);
+    }
+}
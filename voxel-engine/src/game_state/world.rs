@@ -0,0 +1,444 @@
+use std::sync::Arc;
+use ahash::{HashMap, HashSet};
+use crate::game_state::blocks::BlockRegistry;
+use crate::game_state::coords::{AbsoluteBlockCoord, BlockCoord, BlockY, ChunkCoord};
+use crate::game_state::lighting::{self, BlockLight, ChunkLight};
+use crate::game_state::palette::PalettedBlocks;
+
+/// the width and depth of a chunk in blocks
+pub const CHUNK_SIZE: usize = 16;
+
+/// how many blocks tall the world is, [`BlockY`] is picked wide enough to address all of them
+pub const WORLD_HEIGHT: usize = 256;
+
+/// the height of a chunk in blocks, chunks go from the bottom of the world all the way up
+pub const CHUNK_HEIGHT: usize = WORLD_HEIGHT;
+
+/// Whether a block at height `y` is inside the world, from the bottom up to [`WORLD_HEIGHT`]
+pub const fn is_valid_y(y: i64) -> bool {
+    0 <= y && y < WORLD_HEIGHT as i64
+}
+
+/// The height `y` as a block's y, `None` if it's above or below the world
+pub const fn block_y(y: i64) -> Option<BlockY> {
+    const { assert!(WORLD_HEIGHT - 1 <= BlockY::MAX as usize, "BlockY can't address the top of the world") }
+    match is_valid_y(y) {
+        true => Some(y as BlockY),
+        false => None,
+    }
+}
+
+/// the brightest a block can be lit, light levels go from 0 (dark) up to this
+pub const MAX_LIGHT: u8 = 15;
+
+/// x, then z, then y is the order blocks are stored in
+pub const BLOCKS_PER_CHUNK: usize = CHUNK_SIZE * CHUNK_SIZE * CHUNK_HEIGHT;
+
+/// The id of a kind of block, what it is lives in the [`BlockRegistry`]
+#[derive(Debug, Copy, Clone, Hash, Eq, PartialEq, Default)]
+pub struct Block(u16);
+
+impl Block {
+    pub const AIR: Self = Self(0);
+
+    pub const fn from_id(id: u16) -> Self {
+        Self(id)
+    }
+
+    pub const fn id(self) -> u16 {
+        self.0
+    }
+
+    pub const fn is_air(self) -> bool {
+        self.0 == Self::AIR.0
+    }
+}
+
+/// The blocks of a column of the world, kept palette compressed since most chunks are only a handful of block types
+#[derive(Clone)]
+pub struct Chunk {
+    blocks: PalettedBlocks,
+    /// worked out by the world once the chunk is in it
+    light: ChunkLight,
+}
+
+impl Chunk {
+    #[cfg_attr(not(test), expect(dead_code, reason = "nothing generates chunks yet"))]
+    pub fn empty() -> Self {
+        Self::from_blocks(PalettedBlocks::filled(Block::AIR))
+    }
+
+    pub fn from_blocks(blocks: PalettedBlocks) -> Self {
+        Self { blocks, light: ChunkLight::default() }
+    }
+
+    /// Every block in the chunk, in the order described by [`BLOCKS_PER_CHUNK`]
+    pub fn blocks(&self) -> &PalettedBlocks {
+        &self.blocks
+    }
+
+    /// Every block in the chunk along with where it is, in the order they're stored in
+    /// so walking through them goes straight through memory
+    pub fn iter(&self) -> impl ExactSizeIterator<Item = (BlockCoord, Block)> + '_ {
+        self.blocks.iter().enumerate().map(|(index, block)| (Self::coord(index), block))
+    }
+
+    /// Every block in the chunk that isn't air, in the same order as [`Chunk::iter`]
+    pub fn iter_non_air(&self) -> impl Iterator<Item = (BlockCoord, Block)> + '_ {
+        self.iter().filter(|(_, block)| !block.is_air())
+    }
+
+    #[inline(always)]
+    fn index(coord: BlockCoord) -> usize {
+        let [x, y, z] = coord.xyz().to_array().map(usize::from);
+        (y * CHUNK_SIZE + z) * CHUNK_SIZE + x
+    }
+
+    /// The opposite of [`Chunk::index`]
+    #[inline(always)]
+    fn coord(index: usize) -> BlockCoord {
+        let (x, z, y) = (index % CHUNK_SIZE, index / CHUNK_SIZE % CHUNK_SIZE, index / (CHUNK_SIZE * CHUNK_SIZE));
+        BlockCoord::from_xyz(x as u8, y as BlockY, z as u8)
+    }
+
+    #[inline]
+    pub fn get(&self, coord: BlockCoord) -> Block {
+        self.blocks.get(Self::index(coord))
+    }
+
+    /// Replaces a block, returning the old one
+    #[inline]
+    pub fn set(&mut self, coord: BlockCoord, block: Block) -> Block {
+        self.blocks.set(Self::index(coord), block)
+    }
+
+    /// How lit the block is, everything is dark until the chunk is put in a [`World`]
+    #[inline]
+    pub fn light(&self, coord: BlockCoord) -> BlockLight {
+        self.light.get(Self::index(coord))
+    }
+
+    /// Changes how lit a block is, only for [`lighting`] to spread light with
+    pub(super) fn set_light(&mut self, coord: BlockCoord, light: BlockLight) {
+        self.light.set(Self::index(coord), light)
+    }
+}
+
+/// The chunks whose meshes are out of date
+#[derive(Debug, Default)]
+pub struct DirtyChunks {
+    chunks: HashSet<ChunkCoord>,
+}
+
+impl DirtyChunks {
+    pub fn mark(&mut self, chunk: ChunkCoord) {
+        self.chunks.insert(chunk);
+    }
+
+    #[cfg_attr(not(test), expect(dead_code, reason = "only tests need to peek at the dirty set"))]
+    pub fn is_dirty(&self, chunk: ChunkCoord) -> bool {
+        self.chunks.contains(&chunk)
+    }
+
+    #[cfg_attr(not(test), expect(dead_code, reason = "only tests need to peek at the dirty set"))]
+    pub fn len(&self) -> usize {
+        self.chunks.len()
+    }
+
+    /// Takes every dirty chunk, leaving the set clean
+    pub fn drain(&mut self) -> impl Iterator<Item = ChunkCoord> + '_ {
+        self.chunks.drain()
+    }
+}
+
+/// The 8 chunks around `chunk`, diagonals included
+pub fn surrounding_chunks(chunk: ChunkCoord) -> impl Iterator<Item = ChunkCoord> {
+    (-1..=1)
+        .flat_map(|dz| (-1..=1).map(move |dx| (dx, dz)))
+        .filter(|&offset| offset != (0, 0))
+        .filter_map(move |(dx, dz)| chunk.offset(dx, dz))
+}
+
+#[derive(Default)]
+pub struct World {
+    /// chunks are shared copy on write, so meshing can work on a snapshot off the main thread
+    chunks: HashMap<ChunkCoord, Arc<Chunk>>,
+    dirty: DirtyChunks,
+    blocks: Arc<BlockRegistry>,
+    /// goes up with every change to the blocks, so whatever depends on them knows when to look again
+    revision: u64,
+}
+
+impl World {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    #[cfg_attr(not(test), expect(dead_code, reason = "there are only the built in blocks for now"))]
+    pub fn with_blocks(blocks: BlockRegistry) -> Self {
+        Self {
+            blocks: Arc::new(blocks),
+            ..Self::default()
+        }
+    }
+
+    /// What every block in the world is
+    pub fn blocks(&self) -> &Arc<BlockRegistry> {
+        &self.blocks
+    }
+
+    pub fn chunk(&self, coord: ChunkCoord) -> Option<&Arc<Chunk>> {
+        self.chunks.get(&coord)
+    }
+
+    /// Adds (or replaces) a chunk, its neighbors are re-meshed too
+    /// since the faces and shading along the edges depend on it
+    ///
+    /// whatever light the chunk had gets worked out again, light spreads into it from around it
+    #[cfg_attr(not(test), expect(dead_code, reason = "nothing generates chunks yet"))]
+    pub fn insert_chunk(&mut self, coord: ChunkCoord, mut chunk: Chunk) {
+        let columns = lighting::sky_columns(&chunk, &self.blocks);
+        chunk.light = ChunkLight::sky(&columns);
+        let replaced = self.chunks.insert(coord, Arc::new(chunk));
+        self.revision += 1;
+        self.dirty.mark(coord);
+        for neighbor in surrounding_chunks(coord) {
+            if self.chunks.contains_key(&neighbor) {
+                self.dirty.mark(neighbor);
+            }
+        }
+
+        lighting::chunk_loaded(self, coord, &columns, replaced.as_deref());
+    }
+
+    /// The block at `coord`, `None` if its chunk isn't loaded
+    pub fn get_block(&self, coord: AbsoluteBlockCoord) -> Option<Block> {
+        self.chunk(coord.chunk()).map(|chunk| chunk.get(coord.block()))
+    }
+
+    /// Replaces the block at `coord` and returns the old one,
+    /// `None` if it's above the world or its chunk isn't loaded
+    ///
+    /// the chunk gets marked dirty, and so do the neighbors the block touches
+    /// when it sits on the chunk's edge
+    pub fn set_block(&mut self, coord: AbsoluteBlockCoord, block: Block) -> Option<Block> {
+        if !is_valid_y(i64::from(coord.y())) {
+            return None;
+        }
+
+        let chunk_coord = coord.chunk();
+        let chunk = self.chunks.get_mut(&chunk_coord)?;
+        if chunk.get(coord.block()) == block {
+            return Some(block);
+        }
+
+        let old = Arc::make_mut(chunk).set(coord.block(), block);
+        self.revision += 1;
+        self.mark_dirty_around(coord);
+
+        // light only cares about whether the block lets it through and whether it gives any off
+        let blocks = &self.blocks;
+        if blocks.is_transparent(old) != blocks.is_transparent(block) || blocks.light(old) != blocks.light(block) {
+            lighting::block_changed(self, coord);
+        }
+
+        Some(old)
+    }
+
+    /// How lit the block at `coord` is, `None` if its chunk isn't loaded
+    pub fn get_light(&self, coord: AbsoluteBlockCoord) -> Option<BlockLight> {
+        self.chunk(coord.chunk()).map(|chunk| chunk.light(coord.block()))
+    }
+
+    /// Changes how lit a block is, only for [`lighting`] to spread light with
+    pub(super) fn set_light(&mut self, coord: AbsoluteBlockCoord, light: BlockLight) {
+        let Some(chunk) = self.chunks.get_mut(&coord.chunk()) else {
+            return;
+        };
+        if chunk.light(coord.block()) == light {
+            return;
+        }
+
+        Arc::make_mut(chunk).set_light(coord.block(), light);
+        self.mark_dirty_around(coord);
+    }
+
+    /// Marks the chunk `coord` is in dirty, and the neighbors the block touches when it sits on the chunk's edge
+    fn mark_dirty_around(&mut self, coord: AbsoluteBlockCoord) {
+        let chunk_coord = coord.chunk();
+        self.dirty.mark(chunk_coord);
+
+        // a block on the edge hides faces in the chunk next to it and shades their corners,
+        // one in the corner does the same to the chunk diagonally across
+        let edge = |coord: u8| match coord {
+            0 => -1,
+            coord if coord as usize == CHUNK_SIZE - 1 => 1,
+            _ => 0,
+        };
+        let (dx, dz) = (edge(coord.block().x()), edge(coord.block().z()));
+        for (dx, dz) in [(dx, 0), (0, dz), (dx, dz)] {
+            if (dx, dz) == (0, 0) {
+                continue;
+            }
+
+            let neighbor = chunk_coord.offset(dx, dz);
+            if let Some(neighbor) = neighbor.filter(|neighbor| self.chunks.contains_key(neighbor)) {
+                self.dirty.mark(neighbor);
+            }
+        }
+    }
+
+    /// Changes whenever a block or chunk does, two equal revisions mean nothing changed in between
+    pub fn revision(&self) -> u64 {
+        self.revision
+    }
+
+    /// The chunks that need their meshes rebuilt
+    pub fn dirty_chunks_mut(&mut self) -> &mut DirtyChunks {
+        &mut self.dirty
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use voxel_maths::i48;
+    use crate::game_state::test_support::{world_with, STONE};
+    use super::*;
+
+    #[test]
+    fn chunk_get_set() {
+        let mut chunk = Chunk::empty();
+        let coord = BlockCoord::from_xyz(3, 200, 15);
+
+        assert_eq!(chunk.get(coord), Block::AIR);
+        assert_eq!(chunk.set(coord, STONE), Block::AIR);
+        assert_eq!(chunk.get(coord), STONE);
+        assert_eq!(chunk.get(BlockCoord::from_xyz(15, 200, 3)), Block::AIR);
+    }
+
+    #[test]
+    fn iteration_visits_every_block_once() {
+        let chunk = Chunk::empty();
+        assert_eq!(chunk.iter().len(), BLOCKS_PER_CHUNK);
+
+        let coords = chunk.iter().map(|(coord, _)| coord).collect::<Vec<_>>();
+        assert_eq!(coords.iter().copied().collect::<HashSet<_>>().len(), BLOCKS_PER_CHUNK);
+        // in storage order, x first then z then y
+        assert!(coords.iter().enumerate().all(|(index, &coord)| Chunk::index(coord) == index));
+        assert_eq!(coords[1], BlockCoord::from_xyz(1, 0, 0));
+        assert_eq!(coords[CHUNK_SIZE], BlockCoord::from_xyz(0, 0, 1));
+        assert_eq!(coords[CHUNK_SIZE * CHUNK_SIZE], BlockCoord::from_xyz(0, 1, 0));
+        assert_eq!(coords.last(), Some(&BlockCoord::from_xyz(15, BlockY::MAX, 15)));
+        assert!(chunk.iter().all(|(_, block)| block.is_air()));
+    }
+
+    #[test]
+    fn non_air_iteration_skips_air() {
+        let mut chunk = Chunk::empty();
+        assert_eq!(chunk.iter_non_air().count(), 0);
+
+        let placed = [BlockCoord::from_xyz(15, 200, 3), BlockCoord::from_xyz(0, 0, 0), BlockCoord::from_xyz(4, 9, 12)];
+        for coord in placed {
+            chunk.set(coord, STONE);
+        }
+        chunk.set(BlockCoord::from_xyz(4, 9, 13), Block::from_id(2));
+        chunk.set(BlockCoord::from_xyz(4, 9, 13), Block::AIR);
+
+        let found = chunk.iter_non_air().collect::<Vec<_>>();
+        let mut expected = placed.map(|coord| (coord, STONE));
+        expected.sort_by_key(|&(coord, _)| Chunk::index(coord));
+        assert_eq!(found, expected);
+        assert!(chunk.iter().filter(|(coord, _)| !placed.contains(coord)).all(|(_, block)| block.is_air()));
+    }
+
+    #[test]
+    fn interior_edit_marks_only_its_chunk() {
+        let (center, east) = (ChunkCoord::ZERO, ChunkCoord::from_xz(1, 0));
+        let mut world = world_with(&[center, east]);
+
+        let old = world.set_block(AbsoluteBlockCoord::from_xyz(i48!(7), 64, i48!(7)), STONE);
+        assert_eq!(old, Some(Block::AIR));
+
+        let dirty = world.dirty_chunks_mut();
+        assert!(dirty.is_dirty(center));
+        assert!(!dirty.is_dirty(east));
+        assert_eq!(dirty.len(), 1);
+    }
+
+    #[test]
+    fn boundary_edit_marks_the_neighbor() {
+        let (center, east, north) = (ChunkCoord::ZERO, ChunkCoord::from_xz(1, 0), ChunkCoord::from_xz(0, -1));
+        let mut world = world_with(&[center, east, north]);
+
+        // the +x edge of chunk (0, 0)
+        world.set_block(AbsoluteBlockCoord::from_xyz(i48!(15), 64, i48!(7)), STONE);
+        let dirty = world.dirty_chunks_mut().drain().collect::<HashSet<_>>();
+        assert_eq!(dirty, HashSet::from_iter([center, east]));
+
+        // the -x -z corner touches three neighbors, only the loaded one gets marked
+        world.set_block(AbsoluteBlockCoord::from_xyz(i48!(0), 64, i48!(0)), STONE);
+        let dirty = world.dirty_chunks_mut().drain().collect::<HashSet<_>>();
+        assert_eq!(dirty, HashSet::from_iter([center, north]));
+
+        // the +x -z corner is next to the diagonal chunk (1, -1)
+        let north_east = ChunkCoord::from_xz(1, -1);
+        world.insert_chunk(north_east, Chunk::empty());
+        world.dirty_chunks_mut().drain().for_each(drop);
+        world.set_block(AbsoluteBlockCoord::from_xyz(i48!(15), 64, i48!(0)), STONE);
+        let dirty = world.dirty_chunks_mut().drain().collect::<HashSet<_>>();
+        assert_eq!(dirty, HashSet::from_iter([center, east, north, north_east]));
+
+        // and from the other side, the -x edge of chunk (1, 0) is next to chunk (0, 0)
+        world.set_block(AbsoluteBlockCoord::from_xyz(i48!(16), 0, i48!(3)), STONE);
+        let dirty = world.dirty_chunks_mut().drain().collect::<HashSet<_>>();
+        assert_eq!(dirty, HashSet::from_iter([east, center]));
+    }
+
+    #[test]
+    fn heights_outside_the_world_are_rejected() {
+        let height = WORLD_HEIGHT as i64;
+        assert!(is_valid_y(0));
+        assert!(is_valid_y(height - 1));
+        assert!(!is_valid_y(-1));
+        assert!(!is_valid_y(height));
+        assert!(!is_valid_y(i64::MIN));
+        assert_eq!(block_y(height - 1).map(usize::from), Some(WORLD_HEIGHT - 1));
+        assert_eq!(block_y(height), None);
+        assert_eq!(block_y(-1), None);
+
+        // chunks span the whole world, so their size follows from it too
+        assert_eq!(CHUNK_HEIGHT, WORLD_HEIGHT);
+        assert_eq!(BLOCKS_PER_CHUNK, CHUNK_SIZE * CHUNK_SIZE * WORLD_HEIGHT);
+
+        let mut world = world_with(&[ChunkCoord::ZERO]);
+        let highest = AbsoluteBlockCoord::from_xyz(i48!(2), block_y(height - 1).unwrap(), i48!(2));
+        assert_eq!(world.set_block(highest, STONE), Some(Block::AIR));
+        assert_eq!(world.get_block(highest), Some(STONE));
+    }
+
+    #[test]
+    fn unchanged_and_unloaded_edits() {
+        let mut world = world_with(&[ChunkCoord::ZERO]);
+
+        let coord = AbsoluteBlockCoord::from_xyz(i48!(15), 0, i48!(15));
+        assert_eq!(world.set_block(coord, Block::AIR), Some(Block::AIR));
+        assert_eq!(world.dirty_chunks_mut().len(), 0);
+
+        let unloaded = AbsoluteBlockCoord::from_xyz(i48!(-1), 0, i48!(0));
+        assert_eq!(world.set_block(unloaded, STONE), None);
+        assert_eq!(world.get_block(unloaded), None);
+        assert_eq!(world.dirty_chunks_mut().len(), 0);
+    }
+
+    #[test]
+    fn snapshots_are_copy_on_write() {
+        let mut world = world_with(&[ChunkCoord::ZERO]);
+        let snapshot = Arc::clone(world.chunk(ChunkCoord::ZERO).unwrap());
+
+        let coord = AbsoluteBlockCoord::from_xyz(i48!(1), 1, i48!(1));
+        world.set_block(coord, STONE);
+
+        assert_eq!(world.get_block(coord), Some(STONE));
+        assert_eq!(snapshot.get(BlockCoord::from_xyz(1, 1, 1)), Block::AIR);
+    }
+}
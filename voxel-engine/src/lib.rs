@@ -1,17 +1,25 @@
+use std::num::NonZero;
 use std::sync::Arc;
+use std::time::{Duration, Instant};
 use winit::{
     application::ApplicationHandler,
     event::WindowEvent,
     event_loop::{ActiveEventLoop, ControlFlow, EventLoop},
-    window::{Fullscreen, Window, WindowId},
+    window::{Fullscreen, Window, WindowAttributes, WindowId},
 };
+use winit::dpi::{LogicalSize, PhysicalPosition, PhysicalSize};
 use winit::error::ExternalError;
-use winit::event::{DeviceEvent, DeviceId, KeyEvent, RawKeyEvent};
+use winit::event::{DeviceEvent, DeviceId, ElementState, KeyEvent};
+use winit::monitor::MonitorHandle;
 use winit::window::CursorGrabMode;
-use crate::controls::Controls;
+use tracing_subscriber::filter::ParseError;
+use tracing_subscriber::EnvFilter;
+use crate::audio::Audio;
+use crate::controls::{Controls, InputMethod, KeyMapping};
 use crate::game_state::GameState;
+use crate::settings::GameSettingsHandle;
+use crate::game_state::entity::Entity;
 use crate::renderer::Renderer;
-use crate::settings::FullscreenMode;
 
 mod settings;
 
@@ -21,6 +29,16 @@ mod game_state;
 
 mod controls;
 
+mod audio;
+
+mod assets;
+
+pub use game_state::Scene;
+pub use settings::{AimCurve, AntiAliasing, CullMode, DayNightSettings, DepthMode, FogSettings, Fov, FullscreenMode, GameSettings, GameTitle, MonitorChoice, StickSettings, TextureFilter, Vsync, WindowPosition, WindowSize};
+
+#[cfg_attr(not(test), expect(dead_code, reason = "there is no text overlay to type commands into yet"))]
+mod console;
+
 pub(crate) fn attempt_lock_cursor(
     window: &Window,
     grab: bool,
@@ -47,29 +65,373 @@ pub(crate) fn attempt_lock_cursor(
     Ok(())
 }
 
+/// Something that changes whether the cursor should be grabbed
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+enum CursorEvent {
+    Focused,
+    Unfocused,
+    Clicked,
+}
+
+/// Whether the cursor is grabbed by the window
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub(crate) enum CursorGrab {
+    /// the window doesn't have focus, so the cursor is free
+    Released,
+    /// locked or confined to the window
+    Grabbed,
+    /// the window has focus but grabbing the cursor failed (like it can on X11 before the window is shown),
+    /// it's tried again on the next click
+    Failed,
+}
+
+impl CursorGrab {
+    /// What to do with the cursor after `event`, `Some(true)` to grab it and `Some(false)` to let it go
+    fn next_attempt(self, event: CursorEvent) -> Option<bool> {
+        match (self, event) {
+            (_, CursorEvent::Focused) => Some(true),
+            (_, CursorEvent::Unfocused) => Some(false),
+            (CursorGrab::Failed, CursorEvent::Clicked) => Some(true),
+            (CursorGrab::Released | CursorGrab::Grabbed, CursorEvent::Clicked) => None,
+        }
+    }
+
+    /// Where things stand after trying to `grab` (or let go of) the cursor
+    fn attempted(grab: bool, succeeded: bool) -> Self {
+        match (grab, succeeded) {
+            (false, _) => CursorGrab::Released,
+            (true, true) => CursorGrab::Grabbed,
+            (true, false) => CursorGrab::Failed,
+        }
+    }
+
+    fn handle(&mut self, window: &Window, event: CursorEvent) {
+        if let Some(grab) = self.next_attempt(event) {
+            *self = Self::attempted(grab, attempt_lock_cursor(window, grab).is_ok());
+        }
+    }
+
+    /// Whether the game should tell the player to click to capture the cursor
+    #[cfg_attr(not(test), expect(dead_code, reason = "there is no text overlay to show the hint on yet"))]
+    pub fn needs_click(self) -> bool {
+        self == CursorGrab::Failed
+    }
+}
+
+/// Where a window sat on the screen
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+struct WindowGeometry {
+    size: PhysicalSize<u32>,
+    /// not every platform can tell
+    position: Option<PhysicalPosition<i32>>,
+}
+
+impl WindowGeometry {
+    fn of(window: &Window) -> Self {
+        Self {
+            size: window.inner_size(),
+            position: window.outer_position().ok(),
+        }
+    }
+}
+
+/// The part of the desktop a monitor shows, in physical pixels
+#[derive(Debug, Copy, Clone, PartialEq)]
+struct MonitorRect {
+    position: PhysicalPosition<i32>,
+    size: PhysicalSize<u32>,
+    scale_factor: f64,
+}
+
+impl MonitorRect {
+    fn of(monitor: &MonitorHandle) -> Self {
+        Self {
+            position: monitor.position(),
+            size: monitor.size(),
+            scale_factor: monitor.scale_factor(),
+        }
+    }
+
+    /// Where a window of `size` goes to be in the middle of this monitor
+    fn centered(&self, size: PhysicalSize<u32>) -> PhysicalPosition<i32> {
+        let offset = |length: u32, size: u32| ((i64::from(length) - i64::from(size)) / 2) as i32;
+        let middle = PhysicalPosition::new(
+            self.position.x.saturating_add(offset(self.size.width, size.width)),
+            self.position.y.saturating_add(offset(self.size.height, size.height)),
+        );
+        self.clamp(middle, size)
+    }
+
+    fn contains(&self, point: PhysicalPosition<i32>) -> bool {
+        let x = i64::from(point.x) - i64::from(self.position.x);
+        let y = i64::from(point.y) - i64::from(self.position.y);
+        (0..i64::from(self.size.width)).contains(&x) && (0..i64::from(self.size.height)).contains(&y)
+    }
+
+    /// Moves a window of `size` at `position` as little as it takes to be all on this monitor,
+    /// a window bigger than the monitor gets its top left corner on it so the title bar can be reached
+    fn clamp(&self, position: PhysicalPosition<i32>, size: PhysicalSize<u32>) -> PhysicalPosition<i32> {
+        let clamp_axis = |position: i32, size: u32, start: i32, length: u32| {
+            let end = i64::from(start) + i64::from(length) - i64::from(size);
+            i64::from(position).min(end).max(i64::from(start)) as i32
+        };
+
+        PhysicalPosition::new(
+            clamp_axis(position.x, size.width, self.position.x, self.size.width),
+            clamp_axis(position.y, size.height, self.position.y, self.size.height),
+        )
+    }
+}
+
+/// Where a window last left at `saved` goes now, on whichever of the `monitors` it was on
+/// or the `fallback` if that one is gone, `None` if there is no monitor to put it on
+fn restored_position(
+    saved: WindowPosition,
+    size: WindowSize,
+    monitors: &[MonitorRect],
+    fallback: Option<MonitorRect>,
+) -> Option<PhysicalPosition<i32>> {
+    let saved = PhysicalPosition::from(saved);
+    let monitor = monitors
+        .iter()
+        .copied()
+        .find(|monitor| monitor.contains(saved))
+        .or(fallback)
+        .or_else(|| monitors.first().copied())?;
+
+    let size = LogicalSize::new(size.width, size.height).to_physical(monitor.scale_factor);
+    Some(monitor.clamp(saved, size))
+}
+
+/// Which of the monitors, called `names`, the window goes on, the `choice` from the settings
+/// or the `primary` one if that's gone, `None` if there are no monitors at all
+fn select_monitor(choice: Option<&MonitorChoice>, names: &[Option<String>], primary: Option<usize>) -> Option<usize> {
+    let chosen = choice.and_then(|choice| match choice {
+        MonitorChoice::Index(index) => (*index < names.len()).then_some(*index),
+        MonitorChoice::Name(name) => names.iter().position(|other| other.as_deref() == Some(name)),
+    });
+
+    if let (Some(choice), None) = (choice, chosen) {
+        tracing::warn!("the monitor {choice:?} isn't connected, opening on the primary monitor instead");
+    }
+
+    chosen
+        .or(primary.filter(|&primary| primary < names.len()))
+        .or((!names.is_empty()).then_some(0))
+}
+
+/// Which of the `monitors` the `choice` from the settings is, see [`select_monitor`]
+fn chosen_monitor<'a>(
+    choice: Option<&MonitorChoice>,
+    monitors: &'a [MonitorHandle],
+    primary: Option<MonitorHandle>,
+) -> Option<&'a MonitorHandle> {
+    let names = monitors.iter().map(MonitorHandle::name).collect::<Vec<_>>();
+    let primary = primary.and_then(|primary| monitors.iter().position(|monitor| *monitor == primary));
+    select_monitor(choice, &names, primary).map(|index| &monitors[index])
+}
+
+/// What the window gets created with, placed on the monitor picked in the settings
+fn placed_window_attributes(settings: &GameSettings, event_loop: &ActiveEventLoop) -> WindowAttributes {
+    let monitors = event_loop.available_monitors().collect::<Vec<_>>();
+    let monitor = chosen_monitor(settings.monitor.as_ref(), &monitors, event_loop.primary_monitor());
+
+    let attributes = window_attributes(settings, monitor.cloned());
+    let size = settings.window_size.unwrap_or(settings.min_window_size);
+    let position = match (settings.window_position, monitor) {
+        // a window moved off the monitor it should open on gets moved back
+        (Some(saved), Some(monitor)) if settings.monitor.is_some() => {
+            let monitor = MonitorRect::of(monitor);
+            restored_position(saved, size, &[monitor], Some(monitor))
+        }
+        (Some(saved), monitor) => {
+            let rects = monitors.iter().map(MonitorRect::of).collect::<Vec<_>>();
+            restored_position(saved, size, &rects, monitor.map(MonitorRect::of))
+        }
+        // nowhere to go back to, the system picks where it goes unless it should be on a certain monitor
+        (None, Some(monitor)) if settings.monitor.is_some() => {
+            let monitor = MonitorRect::of(monitor);
+            Some(monitor.centered(LogicalSize::new(size.width, size.height).to_physical(monitor.scale_factor)))
+        }
+        (None, _) => None,
+    };
+
+    match position {
+        Some(position) => attributes.with_position(position),
+        None => attributes,
+    }
+}
+
+/// Keeps where the window is in the settings so it opens there next time,
+/// fullscreen and minimized windows aren't where the window should open so they're left out
+fn remember_window_geometry(settings: &GameSettingsHandle, window: &Window) {
+    if window.fullscreen().is_some() || window.is_minimized() == Some(true) {
+        return;
+    }
+
+    let WindowGeometry { size, position } = WindowGeometry::of(window);
+    if size.width == 0 || size.height == 0 {
+        return;
+    }
+
+    let size = size.to_logical::<u32>(window.scale_factor());
+    let size = Some(WindowSize::new(size.width, size.height));
+    let position = position.map(WindowPosition::from);
+    let current = settings.load();
+    if current.window_size == size && (position.is_none() || current.window_position == position) {
+        return;
+    }
+    drop(current);
+
+    settings.edit(|settings| {
+        settings.window_size = size;
+        // when the platform can't tell keep the last one that was known
+        settings.window_position = position.or(settings.window_position);
+    });
+}
+
+/// Switches between borderless fullscreen and a window, remembering where the window was
+#[derive(Debug, Default)]
+struct FullscreenToggle {
+    windowed: Option<WindowGeometry>,
+}
+
+impl FullscreenToggle {
+    /// Going fullscreen out of a window at `windowed`
+    fn enter(&mut self, windowed: WindowGeometry) {
+        self.windowed = Some(windowed)
+    }
+
+    /// Going back to a window, where it was before if it started out windowed
+    fn leave(&mut self) -> Option<WindowGeometry> {
+        self.windowed.take()
+    }
+
+    /// Toggles `window`, going fullscreen on the monitor `choice` from the settings,
+    /// returning the mode it ends up in
+    fn toggle(&mut self, window: &Window, choice: Option<&MonitorChoice>) -> FullscreenMode {
+        if window.fullscreen().is_none() {
+            self.enter(WindowGeometry::of(window));
+            let monitors = window.available_monitors().collect::<Vec<_>>();
+            let monitor = chosen_monitor(choice, &monitors, window.primary_monitor()).cloned();
+            window.set_fullscreen(Some(Fullscreen::Borderless(monitor)));
+            return FullscreenMode::Borderless
+        }
+
+        window.set_fullscreen(None);
+        if let Some(windowed) = self.leave() {
+            // the new size comes back as a resize event if it's applied
+            let _ = window.request_inner_size(windowed.size);
+            if let Some(position) = windowed.position {
+                window.set_outer_position(position);
+            }
+        }
+        FullscreenMode::Off
+    }
+}
+
+/// When the frame after one that started at `last_frame` should start,
+/// `None` if there is no cap and it should start right away
+fn next_frame_deadline(last_frame: Instant, frame_cap: Option<NonZero<u32>>) -> Option<Instant> {
+    frame_cap.map(|fps| last_frame + Duration::from_secs(1) / fps.get())
+}
+
+/// How the engine starts up, for running it from another binary
+#[derive(Debug, Clone)]
+pub struct EngineConfig {
+    /// `None` reads them from the settings file
+    settings: Option<GameSettings>,
+    persist_settings: bool,
+    scene: Scene,
+}
+
+impl Default for EngineConfig {
+    fn default() -> Self {
+        Self {
+            settings: None,
+            persist_settings: true,
+            scene: Scene::default(),
+        }
+    }
+}
+
+impl EngineConfig {
+    /// Starts with `settings` instead of the ones in the settings file
+    pub fn with_settings(mut self, settings: GameSettings) -> Self {
+        self.settings = Some(settings);
+        self
+    }
+
+    /// Keeps the settings in memory, the settings file is never written
+    pub fn without_persistence(mut self) -> Self {
+        self.persist_settings = false;
+        self
+    }
+
+    pub fn with_scene(mut self, scene: Scene) -> Self {
+        self.scene = scene;
+        self
+    }
+
+    fn settings_handle(self) -> GameSettingsHandle {
+        let settings = self.settings.unwrap_or_else(settings::read);
+        match self.persist_settings {
+            true => settings::persisted(settings),
+            false => GameSettingsHandle::in_memory(settings),
+        }
+    }
+}
+
+/// How long closing the game waits for the settings to save
+const SHUTDOWN_WAIT: Duration = Duration::from_secs(2);
+
+/// What the window gets created with, the settings that can't be changed on a window after it exists
+/// going fullscreen on the `monitor` picked from the settings, or whichever the system picks without one
+fn window_attributes(settings: &GameSettings, monitor: Option<MonitorHandle>) -> WindowAttributes {
+    let min_size = settings.min_window_size;
+    let mut attributes = Window::default_attributes()
+        .with_title(&*settings.game_title)
+        .with_window_icon(settings::load_icon(&*assets::source(settings), settings.icon.as_deref()))
+        .with_fullscreen(match settings.fullscreen {
+            FullscreenMode::On => Some(
+                // the most pixels at the highest refresh rate the monitor does
+                monitor
+                    .as_ref()
+                    .and_then(|monitor| monitor.video_modes().max_by_key(|mode| {
+                        (mode.size().width * mode.size().height, mode.refresh_rate_millihertz())
+                    }))
+                    .map_or(Fullscreen::Borderless(monitor), Fullscreen::Exclusive)
+            ),
+            FullscreenMode::Off => None,
+            FullscreenMode::Borderless => Some(Fullscreen::Borderless(monitor)),
+        })
+        .with_resizable(settings.resizable)
+        .with_min_inner_size(min_size);
+
+    if let Some(size) = settings.window_size {
+        attributes = attributes.with_inner_size(size);
+    }
+
+    // a maximum below the minimum would leave no size the window could be
+    attributes.max_inner_size = settings.max_window_size.map(|max| max.at_least(min_size).into());
+    attributes
+}
 
 struct App {
+    settings: GameSettingsHandle,
     controls: Controls,
+    last_frame: Instant,
     game_state: GameState,
-    cursor_locked: bool,
+    audio: Audio,
+    cursor: CursorGrab,
+    fullscreen: FullscreenToggle,
     renderer: Option<Renderer>,
 }
 
 impl ApplicationHandler for App {
     fn resumed(&mut self, event_loop: &ActiveEventLoop) {
-        let settings = settings::load();
-
-        let current_settings = settings.load();
-
-        let attrib = Window::default_attributes()
-            .with_title(&*current_settings.game_title)
-            .with_window_icon(settings::load_icon())
-            .with_fullscreen(match current_settings.fullscreen {
-                FullscreenMode::On => todo!(),
-                FullscreenMode::Off => None,
-                FullscreenMode::Borderless => Some(Fullscreen::Borderless(None)),
-            });
-
+        let settings = self.settings.clone();
+        let attrib = placed_window_attributes(&settings.load(), event_loop);
 
         let window = event_loop
             .create_window(attrib)
@@ -79,7 +441,8 @@ impl ApplicationHandler for App {
         let state = voxel_runtime::block_on(Renderer::new(Arc::clone(&window), settings));
         
         self.renderer = Some(state);
-        let _ = attempt_lock_cursor(&window, self.cursor_locked);
+        // the window starts out focused
+        self.cursor.handle(&window, CursorEvent::Focused);
         
         window.request_redraw();
     }
@@ -90,66 +453,370 @@ impl ApplicationHandler for App {
         
         match event {
             WindowEvent::Focused(focus) => {
-                self.cursor_locked = focus;
-                let _ = attempt_lock_cursor(renderer.window(), focus);
+                let event = match focus {
+                    true => CursorEvent::Focused,
+                    false => CursorEvent::Unfocused,
+                };
+                self.cursor.handle(renderer.window(), event);
                 if !focus {
                     self.controls.lost_focus();
                 }
             }
+            WindowEvent::MouseInput { state: ElementState::Pressed, .. } => {
+                self.cursor.handle(renderer.window(), CursorEvent::Clicked);
+            }
+            // see `Controls` for where each kind of input is taken from
             WindowEvent::KeyboardInput { event: KeyEvent {
                 physical_key,
+                logical_key,
                 state,
+                repeat,
                 ..
             }, .. } => {
-                self.controls.update(&DeviceEvent::Key(RawKeyEvent { physical_key, state }))
+                self.controls.learn_key_label(physical_key, &logical_key);
+                self.controls.key_input(physical_key, state, repeat, Instant::now())
             }
             WindowEvent::CloseRequested | WindowEvent::Destroyed => {
                 tracing::info!("The close button was pressed; stopping");
+                if !self.settings.shutdown(SHUTDOWN_WAIT) {
+                    tracing::warn!("the settings didn't finish saving before closing");
+                }
                 event_loop.exit();
             }
+            // while the window can't be seen redraws stop being requested, until it comes back
+            WindowEvent::RedrawRequested if renderer.is_paused() => {}
             WindowEvent::RedrawRequested => {
+                self.last_frame = Instant::now();
+                renderer.apply_window_settings();
+                let settings = renderer.settings().load();
+                self.game_state.apply_settings(&settings);
+                self.controls.set_cursor_sensitivity_curve(settings.aim_curve);
                 self.game_state.frame_update(&self.controls);
+                if self.controls.triggered(KeyMapping::Fullscreen) {
+                    let fullscreen = self.fullscreen.toggle(renderer.window(), settings.monitor.as_ref());
+                    renderer.settings().edit(|settings| settings.fullscreen = fullscreen);
+                }
+                let player = self.game_state.player();
+                self.audio.set_listener(player.eye(), player.right().as_f32());
+                renderer.update_world(self.game_state.world_mut());
                 renderer.render(&self.game_state);
                 self.controls.new_frame();
-                renderer.window().request_redraw();
-
             },
             WindowEvent::Resized(size) => {
                 // Reconfigures the size of the surface. We do not re-render
                 // here as this event is always followed up by a redrawn request.
+                let was_paused = renderer.is_paused();
                 renderer.resize(size);
+                self.game_state.set_paused(renderer.is_paused());
+                if was_paused && !renderer.is_paused() {
+                    renderer.window().request_redraw();
+                }
+                remember_window_geometry(&self.settings, renderer.window());
+            }
+            WindowEvent::Moved(_) => remember_window_geometry(&self.settings, renderer.window()),
+            WindowEvent::Occluded(occluded) => {
+                let was_paused = renderer.is_paused();
+                renderer.set_occluded(occluded);
+                self.game_state.set_paused(renderer.is_paused());
+                if was_paused && !renderer.is_paused() {
+                    renderer.window().request_redraw();
+                }
+            }
+            WindowEvent::ScaleFactorChanged { scale_factor, mut inner_size_writer } => {
+                let size = renderer.rescale(scale_factor);
+                if let Err(err) = inner_size_writer.request_inner_size(size) {
+                    tracing::warn!("unable to resize the window for its new scale; {err}");
+                }
             }
             _ => ()
         }
     }
 
     fn device_event(&mut self, _: &ActiveEventLoop, _: DeviceId, event: DeviceEvent) {
-        self.controls.update(&event)
+        self.controls.device_input(&event)
+    }
+
+    fn about_to_wait(&mut self, event_loop: &ActiveEventLoop) {
+        let Some(renderer) = self.renderer.as_ref() else {
+            return;
+        };
+
+        // nothing to draw, sleep until the window comes back
+        if renderer.is_paused() {
+            event_loop.set_control_flow(ControlFlow::Wait);
+            return;
+        }
+
+        let frame_cap = renderer.settings().load().frame_cap;
+        match next_frame_deadline(self.last_frame, frame_cap) {
+            Some(deadline) if Instant::now() < deadline => {
+                event_loop.set_control_flow(ControlFlow::WaitUntil(deadline))
+            }
+            _ => {
+                event_loop.set_control_flow(ControlFlow::Poll);
+                renderer.window().request_redraw();
+            }
+        }
+    }
+}
+
+impl App {
+    fn new(config: EngineConfig) -> Self {
+        Self {
+            controls: Controls::default(),
+            last_frame: Instant::now(),
+            game_state: GameState::from_scene(config.scene),
+            audio: Audio::new(),
+            cursor: CursorGrab::Released,
+            fullscreen: FullscreenToggle::default(),
+            renderer: None,
+            settings: config.settings_handle(),
+        }
     }
 }
 
-fn run_app() {
+fn run_app(config: EngineConfig) {
     let event_loop = EventLoop::new().unwrap();
 
     // When the current loop iteration finishes, immediately begin a new
     // iteration regardless of whether new events are available to
-    // process.
+    // process. `about_to_wait` switches to waiting between frames when
+    // there is a frame cap.
     event_loop.set_control_flow(ControlFlow::Poll);
 
-    let mut app = App {
-        controls: Controls::default(),
-        game_state: GameState::new(),
-        cursor_locked: true,
-        renderer: None,
-    };
+    let mut app = App::new(config);
     event_loop.run_app(&mut app).unwrap();
 }
 
+/// What gets logged when `RUST_LOG` isn't set, or can't be parsed
+const DEFAULT_LOG_FILTER: &str = "warn,voxel_engine=info";
+
+/// The filter for `directives`, and why they couldn't be used if they fell back to the default,
+/// that can only be logged once the subscriber is set up with the filter
+fn log_filter(directives: Option<&str>) -> (EnvFilter, Option<ParseError>) {
+    match directives.map(EnvFilter::try_new) {
+        Some(Ok(filter)) => (filter, None),
+        Some(Err(err)) => (EnvFilter::new(DEFAULT_LOG_FILTER), Some(err)),
+        None => (EnvFilter::new(DEFAULT_LOG_FILTER), None),
+    }
+}
+
 fn setup_logging() {
-    tracing_subscriber::fmt::init();
+    let directives = std::env::var(EnvFilter::DEFAULT_ENV).ok();
+    let (filter, invalid) = log_filter(directives.as_deref());
+    tracing_subscriber::fmt()
+        .with_env_filter(filter)
+        .init();
+
+    if let Some(err) = invalid {
+        tracing::warn!(
+            "invalid {} `{}`, logging with `{DEFAULT_LOG_FILTER}` instead; {err}",
+            EnvFilter::DEFAULT_ENV,
+            directives.unwrap_or_default()
+        );
+    }
 }
 
+/// Runs the game with the settings file and the default scene
 pub fn run() {
+    run_with(EngineConfig::default())
+}
+
+pub fn run_with(config: EngineConfig) {
     setup_logging();
-    run_app();
-}
\ No newline at end of file
+    run_app(config);
+}
+
+#[cfg(test)]
+mod tests {
+    use tracing::Level;
+    use tracing_subscriber::layer::SubscriberExt;
+    use super::*;
+
+    #[test]
+    fn log_filter_follows_the_directives() {
+        let enabled = |directives: Option<&str>, target_level: fn() -> bool| {
+            let subscriber = tracing_subscriber::registry().with(log_filter(directives).0);
+            tracing::subscriber::with_default(subscriber, target_level)
+        };
+
+        // by default only the engine's own info logs get through
+        assert!(enabled(None, || tracing::enabled!(Level::INFO)));
+        assert!(!enabled(None, || tracing::enabled!(Level::DEBUG)));
+        assert!(!enabled(None, || tracing::enabled!(target: "wgpu_core", Level::INFO)));
+        assert!(enabled(None, || tracing::enabled!(target: "wgpu_core", Level::WARN)));
+
+        assert!(enabled(Some("voxel_engine=debug"), || tracing::enabled!(Level::DEBUG)));
+        assert!(!enabled(Some("voxel_engine=debug"), || tracing::enabled!(Level::TRACE)));
+        assert!(enabled(Some("trace"), || tracing::enabled!(target: "wgpu_core", Level::TRACE)));
+        assert!(!enabled(Some("error"), || tracing::enabled!(Level::WARN)));
+
+        // nonsense falls back to the default, handing back what was wrong with it
+        assert!(log_filter(Some("voxel_engine=loud")).1.is_some());
+        assert!(log_filter(Some("trace")).1.is_none());
+        assert!(log_filter(None).1.is_none());
+        assert!(!enabled(Some("voxel_engine=loud"), || tracing::enabled!(Level::DEBUG)));
+        assert!(enabled(Some("voxel_engine=loud"), || tracing::enabled!(Level::INFO)));
+    }
+
+    #[test]
+    fn cursor_grab_retries_after_failing() {
+        use CursorEvent::*;
+        use CursorGrab::*;
+
+        // focus always grabs, losing it always lets go
+        for state in [Released, Grabbed, Failed] {
+            assert_eq!(state.next_attempt(Focused), Some(true));
+            assert_eq!(state.next_attempt(Unfocused), Some(false));
+        }
+
+        // clicking only tries again when the last grab failed
+        assert_eq!(Failed.next_attempt(Clicked), Some(true));
+        assert_eq!(Grabbed.next_attempt(Clicked), None);
+        assert_eq!(Released.next_attempt(Clicked), None);
+
+        assert_eq!(CursorGrab::attempted(true, true), Grabbed);
+        assert_eq!(CursorGrab::attempted(true, false), Failed);
+        assert_eq!(CursorGrab::attempted(false, true), Released);
+        assert_eq!(CursorGrab::attempted(false, false), Released);
+
+        // a failed grab at startup, then a click that works
+        let mut state = CursorGrab::attempted(true, false);
+        assert!(state.needs_click());
+        if let Some(grab) = state.next_attempt(Clicked) {
+            state = CursorGrab::attempted(grab, true);
+        }
+        assert_eq!(state, Grabbed);
+        assert!(!state.needs_click());
+    }
+
+    #[test]
+    fn windows_are_created_with_the_configured_sizes() {
+        let logical = |width, height| Some(winit::dpi::Size::Logical(winit::dpi::LogicalSize::new(width, height)));
+
+        let attributes = window_attributes(&GameSettings::default(), None);
+        assert_eq!(attributes.min_inner_size, logical(320.0, 180.0));
+        assert_eq!(attributes.max_inner_size, None);
+        assert!(attributes.resizable);
+
+        let settings = GameSettings {
+            min_window_size: WindowSize::new(800, 600),
+            max_window_size: Some(WindowSize::new(1920, 1080)),
+            resizable: false,
+            ..GameSettings::default()
+        };
+        let attributes = window_attributes(&settings, None);
+        assert_eq!(attributes.min_inner_size, logical(800.0, 600.0));
+        assert_eq!(attributes.max_inner_size, logical(1920.0, 1080.0));
+        assert!(!attributes.resizable);
+        assert_eq!(attributes.title, "Game of Voxels");
+
+        // the maximum never goes under the minimum
+        let settings = GameSettings { max_window_size: Some(WindowSize::new(1024, 100)), ..settings };
+        assert_eq!(window_attributes(&settings, None).max_inner_size, logical(1024.0, 600.0));
+    }
+
+    #[test]
+    fn saved_positions_stay_on_a_monitor() {
+        let left = MonitorRect { position: PhysicalPosition::new(-1920, 0), size: PhysicalSize::new(1920, 1080), scale_factor: 1.0 };
+        let primary = MonitorRect { position: PhysicalPosition::new(0, 0), size: PhysicalSize::new(2560, 1440), scale_factor: 2.0 };
+        let monitors = [left, primary];
+        let size = WindowSize::new(800, 600);
+        let restore = |x, y, monitors: &[MonitorRect]| {
+            restored_position(WindowPosition { x, y }, size, monitors, Some(primary)).map(|position| (position.x, position.y))
+        };
+
+        // already all on a monitor, it stays put
+        assert_eq!(restore(-1800, 100, &monitors), Some((-1800, 100)));
+        assert_eq!(restore(100, 100, &monitors), Some((100, 100)));
+
+        // hanging off the edge of the monitor it's on
+        assert_eq!(restore(-1000, 900, &monitors), Some((-1000, 480)));
+        // the primary monitor is twice as dense, so the window is twice as many pixels there
+        assert_eq!(restore(2000, 1000, &monitors), Some((960, 240)));
+
+        // the monitor on the left was unplugged
+        assert_eq!(restore(-1800, 100, &[primary]), Some((0, 100)));
+        assert_eq!(restore(5000, -3000, &monitors), Some((960, 0)));
+        // without a primary monitor the first one there is stands in
+        assert_eq!(restored_position(WindowPosition { x: 5000, y: 0 }, size, &[left], None), Some(PhysicalPosition::new(-800, 0)));
+        assert_eq!(restored_position(WindowPosition { x: 5000, y: 0 }, size, &[], None), None);
+
+        // a window bigger than the monitor keeps its title bar on screen
+        let tiny = MonitorRect { position: PhysicalPosition::new(0, 0), size: PhysicalSize::new(640, 480), scale_factor: 1.0 };
+        assert_eq!(restore(300, 300, &[tiny]), Some((0, 0)));
+    }
+
+    #[test]
+    fn missing_monitors_fall_back_to_the_primary_one() {
+        let names = [Some("eDP-1".to_string()), Some("DP-1".to_string()), None];
+        let name = |name: &str| Some(MonitorChoice::Name(name.to_string()));
+
+        assert_eq!(select_monitor(None, &names, Some(1)), Some(1));
+        assert_eq!(select_monitor(name("eDP-1").as_ref(), &names, Some(1)), Some(0));
+        assert_eq!(select_monitor(Some(&MonitorChoice::Index(2)), &names, Some(1)), Some(2));
+
+        // unplugged since the settings were saved
+        assert_eq!(select_monitor(name("HDMI-1").as_ref(), &names, Some(1)), Some(1));
+        assert_eq!(select_monitor(Some(&MonitorChoice::Index(3)), &names, Some(1)), Some(1));
+        // without a primary monitor the first one stands in
+        assert_eq!(select_monitor(name("HDMI-1").as_ref(), &names, None), Some(0));
+        assert_eq!(select_monitor(name("HDMI-1").as_ref(), &[], None), None);
+
+        // either one can be written in the settings
+        assert_eq!(serde_json::from_str::<MonitorChoice>("1").unwrap(), MonitorChoice::Index(1));
+        assert_eq!(serde_json::from_str::<MonitorChoice>("\"DP-1\"").ok(), name("DP-1"));
+
+        let monitor = MonitorRect { position: PhysicalPosition::new(-1920, 0), size: PhysicalSize::new(1920, 1080), scale_factor: 1.0 };
+        assert_eq!(monitor.centered(PhysicalSize::new(800, 600)), PhysicalPosition::new(-1360, 240));
+        assert_eq!(monitor.centered(PhysicalSize::new(4000, 600)), PhysicalPosition::new(-1920, 240));
+    }
+
+    #[test]
+    fn apps_start_with_the_configured_settings() {
+        let settings = GameSettings { fov: Fov::MAX, game_title: GameTitle::new("Embedded"), ..GameSettings::default() };
+        let config = EngineConfig::default()
+            .with_settings(settings.clone())
+            .without_persistence()
+            .with_scene(Scene::Empty);
+
+        let app = App::new(config);
+        assert_eq!(*app.settings.load(), settings);
+        assert_eq!(app.game_state.entities().len(), 1);
+
+        let props = App::new(EngineConfig::default().without_persistence().with_settings(settings));
+        assert!(props.game_state.entities().len() > 1);
+    }
+
+    #[test]
+    fn fullscreen_remembers_the_window() {
+        let small = WindowGeometry { size: PhysicalSize::new(800, 600), position: Some(PhysicalPosition::new(40, 30)) };
+        let large = WindowGeometry { size: PhysicalSize::new(1600, 900), position: None };
+
+        let mut toggle = FullscreenToggle::default();
+        // started out fullscreen, there is no window to go back to
+        assert_eq!(toggle.leave(), None);
+
+        toggle.enter(small);
+        assert_eq!(toggle.leave(), Some(small));
+        assert_eq!(toggle.leave(), None);
+
+        // resized while windowed, the latest window is the one that comes back
+        toggle.enter(small);
+        assert_eq!(toggle.leave(), Some(small));
+        toggle.enter(large);
+        assert_eq!(toggle.leave(), Some(large));
+    }
+
+    #[test]
+    fn frame_deadlines_follow_the_cap() {
+        let last_frame = Instant::now();
+        assert_eq!(next_frame_deadline(last_frame, None), None);
+
+        let deadline = next_frame_deadline(last_frame, NonZero::new(60)).unwrap();
+        assert_eq!(deadline - last_frame, Duration::from_nanos(16_666_666));
+
+        let deadline = next_frame_deadline(last_frame, NonZero::new(1)).unwrap();
+        assert_eq!(deadline - last_frame, Duration::from_secs(1));
+    }
+}
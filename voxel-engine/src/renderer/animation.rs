@@ -0,0 +1,179 @@
+use std::borrow::Cow;
+use std::ffi::OsString;
+use std::num::NonZero;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+use anyhow::{ensure, Context, Result};
+use bytemuck::{Pod, Zeroable};
+use serde::Deserialize;
+use crate::assets;
+
+/// the extension appended to a texture's file name to find its animation,
+/// `water.png` is animated by `water.png.anim.toml`
+const SIDECAR_EXTENSION: &str = ".anim.toml";
+
+/// A texture made of equally sized frames stacked top to bottom,
+/// cycled through at a fixed rate
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct TextureAnimation {
+    frames: NonZero<u32>,
+    fps: f32,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct AnimationSidecar {
+    fps: f32,
+    /// defaults to as many square frames as fit in the image
+    frames: Option<u32>,
+}
+
+impl TextureAnimation {
+    pub fn new(frames: NonZero<u32>, fps: f32) -> Result<Self> {
+        ensure!(fps.is_finite() && fps > 0.0, "animation fps has to be positive, found {fps}");
+        Ok(Self { frames, fps })
+    }
+
+    pub fn frames(&self) -> NonZero<u32> {
+        self.frames
+    }
+
+    /// The frame to show after `elapsed` simulation time, looping back to the start
+    pub fn frame_at(&self, elapsed: Duration) -> u32 {
+        let frame = (elapsed.as_secs_f64() * self.fps as f64) as u64;
+        (frame % self.frames.get() as u64) as u32
+    }
+
+    fn sidecar_path(texture: &Path) -> PathBuf {
+        let mut path = OsString::from(texture);
+        path.push(SIDECAR_EXTENSION);
+        PathBuf::from(path)
+    }
+
+    fn from_sidecar(sidecar: &str, (width, height): (u32, u32)) -> Result<Self> {
+        let sidecar = toml::from_str::<AnimationSidecar>(sidecar)?;
+
+        let frames = match sidecar.frames {
+            Some(frames) => frames,
+            None => {
+                ensure!(width != 0, "can't split a zero width texture into frames");
+                height / width
+            }
+        };
+
+        let frames = NonZero::new(frames).context("an animation needs at least one frame")?;
+        ensure!(
+            height % frames == 0,
+            "a texture {height} pixels tall can't be split into {frames} frames"
+        );
+
+        Self::new(frames, sidecar.fps)
+    }
+
+    /// Loads the animation for the texture at `texture`, reading its sidecar file with `read`,
+    /// `None` if it doesn't have one
+    pub fn load_with<'a>(
+        texture: &Path,
+        dimensions: (u32, u32),
+        read: impl FnOnce(&Path) -> Result<Cow<'a, [u8]>>
+    ) -> Result<Option<Self>> {
+        let sidecar_path = Self::sidecar_path(texture);
+        let sidecar = match read(&sidecar_path) {
+            Ok(sidecar) => sidecar,
+            Err(err) if assets::is_not_found(&err) => return Ok(None),
+            Err(err) => return Err(err.context(format!("unable to read animation {}", sidecar_path.display()))),
+        };
+
+        std::str::from_utf8(&sidecar)
+            .map_err(anyhow::Error::from)
+            .and_then(|sidecar| Self::from_sidecar(sidecar, dimensions))
+            .map(Some)
+            .with_context(|| format!("invalid animation {}", sidecar_path.display()))
+    }
+}
+
+/// What the shader needs to sample the current frame out of the stacked texture
+#[derive(Debug, Copy, Clone, PartialEq, Pod, Zeroable)]
+#[repr(C, align(16))]
+pub struct AnimationUniform {
+    frame: u32,
+    frame_count: u32,
+    _padding: [u32; 2],
+}
+
+impl AnimationUniform {
+    /// A texture that only has the one frame
+    pub const STATIC: Self = Self::new(0, 1);
+
+    pub const fn new(frame: u32, frame_count: u32) -> Self {
+        Self {
+            frame,
+            frame_count,
+            _padding: [0; 2],
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn animation(frames: u32, fps: f32) -> TextureAnimation {
+        TextureAnimation::new(NonZero::new(frames).unwrap(), fps).unwrap()
+    }
+
+    #[test]
+    fn frame_follows_elapsed_time() {
+        let animation = animation(4, 2.0);
+
+        assert_eq!(animation.frame_at(Duration::ZERO), 0);
+        assert_eq!(animation.frame_at(Duration::from_millis(499)), 0);
+        assert_eq!(animation.frame_at(Duration::from_millis(500)), 1);
+        assert_eq!(animation.frame_at(Duration::from_millis(1750)), 3);
+        // loops back around
+        assert_eq!(animation.frame_at(Duration::from_secs(2)), 0);
+        assert_eq!(animation.frame_at(Duration::from_millis(2600)), 1);
+    }
+
+    #[test]
+    fn single_frame_never_changes() {
+        let animation = animation(1, 30.0);
+        for millis in [0, 33, 34, 1000, 123_456] {
+            assert_eq!(animation.frame_at(Duration::from_millis(millis)), 0);
+        }
+    }
+
+    #[test]
+    fn sidecar_parsing() {
+        // frames default to the vertical stack of square frames
+        let stacked = TextureAnimation::from_sidecar("fps = 8.0", (16, 64)).unwrap();
+        assert_eq!(stacked, animation(4, 8.0));
+
+        let explicit = TextureAnimation::from_sidecar("fps = 8.0\nframes = 2", (16, 64)).unwrap();
+        assert_eq!(explicit.frames().get(), 2);
+
+        assert!(TextureAnimation::from_sidecar("fps = 8.0\nframes = 3", (16, 64)).is_err());
+        assert!(TextureAnimation::from_sidecar("fps = 0.0", (16, 64)).is_err());
+        assert!(TextureAnimation::from_sidecar("fps = 8.0", (64, 16)).is_err());
+        assert!(TextureAnimation::from_sidecar("fps = 8.0\nspeed = 1", (16, 64)).is_err());
+
+        assert_eq!(
+            TextureAnimation::sidecar_path(Path::new("blocks/water.png")),
+            Path::new("blocks/water.png.anim.toml")
+        );
+    }
+
+    #[test]
+    fn only_missing_sidecars_are_skipped() {
+        let texture = Path::new("blocks/water.png");
+        let read = |result: std::io::Result<&'static [u8]>| {
+            TextureAnimation::load_with(texture, (16, 64), |_| result.map(Cow::Borrowed).map_err(anyhow::Error::from))
+        };
+
+        assert_eq!(read(Ok(b"fps = 8.0")).unwrap(), Some(animation(4, 8.0)));
+        assert_eq!(read(Err(std::io::ErrorKind::NotFound.into())).unwrap(), None);
+        // a sidecar that's there but can't be read is an error, not a still texture
+        assert!(read(Err(std::io::ErrorKind::PermissionDenied.into())).is_err());
+        assert!(read(Ok(b"fps = -1.0")).is_err());
+    }
+}
@@ -26,7 +26,6 @@ impl<T: Pod> Buffer<T> {
         }
     }
     
-    #[expect(dead_code, reason = "still kinda hard coding things")]
     pub fn new(device: &Device, size: BufferAddress, usage: BufferUsages, label: Option<&str>) -> Self {
         let gpu_buffer = device.create_buffer(
             &wgpu::BufferDescriptor {
@@ -52,8 +51,14 @@ impl<T: Pod> Buffer<T> {
     }
 
 
-    fn prep_send<'s>(&mut self, staging_belt: &'s mut StagingBelt, encoder: &mut CommandEncoder, device: &Device) -> Option<BufferSlice<'s>> {
-        let buffer_size = NonZero::new(self.gpu_buffer.size())?;
+    fn prep_send<'s>(
+        &mut self,
+        staging_belt: &'s mut StagingBelt,
+        encoder: &mut CommandEncoder,
+        device: &Device,
+        size: BufferAddress
+    ) -> Option<BufferSlice<'s>> {
+        let buffer_size = NonZero::new(size)?;
         let slice_of_belt = staging_belt.allocate(
             buffer_size,
             const { BufferSize::new(align_of::<T>() as u64).unwrap() },
@@ -69,13 +74,15 @@ impl<T: Pod> Buffer<T> {
 
         Some(slice_of_belt) 
     }
-    
-    pub fn write(&mut self, staging_belt: &mut StagingBelt, encoder: &mut CommandEncoder, device: &Device, data: &[T]) {
-        let Some(buffer) = self.prep_send(staging_belt, encoder, device) else {
-            assert!(data.is_empty(), "buffer length mismatch");
+
+    fn write_prefix(&mut self, staging_belt: &mut StagingBelt, encoder: &mut CommandEncoder, device: &Device, data: &[T]) {
+        let size = size_of_val(data) as BufferAddress;
+        assert!(size <= self.gpu_buffer.size(), "buffer too small for data");
+
+        let Some(buffer) = self.prep_send(staging_belt, encoder, device, size) else {
             return;
         };
-        
+
         let mut view = buffer.get_mapped_range_mut();
         let dst = &mut *view;
         match bytemuck::try_cast_slice_mut::<u8, T>(dst) {
@@ -83,6 +90,78 @@ impl<T: Pod> Buffer<T> {
             Err(_) => dst.copy_from_slice(bytemuck::must_cast_slice(data))
         }
     }
+    
+    pub fn write(&mut self, staging_belt: &mut StagingBelt, encoder: &mut CommandEncoder, device: &Device, data: &[T]) {
+        assert_eq!(self.len(), data.len() as BufferAddress, "buffer length mismatch");
+        self.write_prefix(staging_belt, encoder, device, data)
+    }
+}
+
+/// A buffer that holds a varying amount of elements,
+/// the gpu side allocation only ever grows (by doubling) to fit whatever was last written
+pub struct GrowableBuffer<T> {
+    buffer: Buffer<T>,
+    len: BufferAddress,
+    usage: BufferUsages,
+    label: Option<Box<str>>,
+}
+
+impl<T: Pod> GrowableBuffer<T> {
+    pub fn new(device: &Device, capacity: BufferAddress, usage: BufferUsages, label: Option<&str>) -> Self {
+        // wgpu doesn't like zero sized bindings
+        let capacity = capacity.max(1);
+        // growing means copying the new data in
+        let usage = usage | BufferUsages::COPY_DST;
+        Self {
+            buffer: Buffer::new(device, capacity * size_of::<T>() as BufferAddress, usage, label),
+            len: 0,
+            usage,
+            label: label.map(Box::from),
+        }
+    }
+
+    pub fn len(&self) -> BufferAddress {
+        self.len
+    }
+
+    pub fn len_u32(&self) -> u32 {
+        self.len().try_into().expect("buffer too large, cannot fit in u32")
+    }
+
+    pub fn capacity(&self) -> BufferAddress {
+        self.buffer.len()
+    }
+
+    /// The whole buffer, it's replaced by a new one whenever it grows
+    pub fn buffer(&self) -> &Buffer<T> {
+        &self.buffer
+    }
+
+    /// A slice over the written elements
+    pub fn slice(&self) -> BufferSlice<'_> {
+        let end = self.len * size_of::<T>() as BufferAddress;
+        match end {
+            0 => self.buffer.slice(..),
+            end => self.buffer.slice(..end)
+        }
+    }
+
+    /// Replaces the contents of the buffer, reallocating if `data` doesn't fit
+    pub fn write(&mut self, staging_belt: &mut StagingBelt, encoder: &mut CommandEncoder, device: &Device, data: &[T]) {
+        let len = data.len() as BufferAddress;
+        if len > self.capacity() {
+            let capacity = len.next_power_of_two();
+            self.buffer = Buffer::new(
+                device,
+                capacity * size_of::<T>() as BufferAddress,
+                self.usage,
+                self.label.as_deref()
+            );
+        }
+
+        self.buffer.write_prefix(staging_belt, encoder, device, data);
+        self.len = len;
+    }
 }
 
 impl<T> Deref for Buffer<T> {
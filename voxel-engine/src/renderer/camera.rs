@@ -1,59 +1,297 @@
-use glam::{Mat4, Vec3};
+use std::time::Duration;
+use glam::{Mat4, Vec3, Vec4};
 use crate::game_state::entity::Entity;
-use crate::settings::Fov;
+use crate::settings::{DepthMode, Fov};
 
-pub struct Camera<'a>(&'a dyn Entity);
+pub struct Camera<'a> {
+    entity: &'a dyn Entity,
+    eye: Vec3,
+}
 
 impl<'a> Camera<'a> {
-    pub fn new(entity: &'a dyn Entity) -> Self {
-        Self(entity)
+    /// A camera looking the way `entity` does, but with the entity standing at `position`,
+    /// used to draw from an interpolated position between simulation steps
+    pub fn interpolated(entity: &'a dyn Entity, position: Vec3) -> Self {
+        let eye_offset = entity.eye().xyz().as_f32() - entity.position().xyz().as_f32();
+        Self { entity, eye: position + eye_offset }
     }
 
     pub fn eye(&self) -> Vec3 {
-        self.0.eye().xyz().as_f32()
+        self.eye
     }
     
     pub fn calc_matrix(&self) -> Mat4 {
-        let entity = self.0;
-        let direction = entity.camera_direction().as_f32();
-        let eye = entity.eye().xyz().as_f32();
+        let direction = self.entity.camera_direction().as_f32();
 
         Mat4::look_to_rh(
-            eye,
+            self.eye,
             direction,
-            Vec3::Y
+            self.entity.camera_up()
         )
     }
 }
 
+/// How the field of view moves to a new target
+#[cfg_attr(not(test), expect(dead_code, reason = "the easing isn't a setting yet"))]
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum FovEasing {
+    /// jumps straight to the target
+    Snap,
+    /// at a steady rate, taking the whole duration
+    Linear(Duration),
+    /// fast at first, slowing down as it gets close, taking the whole duration
+    EaseOut(Duration),
+}
+
+impl FovEasing {
+    /// How far along a transition is, between 0 and 1, after `elapsed`
+    fn progress(self, elapsed: Duration) -> f32 {
+        let (duration, ease): (_, fn(f32) -> f32) = match self {
+            FovEasing::Snap => return 1.0,
+            FovEasing::Linear(duration) => (duration, |t| t),
+            FovEasing::EaseOut(duration) => (duration, |t| 1.0 - (1.0 - t) * (1.0 - t)),
+        };
+
+        if duration.is_zero() {
+            return 1.0
+        }
+
+        ease((elapsed.as_secs_f32() / duration.as_secs_f32()).min(1.0))
+    }
+}
+
+impl Default for FovEasing {
+    fn default() -> Self {
+        FovEasing::EaseOut(Duration::from_millis(150))
+    }
+}
+
+/// A move from one field of view to another, in radians
+#[derive(Debug, Copy, Clone)]
+struct FovTransition {
+    from: f32,
+    to: f32,
+    elapsed: Duration,
+}
+
 pub struct Projection {
     aspect: f32,
+    /// the field of view right now
     fov: f32,
+    transition: Option<FovTransition>,
+    easing: FovEasing,
+    far: f32,
+    depth: DepthMode,
 }
 
 impl Projection {
-    pub fn new(width: u32, height: u32, fov: Fov) -> Self {
+    pub const NEAR: f32 = 0.1;
+    pub const DEFAULT_FAR: f32 = 100.0;
+
+    pub fn new(width: u32, height: u32, fov: Fov, depth: DepthMode) -> Self {
         Self {
             aspect: (width as f64 / height as f64) as f32,
-            fov: (fov.get_degrees() as f32).to_radians()
+            fov: Self::fov_radians(fov),
+            transition: None,
+            easing: FovEasing::default(),
+            far: Self::DEFAULT_FAR,
+            depth,
         }
     }
 
+    fn fov_radians(fov: Fov) -> f32 {
+        (fov.get_degrees() as f32).to_radians()
+    }
+
+    /// The view distance, nothing further away than this gets drawn
+    pub fn far(&self) -> f32 {
+        self.far
+    }
+
+    #[cfg_attr(not(test), expect(dead_code, reason = "the view distance isn't a setting yet"))]
+    pub fn set_far(&mut self, far: f32) {
+        assert!(far > Self::NEAR, "the far plane has to be past the near plane");
+        self.far = far
+    }
+
     pub fn resize(&mut self, width: u32, height: u32) {
         self.aspect = width as f32 / height as f32;
     }
 
-    pub fn change_fov(&mut self, fov: Fov) {
-        self.fov = (fov.get_degrees() as f32).to_radians()
+    /// The field of view right now, in radians
+    #[cfg_attr(not(test), expect(dead_code, reason = "nothing outside the projection needs it yet"))]
+    pub fn fov(&self) -> f32 {
+        self.fov
+    }
+
+    #[cfg_attr(not(test), expect(dead_code, reason = "the easing isn't a setting yet"))]
+    pub fn set_easing(&mut self, easing: FovEasing) {
+        self.easing = easing
+    }
+
+    /// Starts moving the field of view towards `fov`, [`update`](Self::update) moves it along
+    pub fn set_target_fov(&mut self, fov: Fov) {
+        let target = Self::fov_radians(fov);
+        let current_target = self.transition.map_or(self.fov, |transition| transition.to);
+        if target != current_target {
+            self.transition = Some(FovTransition { from: self.fov, to: target, elapsed: Duration::ZERO });
+        }
+    }
+
+    /// Moves the field of view `dt` further towards its target
+    pub fn update(&mut self, dt: Duration) {
+        let Some(transition) = &mut self.transition else {
+            return;
+        };
+
+        transition.elapsed += dt;
+        let progress = self.easing.progress(transition.elapsed);
+        let (min, max) = (Self::fov_radians(Fov::MIN), Self::fov_radians(Fov::MAX));
+        self.fov = (transition.from + (transition.to - transition.from) * progress).clamp(min, max);
+        if progress >= 1.0 {
+            self.fov = transition.to;
+            self.transition = None;
+        }
     }
 
     pub fn calc_matrix(&self) -> Mat4 {
+        // the depth runs from the first plane at 0 to the second at 1
+        let (zero, one) = match self.depth {
+            DepthMode::Standard => (Self::NEAR, self.far),
+            DepthMode::Reversed => (self.far, Self::NEAR),
+        };
+
         Mat4::perspective_rh(
             self.fov,
             self.aspect,
-            0.1,
-            100.0
+            zero,
+            one
         )
     }
+}
+
+/// The volume a camera can see, as the six planes bounding it with their normals pointing inwards
+pub struct Frustum {
+    planes: [Vec4; 6],
+}
+
+impl Frustum {
+    /// Pulls the planes out of a view projection matrix with a 0 to 1 depth range
+    pub fn from_matrix(view_proj: Mat4) -> Self {
+        let [x, y, z, w] = [0, 1, 2, 3].map(|i| view_proj.row(i));
+        let planes = [w + x, w - x, w + y, w - y, z, w - z]
+            .map(|plane| plane / plane.truncate().length());
+
+        Self { planes }
+    }
+
+    /// Whether any part of the box from `min` to `max` might be visible,
+    /// boxes near the corners of the frustum can pass without being inside it
+    pub fn intersects_box(&self, min: Vec3, max: Vec3) -> bool {
+        self.planes.iter().all(|plane| {
+            // the corner furthest along the plane's normal, if even that is behind the plane so is the box
+            let normal = plane.truncate();
+            let corner = Vec3::select(normal.cmpge(Vec3::ZERO), max, min);
+            normal.dot(corner) + plane.w >= 0.0
+        })
+    }
+}
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn degrees(projection: &Projection) -> f32 {
+        projection.fov().to_degrees()
+    }
+
+    #[test]
+    fn fov_eases_to_the_target() {
+        let step = Duration::from_millis(10);
+        for easing in [FovEasing::Linear(Duration::from_millis(200)), FovEasing::EaseOut(Duration::from_millis(200))] {
+            let mut projection = Projection::new(16, 9, Fov::new(60).unwrap(), DepthMode::default());
+            projection.set_easing(easing);
+            projection.set_target_fov(Fov::new(90).unwrap());
+            // nothing moves until time passes
+            assert!((degrees(&projection) - 60.0).abs() < 1e-3);
+
+            let mut last = degrees(&projection);
+            for _ in 0..20 {
+                projection.update(step);
+                let now = degrees(&projection);
+                assert!(now > last && now <= 90.0 + 1e-3, "{easing:?}: {last} -> {now}");
+                last = now;
+            }
+            assert_eq!(projection.fov(), 90_f32.to_radians());
+
+            // stays there
+            projection.update(Duration::from_secs(1));
+            assert_eq!(projection.fov(), 90_f32.to_radians());
+        }
+
+        let mut projection = Projection::new(16, 9, Fov::new(60).unwrap(), DepthMode::default());
+        projection.set_easing(FovEasing::Snap);
+        projection.set_target_fov(Fov::new(100).unwrap());
+        projection.update(Duration::ZERO);
+        assert_eq!(projection.fov(), 100_f32.to_radians());
+    }
+
+    #[test]
+    fn depth_runs_the_right_way() {
+        let depth_at = |projection: &Projection, distance: f32| {
+            // the camera looks down -z
+            let clip = projection.calc_matrix() * Vec4::new(0.0, 0.0, -distance, 1.0);
+            clip.z / clip.w
+        };
+
+        let standard = Projection::new(16, 9, Fov::default(), DepthMode::Standard);
+        let reversed = Projection::new(16, 9, Fov::default(), DepthMode::Reversed);
+        let far = standard.far();
+
+        assert!(depth_at(&standard, Projection::NEAR).abs() < 1e-6);
+        assert!((depth_at(&standard, far) - 1.0).abs() < 1e-6);
+        assert!((depth_at(&reversed, Projection::NEAR) - 1.0).abs() < 1e-6);
+        assert!(depth_at(&reversed, far).abs() < 1e-6);
+
+        // the compare lets closer things through, and the clear value is past everything
+        for (mode, projection) in [(DepthMode::Standard, &standard), (DepthMode::Reversed, &reversed)] {
+            let (near, middle, far) = (depth_at(projection, 1.0), depth_at(projection, 10.0), depth_at(projection, far));
+            let closer = |a: f32, b: f32| match mode.compare() {
+                wgpu::CompareFunction::Less => a < b,
+                wgpu::CompareFunction::Greater => a > b,
+                other => panic!("{other:?} isn't a depth test"),
+            };
+            assert!(closer(near, middle) && closer(middle, far), "{mode:?}");
+            assert!(closer(far, mode.clear_value()) || far == mode.clear_value(), "{mode:?}");
+            assert!(closer(middle, mode.clear_value()), "{mode:?}");
+        }
 
-}
\ No newline at end of file
+        // both ends are in the frustum either way
+        let eye = Vec3::ZERO;
+        for projection in [&standard, &reversed] {
+            let frustum = Frustum::from_matrix(projection.calc_matrix());
+            assert!(frustum.intersects_box(eye - Vec3::new(0.1, 0.1, 1.1), eye - Vec3::new(-0.1, -0.1, 0.9)));
+            assert!(!frustum.intersects_box(eye - Vec3::new(0.1, 0.1, far + 2.0), eye - Vec3::new(-0.1, -0.1, far + 1.0)));
+            assert!(!frustum.intersects_box(eye + Vec3::new(-0.1, -0.1, 1.0), eye + Vec3::new(0.1, 0.1, 2.0)));
+        }
+    }
+
+    #[test]
+    fn fov_stays_in_bounds() {
+        let mut projection = Projection::new(16, 9, Fov::MIN, DepthMode::default());
+        projection.set_target_fov(Fov::MAX);
+        projection.update(Duration::from_millis(50));
+        // turning back halfway starts from where it got to
+        let halfway = degrees(&projection);
+        assert!(halfway > 30.0 && halfway < 120.0);
+        projection.set_target_fov(Fov::MIN);
+        projection.update(Duration::from_millis(1));
+        assert!(degrees(&projection) < halfway);
+
+        for _ in 0..100 {
+            projection.update(Duration::from_millis(7));
+            let now = degrees(&projection);
+            assert!((30.0 - 1e-3..=120.0 + 1e-3).contains(&now), "{now}");
+        }
+        assert_eq!(projection.fov(), 30_f32.to_radians());
+    }
+}
@@ -0,0 +1,669 @@
+use std::num::NonZero;
+use std::sync::Arc;
+use ahash::HashMap;
+use bytemuck::{Pod, Zeroable};
+use glam::{ivec3, vec3, IVec3, Vec3};
+use wgpu::{BindGroup, BindGroupLayout, BufferUsages, Device, IndexFormat, RenderPass, TextureFormat, VertexBufferLayout};
+use voxel_runtime::rt::{JobHandle, JobLimit};
+use crate::game_state::blocks::BlockRegistry;
+use crate::game_state::coords::{BlockCoord, ChunkCoord};
+use crate::game_state::world::{block_y, Block, Chunk, World, CHUNK_SIZE, MAX_LIGHT};
+use crate::renderer::buffer::Buffer;
+use crate::renderer::draw_data::{DrawData, DrawDataBinding, DrawDataMode, DrawSlot};
+use crate::renderer::{buffer_size_of, DepthStage};
+use crate::renderer::model::VertexComponent;
+use crate::renderer::stats::FrameStats;
+use crate::settings::{CullMode, DepthMode};
+
+#[derive(Debug, Copy, Clone, PartialEq, Pod, Zeroable)]
+#[repr(C)]
+pub struct ChunkVertex {
+    pub position: Vec3,
+    pub normal: Vec3,
+    pub color: Vec3,
+    /// the corner's ambient occlusion and light level, see [`pack_lighting`]
+    pub lighting: u32,
+}
+
+impl VertexComponent for ChunkVertex {
+    const DESC: VertexBufferLayout<'static> = VertexBufferLayout {
+        array_stride: buffer_size_of::<ChunkVertex>(),
+        step_mode: wgpu::VertexStepMode::Vertex,
+        attributes: &const { wgpu::vertex_attr_array![0 => Float32x3, 1 => Float32x3, 2 => Float32x3, 3 => Uint32] },
+    };
+}
+
+/// A face of a block, `u` cross `v` is `normal` so walking
+/// `0, u, u + v, v` goes counterclockwise when looking at the face from outside
+struct Face {
+    normal: IVec3,
+    u: IVec3,
+    v: IVec3,
+}
+
+const FACES: [Face; 6] = [
+    Face { normal: ivec3(1, 0, 0), u: ivec3(0, 0, -1), v: ivec3(0, 1, 0) },
+    Face { normal: ivec3(-1, 0, 0), u: ivec3(0, 0, 1), v: ivec3(0, 1, 0) },
+    Face { normal: ivec3(0, 1, 0), u: ivec3(1, 0, 0), v: ivec3(0, 0, -1) },
+    Face { normal: ivec3(0, -1, 0), u: ivec3(1, 0, 0), v: ivec3(0, 0, 1) },
+    Face { normal: ivec3(0, 0, 1), u: ivec3(1, 0, 0), v: ivec3(0, 1, 0) },
+    Face { normal: ivec3(0, 0, -1), u: ivec3(-1, 0, 0), v: ivec3(0, 1, 0) },
+];
+
+impl Face {
+    /// The corners of the face on a unit cube at the origin, in winding order
+    fn corners(&self) -> [IVec3; 4] {
+        let origin = self.normal.max(IVec3::ZERO) + (-self.u).max(IVec3::ZERO) + (-self.v).max(IVec3::ZERO);
+        [origin, origin + self.u, origin + self.u + self.v, origin + self.v]
+    }
+
+    /// Which way each corner from [`Face::corners`] points along `u` and `v`
+    const CORNER_DIRECTIONS: [(i32, i32); 4] = [(-1, -1), (1, -1), (1, 1), (-1, 1)];
+}
+
+/// Packs how many of the blocks around a corner leave it open (0 fully occluded to 3)
+/// into the low 2 bits and its light level into the 4 above them
+const fn pack_lighting(ao: u32, light: u8) -> u32 {
+    ao | (light as u32) << 2
+}
+
+/// The light at a face corner, the average of the see through blocks in front of the corner,
+/// the ones that are opaque don't carry any light to average
+fn smooth_light(samples: [Option<u8>; 4]) -> u8 {
+    let (sum, count) = samples
+        .into_iter()
+        .flatten()
+        .fold((0_u32, 0_u32), |(sum, count), light| (sum + light as u32, count + 1));
+
+    match count {
+        0 => 0,
+        count => ((sum + count / 2) / count) as u8,
+    }
+}
+
+/// Whether a quad should be split along its `1-3` diagonal rather than `0-2`
+///
+/// the two triangles interpolate the corners differently, so the diagonal through
+/// the darker corners leaves a dark seam across the face, the brighter one is split on instead
+const fn flip_triangulation(ao: [u32; 4]) -> bool {
+    ao[1] + ao[3] > ao[0] + ao[2]
+}
+
+/// The ambient occlusion of a face corner from the blocks touching it in front of the face,
+/// the two along its edges and the one diagonally across
+///
+/// two sides already close the corner off completely, whatever is in the diagonal
+const fn vertex_ao(side1: bool, side2: bool, corner: bool) -> u32 {
+    if side1 && side2 {
+        return 0;
+    }
+
+    3 - (side1 as u32 + side2 as u32 + corner as u32)
+}
+
+/// A stand in until blocks get textures, every block id gets its own muted color
+fn block_color(block: Block) -> Vec3 {
+    let hash = (block.id() as u32).wrapping_mul(0x9E37_79B9);
+    let channel = |shift: u32| 0.3 + 0.6 * ((hash >> shift) & 0xFF) as f32 / 255.0;
+    vec3(channel(8), channel(16), channel(24))
+}
+
+/// A chunk and the eight chunks around it, all that's needed to mesh it
+pub struct ChunkSnapshot {
+    coord: ChunkCoord,
+    chunk: Arc<Chunk>,
+    /// the 3x3 chunks centered on this one, x first
+    around: [Option<Arc<Chunk>>; 9],
+    blocks: Arc<BlockRegistry>,
+}
+
+impl ChunkSnapshot {
+    pub fn take(world: &World, coord: ChunkCoord) -> Option<Self> {
+        let chunk = Arc::clone(world.chunk(coord)?);
+        let around = std::array::from_fn(|i| {
+            let (dx, dz) = (i as i32 % 3 - 1, i as i32 / 3 - 1);
+            coord.offset(dx, dz).and_then(|neighbor| world.chunk(neighbor)).cloned()
+        });
+
+        Some(Self { coord, chunk, around, blocks: Arc::clone(world.blocks()) })
+    }
+
+    /// The chunk a position relative to the chunk is in and where in it, looking into the neighbors
+    /// when it's outside, `None` when it's outside of the world or the chunk isn't loaded
+    fn chunk_at(&self, position: IVec3) -> Option<(&Chunk, BlockCoord)> {
+        const SIZE: i32 = CHUNK_SIZE as i32;
+
+        let y = block_y(i64::from(position.y))?;
+        let (dx, dz) = (position.x.div_euclid(SIZE), position.z.div_euclid(SIZE));
+        if !(-1..=1).contains(&dx) || !(-1..=1).contains(&dz) {
+            return None;
+        }
+
+        let (x, z) = (position.x.rem_euclid(SIZE), position.z.rem_euclid(SIZE));
+        let chunk = self.around[((dz + 1) * 3 + dx + 1) as usize].as_deref()?;
+        Some((chunk, BlockCoord::from_xyz(x as u8, y, z as u8)))
+    }
+
+    /// The block at a position relative to the chunk, anything not loaded counts as air
+    fn block_at(&self, position: IVec3) -> Block {
+        self.chunk_at(position).map_or(Block::AIR, |(chunk, coord)| chunk.get(coord))
+    }
+
+    fn occludes(&self, position: IVec3) -> bool {
+        !self.blocks.is_transparent(self.block_at(position))
+    }
+
+    /// Whether the block at a position darkens the corners of the faces next to it,
+    /// only solid blocks that can't be seen through do
+    fn shades(&self, position: IVec3) -> bool {
+        let block = self.block_at(position);
+        self.blocks.is_solid(block) && !self.blocks.is_transparent(block)
+    }
+
+    /// The light level of the block at a position, `None` when the block is opaque,
+    /// anything not loaded is as lit as open sky
+    fn light_at(&self, position: IVec3) -> Option<u8> {
+        if self.occludes(position) {
+            return None;
+        }
+
+        Some(self.chunk_at(position).map_or(MAX_LIGHT, |(chunk, coord)| chunk.light(coord).level()))
+    }
+
+    /// The light at every corner of a block's face, in [`Face::corners`] order
+    fn face_light(&self, position: IVec3, face: &Face) -> [u8; 4] {
+        let front = position + face.normal;
+        Face::CORNER_DIRECTIONS.map(|(du, dv)| {
+            let (along_u, along_v) = (face.u * du, face.v * dv);
+            smooth_light([
+                self.light_at(front),
+                self.light_at(front + along_u),
+                self.light_at(front + along_v),
+                self.light_at(front + along_u + along_v),
+            ])
+        })
+    }
+
+    /// The ambient occlusion of every corner of a block's face, in [`Face::corners`] order
+    fn face_ao(&self, position: IVec3, face: &Face) -> [u32; 4] {
+        let front = position + face.normal;
+        Face::CORNER_DIRECTIONS.map(|(du, dv)| {
+            let (along_u, along_v) = (face.u * du, face.v * dv);
+            vertex_ao(
+                self.shades(front + along_u),
+                self.shades(front + along_v),
+                self.shades(front + along_u + along_v),
+            )
+        })
+    }
+}
+
+#[derive(Debug, Default)]
+pub struct ChunkMeshData {
+    pub vertices: Vec<ChunkVertex>,
+    pub indices: Vec<u32>,
+}
+
+/// Where the chunk's corner is in world space, its mesh gets moved there when it's drawn
+fn chunk_origin(coord: ChunkCoord) -> Vec3 {
+    vec3(coord.x().as_i64() as f32, 0.0, coord.z().as_i64() as f32)
+}
+
+/// Builds the mesh of every block face that isn't hidden behind an opaque block
+///
+/// positions are relative to the chunk's corner, so they stay precise however far the chunk is from the origin
+#[tracing::instrument(level = "debug", skip_all, fields(coord = ?snapshot.coord))]
+pub fn mesh_chunk(snapshot: &ChunkSnapshot) -> ChunkMeshData {
+    let mut mesh = ChunkMeshData::default();
+
+    for (coord, block) in snapshot.chunk.iter_non_air() {
+        let position = coord.xyz().as_ivec3();
+        let color = block_color(block);
+        for face in &FACES {
+            if !snapshot.blocks.face_visible(block, snapshot.block_at(position + face.normal)) {
+                continue;
+            }
+
+            let start = mesh.vertices.len() as u32;
+            let corners = face.corners();
+            let ao = snapshot.face_ao(position, face);
+            let light = snapshot.face_light(position, face);
+            mesh.vertices.extend((0..4).map(|i| ChunkVertex {
+                position: (position + corners[i]).as_vec3(),
+                normal: face.normal.as_vec3(),
+                color,
+                lighting: pack_lighting(ao[i], light[i]),
+            }));
+
+            let indices = match flip_triangulation(ao) {
+                false => [0, 1, 2, 0, 2, 3],
+                true => [1, 2, 3, 1, 3, 0],
+            };
+            mesh.indices.extend(indices.map(|i| start + i));
+        }
+    }
+
+    mesh
+}
+
+struct ChunkMesh {
+    vertex_buffer: Buffer<ChunkVertex>,
+    index_buffer: Buffer<u32>,
+}
+
+/// The bind group the chunks' draw data goes in with the uniform fallback, after the camera and light
+const DRAW_DATA_GROUP: u32 = 2;
+
+/// The shader chunks are drawn with, taking their draw data like `mode` hands it over
+pub fn shader(mode: DrawDataMode) -> wgpu::ShaderModuleDescriptor<'static> {
+    mode.shader("chunk.wgsl", include_lit_wgsl!("chunk.wgsl"), DRAW_DATA_GROUP)
+}
+
+/// Keeps a mesh for every chunk, rebuilding the ones the world marks dirty on the thread pool
+pub struct ChunkMeshes {
+    pipeline: wgpu::RenderPipeline,
+    /// only there with the depth pre-pass on
+    depth_pipeline: Option<wgpu::RenderPipeline>,
+    meshes: HashMap<ChunkCoord, ChunkMesh>,
+    building: HashMap<ChunkCoord, JobHandle<ChunkMeshData>>,
+    mesh_jobs: JobLimit,
+}
+
+impl ChunkMeshes {
+    #[expect(clippy::too_many_arguments, reason = "they're all part of the pipelines' description")]
+    pub fn new(
+        device: &Device,
+        camera_layout: &BindGroupLayout,
+        light_layout: &BindGroupLayout,
+        draw_data: &DrawDataBinding,
+        color_format: TextureFormat,
+        depth: DepthMode,
+        prepass: bool,
+        cull: CullMode,
+    ) -> Self {
+        let mut bind_group_layouts = vec![camera_layout, light_layout];
+        bind_group_layouts.extend(draw_data.bind_group_layout());
+        let layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: Some("Chunk Pipeline Layout"),
+            bind_group_layouts: &bind_group_layouts,
+            push_constant_ranges: draw_data.push_constant_ranges(),
+        });
+
+        let pipeline = |stage| super::create_render_pipeline(
+            device,
+            &layout,
+            "vs_main",
+            color_format,
+            Some((depth, stage)),
+            cull,
+            &[ChunkVertex::DESC],
+            shader(draw_data.mode()),
+        );
+
+        Self {
+            pipeline: pipeline(DepthStage::color_pass(prepass)),
+            depth_pipeline: prepass.then(|| pipeline(DepthStage::Prepass)),
+            meshes: HashMap::default(),
+            building: HashMap::default(),
+            // leave a thread for everything else
+            mesh_jobs: JobLimit::new(
+                std::thread::available_parallelism()
+                    .ok()
+                    .and_then(|threads| NonZero::new(threads.get() - 1))
+                    .unwrap_or(NonZero::<usize>::MIN)
+            ),
+        }
+    }
+
+    /// Starts rebuilding every dirty chunk, a chunk that is already being rebuilt
+    /// gets restarted with the newer blocks and the stale result is thrown away
+    pub fn queue_rebuilds(&mut self, world: &mut World) {
+        let dirty = world.dirty_chunks_mut().drain().collect::<Vec<_>>();
+
+        for coord in dirty {
+            match ChunkSnapshot::take(world, coord) {
+                Some(snapshot) => {
+                    let job = voxel_runtime::rt::spawn_bounded(&self.mesh_jobs, move || mesh_chunk(&snapshot));
+                    self.building.insert(coord, job);
+                }
+                None => {
+                    // unloaded, there's nothing left to draw
+                    self.building.remove(&coord);
+                    self.meshes.remove(&coord);
+                }
+            }
+        }
+    }
+
+    /// Uploads whatever meshes finished building since the last call, never waits on one
+    pub fn upload_finished(&mut self, device: &Device) {
+        let mut finished = Vec::new();
+        for (coord, job) in std::mem::take(&mut self.building) {
+            match job.try_join() {
+                Ok(mesh) => finished.push((coord, mesh)),
+                Err(job) => {
+                    self.building.insert(coord, job);
+                }
+            }
+        }
+
+        for (coord, mesh) in finished {
+            if mesh.indices.is_empty() {
+                self.meshes.remove(&coord);
+                continue;
+            }
+
+            let label = format!("chunk {}, {}", coord.chunk_x(), coord.chunk_z());
+            self.meshes.insert(coord, ChunkMesh {
+                vertex_buffer: Buffer::with_init(device, &mesh.vertices, BufferUsages::VERTEX, Some(&label)),
+                index_buffer: Buffer::with_init(device, &mesh.indices, BufferUsages::INDEX, Some(&label)),
+            });
+        }
+    }
+
+    /// Pushes the draw data of the `visible` chunks that have a mesh, each is moved to where the chunk is.
+    /// What comes back is what [`ChunkMeshes::draw`] draws, it has to be uploaded with the rest of the draw data first
+    pub fn prepare_draws(&self, visible: &[ChunkCoord], draw_data: &mut DrawDataBinding) -> Vec<(ChunkCoord, DrawSlot)> {
+        self.meshes_in(visible)
+            .map(|(&coord, _)| (coord, draw_data.push(DrawData::from_offset(chunk_origin(coord)))))
+            .collect()
+    }
+
+    /// Draws the chunks [`ChunkMeshes::prepare_draws`] picked out
+    pub fn draw(
+        &self,
+        pass: &mut RenderPass,
+        draws: &[(ChunkCoord, DrawSlot)],
+        draw_data: &DrawDataBinding,
+        camera_bind_group: &BindGroup,
+        light_bind_group: &BindGroup
+    ) -> FrameStats {
+        self.draw_with(&self.pipeline, pass, draws, draw_data, camera_bind_group, light_bind_group)
+    }
+
+    /// Fills in the depth of the chunks for the depth pre-pass, draws nothing if it's off
+    pub fn draw_depth(
+        &self,
+        pass: &mut RenderPass,
+        draws: &[(ChunkCoord, DrawSlot)],
+        draw_data: &DrawDataBinding,
+        camera_bind_group: &BindGroup,
+        light_bind_group: &BindGroup
+    ) -> FrameStats {
+        match &self.depth_pipeline {
+            Some(pipeline) => self.draw_with(pipeline, pass, draws, draw_data, camera_bind_group, light_bind_group),
+            None => FrameStats::default(),
+        }
+    }
+
+    /// Whether the chunk has anything to draw
+    pub fn has_mesh(&self, coord: &ChunkCoord) -> bool {
+        self.meshes.contains_key(coord)
+    }
+
+    /// The meshes built for the chunks in `visible`
+    fn meshes_in<'a>(&'a self, visible: &'a [ChunkCoord]) -> impl Iterator<Item = (&'a ChunkCoord, &'a ChunkMesh)> {
+        visible.iter().filter_map(|coord| self.meshes.get_key_value(coord))
+    }
+
+    fn draw_with(
+        &self,
+        pipeline: &wgpu::RenderPipeline,
+        pass: &mut RenderPass,
+        draws: &[(ChunkCoord, DrawSlot)],
+        draw_data: &DrawDataBinding,
+        camera_bind_group: &BindGroup,
+        light_bind_group: &BindGroup
+    ) -> FrameStats {
+        let mut meshes = draws.iter().filter_map(|(coord, slot)| Some((self.meshes.get(coord)?, *slot))).peekable();
+        if meshes.peek().is_none() {
+            return FrameStats::default();
+        }
+
+        pass.set_pipeline(pipeline);
+        pass.set_bind_group(0, camera_bind_group, &[]);
+        pass.set_bind_group(1, light_bind_group, &[]);
+        let mut stats = FrameStats::default();
+        for (mesh, slot) in meshes {
+            draw_data.bind(pass, DRAW_DATA_GROUP, slot);
+            pass.set_vertex_buffer(0, mesh.vertex_buffer.slice(..));
+            pass.set_index_buffer(mesh.index_buffer.slice(..), IndexFormat::Uint32);
+            pass.draw_indexed(0..mesh.index_buffer.len_u32(), 0, 0..1);
+            stats += FrameStats::draw(mesh.index_buffer.len_u32() / 3, 0..1);
+        }
+        stats
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use voxel_maths::i48;
+    use crate::game_state::blocks::{BlockProperties, FaceTextures};
+    use crate::game_state::coords::AbsoluteBlockCoord;
+    use crate::game_state::test_support::{block_at, STONE};
+    use super::*;
+
+    fn world_with(blocks: &[(i64, u8, i64)]) -> World {
+        world_of(World::new(), &blocks.iter().map(|&coord| (coord, STONE)).collect::<Vec<_>>())
+    }
+
+    fn world_of(mut world: World, blocks: &[((i64, u8, i64), Block)]) -> World {
+        for x in -1..=1 {
+            for z in -1..=1 {
+                world.insert_chunk(ChunkCoord::from_xz(x, z), Chunk::empty());
+            }
+        }
+
+        for &((x, y, z), block) in blocks {
+            world.set_block(block_at(x, y, z), block);
+        }
+        world
+    }
+
+    fn faces(world: &World, chunk: ChunkCoord) -> usize {
+        let mesh = mesh_chunk(&ChunkSnapshot::take(world, chunk).unwrap());
+        assert_eq!(mesh.vertices.len() / 4, mesh.indices.len() / 6);
+        mesh.indices.len() / 6
+    }
+
+    #[test]
+    fn faces_wind_outwards() {
+        for face in &FACES {
+            assert_eq!(face.u.cross(face.v), face.normal);
+            for corner in face.corners() {
+                assert!(corner.cmpge(IVec3::ZERO).all() && corner.cmple(IVec3::ONE).all());
+            }
+        }
+    }
+
+    #[test]
+    fn lone_block_has_six_faces() {
+        let world = world_with(&[(4, 10, 4)]);
+        assert_eq!(faces(&world, ChunkCoord::ZERO), 6);
+    }
+
+    #[test]
+    fn touching_blocks_hide_faces() {
+        let world = world_with(&[(4, 10, 4), (5, 10, 4), (4, 11, 4)]);
+        assert_eq!(faces(&world, ChunkCoord::ZERO), 3 * 6 - 2 * 2);
+    }
+
+    #[test]
+    fn faces_are_hidden_across_chunks() {
+        // (15, 0, 0) in chunk (0, 0) touches a block in chunk (1, 0) and one in chunk (0, -1)
+        let world = world_with(&[(15, 0, 0), (16, 0, 0), (15, 0, -1)]);
+        assert_eq!(faces(&world, ChunkCoord::ZERO), 4);
+        assert_eq!(faces(&world, ChunkCoord::from_xz(1, 0)), 5);
+        assert_eq!(faces(&world, ChunkCoord::from_xz(0, -1)), 5);
+    }
+
+    #[test]
+    fn corner_ao_lookup() {
+        // (side1, side2, corner) -> ao, 3 is fully open
+        let table = [
+            ((false, false, false), 3),
+            ((false, false, true), 2),
+            ((true, false, false), 2),
+            ((false, true, false), 2),
+            ((true, false, true), 1),
+            ((false, true, true), 1),
+            ((true, true, false), 0),
+            ((true, true, true), 0),
+        ];
+
+        for ((side1, side2, corner), ao) in table {
+            assert_eq!(vertex_ao(side1, side2, corner), ao, "{side1} {side2} {corner}");
+        }
+    }
+
+    #[test]
+    fn triangulation_follows_the_bright_diagonal() {
+        assert!(!flip_triangulation([3; 4]));
+        assert!(!flip_triangulation([0; 4]));
+
+        // one dark corner, splitting through it would smear it across the face
+        assert!(flip_triangulation([0, 3, 3, 3]));
+        assert!(flip_triangulation([3, 3, 1, 3]));
+        assert!(!flip_triangulation([3, 0, 3, 3]));
+        assert!(!flip_triangulation([3, 3, 3, 2]));
+
+        // equally bright diagonals keep the usual split
+        assert!(!flip_triangulation([3, 2, 1, 2]));
+        assert!(!flip_triangulation([2, 3, 2, 1]));
+    }
+
+    #[test]
+    fn light_is_averaged_over_open_blocks() {
+        assert_eq!(smooth_light([Some(15); 4]), 15);
+        assert_eq!(smooth_light([Some(15), Some(5), None, None]), 10);
+        assert_eq!(smooth_light([Some(15), Some(0), Some(0), Some(0)]), 4);
+        assert_eq!(smooth_light([None; 4]), 0);
+
+        let lighting = pack_lighting(2, 13);
+        assert_eq!((lighting & 0b11, lighting >> 2), (2, 13));
+    }
+
+    fn top_face_ao(world: &World, chunk: ChunkCoord, position: IVec3) -> [u32; 4] {
+        let snapshot = ChunkSnapshot::take(world, chunk).unwrap();
+        snapshot.face_ao(position, &FACES[2])
+    }
+
+    #[test]
+    fn neighbors_shade_face_corners() {
+        let lone = world_with(&[(4, 10, 4)]);
+        assert_eq!(top_face_ao(&lone, ChunkCoord::ZERO, ivec3(4, 10, 4)), [3; 4]);
+
+        // a block up and to the +x side darkens the two +x corners of the top face
+        let world = world_with(&[(4, 10, 4), (5, 11, 4)]);
+        assert_eq!(top_face_ao(&world, ChunkCoord::ZERO, ivec3(4, 10, 4)), [3, 2, 2, 3]);
+
+        // blocks on both sides of a corner close it off entirely
+        let world = world_with(&[(4, 10, 4), (5, 11, 4), (4, 11, 3)]);
+        assert_eq!(top_face_ao(&world, ChunkCoord::ZERO, ivec3(4, 10, 4)), [3, 2, 0, 2]);
+
+        // the diagonal chunk counts too, (-1, 11, -1) is across the -x -z corner of (0, 10, 0)
+        let world = world_with(&[(0, 10, 0), (-1, 11, -1)]);
+        assert_eq!(top_face_ao(&world, ChunkCoord::ZERO, ivec3(0, 10, 0)), [3, 3, 3, 2]);
+
+        // a block the player walks through doesn't, even when it can't be seen through
+        let mut blocks = BlockRegistry::new();
+        let stone = blocks.register(BlockProperties::solid("stone", FaceTextures::all(1)));
+        let curtain = blocks.register(BlockProperties {
+            solid: false,
+            ..BlockProperties::solid("curtain", FaceTextures::all(2))
+        });
+        let world = world_of(World::with_blocks(blocks), &[((4, 10, 4), stone), ((5, 11, 4), curtain)]);
+        assert_eq!(top_face_ao(&world, ChunkCoord::ZERO, ivec3(4, 10, 4)), [3; 4]);
+    }
+
+    #[test]
+    fn faces_are_lit_by_the_world_light() {
+        let top_face_light = |world: &World| {
+            ChunkSnapshot::take(world, ChunkCoord::ZERO).unwrap().face_light(ivec3(4, 10, 4), &FACES[2])
+        };
+        assert_eq!(top_face_light(&world_with(&[(4, 10, 4)])), [MAX_LIGHT; 4]);
+
+        // under a roof the sky only reaches in from the sides, a few blocks away
+        let roof = (2..=6).flat_map(|x| (2..=6).map(move |z| (x, 12, z)));
+        let world = world_with(&[(4, 10, 4)].into_iter().chain(roof).collect::<Vec<_>>());
+        let light = top_face_light(&world);
+        assert!(light.iter().all(|&light| (MAX_LIGHT - 4..MAX_LIGHT - 1).contains(&light)), "{light:?}");
+    }
+
+    #[test]
+    fn faces_show_through_transparent_blocks() {
+        let mut blocks = BlockRegistry::new();
+        let stone = blocks.register(BlockProperties::solid("stone", FaceTextures::all(1)));
+        let glass = blocks.register(BlockProperties {
+            transparent: true,
+            ..BlockProperties::solid("glass", FaceTextures::all(2))
+        });
+
+        // stone, glass, glass in a row, the stone face behind the glass stays
+        // but the glass faces against the stone and each other go
+        let world = world_of(World::with_blocks(blocks), &[
+            ((4, 10, 4), stone),
+            ((5, 10, 4), glass),
+            ((6, 10, 4), glass),
+        ]);
+        assert_eq!(faces(&world, ChunkCoord::ZERO), 6 + 4 + 5);
+    }
+
+    #[test]
+    fn rebuilds_follow_edits() {
+        let Some((device, _queue)) = crate::renderer::test_device() else {
+            return;
+        };
+
+        let camera_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            entries: &[wgpu::BindGroupLayoutEntry {
+                binding: 0,
+                visibility: wgpu::ShaderStages::VERTEX_FRAGMENT,
+                ty: wgpu::BindingType::Buffer {
+                    ty: wgpu::BufferBindingType::Uniform,
+                    has_dynamic_offset: false,
+                    min_binding_size: None,
+                },
+                count: None,
+            }],
+            label: None,
+        });
+        let light_layout = crate::renderer::light_bind_group_layout(&device);
+        let draw_data = DrawDataBinding::new(&device, DrawDataMode::Uniform);
+        let mut meshes = ChunkMeshes::new(
+            &device,
+            &camera_layout,
+            &light_layout,
+            &draw_data,
+            TextureFormat::Rgba8UnormSrgb,
+            DepthMode::default(),
+            false,
+            CullMode::default()
+        );
+
+        let mut world = world_with(&[(15, 3, 3)]);
+        meshes.queue_rebuilds(&mut world);
+        assert_eq!(world.dirty_chunks_mut().len(), 0);
+        assert_eq!(meshes.building.len(), 9);
+
+        while !meshes.building.is_empty() {
+            meshes.upload_finished(&device);
+            std::thread::yield_now();
+        }
+        // only the one chunk has anything in it
+        assert_eq!(meshes.meshes.len(), 1);
+        assert!(meshes.meshes.contains_key(&ChunkCoord::ZERO));
+        // and it's only drawn while it's in view
+        assert_eq!(meshes.meshes_in(&[ChunkCoord::from_xz(1, 0), ChunkCoord::ZERO]).count(), 1);
+        assert_eq!(meshes.meshes_in(&[ChunkCoord::from_xz(1, 0), ChunkCoord::from_xz(-1, 0)]).count(), 0);
+
+        world.set_block(AbsoluteBlockCoord::from_xyz(i48!(15), 3, i48!(3)), Block::AIR);
+        meshes.queue_rebuilds(&mut world);
+        assert_eq!(meshes.building.len(), 2);
+        while !meshes.building.is_empty() {
+            meshes.upload_finished(&device);
+            std::thread::yield_now();
+        }
+        assert!(meshes.meshes.is_empty());
+    }
+}
@@ -0,0 +1,474 @@
+use std::borrow::Cow;
+use std::num::NonZero;
+use bytemuck::{Pod, Zeroable};
+use glam::{Mat4, Vec3};
+use wgpu::{BindGroup, BindGroupLayout, BufferUsages, CommandEncoder, Device, Features, Limits, PushConstantRange, RenderPass, ShaderStages};
+use wgpu::util::StagingBelt;
+use crate::renderer::buffer::GrowableBuffer;
+use crate::renderer::{buffer_size_of, normal_matrix, PaddedVec3};
+
+/// Small per draw data for the main and chunk shaders
+#[derive(Debug, Copy, Clone, PartialEq, Pod, Zeroable)]
+#[repr(C)]
+pub struct DrawData {
+    /// applied to every vertex on top of the instance's own transform,
+    /// it places a chunk or something drawn without an instance buffer
+    model: Mat4,
+    /// the normals' version of `model`, see [`normal_matrix`]
+    normal: [PaddedVec3; 3],
+}
+
+impl DrawData {
+    /// Leaves everything where it is
+    pub const IDENTITY: Self = Self {
+        model: Mat4::IDENTITY,
+        normal: [
+            PaddedVec3 { vec: [1.0, 0.0, 0.0], _padding: 0 },
+            PaddedVec3 { vec: [0.0, 1.0, 0.0], _padding: 0 },
+            PaddedVec3 { vec: [0.0, 0.0, 1.0], _padding: 0 },
+        ],
+    };
+
+    pub fn from_model(model: Mat4) -> Self {
+        let normal = normal_matrix(model);
+        Self { model, normal: [normal.x_axis, normal.y_axis, normal.z_axis].map(PaddedVec3::from) }
+    }
+
+    /// Only moves things by `offset`, like a chunk to where it is
+    pub fn from_offset(offset: Vec3) -> Self {
+        Self { model: Mat4::from_translation(offset), ..Self::IDENTITY }
+    }
+}
+
+/// How the shaders see [`DrawData`]
+const DRAW_DATA_WGSL: &str = "struct DrawData { model: mat4x4<f32>, normal: mat3x3<f32> }";
+
+const DRAW_DATA_STAGES: ShaderStages = ShaderStages::VERTEX;
+
+const PUSH_CONSTANT_RANGES: &[PushConstantRange] = &[PushConstantRange {
+    stages: DRAW_DATA_STAGES,
+    range: 0..buffer_size_of::<DrawData>() as u32,
+}];
+
+/// How [`DrawData`] reaches the shader
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub enum DrawDataMode {
+    /// recorded straight into the render pass, no buffers involved
+    PushConstants,
+    /// a uniform buffer, for when push constants aren't supported
+    Uniform,
+}
+
+impl DrawDataMode {
+    /// Push constants when the device has them and they are large enough, otherwise a uniform
+    pub fn select(features: Features, limits: &Limits) -> Self {
+        let fits = limits.max_push_constant_size as u64 >= buffer_size_of::<DrawData>();
+        match features.contains(Features::PUSH_CONSTANTS) && fits {
+            true => DrawDataMode::PushConstants,
+            false => DrawDataMode::Uniform,
+        }
+    }
+
+    /// declares `draw` for the shader, the two only differ in where the data comes from,
+    /// the uniform fallback is bound at `group`
+    fn shader_prelude(self, group: u32) -> String {
+        match self {
+            DrawDataMode::PushConstants => format!("
+                {DRAW_DATA_WGSL}
+                var<push_constant> draw: DrawData;
+            "),
+            DrawDataMode::Uniform => format!("
+                {DRAW_DATA_WGSL}
+                @group({group}) @binding(0)
+                var<uniform> draw: DrawData;
+            "),
+        }
+    }
+
+    /// Prepends the declaration of `draw` to `source`, see [`DrawDataBinding::bind`] for `group`
+    pub fn shader(self, label: &'static str, source: &str, group: u32) -> wgpu::ShaderModuleDescriptor<'static> {
+        wgpu::ShaderModuleDescriptor {
+            label: Some(label),
+            source: wgpu::ShaderSource::Wgsl(Cow::Owned(format!("{}\n{source}", self.shader_prelude(group)))),
+        }
+    }
+}
+
+/// Which of the frame's [`DrawData`] a draw uses, from [`DrawDataBinding::push`]
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub struct DrawSlot(u32);
+
+enum Binding {
+    PushConstants,
+    Uniform {
+        layout: BindGroupLayout,
+        /// a slot `stride` bytes long for every draw, the bind group is offset to the one being drawn
+        buffer: GrowableBuffer<u32>,
+        bind_group: BindGroup,
+        stride: u32,
+    },
+}
+
+/// How far apart the draws' slots are in the uniform fallback's buffer,
+/// dynamic offsets have to be a multiple of the device's `alignment`
+fn slot_stride(alignment: u32) -> u32 {
+    (buffer_size_of::<DrawData>() as u32).next_multiple_of(alignment)
+}
+
+fn uniform_bind_group(device: &Device, layout: &BindGroupLayout, buffer: &GrowableBuffer<u32>) -> BindGroup {
+    device.create_bind_group(&wgpu::BindGroupDescriptor {
+        layout,
+        entries: &[wgpu::BindGroupEntry {
+            binding: 0,
+            // one slot at a time, the dynamic offset picks which
+            resource: wgpu::BindingResource::Buffer(wgpu::BufferBinding {
+                buffer: buffer.buffer(),
+                offset: 0,
+                size: NonZero::new(buffer_size_of::<DrawData>()),
+            }),
+        }],
+        label: Some("draw data bind group"),
+    })
+}
+
+/// Owns whatever is needed to hand [`DrawData`] to the shaders in the selected [`DrawDataMode`],
+/// every draw of a frame gets its own
+pub struct DrawDataBinding {
+    binding: Binding,
+    /// the data of every draw this frame, a [`DrawSlot`] is an index into it
+    draws: Vec<DrawData>,
+}
+
+impl DrawDataBinding {
+    /// how many draws the uniform fallback has room for before its buffer has to grow
+    const INITIAL_SLOTS: u64 = 64;
+
+    pub fn new(device: &Device, mode: DrawDataMode) -> Self {
+        let binding = match mode {
+            DrawDataMode::PushConstants => Binding::PushConstants,
+            DrawDataMode::Uniform => {
+                let layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+                    entries: &[wgpu::BindGroupLayoutEntry {
+                        binding: 0,
+                        visibility: DRAW_DATA_STAGES,
+                        ty: wgpu::BindingType::Buffer {
+                            ty: wgpu::BufferBindingType::Uniform,
+                            has_dynamic_offset: true,
+                            min_binding_size: NonZero::new(buffer_size_of::<DrawData>()),
+                        },
+                        count: None,
+                    }],
+                    label: Some("draw data bind group layout"),
+                });
+
+                let stride = slot_stride(device.limits().min_uniform_buffer_offset_alignment);
+                let slot_words = u64::from(stride) / size_of::<u32>() as u64;
+                let buffer = GrowableBuffer::new(
+                    device,
+                    Self::INITIAL_SLOTS * slot_words,
+                    BufferUsages::UNIFORM,
+                    Some("draw data buffer")
+                );
+                let bind_group = uniform_bind_group(device, &layout, &buffer);
+
+                Binding::Uniform { layout, buffer, bind_group, stride }
+            }
+        };
+
+        Self { binding, draws: Vec::new() }
+    }
+
+    pub fn mode(&self) -> DrawDataMode {
+        match self.binding {
+            Binding::PushConstants => DrawDataMode::PushConstants,
+            Binding::Uniform { .. } => DrawDataMode::Uniform,
+        }
+    }
+
+    /// The extra bind group layout the pipeline needs for the uniform fallback, if any,
+    /// it goes after the pipeline's other bind groups
+    pub fn bind_group_layout(&self) -> Option<&BindGroupLayout> {
+        match &self.binding {
+            Binding::PushConstants => None,
+            Binding::Uniform { layout, .. } => Some(layout),
+        }
+    }
+
+    pub fn push_constant_ranges(&self) -> &'static [PushConstantRange] {
+        match self.binding {
+            Binding::PushConstants => PUSH_CONSTANT_RANGES,
+            Binding::Uniform { .. } => &[],
+        }
+    }
+
+    /// Forgets the last frame's draws, the slots they were given can't be bound anymore
+    pub fn clear(&mut self) {
+        self.draws.clear();
+    }
+
+    /// Keeps `data` for a draw this frame, the draw binds the slot that comes back
+    pub fn push(&mut self, data: DrawData) -> DrawSlot {
+        let slot = DrawSlot(u32::try_from(self.draws.len()).expect("fewer than 4 billion draws in a frame"));
+        self.draws.push(data);
+        slot
+    }
+
+    /// Uploads the data pushed this frame, has to be called before the render pass starts
+    /// since the uniform fallback goes through the staging belt
+    pub fn upload(
+        &mut self,
+        staging_belt: &mut StagingBelt,
+        encoder: &mut CommandEncoder,
+        device: &Device,
+    ) {
+        let Binding::Uniform { layout, buffer, bind_group, stride } = &mut self.binding else {
+            return;
+        };
+
+        let slot_words = *stride as usize / size_of::<u32>();
+        let mut words = vec![0; self.draws.len() * slot_words];
+        for (slot, data) in words.chunks_exact_mut(slot_words).zip(&self.draws) {
+            let data = bytemuck::cast_slice::<DrawData, u32>(std::slice::from_ref(data));
+            slot[..data.len()].copy_from_slice(data);
+        }
+
+        let capacity = buffer.capacity();
+        buffer.write(staging_belt, encoder, device, &words);
+        // growing made a new buffer, the bind group has to point at that one
+        if buffer.capacity() != capacity {
+            *bind_group = uniform_bind_group(device, layout, buffer);
+        }
+    }
+
+    /// Makes the data in `slot` visible to the draws that follow,
+    /// the uniform fallback is bound at `group`, the one after the pipeline's other bind groups
+    pub fn bind(&self, pass: &mut RenderPass, group: u32, slot: DrawSlot) {
+        match &self.binding {
+            Binding::PushConstants => {
+                let data = &self.draws[slot.0 as usize];
+                pass.set_push_constants(DRAW_DATA_STAGES, 0, bytemuck::bytes_of(data))
+            }
+            Binding::Uniform { bind_group, stride, .. } => {
+                pass.set_bind_group(group, bind_group, &[slot.0 * stride])
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn push_constants_need_the_feature_and_room() {
+        let roomy = Limits {
+            max_push_constant_size: 128,
+            ..Limits::downlevel_defaults()
+        };
+        let cramped = Limits {
+            max_push_constant_size: 8,
+            ..Limits::downlevel_defaults()
+        };
+
+        assert_eq!(DrawDataMode::select(Features::empty(), &roomy), DrawDataMode::Uniform);
+        assert_eq!(DrawDataMode::select(Features::PUSH_CONSTANTS, &cramped), DrawDataMode::Uniform);
+        assert_eq!(DrawDataMode::select(Features::PUSH_CONSTANTS, &roomy), DrawDataMode::PushConstants);
+        assert_eq!(
+            DrawDataMode::select(Features::PUSH_CONSTANTS | Features::DEPTH_CLIP_CONTROL, &roomy),
+            DrawDataMode::PushConstants
+        );
+    }
+
+    #[test]
+    fn binding_matches_mode() {
+        let Some((device, _queue)) = crate::renderer::test_device() else {
+            return;
+        };
+
+        let uniform = DrawDataBinding::new(&device, DrawDataMode::Uniform);
+        assert_eq!(uniform.mode(), DrawDataMode::Uniform);
+        assert!(uniform.bind_group_layout().is_some());
+        assert!(uniform.push_constant_ranges().is_empty());
+
+        let push = DrawDataBinding::new(&device, DrawDataMode::PushConstants);
+        assert_eq!(push.mode(), DrawDataMode::PushConstants);
+        assert!(push.bind_group_layout().is_none());
+        // a mat4x4 and a mat3x3, whose columns are padded out to 16 bytes in WGSL too
+        assert_eq!(push.push_constant_ranges()[0].range.end, 64 + 48);
+    }
+
+    #[test]
+    fn slots_follow_the_offset_alignment() {
+        assert_eq!(slot_stride(256), 256);
+        assert_eq!(slot_stride(4), buffer_size_of::<DrawData>() as u32);
+        assert_eq!(slot_stride(4) % 4, 0);
+    }
+
+    /// Draws a quad over the left half of the target, moved right by how far `draw.model` moves things along x
+    /// and as red as how far it moves them along y
+    const OFFSET_SHADER: &str = "
+        struct Out {
+            @builtin(position) position: vec4<f32>,
+            @location(0) red: f32,
+        }
+
+        @vertex
+        fn vs_main(@builtin(vertex_index) index: u32) -> Out {
+            var corners = array(
+                vec2(-1.0, -1.0), vec2(0.0, -1.0), vec2(0.0, 1.0),
+                vec2(-1.0, -1.0), vec2(0.0, 1.0), vec2(-1.0, 1.0),
+            );
+            var out: Out;
+            let offset = draw.model[3];
+            out.position = vec4(corners[index] + vec2(offset.x, 0.0), 0.0, 1.0);
+            out.red = offset.y;
+            return out;
+        }
+
+        @fragment
+        fn fs_main(in: Out) -> @location(0) vec4<f32> {
+            return vec4(in.red, 0.0, 0.0, 1.0);
+        }
+    ";
+
+    /// The red of the two pixels of a target where one draw went on the left and another on the right, in one pass
+    fn draw_left_and_right(device: &Device, queue: &wgpu::Queue, mode: DrawDataMode) -> [u8; 2] {
+        const FORMAT: wgpu::TextureFormat = wgpu::TextureFormat::Rgba8Unorm;
+
+        let mut draw_data = DrawDataBinding::new(device, mode);
+        let layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: None,
+            bind_group_layouts: &Vec::from_iter(draw_data.bind_group_layout()),
+            push_constant_ranges: draw_data.push_constant_ranges(),
+        });
+        let shader = device.create_shader_module(mode.shader("offset test", OFFSET_SHADER, 0));
+        let pipeline = device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+            label: None,
+            layout: Some(&layout),
+            vertex: wgpu::VertexState {
+                module: &shader,
+                entry_point: Some("vs_main"),
+                buffers: &[],
+                compilation_options: Default::default(),
+            },
+            fragment: Some(wgpu::FragmentState {
+                module: &shader,
+                entry_point: Some("fs_main"),
+                targets: &[Some(FORMAT.into())],
+                compilation_options: Default::default(),
+            }),
+            primitive: wgpu::PrimitiveState::default(),
+            depth_stencil: None,
+            multisample: wgpu::MultisampleState::default(),
+            multiview: None,
+            cache: None,
+        });
+
+        let target = device.create_texture(&wgpu::TextureDescriptor {
+            label: None,
+            size: wgpu::Extent3d { width: 2, height: 1, depth_or_array_layers: 1 },
+            mip_level_count: 1,
+            sample_count: 1,
+            dimension: wgpu::TextureDimension::D2,
+            format: FORMAT,
+            usage: wgpu::TextureUsages::RENDER_ATTACHMENT | wgpu::TextureUsages::COPY_SRC,
+            view_formats: &[],
+        });
+        let readback = device.create_buffer(&wgpu::BufferDescriptor {
+            label: None,
+            size: wgpu::COPY_BYTES_PER_ROW_ALIGNMENT as u64,
+            usage: BufferUsages::COPY_DST | BufferUsages::MAP_READ,
+            mapped_at_creation: false,
+        });
+
+        let left = draw_data.push(DrawData::from_offset(Vec3::new(0.0, 0.25, 0.0)));
+        let right = draw_data.push(DrawData::from_offset(Vec3::new(1.0, 0.75, 0.0)));
+        let mut staging_belt = StagingBelt::new(1024);
+        let mut encoder = device.create_command_encoder(&Default::default());
+        draw_data.upload(&mut staging_belt, &mut encoder, device);
+        staging_belt.finish();
+        {
+            let view = target.create_view(&Default::default());
+            let mut pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+                label: None,
+                color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+                    view: &view,
+                    resolve_target: None,
+                    ops: wgpu::Operations { load: wgpu::LoadOp::Clear(wgpu::Color::BLACK), store: wgpu::StoreOp::Store },
+                })],
+                depth_stencil_attachment: None,
+                timestamp_writes: None,
+                occlusion_query_set: None,
+            });
+            pass.set_pipeline(&pipeline);
+            draw_data.bind(&mut pass, 0, left);
+            pass.draw(0..6, 0..1);
+            draw_data.bind(&mut pass, 0, right);
+            pass.draw(0..6, 0..1);
+        }
+        encoder.copy_texture_to_buffer(
+            target.as_image_copy(),
+            wgpu::TexelCopyBufferInfo {
+                buffer: &readback,
+                layout: wgpu::TexelCopyBufferLayout {
+                    offset: 0,
+                    bytes_per_row: Some(wgpu::COPY_BYTES_PER_ROW_ALIGNMENT),
+                    rows_per_image: None,
+                },
+            },
+            target.size(),
+        );
+        queue.submit([encoder.finish()]);
+        staging_belt.recall();
+
+        readback.slice(..).map_async(wgpu::MapMode::Read, |res| res.unwrap());
+        device.poll(wgpu::PollType::Wait).unwrap();
+        let pixels = readback.slice(..).get_mapped_range();
+        [pixels[0], pixels[4]]
+    }
+
+    #[test]
+    fn draws_in_one_pass_get_their_own_data() {
+        let Some((device, queue)) = crate::renderer::test_device() else {
+            return;
+        };
+
+        let mut modes = vec![DrawDataMode::Uniform];
+        if DrawDataMode::select(device.features(), &device.limits()) == DrawDataMode::PushConstants {
+            modes.push(DrawDataMode::PushConstants);
+        }
+        for mode in modes {
+            device.push_error_scope(wgpu::ErrorFilter::Validation);
+            let [left, right] = draw_left_and_right(&device, &queue, mode);
+            let error = voxel_runtime::block_on(device.pop_error_scope());
+            assert!(error.is_none(), "{mode:?}: {error:?}");
+            // a quarter and three quarters of the way to full red
+            assert!(left.abs_diff(64) <= 1, "{mode:?}: {left}");
+            assert!(right.abs_diff(191) <= 1, "{mode:?}: {right}");
+        }
+    }
+
+    #[test]
+    fn uniform_slots_grow_with_the_draws() {
+        let Some((device, _queue)) = crate::renderer::test_device() else {
+            return;
+        };
+
+        let mut draw_data = DrawDataBinding::new(&device, DrawDataMode::Uniform);
+        let capacity = |draw_data: &DrawDataBinding| match &draw_data.binding {
+            Binding::Uniform { buffer, stride, .. } => buffer.capacity() * 4 / u64::from(*stride),
+            Binding::PushConstants => unreachable!(),
+        };
+        assert_eq!(capacity(&draw_data), DrawDataBinding::INITIAL_SLOTS);
+
+        let mut staging_belt = StagingBelt::new(1024);
+        let mut encoder = device.create_command_encoder(&Default::default());
+        for frame in 0..2 {
+            draw_data.clear();
+            let last = (0..=DrawDataBinding::INITIAL_SLOTS).map(|i| draw_data.push(DrawData::from_offset(Vec3::splat(i as f32)))).last();
+            assert_eq!(last, Some(DrawSlot(DrawDataBinding::INITIAL_SLOTS as u32)), "frame {frame}");
+            draw_data.upload(&mut staging_belt, &mut encoder, &device);
+        }
+        assert!(capacity(&draw_data) > DrawDataBinding::INITIAL_SLOTS);
+    }
+}
@@ -0,0 +1,222 @@
+use wgpu::{BindGroup, BindGroupLayout, CommandEncoder, Device, Extent3d, SurfaceConfiguration, TextureFormat, TextureUsages, TextureView};
+use crate::renderer::stats::FrameStats;
+
+/// A color texture the scene gets drawn into instead of the surface, so a pass can read it back afterwards
+pub struct OffscreenTarget {
+    pub view: TextureView,
+    #[cfg_attr(not(test), expect(dead_code, reason = "nothing scales the target away from the surface's size yet"))]
+    pub size: Extent3d,
+}
+
+impl OffscreenTarget {
+    /// How big the target for a surface configured with `config` is, the surface's size but never empty
+    pub fn size_for(config: &SurfaceConfiguration) -> Extent3d {
+        Extent3d {
+            width: config.width.max(1),
+            height: config.height.max(1),
+            depth_or_array_layers: 1,
+        }
+    }
+
+    pub fn new(device: &Device, config: &SurfaceConfiguration, label: &str) -> Self {
+        let size = Self::size_for(config);
+        let texture = device.create_texture(&wgpu::TextureDescriptor {
+            label: Some(label),
+            size,
+            mip_level_count: 1,
+            sample_count: 1,
+            dimension: wgpu::TextureDimension::D2,
+            format: config.format,
+            usage: TextureUsages::RENDER_ATTACHMENT | TextureUsages::TEXTURE_BINDING,
+            view_formats: &[],
+        });
+
+        Self {
+            view: texture.create_view(&Default::default()),
+            size,
+        }
+    }
+}
+
+/// Smooths jagged edges by blurring along them once the scene is drawn,
+/// the scene gets drawn into [`Fxaa::target`] and [`Fxaa::draw`] writes the smoothed result out
+pub struct Fxaa {
+    pipeline: wgpu::RenderPipeline,
+    layout: BindGroupLayout,
+    sampler: wgpu::Sampler,
+    target: OffscreenTarget,
+    bind_group: BindGroup,
+}
+
+impl Fxaa {
+    pub fn new(device: &Device, config: &SurfaceConfiguration) -> Self {
+        let layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            entries: &[
+                wgpu::BindGroupLayoutEntry {
+                    binding: 0,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Texture {
+                        multisampled: false,
+                        view_dimension: wgpu::TextureViewDimension::D2,
+                        sample_type: wgpu::TextureSampleType::Float { filterable: true },
+                    },
+                    count: None,
+                },
+                wgpu::BindGroupLayoutEntry {
+                    binding: 1,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Sampler(wgpu::SamplerBindingType::Filtering),
+                    count: None,
+                },
+            ],
+            label: Some("fxaa_bind_group_layout"),
+        });
+
+        // the edges get found by sampling between texels
+        let sampler = device.create_sampler(&wgpu::SamplerDescriptor {
+            label: Some("fxaa sampler"),
+            mag_filter: wgpu::FilterMode::Linear,
+            min_filter: wgpu::FilterMode::Linear,
+            ..Default::default()
+        });
+
+        let pipeline = Self::pipeline(device, &layout, config.format);
+        let target = OffscreenTarget::new(device, config, "fxaa target");
+        let bind_group = Self::bind_group(device, &layout, &sampler, &target);
+
+        Self { pipeline, layout, sampler, target, bind_group }
+    }
+
+    fn pipeline(device: &Device, layout: &BindGroupLayout, color_format: TextureFormat) -> wgpu::RenderPipeline {
+        let pipeline_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: Some("FXAA Pipeline Layout"),
+            bind_group_layouts: &[layout],
+            push_constant_ranges: &[],
+        });
+
+        let shader = device.create_shader_module(wgpu::include_wgsl!("./shaders/fxaa.wgsl"));
+        device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+            label: Some("FXAA Pipeline"),
+            layout: Some(&pipeline_layout),
+            vertex: wgpu::VertexState {
+                module: &shader,
+                entry_point: Some("vs_main"),
+                buffers: &[],
+                compilation_options: Default::default(),
+            },
+            fragment: Some(wgpu::FragmentState {
+                module: &shader,
+                entry_point: Some("fs_main"),
+                targets: &[Some(wgpu::ColorTargetState {
+                    format: color_format,
+                    blend: None,
+                    write_mask: wgpu::ColorWrites::ALL,
+                })],
+                compilation_options: Default::default(),
+            }),
+            primitive: wgpu::PrimitiveState::default(),
+            depth_stencil: None,
+            multisample: wgpu::MultisampleState::default(),
+            multiview: None,
+            cache: None,
+        })
+    }
+
+    fn bind_group(device: &Device, layout: &BindGroupLayout, sampler: &wgpu::Sampler, target: &OffscreenTarget) -> BindGroup {
+        device.create_bind_group(&wgpu::BindGroupDescriptor {
+            layout,
+            entries: &[
+                wgpu::BindGroupEntry {
+                    binding: 0,
+                    resource: wgpu::BindingResource::TextureView(&target.view),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 1,
+                    resource: wgpu::BindingResource::Sampler(sampler),
+                },
+            ],
+            label: Some("fxaa_bind_group"),
+        })
+    }
+
+    /// Makes a new target to match the surface
+    pub fn resize(&mut self, device: &Device, config: &SurfaceConfiguration) {
+        self.target = OffscreenTarget::new(device, config, "fxaa target");
+        self.bind_group = Self::bind_group(device, &self.layout, &self.sampler, &self.target);
+    }
+
+    /// Where the scene gets drawn
+    pub fn target(&self) -> &TextureView {
+        &self.target.view
+    }
+
+    /// Writes the smoothed scene to `output`, covering all of it
+    pub fn draw(&self, encoder: &mut CommandEncoder, output: &TextureView) -> FrameStats {
+        let mut pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+            label: Some("FXAA pass"),
+            color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+                view: output,
+                resolve_target: None,
+                ops: wgpu::Operations {
+                    load: wgpu::LoadOp::Clear(wgpu::Color::BLACK),
+                    store: wgpu::StoreOp::Store,
+                },
+            })],
+            depth_stencil_attachment: None,
+            timestamp_writes: None,
+            occlusion_query_set: None,
+        });
+
+        pass.set_pipeline(&self.pipeline);
+        pass.set_bind_group(0, &self.bind_group, &[]);
+        // one triangle big enough to cover the screen
+        pass.draw(0..3, 0..1);
+        FrameStats::draw(1, 0..1)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use wgpu::{CompositeAlphaMode, PresentMode};
+    use super::*;
+
+    fn config(width: u32, height: u32) -> SurfaceConfiguration {
+        SurfaceConfiguration {
+            usage: TextureUsages::RENDER_ATTACHMENT,
+            format: TextureFormat::Rgba8UnormSrgb,
+            width,
+            height,
+            present_mode: PresentMode::Fifo,
+            desired_maximum_frame_latency: 2,
+            alpha_mode: CompositeAlphaMode::Auto,
+            view_formats: vec![],
+        }
+    }
+
+    #[test]
+    fn targets_match_the_surface() {
+        let size = OffscreenTarget::size_for(&config(1920, 1080));
+        assert_eq!((size.width, size.height, size.depth_or_array_layers), (1920, 1080, 1));
+
+        // a texture can't be empty, even while the window is
+        let size = OffscreenTarget::size_for(&config(0, 0));
+        assert_eq!((size.width, size.height), (1, 1));
+
+        let Some((device, queue)) = crate::renderer::test_device() else {
+            return;
+        };
+
+        device.push_error_scope(wgpu::ErrorFilter::Validation);
+        let mut fxaa = Fxaa::new(&device, &config(8, 4));
+        assert_eq!((fxaa.target.size.width, fxaa.target.size.height), (8, 4));
+        fxaa.resize(&device, &config(16, 32));
+        assert_eq!((fxaa.target.size.width, fxaa.target.size.height), (16, 32));
+
+        let output = OffscreenTarget::new(&device, &config(16, 32), "output");
+        let mut encoder = device.create_command_encoder(&Default::default());
+        fxaa.draw(&mut encoder, &output.view);
+        queue.submit([encoder.finish()]);
+        let error = voxel_runtime::block_on(device.pop_error_scope());
+        assert!(error.is_none(), "{error:?}");
+    }
+}
@@ -0,0 +1,208 @@
+use bytemuck::{Pod, Zeroable};
+use glam::{Vec3, Vec4};
+use wgpu::{BindGroup, BindGroupLayout, BufferUsages, CommandEncoder, Device, RenderPass, TextureFormat, VertexBufferLayout};
+use wgpu::util::StagingBelt;
+use crate::game_state::coords::AbsoluteBlockCoord;
+use crate::renderer::buffer::GrowableBuffer;
+use crate::renderer::buffer_size_of;
+use crate::renderer::model::VertexComponent;
+use crate::renderer::stats::FrameStats;
+use crate::settings::DepthMode;
+
+/// How far past the block its outline sits, so the block's own faces don't hide it
+const OUTLINE_MARGIN: f32 = 0.002;
+
+/// One end of a line, every two make a line
+#[derive(Debug, Copy, Clone, PartialEq, Pod, Zeroable)]
+#[repr(C)]
+pub struct LineVertex {
+    pub position: Vec3,
+    pub color: [f32; 4],
+}
+
+impl VertexComponent for LineVertex {
+    const DESC: VertexBufferLayout<'static> = VertexBufferLayout {
+        array_stride: buffer_size_of::<LineVertex>(),
+        step_mode: wgpu::VertexStepMode::Vertex,
+        attributes: &const { wgpu::vertex_attr_array![0 => Float32x3, 1 => Float32x4] },
+    };
+}
+
+/// The 12 edges of the box from `min` to `max`
+pub fn box_lines(min: Vec3, max: Vec3, color: Vec4) -> [LineVertex; 24] {
+    let corner = |i: usize| LineVertex {
+        position: Vec3::select(glam::BVec3::new(i & 1 != 0, i & 2 != 0, i & 4 != 0), max, min),
+        color: color.to_array(),
+    };
+
+    // the corners are numbered by which axes they are at the max of, x being the lowest bit,
+    // so every edge joins two corners one bit apart
+    let mut lines = [corner(0); 24];
+    let edges = (0..8_usize)
+        .flat_map(|from| [1, 2, 4].map(|axis| (from, from | axis)))
+        .filter(|&(from, to)| from != to);
+    for (i, (from, to)) in edges.enumerate() {
+        lines[2 * i] = corner(from);
+        lines[2 * i + 1] = corner(to);
+    }
+    lines
+}
+
+/// Where the outline around `block` goes in world space
+pub fn block_outline(block: AbsoluteBlockCoord) -> (Vec3, Vec3) {
+    let (x, y, z) = block.xyz();
+    let min = Vec3::new(x.as_i64() as f32, y as f32, z.as_i64() as f32);
+    (min - OUTLINE_MARGIN, min + 1.0 + OUTLINE_MARGIN)
+}
+
+/// Draws lines queued up over a frame, like outlines and other gizmos,
+/// they get hidden by the world but don't write depth themselves
+pub struct LineRenderer {
+    pipeline: wgpu::RenderPipeline,
+    lines: Vec<LineVertex>,
+    vertex_buffer: GrowableBuffer<LineVertex>,
+}
+
+impl LineRenderer {
+    pub fn new(
+        device: &Device,
+        camera_layout: &BindGroupLayout,
+        color_format: TextureFormat,
+        depth: DepthMode,
+    ) -> Self {
+        let layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: Some("Line Pipeline Layout"),
+            bind_group_layouts: &[camera_layout],
+            push_constant_ranges: &[],
+        });
+
+        let shader = device.create_shader_module(wgpu::include_wgsl!("./shaders/lines.wgsl"));
+        let pipeline = device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+            label: Some("Line Pipeline"),
+            layout: Some(&layout),
+            vertex: wgpu::VertexState {
+                module: &shader,
+                entry_point: Some("vs_main"),
+                buffers: &[LineVertex::DESC],
+                compilation_options: Default::default(),
+            },
+            fragment: Some(wgpu::FragmentState {
+                module: &shader,
+                entry_point: Some("fs_main"),
+                targets: &[Some(wgpu::ColorTargetState {
+                    format: color_format,
+                    blend: Some(wgpu::BlendState::ALPHA_BLENDING),
+                    write_mask: wgpu::ColorWrites::ALL,
+                })],
+                compilation_options: Default::default(),
+            }),
+            primitive: wgpu::PrimitiveState {
+                topology: wgpu::PrimitiveTopology::LineList,
+                cull_mode: None,
+                ..Default::default()
+            },
+            depth_stencil: Some(wgpu::DepthStencilState {
+                format: depth.format(),
+                depth_write_enabled: false,
+                depth_compare: depth.compare_or_equal(),
+                stencil: wgpu::StencilState::default(),
+                bias: wgpu::DepthBiasState::default(),
+            }),
+            multisample: wgpu::MultisampleState::default(),
+            multiview: None,
+            cache: None,
+        });
+
+        Self {
+            pipeline,
+            lines: Vec::new(),
+            vertex_buffer: GrowableBuffer::new(
+                device,
+                0,
+                BufferUsages::VERTEX,
+                Some("line vertex buffer")
+            ),
+        }
+    }
+
+    /// Queues the edges of the box from `min` to `max` to be drawn this frame
+    pub fn push_box(&mut self, min: Vec3, max: Vec3, color: Vec4) {
+        self.lines.extend(box_lines(min, max, color))
+    }
+
+    /// Uploads the lines queued since the last frame and starts queueing the next one's,
+    /// has to be called before the render pass starts
+    pub fn prepare(
+        &mut self,
+        staging_belt: &mut StagingBelt,
+        encoder: &mut CommandEncoder,
+        device: &Device,
+    ) {
+        self.vertex_buffer.write(staging_belt, encoder, device, &self.lines);
+        self.lines.clear();
+    }
+
+    /// Draws the lines uploaded in [`LineRenderer::prepare`],
+    /// should come after everything opaque so blending sees what's behind
+    pub fn draw(&self, pass: &mut RenderPass, camera_bind_group: &BindGroup) -> FrameStats {
+        if self.vertex_buffer.len() == 0 {
+            return FrameStats::default();
+        }
+
+        pass.set_pipeline(&self.pipeline);
+        pass.set_bind_group(0, camera_bind_group, &[]);
+        pass.set_vertex_buffer(0, self.vertex_buffer.slice());
+        pass.draw(0..self.vertex_buffer.len_u32(), 0..1);
+        FrameStats::draw(0, 0..1)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use voxel_maths::i48;
+    use super::*;
+
+    #[test]
+    fn block_outlines_cover_the_block() {
+        let block = AbsoluteBlockCoord::from_xyz(i48!(-3), 64, i48!(17));
+        let (min, max) = block_outline(block);
+        assert!(min.abs_diff_eq(Vec3::new(-3.0, 64.0, 17.0), 0.01) && min.cmplt(Vec3::new(-3.0, 64.0, 17.0)).all());
+        assert!(max.abs_diff_eq(Vec3::new(-2.0, 65.0, 18.0), 0.01) && max.cmpgt(Vec3::new(-2.0, 65.0, 18.0)).all());
+
+        let color = Vec4::new(0.0, 0.0, 0.0, 0.5);
+        let lines = box_lines(min, max, color);
+        assert!(lines.iter().all(|vertex| vertex.color == color.to_array()));
+
+        let mut edges = lines
+            .chunks_exact(2)
+            .map(|line| {
+                let (from, to) = (line[0].position, line[1].position);
+                // every edge runs along one axis, the whole way across the box
+                let along = (to - from).abs();
+                assert_eq!(along.cmpgt(Vec3::ZERO).bitmask().count_ones(), 1, "{from} -> {to}");
+                assert_eq!(Vec3::select(along.cmpgt(Vec3::ZERO), max - min, Vec3::ZERO), along);
+                for end in [from, to] {
+                    assert!((end.cmpeq(min) | end.cmpeq(max)).all(), "{end} isn't a corner");
+                }
+                [from, to].map(|end| end.to_array().map(f32::to_bits))
+            })
+            .collect::<Vec<_>>();
+        edges.sort();
+        edges.dedup();
+        assert_eq!(edges.len(), 12);
+    }
+
+    #[test]
+    fn pipeline_builds() {
+        let Some((device, _queue)) = crate::renderer::test_device() else {
+            return;
+        };
+
+        device.push_error_scope(wgpu::ErrorFilter::Validation);
+        let camera_layout = crate::renderer::camera_bind_group_layout(&device);
+        let mut renderer = LineRenderer::new(&device, &camera_layout, TextureFormat::Rgba8UnormSrgb, DepthMode::Reversed);
+        renderer.push_box(Vec3::ZERO, Vec3::ONE, Vec4::ONE);
+        assert_eq!(renderer.lines.len(), 24);
+        assert!(voxel_runtime::block_on(device.pop_error_scope()).is_none());
+    }
+}
@@ -1,21 +1,51 @@
 use std::num::NonZero;
+use std::path::Path;
 use std::sync::Arc;
+use std::time::{Duration, Instant};
 use bytemuck::{Pod, Zeroable};
-use glam::{vec3a, Mat4, Quat, Vec3, Vec3A};
-use wgpu::{Instance as WGPUInstance, Device, DeviceDescriptor, MemoryHints, PowerPreference, Queue, RequestAdapterOptions, Surface, TextureFormat, Trace, InstanceDescriptor, SurfaceConfiguration, TextureUsages, CompositeAlphaMode, PresentMode, TextureViewDescriptor, Operations, RenderPassColorAttachment, LoadOp, StoreOp, RenderPassDescriptor, BufferAddress, BufferUsages, BindGroup, CommandEncoder, VertexBufferLayout, Color};
+use glam::{vec3, Mat3, Mat4, Vec3, Vec4, Vec3A, Vec3Swizzles};
+use wgpu::{Instance as WGPUInstance, Device, DeviceDescriptor, MemoryHints, PowerPreference, Queue, RequestAdapterOptions, Surface, TextureFormat, Trace, InstanceDescriptor, SurfaceConfiguration, TextureUsages, CompositeAlphaMode, PresentMode, TextureViewDescriptor, Operations, RenderPassColorAttachment, LoadOp, StoreOp, RenderPassDescriptor, BufferAddress, BufferUsages, BindGroup, BindGroupLayout, CommandEncoder, VertexBufferLayout, Color};
 use wgpu::util::StagingBelt;
 use winit::window::Window;
 use voxel_maths::Transform;
+use crate::assets::AssetSource;
+use crate::game_state::entities::EntityId;
 use crate::game_state::GameState;
-use crate::renderer::buffer::Buffer;
-use crate::renderer::camera::{Camera, Projection};
-use crate::renderer::model::{DrawLightExt, DrawObjExt, Model, ModelVertex, VertexComponent};
-use crate::renderer::texture::Texture;
-use crate::settings::{GameSettings, GameSettingsHandle, Vsync};
+use crate::game_state::light::Light;
+use crate::game_state::coords::ChunkCoord;
+use crate::game_state::world::{World, CHUNK_HEIGHT, CHUNK_SIZE};
+use crate::renderer::buffer::{Buffer, GrowableBuffer};
+use crate::renderer::camera::{Camera, Frustum, Projection};
+use crate::renderer::draw_data::{DrawData, DrawDataBinding, DrawDataMode};
+use crate::renderer::model::{DrawLightExt, DrawObjExt, Mesh, Model, ModelVertex, VertexComponent};
+use crate::renderer::chunk_mesh::ChunkMeshes;
+use crate::renderer::fxaa::Fxaa;
+use crate::renderer::lines::LineRenderer;
+use crate::renderer::occlusion::{BoundingBox, OcclusionCuller};
+use crate::renderer::particles::ParticleRenderer;
+use crate::renderer::stats::FrameStats;
+use crate::renderer::texture::{Texture, TextureCache};
+use crate::settings::{AntiAliasing, CullMode, DepthMode, FogSettings, GameSettings, GameSettingsHandle, TextureFilter, Vsync, WindowChanges};
 
 mod texture;
+mod animation;
+/// Includes one of the shaders that draw lit things, with the lighting they share put in front of it
+macro_rules! include_lit_wgsl {
+    ($file:literal) => {
+        concat!(include_str!("./shaders/lighting.wgsl"), include_str!(concat!("./shaders/", $file)))
+    };
+}
+
 mod buffer;
 mod camera;
+mod chunk_mesh;
+mod draw_data;
+mod particles;
+mod fxaa;
+mod lines;
+mod shapes;
+mod stats;
+mod occlusion;
 
 pub mod model;
 
@@ -40,45 +70,220 @@ macro_rules! buffer_size_of {
 pub(super) struct Renderer {
     window: Arc<Window>,
     settings: GameSettingsHandle,
-    device: Device,
-    queue: Queue,
+    /// the settings the window last had applied to it
+    window_settings: Arc<GameSettings>,
     size: winit::dpi::PhysicalSize<u32>,
+    /// how many physical pixels make up a logical one on the monitor the window is on
+    scale_factor: f64,
+    visibility: Visibility,
     surface: Surface<'static>,
     surface_format: TextureFormat,
+    /// where the window's icon gets read from, the same place the scene's assets came from
+    assets: Box<dyn AssetSource>,
+    scene: SceneRenderer,
+}
+
+/// Everything that goes into drawing the game, into whatever texture it's given
+/// so it doesn't need a window to draw into
+struct SceneRenderer {
+    settings: GameSettingsHandle,
+    device: Device,
+    queue: Queue,
+    /// only draws what won the depth pre-pass when it's on
     render_pipeline: wgpu::RenderPipeline,
+    /// `None` when the depth pre-pass is off, everything is drawn in one pass then
+    prepass: Option<wgpu::RenderPipeline>,
+    /// draws the held item, placed by its [`DrawData`] instead of an instance buffer
+    single_render_pipeline: wgpu::RenderPipeline,
+    draw_data: DrawDataBinding,
     light_render_pipeline: wgpu::RenderPipeline,
     staging_belt: StagingBelt,
     projection: Projection,
+    /// the simulation time of the last frame, the projection animates with it
+    last_simulation_time: Duration,
     last_camera_uniform: CameraUniform,
     camera_buffer: Buffer<CameraUniform>,
     camera_bind_group: BindGroup,
-    _light_buffer: Buffer<LightUniform>,
+    light_buffer: Buffer<LightUniform>,
+    fog_buffer: Buffer<FogUniform>,
+    display_buffer: Buffer<DisplayUniform>,
+    /// whether the textures drawn into keep colors brighter than white
+    hdr: bool,
     light_bind_group: BindGroup,
     depth_texture: Texture,
+    depth_mode: DepthMode,
     
+    /// every model's textures, so models using the same file share it
+    #[expect(dead_code, reason = "the cube is the only model loaded so far, it's kept for the ones loaded after it")]
+    textures: TextureCache,
     model: Model,
-    instance_buffer: Buffer<InstanceRaw>
+    /// what the sun gets drawn as, in the light's color
+    sun: Mesh,
+    instances: Vec<InstanceRaw>,
+    instance_cap: InstanceCap,
+    instance_buffer: GrowableBuffer<InstanceRaw>,
+    particles: ParticleRenderer,
+    lines: LineRenderer,
+    /// `None` when anti-aliasing is off, the scene goes straight to the surface then
+    fxaa: Option<Fxaa>,
+    chunks: ChunkMeshes,
+    /// skips the chunks that were completely hidden behind what was drawn last frame
+    chunk_occlusion: OcclusionCuller<ChunkCoord>,
+}
+
+/// The shader everything but the chunks is drawn with, before the draw data gets declared in front of it
+const MAIN_SHADER: &str = include_lit_wgsl!("main_shader.wgsl");
+
+/// The bind group the main shader's draw data goes in with the uniform fallback, after the texture, camera and light
+const DRAW_DATA_GROUP: u32 = 3;
+
+/// How many chunks get an occlusion query each frame, any more are drawn without one
+const CHUNK_QUERIES: u32 = 1024;
+
+/// The physical size a window of `size` pixels at `old_scale` has once it moves to `new_scale`
+fn rescaled_size(
+    size: winit::dpi::PhysicalSize<u32>,
+    old_scale: f64,
+    new_scale: f64
+) -> winit::dpi::PhysicalSize<u32> {
+    let logical = size.to_logical::<f64>(old_scale);
+    let physical = logical.to_physical::<u32>(new_scale);
+    // don't round a tiny window down to nothing, the surface needs at least a pixel
+    winit::dpi::PhysicalSize::new(physical.width.max(1), physical.height.max(1))
 }
 
+/// Whether there is anywhere on screen to draw to,
+/// there is no point rendering while the window is minimized or covered up
+#[derive(Debug, Default, Copy, Clone, Eq, PartialEq)]
+struct Visibility {
+    minimized: bool,
+    occluded: bool,
+}
+
+impl Visibility {
+    /// some platforms minimize windows by shrinking them down to nothing
+    fn resized(&mut self, size: winit::dpi::PhysicalSize<u32>) {
+        self.minimized = size.width == 0 || size.height == 0;
+    }
+
+    fn set_occluded(&mut self, occluded: bool) {
+        self.occluded = occluded;
+    }
+
+    fn is_paused(self) -> bool {
+        self.minimized || self.occluded
+    }
+}
+
+/// Where an instance is drawn, and a tint multiplied into its color
 #[derive(Copy, Clone)]
-struct Instance(Transform);
+struct Instance(Transform, Vec4);
+
+/// The most instances drawn in a frame, so however many entities there are
+/// the instance buffer never grows past what the GPU can take
+const MAX_INSTANCES: usize = 1 << 16;
+
+/// Keeps the instances drawn in a frame under a cap, past it they get left out
+struct InstanceCap {
+    max: usize,
+    /// when going over the cap was last warned about, a scene that goes over usually does so every frame
+    last_warned: Option<Instant>,
+    /// the frames that went over since the last warning
+    frames_over: u32,
+}
+
+impl InstanceCap {
+    /// the least time between two warnings about going over the cap
+    const WARN_EVERY: Duration = Duration::from_secs(10);
+
+    fn new(max: usize) -> Self {
+        Self { max, last_warned: None, frames_over: 0 }
+    }
+
+    /// Leaves out the instances past the cap, warning about it at most every [`InstanceCap::WARN_EVERY`]
+    fn apply(&mut self, raw: &mut Vec<InstanceRaw>, now: Instant) {
+        if raw.len() <= self.max {
+            return;
+        }
+
+        self.frames_over += 1;
+        let due = self.last_warned.is_none_or(|last| now.saturating_duration_since(last) >= Self::WARN_EVERY);
+        if due {
+            tracing::warn!(
+                "{} instances is over the limit of {}, only the first {} get drawn ({} frames went over since the last warning)",
+                raw.len(),
+                self.max,
+                self.max,
+                self.frames_over
+            );
+            self.last_warned = Some(now);
+            self.frames_over = 0;
+        }
+        raw.truncate(self.max);
+    }
+}
+
+/// Converts the objects in the game into what gets uploaded to the instance buffer,
+/// `raw` is cleared first so its allocation can be reused every frame
+fn collect_instances(game: &GameState, raw: &mut Vec<InstanceRaw>) {
+    let alpha = game.interpolation_alpha();
+
+    raw.clear();
+    raw.reserve(game.entities().len());
+    raw.extend(
+        game.entities()
+            .iter()
+            .filter(|(_, entity)| entity.kind.is_rendered())
+            .map(|(_, entity)| Instance(entity.render_transform(alpha), Vec4::ONE).to_raw())
+    );
+}
 
 impl Instance {
     fn to_raw(self) -> InstanceRaw {
-        InstanceRaw {
-            model: Mat4::from_rotation_translation(
+        InstanceRaw::new(
+            Mat4::from_rotation_translation(
                 self.0.rotation,
                 self.0.position.into()
             ),
-        }
+            self.1
+        )
     }
 }
 
+/// Where the held item sits in view space, down and to the right of the middle of the screen
+/// and turned a little so more than one face shows
+fn held_item_view_transform() -> Mat4 {
+    Mat4::from_scale_rotation_translation(
+        Vec3::splat(0.35),
+        glam::Quat::from_rotation_y(-0.5) * glam::Quat::from_rotation_x(0.3),
+        vec3(0.55, -0.45, -1.0)
+    )
+}
+
+/// The model matrix that puts the held item at [`held_item_view_transform`] in front of a camera with `view`,
+/// so it stays put on screen however the camera moves
+fn held_item_model(view: Mat4) -> Mat4 {
+    view.inverse() * held_item_view_transform()
+}
+
+/// What normals get transformed by so they stay perpendicular to their surface,
+/// the inverse transpose of the model matrix without its translation
+fn normal_matrix(model: Mat4) -> Mat3 {
+    Mat3::from_mat4(model).inverse().transpose()
+}
 
 #[derive(Copy, Clone, Pod, Zeroable)]
 #[repr(C, packed(4))] // 4 for f32
 struct InstanceRaw {
     model: Mat4,
+    normal: Mat3,
+    tint: Vec4,
+}
+
+impl InstanceRaw {
+    fn new(model: Mat4, tint: Vec4) -> Self {
+        Self { model, normal: normal_matrix(model), tint }
+    }
 }
 
 impl VertexComponent for InstanceRaw {
@@ -113,6 +318,27 @@ impl VertexComponent for InstanceRaw {
                 shader_location: 8,
                 format: wgpu::VertexFormat::Float32x4,
             },
+            // and the normal matrix takes 3 more for its columns
+            wgpu::VertexAttribute {
+                offset: buffer_size_of!([f32; 16]),
+                shader_location: 9,
+                format: wgpu::VertexFormat::Float32x3,
+            },
+            wgpu::VertexAttribute {
+                offset: buffer_size_of!([f32; 19]),
+                shader_location: 10,
+                format: wgpu::VertexFormat::Float32x3,
+            },
+            wgpu::VertexAttribute {
+                offset: buffer_size_of!([f32; 22]),
+                shader_location: 11,
+                format: wgpu::VertexFormat::Float32x3,
+            },
+            wgpu::VertexAttribute {
+                offset: buffer_size_of!([f32; 25]),
+                shader_location: 12,
+                format: wgpu::VertexFormat::Float32x4,
+            },
         ],
     };
 }
@@ -151,12 +377,246 @@ struct CameraUniform {
     view_proj: Mat4
 }
 
-#[derive(Debug, Copy, Clone, bytemuck::Pod, bytemuck::Zeroable)]
+/// How far from the camera directional lights get drawn, far enough out to look infinitely far away,
+/// but still inside the far plane so the sun shows up in the sky
+const SUN_DISTANCE: f32 = 80.0;
+
+/// A [`Light`] as the shaders see it, which fields mean anything depends on `kind`
+#[derive(Debug, Copy, Clone, PartialEq, bytemuck::Pod, bytemuck::Zeroable)]
 #[repr(C)]
 struct LightUniform {
-    // Due to uniforms requiring 16 byte (4 float) spacing, we use these padded vecs
-    position: PaddedVec3,
-    color: PaddedVec3,
+    position: [f32; 3],
+    kind: u32,
+    /// towards a directional light, the way a spot light shines
+    direction: [f32; 3],
+    range: f32,
+    color: [f32; 3],
+    intensity: f32,
+    /// the cosines of the angles a spot light starts fading out at and is gone by
+    cos_inner: f32,
+    cos_outer: f32,
+    _padding: [u32; 2],
+}
+
+impl LightUniform {
+    const DIRECTIONAL: u32 = 0;
+    const POINT: u32 = 1;
+    const SPOT: u32 = 2;
+
+    /// How far out from the edge of a spot light's cone it starts to fade out
+    const SPOT_SOFTNESS: f32 = 0.8;
+
+    /// Packs `light`, directional lights get drawn `distance` away from `eye` towards them
+    fn new(light: &Light, eye: Vec3, distance: f32) -> Self {
+        let (kind, position, direction, range, cone) = match *light {
+            Light::Directional { direction, .. } => {
+                let direction = direction.normalize();
+                (Self::DIRECTIONAL, eye + direction * distance, direction, f32::INFINITY, 0.0)
+            }
+            Light::Point { position, range, .. } => (Self::POINT, position, Vec3::ZERO, range, 0.0),
+            Light::Spot { position, direction, range, cone, .. } => (Self::SPOT, position, direction.normalize(), range, cone),
+        };
+
+        Self {
+            position: position.to_array(),
+            kind,
+            direction: direction.to_array(),
+            range,
+            color: light.color().to_array(),
+            intensity: light.intensity(),
+            cos_inner: (cone * Self::SPOT_SOFTNESS).cos(),
+            cos_outer: cone.cos(),
+            _padding: [0; 2],
+        }
+    }
+}
+
+/// Distance fog, it fades everything between `start` and `end` (in view space depth) into `color`
+#[derive(Debug, Copy, Clone, PartialEq, bytemuck::Pod, bytemuck::Zeroable)]
+#[repr(C)]
+struct FogUniform {
+    color: [f32; 3],
+    enabled: u32,
+    start: f32,
+    end: f32,
+    _padding: [u32; 2],
+}
+
+impl FogUniform {
+    /// `far` is the view distance the settings' start and end are relative to
+    fn new(settings: &FogSettings, color: Vec3, far: f32) -> Self {
+        let start = settings.start.clamp(0.0, 1.0) * far;
+        // the shader divides by the length of the fog, it can't be zero
+        let end = (settings.end.clamp(0.0, 1.0) * far).max(start + f32::EPSILON * far);
+
+        Self {
+            color: color.to_array(),
+            enabled: settings.enabled as u32,
+            start,
+            end,
+            _padding: [0; 2],
+        }
+    }
+}
+
+/// How the lit scene is mapped onto the surface, see `tone_map` in lighting.wgsl
+#[derive(Debug, Copy, Clone, PartialEq, bytemuck::Pod, bytemuck::Zeroable)]
+#[repr(C)]
+struct DisplayUniform {
+    exposure: f32,
+    /// whether the surface keeps what's brighter than white, so nothing has to be squeezed under it
+    hdr: u32,
+    _padding: [u32; 2],
+}
+
+impl DisplayUniform {
+    fn new(exposure: f32, hdr: bool) -> Self {
+        Self {
+            exposure: if exposure.is_finite() { exposure.max(0.0) } else { 1.0 },
+            hdr: hdr as u32,
+            _padding: [0; 2],
+        }
+    }
+}
+
+/// Whether colors past white make it onto a surface of `format`, rather than getting clamped
+fn is_hdr(format: TextureFormat) -> bool {
+    format == TextureFormat::Rgba16Float
+}
+
+/// The layout of a model material, its texture and sampler, the animation uniform and its color
+fn texture_bind_group_layout(device: &Device, filter: TextureFilter) -> BindGroupLayout {
+    device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+        entries: &[
+            wgpu::BindGroupLayoutEntry {
+                binding: 0,
+                visibility: wgpu::ShaderStages::FRAGMENT,
+                ty: wgpu::BindingType::Texture {
+                    multisampled: false,
+                    view_dimension: wgpu::TextureViewDimension::D2,
+                    sample_type: wgpu::TextureSampleType::Float { filterable: true },
+                },
+                count: None,
+            },
+            wgpu::BindGroupLayoutEntry {
+                binding: 1,
+                visibility: wgpu::ShaderStages::FRAGMENT,
+                // has to match the filter the material's samplers are made with
+                ty: wgpu::BindingType::Sampler(filter.sampler_binding_type()),
+                count: None,
+            },
+            wgpu::BindGroupLayoutEntry {
+                binding: 2,
+                visibility: wgpu::ShaderStages::FRAGMENT,
+                ty: wgpu::BindingType::Buffer {
+                    ty: wgpu::BufferBindingType::Uniform,
+                    has_dynamic_offset: false,
+                    min_binding_size: NonZero::new(buffer_size_of!(animation::AnimationUniform)),
+                },
+                count: None,
+            },
+            wgpu::BindGroupLayoutEntry {
+                binding: 3,
+                visibility: wgpu::ShaderStages::FRAGMENT,
+                ty: wgpu::BindingType::Buffer {
+                    ty: wgpu::BufferBindingType::Uniform,
+                    has_dynamic_offset: false,
+                    min_binding_size: NonZero::new(buffer_size_of!(model::MaterialUniform)),
+                },
+                count: None,
+            },
+        ],
+        label: Some("texture_bind_group_layout"),
+    })
+}
+
+/// The layout of the light and fog uniforms, every pipeline that shades with the sun uses it
+fn light_bind_group_layout(device: &Device) -> BindGroupLayout {
+    let uniform = |binding| wgpu::BindGroupLayoutEntry {
+        binding,
+        visibility: wgpu::ShaderStages::VERTEX | wgpu::ShaderStages::FRAGMENT,
+        ty: wgpu::BindingType::Buffer {
+            ty: wgpu::BufferBindingType::Uniform,
+            has_dynamic_offset: false,
+            min_binding_size: None,
+        },
+        count: None,
+    };
+
+    device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+        entries: &[uniform(0), uniform(1), uniform(2)],
+        label: Some("light_bind_group_layout"),
+    })
+}
+
+/// The format to draw to out of the ones the surface supports, `formats` is never empty.
+///
+/// With `hdr` a linear half float surface is preferred, the shaders already output linear colors
+/// and it keeps what's brighter than white instead of clamping it.
+/// Otherwise (or without one) the first sRGB format, so the colors get encoded on the way out
+fn surface_format(formats: &[TextureFormat], hdr: bool) -> TextureFormat {
+    let find = |predicate: fn(&TextureFormat) -> bool| formats.iter().copied().find(predicate);
+
+    hdr.then(|| find(|&format| format == TextureFormat::Rgba16Float))
+        .flatten()
+        .or_else(|| find(TextureFormat::is_srgb))
+        .unwrap_or(formats[0])
+}
+
+fn camera_bind_group_layout(device: &Device) -> BindGroupLayout {
+    device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+        entries: &[
+            wgpu::BindGroupLayoutEntry {
+                binding: 0,
+                visibility: wgpu::ShaderStages::VERTEX_FRAGMENT,
+                ty: wgpu::BindingType::Buffer {
+                    ty: wgpu::BufferBindingType::Uniform,
+                    has_dynamic_offset: false,
+                    min_binding_size: NonZero::new(buffer_size_of!(CameraUniform)),
+                },
+                count: None,
+            }
+        ],
+        label: Some("camera bind group layout"),
+    })
+}
+
+/// The camera at where the player is drawn this frame, between the last two simulation steps
+fn player_camera(game: &GameState) -> Camera<'_> {
+    let player = game.entities().get(EntityId::PLAYER).expect("the player can't be despawned");
+    let position = player.render_transform(game.interpolation_alpha()).position;
+    Camera::interpolated(game.player(), position.into())
+}
+
+/// The box a chunk's blocks all fit in
+fn chunk_bounds(coord: ChunkCoord) -> BoundingBox {
+    let size = CHUNK_SIZE as f32;
+    let min = vec3(coord.chunk_x() as f32 * size, 0.0, coord.chunk_z() as f32 * size);
+    BoundingBox { min, max: min + vec3(size, CHUNK_HEIGHT as f32, size) }
+}
+
+/// The chunks at least partly inside the frustum of `camera`, and within the far plane of it horizontally
+fn chunks_in_view(camera: &Camera, projection: &Projection) -> Vec<ChunkCoord> {
+    let frustum = Frustum::from_matrix(projection.calc_matrix() * camera.calc_matrix());
+    let eye = camera.eye();
+    let far = projection.far();
+    let size = CHUNK_SIZE as f32;
+
+    let chunk_range = |center: f32| {
+        let start = ((center - far) / size).floor() as i32;
+        let end = ((center + far) / size).floor() as i32;
+        start..=end
+    };
+
+    chunk_range(eye.z)
+        .flat_map(|z| chunk_range(eye.x).map(move |x| ChunkCoord::from_xz(x, z)))
+        .filter(|&coord| {
+            let BoundingBox { min, max } = chunk_bounds(coord);
+
+            let closest = eye.clamp(min, max);
+            closest.xz().distance_squared(eye.xz()) <= far * far && frustum.intersects_box(min, max)
+        })
+        .collect()
 }
 
 impl CameraUniform {
@@ -169,57 +629,138 @@ impl CameraUniform {
     }
 }
 
+/// The main pipeline without the instance buffer, for drawing one thing at a time (like UI quads or gizmos)
+/// without making a buffer for its one instance, it's drawn with `draw_obj_instanced(.., 0..1)`
+/// and only placed by the [`DrawData`] model matrix
+fn single_render_pipeline(
+    device: &Device,
+    layout: &wgpu::PipelineLayout,
+    draw_data_mode: DrawDataMode,
+    color_format: TextureFormat,
+    depth: DepthMode,
+    cull: CullMode,
+) -> wgpu::RenderPipeline {
+    let shader = draw_data_mode.shader(
+        "main_shader.wgsl",
+        MAIN_SHADER,
+        DRAW_DATA_GROUP
+    );
+
+    create_render_pipeline(
+        device,
+        layout,
+        "vs_single",
+        color_format,
+        Some((depth, DepthStage::Full)),
+        cull,
+        &[ModelVertex::DESC],
+        shader
+    )
+}
+
+/// How a pipeline uses the depth buffer, with the depth pre-pass on the depth of everything opaque is drawn first
+/// so the color pass only runs the fragment shader for what ends up on screen
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+enum DepthStage {
+    /// tests and writes depth as it draws, how everything is drawn without the pre-pass
+    Full,
+    /// only writes depth, without a fragment shader or anything to draw color to
+    Prepass,
+    /// only draws what the pre-pass left in front, the depth buffer is already as it should be
+    AfterPrepass,
+}
+
+impl DepthStage {
+    /// What the scene's color pass is drawn with, depending on whether the pre-pass ran before it
+    fn color_pass(prepass: bool) -> Self {
+        match prepass {
+            true => DepthStage::AfterPrepass,
+            false => DepthStage::Full,
+        }
+    }
+
+    fn depth_stencil(self, mode: DepthMode) -> wgpu::DepthStencilState {
+        let (depth_write_enabled, depth_compare) = match self {
+            DepthStage::Full | DepthStage::Prepass => (true, mode.compare()),
+            // the same vertices give the same depth, only what won the pre-pass is equal to it
+            DepthStage::AfterPrepass => (false, wgpu::CompareFunction::Equal),
+        };
+
+        wgpu::DepthStencilState {
+            format: mode.format(),
+            depth_write_enabled,
+            depth_compare,
+            stencil: wgpu::StencilState::default(),
+            bias: wgpu::DepthBiasState::default(),
+        }
+    }
+}
+
+impl CullMode {
+    fn face(self) -> Option<wgpu::Face> {
+        match self {
+            CullMode::Back => Some(wgpu::Face::Back),
+            CullMode::Front => Some(wgpu::Face::Front),
+            CullMode::None => None,
+        }
+    }
+}
+
+/// How the scene's triangles get rasterized, counter-clockwise ones face the camera
+fn primitive_state(cull: CullMode) -> wgpu::PrimitiveState {
+    wgpu::PrimitiveState {
+        topology: wgpu::PrimitiveTopology::TriangleList,
+        strip_index_format: None,
+        front_face: wgpu::FrontFace::Ccw,
+        cull_mode: cull.face(),
+        // Setting this to anything other than Fill requires Features::NON_FILL_POLYGON_MODE
+        polygon_mode: wgpu::PolygonMode::Fill,
+        // Requires Features::DEPTH_CLIP_CONTROL
+        unclipped_depth: false,
+        // Requires Features::CONSERVATIVE_RASTERIZATION
+        conservative: false,
+    }
+}
+
+#[expect(clippy::too_many_arguments, reason = "they're all part of the pipeline's description")]
 fn create_render_pipeline(
     device: &Device,
     layout: &wgpu::PipelineLayout,
+    vertex_entry_point: &str,
     color_format: TextureFormat,
-    depth_format: Option<TextureFormat>,
+    depth: Option<(DepthMode, DepthStage)>,
+    cull: CullMode,
     vertex_layouts: &[VertexBufferLayout],
     shader: wgpu::ShaderModuleDescriptor,
 ) -> wgpu::RenderPipeline {
     let shader = device.create_shader_module(shader);
+    let color_targets = [Some(wgpu::ColorTargetState {
+        format: color_format,
+        blend: Some(wgpu::BlendState {
+            alpha: wgpu::BlendComponent::REPLACE,
+            color: wgpu::BlendComponent::REPLACE,
+        }),
+        write_mask: wgpu::ColorWrites::ALL,
+    })];
 
     device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
         label: Some("Render Pipeline"),
         layout: Some(layout),
         vertex: wgpu::VertexState {
             module: &shader,
-            entry_point: Some("vs_main"),
+            entry_point: Some(vertex_entry_point),
             buffers: vertex_layouts,
             compilation_options: Default::default(),
         },
-        fragment: Some(wgpu::FragmentState {
+        // the pre-pass only writes depth, there's no color to shade
+        fragment: (!matches!(depth, Some((_, DepthStage::Prepass)))).then(|| wgpu::FragmentState {
             module: &shader,
             entry_point: Some("fs_main"),
-            targets: &[Some(wgpu::ColorTargetState {
-                format: color_format,
-                blend: Some(wgpu::BlendState {
-                    alpha: wgpu::BlendComponent::REPLACE,
-                    color: wgpu::BlendComponent::REPLACE,
-                }),
-                write_mask: wgpu::ColorWrites::ALL,
-            })],
+            targets: &color_targets,
             compilation_options: Default::default(),
         }),
-        primitive: wgpu::PrimitiveState {
-            topology: wgpu::PrimitiveTopology::TriangleList,
-            strip_index_format: None,
-            front_face: wgpu::FrontFace::Ccw,
-            cull_mode: Some(wgpu::Face::Back),
-            // Setting this to anything other than Fill requires Features::NON_FILL_POLYGON_MODE
-            polygon_mode: wgpu::PolygonMode::Fill,
-            // Requires Features::DEPTH_CLIP_CONTROL
-            unclipped_depth: false,
-            // Requires Features::CONSERVATIVE_RASTERIZATION
-            conservative: false,
-        },
-        depth_stencil: depth_format.map(|format| wgpu::DepthStencilState {
-            format,
-            depth_write_enabled: true,
-            depth_compare: wgpu::CompareFunction::Less,
-            stencil: wgpu::StencilState::default(),
-            bias: wgpu::DepthBiasState::default(),
-        }),
+        primitive: primitive_state(cull),
+        depth_stencil: depth.map(|(mode, stage)| stage.depth_stencil(mode)),
         multisample: wgpu::MultisampleState {
             count: 1,
             mask: !0,
@@ -233,6 +774,7 @@ fn create_render_pipeline(
 
 
 impl Renderer {
+    #[tracing::instrument(name = "Renderer::new", skip_all)]
     pub async fn new(window: Arc<Window>, settings: GameSettingsHandle) -> Renderer {
         let instance = WGPUInstance::new(&InstanceDescriptor::from_env_or_default());
 
@@ -261,50 +803,188 @@ impl Renderer {
 
         let surface_caps = surface.get_capabilities(&adapter);
 
-        let surface_format = surface_caps.formats.iter()
-            .find(|&&f| f.is_srgb())
-            .copied()
-            .unwrap_or(surface_caps.formats[0]);
-
+        let loaded_settings = settings.load();
+        let surface_format = surface_format(&surface_caps.formats, loaded_settings.hdr);
+        tracing::info!("drawing to a {surface_format:?} surface");
 
         let size = window.inner_size();
+        let scale_factor = window.scale_factor();
+        let config = Self::make_config_with_settings(&loaded_settings, size, surface_format);
+        let assets = crate::assets::source(&loaded_settings);
+        drop(loaded_settings);
+        surface.configure(&device, &config);
+
+        Renderer {
+            window_settings: settings.snapshot(),
+            scene: SceneRenderer::new(device, queue, settings.clone(), &*assets, &config),
+            assets,
+            settings,
+            window,
+            size,
+            scale_factor,
+            visibility: Visibility::default(),
+            surface,
+            surface_format,
+        }
+    }
+
+    pub fn window(&self) -> &Window {
+        &self.window
+    }
+
+    pub fn settings(&self) -> &GameSettingsHandle {
+        &self.settings
+    }
+
+    /// Brings the window in line with the settings, if they changed since the last call
+    pub fn apply_window_settings(&mut self) {
+        let current = self.settings.load();
+        if std::ptr::eq(&*self.window_settings, &*current) {
+            return;
+        }
+
+        let changes = WindowChanges::between(&self.window_settings, &current);
+        if changes.title {
+            self.window.set_title(&current.game_title);
+        }
+        if changes.icon {
+            self.window.set_window_icon(crate::settings::load_icon(&*self.assets, current.icon.as_deref()));
+        }
+
+        self.window_settings = current.into_arc();
+    }
+
+    fn make_config_with_settings(
+        settings: &GameSettings,
+        size: winit::dpi::PhysicalSize<u32>,
+        surface_format: TextureFormat
+    ) -> SurfaceConfiguration {
+        let surface_config = SurfaceConfiguration {
+            usage: TextureUsages::RENDER_ATTACHMENT,
+            format: surface_format,
+            view_formats: vec![surface_format.add_srgb_suffix()],
+            alpha_mode: CompositeAlphaMode::Auto,
+            width: size.width,
+            height: size.height,
+            desired_maximum_frame_latency: settings.max_frame_latency.clamp(1, 3),
+            present_mode: match settings.vsync {
+                Vsync::On => PresentMode::AutoVsync,
+                Vsync::Off => PresentMode::AutoNoVsync
+            },
+        };
+
+        tracing::info!("new surface {:#?}", surface_config);
+        surface_config
+    }
+    
+    pub fn reconfigure(&mut self) {
+        let settings = self.settings.load();
+        let config = Self::make_config_with_settings(&settings, self.size, self.surface_format);
+        self.surface.configure(&self.scene.device, &config);
+        self.scene.resize(&config);
+    }
+
+    pub fn resize(&mut self, new_size: winit::dpi::PhysicalSize<u32>) {
+        self.visibility.resized(new_size);
+        // a surface can't be configured with no area, keep the old one around until the window comes back
+        if self.visibility.minimized {
+            return;
+        }
+
+        self.size = new_size;
+        self.reconfigure();
+    }
+
+    pub fn set_occluded(&mut self, occluded: bool) {
+        self.visibility.set_occluded(occluded);
+    }
+
+    /// Whether the window can't be seen, nothing gets rendered until it can be again
+    pub fn is_paused(&self) -> bool {
+        self.visibility.is_paused()
+    }
+
+    /// Follows the window onto a monitor with a different scale, keeping its logical size the same
+    ///
+    /// returns the physical size the window should now have
+    pub fn rescale(&mut self, scale_factor: f64) -> winit::dpi::PhysicalSize<u32> {
+        let size = rescaled_size(self.size, self.scale_factor, scale_factor);
+        self.scale_factor = scale_factor;
+        self.resize(size);
+        size
+    }
+
+    /// Starts re-meshing the chunks that changed since the last call
+    pub fn update_world(&mut self, world: &mut World) {
+        self.scene.chunks.queue_rebuilds(world);
+    }
+
+    #[tracing::instrument(name = "Renderer::render", level = "trace", skip_all)]
+    /// Draws the frame, returning what went into it
+    pub fn render(&mut self, game: &GameState) -> FrameStats {
+        if self.is_paused() {
+            return FrameStats::default();
+        }
+
+        let frame_start = Instant::now();
+
+        let surface_texture = self
+            .surface
+            .get_current_texture()
+            .expect("failed to acquire next swap-chain texture");
+
+        let texture_view = surface_texture
+            .texture
+            .create_view(&TextureViewDescriptor {
+                // Without add_srgb_suffix() the image we will be working with
+                // might not be "gamma correct".
+                format: Some(self.surface_format.add_srgb_suffix()),
+                ..Default::default()
+            });
 
+        let mut stats = self.scene.draw(game, &texture_view);
+
+        self.window.pre_present_notify();
+        surface_texture.present();
+        stats.cpu_time = frame_start.elapsed();
+        tracing::trace!(
+            elapsed = ?stats.cpu_time,
+            draw_calls = stats.draw_calls,
+            prepass_draw_calls = stats.prepass_draw_calls,
+            instances = stats.instances_drawn,
+            triangles = stats.triangles,
+            "frame rendered"
+        );
+        stats
+    }
+}
+
+impl SceneRenderer {
+    /// Sets up drawing into textures configured like `config`, with the models read out of `assets`
+    fn new(
+        device: Device,
+        queue: Queue,
+        settings: GameSettingsHandle,
+        assets: &dyn AssetSource,
+        config: &SurfaceConfiguration,
+    ) -> Self {
         let loaded_settings = settings.load();
+        let depth_mode = loaded_settings.depth_mode;
+        let depth_prepass = loaded_settings.depth_prepass;
+        let cull_mode = loaded_settings.cull_mode;
         let projection = Projection::new(
-            size.width,
-            size.height,
-            loaded_settings.fov
+            config.width,
+            config.height,
+            loaded_settings.fov,
+            depth_mode
         );
-        let config = Self::make_config_with_settings(&loaded_settings, size, surface_format);
+        let texture_filter = loaded_settings.texture_filter;
+        let anti_aliasing = loaded_settings.anti_aliasing;
         drop(loaded_settings);
-        surface.configure(&device, &config);
-        
-        let depth_texture = Texture::create_depth_texture(&device, &config, "depth texture");
+
+        let depth_texture = Texture::create_depth_texture(&device, config, depth_mode, "depth texture");
         
-        let texture_bind_group_layout =
-            device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
-                entries: &[
-                    wgpu::BindGroupLayoutEntry {
-                        binding: 0,
-                        visibility: wgpu::ShaderStages::FRAGMENT,
-                        ty: wgpu::BindingType::Texture {
-                            multisampled: false,
-                            view_dimension: wgpu::TextureViewDimension::D2,
-                            sample_type: wgpu::TextureSampleType::Float { filterable: true },
-                        },
-                        count: None,
-                    },
-                    wgpu::BindGroupLayoutEntry {
-                        binding: 1,
-                        visibility: wgpu::ShaderStages::FRAGMENT,
-                        // This should match the filterable field of the
-                        // corresponding Texture entry above.
-                        ty: wgpu::BindingType::Sampler(wgpu::SamplerBindingType::Filtering),
-                        count: None,
-                    },
-                ],
-                label: Some("texture_bind_group_layout"),
-            });
+        let texture_bind_group_layout = texture_bind_group_layout(&device, texture_filter);
         
         let camera_uniform = CameraUniform {
             view_position: PaddedVec3::from(Vec3::ZERO),
@@ -317,21 +997,7 @@ impl Renderer {
             Some("camera buffer")
         );
 
-        let camera_bind_group_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
-            entries: &[
-                wgpu::BindGroupLayoutEntry {
-                    binding: 0,
-                    visibility: wgpu::ShaderStages::VERTEX_FRAGMENT,
-                    ty: wgpu::BindingType::Buffer {
-                        ty: wgpu::BufferBindingType::Uniform,
-                        has_dynamic_offset: false,
-                        min_binding_size: NonZero::new(buffer_size_of!(CameraUniform)),
-                    },
-                    count: None,
-                }
-            ],
-            label: Some("camera bind group layout"),
-        });
+        let camera_bind_group_layout = camera_bind_group_layout(&device);
 
         let camera_bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
             layout: &camera_bind_group_layout,
@@ -344,12 +1010,13 @@ impl Renderer {
             label: Some("camera_bind_group"),
         });
 
-        let light_uniform = LightUniform {
-            position: vec3a(2.0, 2.0, 2.0).into(),
-            color: vec3a(1.0, 1.0, 1.0).into(),
-        };
+        let light_uniform = LightUniform::new(
+            &Light::Point { position: Vec3::splat(2.0), range: 100.0, color: Vec3::ONE, intensity: 1.0 },
+            Vec3::ZERO,
+            SUN_DISTANCE
+        );
 
-        // We'll want to update our lights position, so we use COPY_DST
+        // the light follows the sun, so it gets rewritten every frame
         let light_buffer = Buffer::with_init(
             &device,
             &[light_uniform],
@@ -358,55 +1025,92 @@ impl Renderer {
         );
 
 
-        let light_bind_group_layout =
-            device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
-                entries: &[wgpu::BindGroupLayoutEntry {
-                    binding: 0,
-                    visibility: wgpu::ShaderStages::VERTEX | wgpu::ShaderStages::FRAGMENT,
-                    ty: wgpu::BindingType::Buffer {
-                        ty: wgpu::BufferBindingType::Uniform,
-                        has_dynamic_offset: false,
-                        min_binding_size: None,
-                    },
-                    count: None,
-                }],
-                label: None,
-            });
+        let fog_buffer = Buffer::with_init(
+            &device,
+            &[FogUniform::new(&FogSettings::default(), Vec3::ZERO, Projection::DEFAULT_FAR)],
+            BufferUsages::UNIFORM | BufferUsages::COPY_DST,
+            Some("Fog buffer")
+        );
+
+        let hdr = is_hdr(config.format);
+        let display_buffer = Buffer::with_init(
+            &device,
+            &[DisplayUniform::new(1.0, hdr)],
+            BufferUsages::UNIFORM | BufferUsages::COPY_DST,
+            Some("Display buffer")
+        );
+
+        let light_bind_group_layout = light_bind_group_layout(&device);
 
         let light_bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
             layout: &light_bind_group_layout,
-            entries: &[wgpu::BindGroupEntry {
-                binding: 0,
-                resource: light_buffer.as_entire_binding(),
-            }],
+            entries: &[
+                wgpu::BindGroupEntry {
+                    binding: 0,
+                    resource: light_buffer.as_entire_binding(),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 1,
+                    resource: fog_buffer.as_entire_binding(),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 2,
+                    resource: display_buffer.as_entire_binding(),
+                },
+            ],
             label: None,
         });
 
+        let draw_data_mode = DrawDataMode::select(device.features(), &device.limits());
+        let draw_data = DrawDataBinding::new(&device, draw_data_mode);
+        tracing::info!("passing draw data with {:?}", draw_data.mode());
+
+        let mut bind_group_layouts = vec![
+            &texture_bind_group_layout,
+            &camera_bind_group_layout,
+            &light_bind_group_layout,
+        ];
+        bind_group_layouts.extend(draw_data.bind_group_layout());
+
         let render_pipeline_layout =
             device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
                 label: Some("Render Pipeline Layout"),
-                bind_group_layouts: &[
-                    &texture_bind_group_layout,
-                    &camera_bind_group_layout,
-                    &light_bind_group_layout,
-                ],
-                push_constant_ranges: &[],
+                bind_group_layouts: &bind_group_layouts,
+                push_constant_ranges: draw_data.push_constant_ranges(),
             });
 
 
-        let render_pipeline = {
-            let shader = wgpu::include_wgsl!("./shaders/main_shader.wgsl");
+        let main_pipeline = |stage| {
+            let shader = draw_data_mode.shader(
+                "main_shader.wgsl",
+                MAIN_SHADER,
+                DRAW_DATA_GROUP
+            );
 
             create_render_pipeline(
                 &device,
                 &render_pipeline_layout,
+                "vs_main",
                 config.format,
-                Some(Texture::DEPTH_FORMAT),
+                Some((depth_mode, stage)),
+                cull_mode,
                 &[ModelVertex::DESC, InstanceRaw::DESC],
                 shader,
             )
         };
 
+        let render_pipeline = main_pipeline(DepthStage::color_pass(depth_prepass));
+        let prepass = depth_prepass.then(|| main_pipeline(DepthStage::Prepass));
+        // the held item is drawn over a fresh depth buffer, it never has a pre-pass to test against
+        let single_render_pipeline = single_render_pipeline(
+            &device,
+            &render_pipeline_layout,
+            draw_data_mode,
+            config.format,
+            depth_mode,
+            cull_mode
+        );
+
         let light_render_pipeline = {
             let layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
                 label: Some("Light Pipeline Layout"),
@@ -417,8 +1121,11 @@ impl Renderer {
             create_render_pipeline(
                 &device,
                 &layout,
+                "vs_main",
                 config.format,
-                Some(Texture::DEPTH_FORMAT),
+                Some((depth_mode, DepthStage::Full)),
+                // the sun is only ever seen from the outside
+                CullMode::Back,
                 &[ModelVertex::DESC],
                 shader,
             )
@@ -429,96 +1136,104 @@ impl Renderer {
         const STAGING_BELT_SIZE: BufferAddress = 64 * 1024 * 1024; // 64 Mib
 
 
-        const NUM_INSTANCES_PER_ROW: u32 = 10;
-
-        const SPACE_BETWEEN: f32 = 3.0;
-        let instances = (0..NUM_INSTANCES_PER_ROW).flat_map(|z| {
-            (0..NUM_INSTANCES_PER_ROW).map(move |x| {
-                let x = SPACE_BETWEEN * (x as f32 - NUM_INSTANCES_PER_ROW as f32 / 2.0);
-                let z = SPACE_BETWEEN * (z as f32 - NUM_INSTANCES_PER_ROW as f32 / 2.0);
-
-                let position = vec3a(x, 0.0, z);
-
-                let rotation = if position.cmpeq(Vec3A::ZERO).all() {
-                    Quat::from_axis_angle(Vec3::Z, 0.0)
-                } else {
-                    Quat::from_axis_angle(position.normalize().into(), 45.0_f32.to_radians())
-                };
+        let instance_buffer = GrowableBuffer::new(
+            &device,
+            0,
+            BufferUsages::VERTEX,
+            Some("instance buffer")
+        );
 
-                Instance(Transform {
-                    position,
-                    rotation
-                })
-            })
-        }).collect::<Vec<_>>();
+        let particles = ParticleRenderer::new(
+            &device,
+            &camera_bind_group_layout,
+            config.format,
+            depth_mode
+        );
 
+        let fxaa = match anti_aliasing {
+            AntiAliasing::Off => None,
+            AntiAliasing::Fxaa => Some(Fxaa::new(&device, config)),
+        };
 
-        let instance_buffer = Buffer::with_init(
+        let lines = LineRenderer::new(
             &device,
-            // TODO: get rid of collect and collect directly into buffer
-            &instances.iter().map(|instance: &Instance| instance.to_raw()).collect::<Vec<_>>(),
-            BufferUsages::VERTEX,
-            Some("instance buffer")
+            &camera_bind_group_layout,
+            config.format,
+            depth_mode
         );
 
-        let model = Model::load(
-            "./voxel-engine/assets/cube/cube.obj",
+        let chunks = ChunkMeshes::new(
             &device,
-            &queue,
-            &texture_bind_group_layout
-        ).unwrap();
+            &camera_bind_group_layout,
+            &light_bind_group_layout,
+            &draw_data,
+            config.format,
+            depth_mode,
+            depth_prepass,
+            cull_mode
+        );
+        let chunk_occlusion = OcclusionCuller::new(&device, &camera_bind_group_layout, depth_mode, CHUNK_QUERIES);
+
+        // one in the assets stands in for the built in cube
+        let cube = Path::new("cube/cube.obj");
+        let mut textures = TextureCache::new(&device, &queue, texture_filter);
+        let model = Model::load_from_source(assets, cube, &device, &queue, &texture_bind_group_layout, &mut textures)
+            .inspect_err(|err| tracing::info!("using the built in cube; {err:#}"))
+            .or_else(|_| Model::builtin_cube(&device, &queue, &texture_bind_group_layout, &mut textures))
+            .unwrap();
+
+        let (vertices, indices) = shapes::sphere(1.0, 8, 16);
+        // the light pipeline only uses the positions, the material is never looked at
+        let sun = Mesh::new(&device, "sun", &vertices, &indices, 0).unwrap();
         
-        Renderer {
+        SceneRenderer {
             settings,
-            window,
             device,
             queue,
-            size,
-            surface,
-            surface_format,
             render_pipeline,
+            prepass,
+            single_render_pipeline,
+            draw_data,
             light_render_pipeline,
             staging_belt: StagingBelt::new(STAGING_BELT_SIZE),
             projection,
+            last_simulation_time: Duration::ZERO,
             last_camera_uniform: camera_uniform,
             camera_buffer,
             camera_bind_group,
-            _light_buffer: light_buffer,
+            light_buffer,
+            fog_buffer,
+            display_buffer,
+            hdr,
             light_bind_group,
             depth_texture,
+            depth_mode,
             
+            textures,
             model,
+            sun,
+            instances: Vec::new(),
+            instance_cap: InstanceCap::new(MAX_INSTANCES),
             instance_buffer,
+            particles,
+            lines,
+            fxaa,
+            chunks,
+            chunk_occlusion,
         }
     }
 
-    pub fn window(&self) -> &Window {
-        &self.window
-    }
 
-    fn make_config_with_settings(
-        settings: &GameSettings,
-        size: winit::dpi::PhysicalSize<u32>,
-        surface_format: TextureFormat
-    ) -> SurfaceConfiguration {
-        let surface_config = SurfaceConfiguration {
-            usage: TextureUsages::RENDER_ATTACHMENT,
-            format: surface_format,
-            view_formats: vec![surface_format.add_srgb_suffix()],
-            alpha_mode: CompositeAlphaMode::Auto,
-            width: size.width,
-            height: size.height,
-            desired_maximum_frame_latency: 2,
-            present_mode: match settings.vsync {
-                Vsync::On => PresentMode::AutoVsync,
-                Vsync::Off => PresentMode::AutoNoVsync
-            },
-        };
-
-        tracing::info!("new surface {:#?}", surface_config);
-        surface_config
+    /// Follows the texture being drawn into changing size
+    fn resize(&mut self, config: &SurfaceConfiguration) {
+        self.depth_texture = Texture::create_depth_texture(&self.device, config, self.depth_mode, "depth texture");
+        if let Some(fxaa) = &mut self.fxaa {
+            fxaa.resize(&self.device, config);
+        }
+        self.projection.resize(config.width, config.height);
+        self.projection.set_target_fov(self.settings.load().fov);
     }
-    
+
     fn render_camera(&mut self, camera: Camera, encoder: &mut CommandEncoder) {
         let new_uniform = CameraUniform::new(
             &camera,
@@ -536,83 +1251,889 @@ impl Renderer {
         }
     }
     
-    pub fn reconfigure(&mut self) {
-        let settings = self.settings.load();
-        let config = Self::make_config_with_settings(&settings, self.size, self.surface_format);
-        self.surface.configure(&self.device, &config);
-        self.depth_texture = Texture::create_depth_texture(&self.device, &config, "depth texture");
-        self.projection.resize(self.size.width, self.size.height);
-        self.projection.change_fov(settings.fov);
-    }
-
-    pub fn resize(&mut self, new_size: winit::dpi::PhysicalSize<u32>) {
-        self.size = new_size;
-        self.reconfigure();
-    }
-
-    pub fn render(&mut self, game: &GameState) {
-        let surface_texture = self
-            .surface
-            .get_current_texture()
-            .expect("failed to acquire next swap-chain texture");
-
-        let texture_view = surface_texture
-            .texture
-            .create_view(&TextureViewDescriptor {
-                // Without add_srgb_suffix() the image we will be working with
-                // might not be "gamma correct".
-                format: Some(self.surface_format.add_srgb_suffix()),
-                ..Default::default()
-            });
-
-        
-        let camera = Camera::new(game.player());
-        
+    /// Draws the frame into `target` and submits it, returning what went into it
+    fn draw(&mut self, game: &GameState, target: &wgpu::TextureView) -> FrameStats {
+        // eases towards the fov in the settings, as fast as the simulation moves
+        self.projection.set_target_fov(self.settings.load().fov);
+        let dt = game.simulation_time().saturating_sub(self.last_simulation_time);
+        self.last_simulation_time = game.simulation_time();
+        self.projection.update(dt);
+
+        let camera = player_camera(game);
+        let eye = camera.eye();
+        let view = camera.calc_matrix();
+        // chunks outside the frustum or past the far plane aren't drawn at all
+        let mut visible = chunks_in_view(&camera, &self.projection);
         let mut encoder = self.device.create_command_encoder(&Default::default());       
         self.render_camera(camera, &mut encoder);
+
+        let time = game.day_cycle().time();
+        let light = LightUniform::new(&game.light(), eye, SUN_DISTANCE);
+        self.light_buffer.write(&mut self.staging_belt, &mut encoder, &self.device, &[light]);
+        let sky = time.sky_color();
+        // fog blends into the sky so far away chunks fade out instead of popping in
+        let fog = FogUniform::new(&self.settings.load().fog, sky, self.projection.far());
+        self.fog_buffer.write(&mut self.staging_belt, &mut encoder, &self.device, &[fog]);
+        let display = DisplayUniform::new(self.settings.load().exposure, self.hdr);
+        self.display_buffer.write(&mut self.staging_belt, &mut encoder, &self.device, &[display]);
+        let sky = sky.as_dvec3();
+
+        collect_instances(game, &mut self.instances);
+        self.instance_cap.apply(&mut self.instances, Instant::now());
+        self.instance_buffer.write(
+            &mut self.staging_belt,
+            &mut encoder,
+            &self.device,
+            &self.instances
+        );
+        self.particles.prepare(&mut self.staging_belt, &mut encoder, &self.device, game);
+        if let Some((block, _)) = game.target_block() {
+            let (min, max) = lines::block_outline(block);
+            self.lines.push_box(min, max, Vec4::from_array(self.settings.load().outline_color));
+        }
+        self.lines.prepare(&mut self.staging_belt, &mut encoder, &self.device);
+        self.chunks.upload_finished(&self.device);
+        // every chunk in view is queried again, so a hidden one comes back as soon as it's uncovered
+        self.chunk_occlusion.begin_frame(&self.device);
+        self.chunk_occlusion.prepare(
+            &mut self.staging_belt,
+            &mut encoder,
+            &self.device,
+            visible.iter().filter(|coord| self.chunks.has_mesh(coord)).map(|&coord| (coord, chunk_bounds(coord)))
+        );
+        self.chunk_occlusion.retain_visible(&mut visible);
+        self.model.animate(&mut self.staging_belt, &mut encoder, &self.device, game.simulation_time());
+        // props are positioned in world space already, each chunk is moved to where it is
+        self.draw_data.clear();
+        let props = self.draw_data.push(DrawData::IDENTITY);
+        let chunk_draws = self.chunks.prepare_draws(&visible, &mut self.draw_data);
+        let held_item = game
+            .held_item()
+            .filter(|kind| kind.is_rendered())
+            .map(|_| self.draw_data.push(DrawData::from_model(held_item_model(view))));
+        self.draw_data.upload(&mut self.staging_belt, &mut encoder, &self.device);
         
+        // with anti-aliasing on the scene goes somewhere it can be read back from first
+        let scene_view = self.fxaa.as_ref().map_or(target, Fxaa::target);
+        let mut stats = FrameStats::default();
+        if let Some(prepass) = &self.prepass {
+            // only the depth of what's opaque and costly to shade, everything else is tested against it as usual
+            let mut render_pass = encoder.begin_render_pass(&RenderPassDescriptor {
+                label: Some("Depth pre-pass"),
+                color_attachments: &[],
+                depth_stencil_attachment: Some(wgpu::RenderPassDepthStencilAttachment {
+                    view: &self.depth_texture.view,
+                    depth_ops: Some(Operations {
+                        load: LoadOp::Clear(self.depth_mode.clear_value()),
+                        store: StoreOp::Store
+                    }),
+                    stencil_ops: None
+                }),
+                timestamp_writes: None,
+                occlusion_query_set: None,
+            });
+
+            render_pass.set_pipeline(prepass);
+            render_pass.set_bind_group(1, &self.camera_bind_group, &[]);
+            render_pass.set_bind_group(2, &self.light_bind_group, &[]);
+            self.draw_data.bind(&mut render_pass, DRAW_DATA_GROUP, props);
+            render_pass.set_vertex_buffer(1, self.instance_buffer.slice());
+            // the same things get drawn again in the color pass, they only count as pre-pass draws here
+            stats += render_pass.draw_obj_instanced(&self.model, 0..self.instance_buffer.len_u32()).in_prepass();
+            stats += self.chunks.draw_depth(
+                &mut render_pass,
+                &chunk_draws,
+                &self.draw_data,
+                &self.camera_bind_group,
+                &self.light_bind_group
+            ).in_prepass();
+        }
+
         {
             // we need the render pass to drop before we can move out of encoder
             let mut render_pass = encoder.begin_render_pass(&RenderPassDescriptor {
                 label: Some("Render pass"),
                 color_attachments: &[Some(RenderPassColorAttachment {
-                    view: &texture_view,
+                    view: scene_view,
                     resolve_target: None,
                     ops: Operations {
-                        load: LoadOp::Clear(Color::BLACK),
+                        load: LoadOp::Clear(Color { r: sky.x, g: sky.y, b: sky.z, a: 1.0 }),
                         store: StoreOp::Store,
                     },
                 })],
                 depth_stencil_attachment: Some(wgpu::RenderPassDepthStencilAttachment {
                     view: &self.depth_texture.view,
                     depth_ops: Some(Operations {
-                        load: LoadOp::Clear(1.0),
+                        load: match self.prepass {
+                            Some(_) => LoadOp::Load,
+                            None => LoadOp::Clear(self.depth_mode.clear_value()),
+                        },
                         store: StoreOp::Store
                     }),
                     stencil_ops: None
                 }),
                 timestamp_writes: None,
-                occlusion_query_set: None,
+                occlusion_query_set: Some(self.chunk_occlusion.query_set()),
             });
 
             render_pass.set_pipeline(&self.light_render_pipeline);
             render_pass.set_bind_group(0, &self.camera_bind_group, &[]);
             render_pass.set_bind_group(1, &self.light_bind_group, &[]);
-            render_pass.draw_light_instanced(&self.model, 0..1);
+            stats += render_pass.draw_light_instanced(&self.sun, 0..1);
             
             render_pass.set_pipeline(&self.render_pipeline);
             render_pass.set_bind_group(1, &self.camera_bind_group, &[]);
             render_pass.set_bind_group(2, &self.light_bind_group, &[]);
-            render_pass.set_vertex_buffer(1, self.instance_buffer.slice(..));
-            render_pass.draw_obj_instanced(&self.model, 0..self.instance_buffer.len_u32());
+            self.draw_data.bind(&mut render_pass, DRAW_DATA_GROUP, props);
+            render_pass.set_vertex_buffer(1, self.instance_buffer.slice());
+            stats += render_pass.draw_obj_instanced(&self.model, 0..self.instance_buffer.len_u32());
+
+            stats += self.chunks.draw(&mut render_pass, &chunk_draws, &self.draw_data, &self.camera_bind_group, &self.light_bind_group);
+            // after everything opaque, so the queries are tested against all of it
+            self.chunk_occlusion.draw_queries(&mut render_pass, &self.camera_bind_group);
+            stats += self.particles.draw(&mut render_pass, &self.camera_bind_group);
+            stats += self.lines.draw(&mut render_pass, &self.camera_bind_group);
         }
 
+        if let Some(held_item) = held_item {
+            // drawn over the world with a fresh depth buffer, so it never sinks into a wall the player stands against
+            let mut render_pass = encoder.begin_render_pass(&RenderPassDescriptor {
+                label: Some("Held item pass"),
+                color_attachments: &[Some(RenderPassColorAttachment {
+                    view: scene_view,
+                    resolve_target: None,
+                    ops: Operations { load: LoadOp::Load, store: StoreOp::Store },
+                })],
+                depth_stencil_attachment: Some(wgpu::RenderPassDepthStencilAttachment {
+                    view: &self.depth_texture.view,
+                    depth_ops: Some(Operations {
+                        load: LoadOp::Clear(self.depth_mode.clear_value()),
+                        store: StoreOp::Discard
+                    }),
+                    stencil_ops: None
+                }),
+                timestamp_writes: None,
+                occlusion_query_set: None,
+            });
+
+            render_pass.set_pipeline(&self.single_render_pipeline);
+            render_pass.set_bind_group(1, &self.camera_bind_group, &[]);
+            render_pass.set_bind_group(2, &self.light_bind_group, &[]);
+            self.draw_data.bind(&mut render_pass, DRAW_DATA_GROUP, held_item);
+            stats += render_pass.draw_obj_instanced(&self.model, 0..1);
+        }
+
+        if let Some(fxaa) = &self.fxaa {
+            stats += fxaa.draw(&mut encoder, target);
+        }
+        self.chunk_occlusion.resolve(&mut encoder);
+
         // Submit the command in the queue to execute
         self.staging_belt.finish();
         self.queue.submit(std::iter::once(encoder.finish()));
         self.staging_belt.recall();
-        
-        self.window.pre_present_notify();
-        surface_texture.present();
+        self.chunk_occlusion.after_submit();
+        stats
+    }
+}
+
+/// A device for tests that need to talk to wgpu,
+/// `None` when there is no adapter at all (even a software one) to run on
+#[cfg(test)]
+pub(crate) fn test_device() -> Option<(Device, Queue)> {
+    let instance = WGPUInstance::new(&InstanceDescriptor::from_env_or_default());
+    let adapter = voxel_runtime::block_on(instance.request_adapter(&RequestAdapterOptions {
+        power_preference: PowerPreference::LowPower,
+        compatible_surface: None,
+        force_fallback_adapter: false,
+    })).ok()?;
+
+    voxel_runtime::block_on(adapter.request_device(&DeviceDescriptor {
+        required_features: adapter.features(),
+        required_limits: adapter.limits(),
+        label: Some("test device"),
+        memory_hints: MemoryHints::default(),
+        trace: Trace::Off,
+    })).ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use voxel_maths::FixedPointVec3;
+    use crate::game_state::coords::AbsoluteCoord;
+    use crate::game_state::day_cycle::TimeOfDay;
+    use crate::settings::Fov;
+    use super::*;
+
+    #[test]
+    fn uploads_follow_game_state() {
+        let mut game = GameState::new();
+        let mut raw = Vec::new();
+
+        collect_instances(&game, &mut raw);
+        // everything but the player gets drawn
+        assert_eq!(raw.len(), game.entities().len() - 1);
+        let before = bytemuck::cast_slice::<InstanceRaw, u8>(&raw).to_vec();
+
+        let (first, _) = game.entities().iter().find(|(_, entity)| entity.kind.is_rendered()).unwrap();
+        let entity = game.entities_mut().get_mut(first).unwrap();
+        entity.teleport(entity.position + AbsoluteCoord::from_xyz_vec(FixedPointVec3::Y));
+        let moved = entity.transform();
+
+        collect_instances(&game, &mut raw);
+        assert_ne!(bytemuck::cast_slice::<InstanceRaw, u8>(&raw), before);
+        assert_eq!(bytemuck::bytes_of(&raw[0]), bytemuck::bytes_of(&Instance(moved, Vec4::ONE).to_raw()));
+
+        game.entities_mut().despawn(first);
+        collect_instances(&game, &mut raw);
+        assert_eq!(raw.len(), before.len() / size_of::<InstanceRaw>() - 1);
+    }
+
+    #[test]
+    fn instances_past_the_cap_get_left_out() {
+        let mut raw = Vec::new();
+        collect_instances(&GameState::new(), &mut raw);
+        let all = raw.clone();
+
+        let start = Instant::now();
+        let mut cap = InstanceCap::new(10);
+        cap.apply(&mut raw, start);
+        assert_eq!(raw.len(), 10);
+        assert_eq!((cap.last_warned, cap.frames_over), (Some(start), 0));
+        // the first ones are kept
+        assert_eq!(bytemuck::cast_slice::<InstanceRaw, u8>(&raw), bytemuck::cast_slice::<InstanceRaw, u8>(&all[..10]));
+
+        // going over again right after doesn't warn again, but it's kept count of
+        for frame in 1..=3 {
+            let mut raw = all.clone();
+            cap.apply(&mut raw, start + Duration::from_millis(16 * frame));
+            assert_eq!(raw.len(), 10);
+        }
+        assert_eq!((cap.last_warned, cap.frames_over), (Some(start), 3));
+
+        // until enough time has passed
+        let later = start + InstanceCap::WARN_EVERY;
+        cap.apply(&mut all.clone(), later);
+        assert_eq!((cap.last_warned, cap.frames_over), (Some(later), 0));
+
+        let mut raw = all.clone();
+        let mut uncapped = InstanceCap::new(MAX_INSTANCES);
+        uncapped.apply(&mut raw, start);
+        assert_eq!(raw.len(), all.len());
+        assert_eq!(uncapped.last_warned, None);
+    }
+
+    #[test]
+    fn normals_stay_perpendicular_under_scaling() {
+        let model = Mat4::from_scale_rotation_translation(
+            vec3(2.0, 1.0, 0.5),
+            glam::Quat::from_rotation_y(30_f32.to_radians()),
+            vec3(5.0, -3.0, 1.0)
+        );
+        let normal = normal_matrix(model);
+
+        // a scale of 2 along x squashes the normals' x by 2, the rotation carries over untouched
+        let scaled = normal_matrix(Mat4::from_scale(vec3(2.0, 1.0, 0.5)));
+        assert!(scaled.abs_diff_eq(Mat3::from_diagonal(vec3(0.5, 1.0, 2.0)), 1e-6), "{scaled}");
+        let rotation = Mat4::from_rotation_x(1.0);
+        assert!(normal_matrix(rotation).abs_diff_eq(Mat3::from_mat4(rotation), 1e-6));
+
+        // a slanted surface, its tangents stay perpendicular to the transformed normal,
+        // while just running the normal through the model matrix leaves it tilted
+        let (tangent, bitangent) = (vec3(1.0, -1.0, 0.0), Vec3::Z);
+        let surface_normal = tangent.cross(bitangent).normalize();
+        let [tangent, bitangent] = [tangent, bitangent].map(|direction| model.transform_vector3(direction));
+        let transformed = (normal * surface_normal).normalize();
+        assert!(transformed.dot(tangent).abs() < 1e-5 && transformed.dot(bitangent).abs() < 1e-5);
+        assert!(model.transform_vector3(surface_normal).normalize().dot(tangent).abs() > 0.1);
+
+        let raw = InstanceRaw::new(model, Vec4::ONE);
+        assert!({ raw.normal }.abs_diff_eq(normal, 1e-6));
+    }
+
+    #[test]
+    fn held_items_stay_put_on_screen() {
+        let expected = held_item_view_transform();
+        let projection = Projection::new(16, 9, Fov::default(), DepthMode::default()).calc_matrix();
+        let on_screen = |view: Mat4| {
+            let model = held_item_model(view);
+            let clip = projection * view * model * glam::Vec4::W;
+            (view * model, clip / clip.w)
+        };
+
+        let (view_space, first) = on_screen(Mat4::look_to_rh(Vec3::ZERO, Vec3::NEG_Z, Vec3::Y));
+        assert!(view_space.abs_diff_eq(expected, 1e-6), "{view_space}");
+        // right of and below the middle of the screen, and inside the depth range
+        assert!(first.x > 0.0 && first.y < 0.0 && (0.0..1.0).contains(&first.z), "{first}");
+
+        let cameras = [
+            (vec3(5.0, 2.0, -7.0), vec3(1.0, 0.3, 0.2), Vec3::Y),
+            (vec3(-100.0, 30.0, 64.0), vec3(-0.2, -0.9, 0.1), Vec3::Y),
+            // rolled over in free look
+            (Vec3::ZERO, Vec3::X, vec3(0.0, 0.6, 0.8)),
+        ];
+        for (eye, direction, up) in cameras {
+            let (view_space, clip) = on_screen(Mat4::look_to_rh(eye, direction.normalize(), up));
+            assert!(view_space.abs_diff_eq(expected, 1e-4), "{eye} {direction}: {view_space}");
+            assert!(clip.abs_diff_eq(first, 1e-4), "{eye} {direction}: {clip}");
+        }
+    }
+
+    #[test]
+    fn instance_layout_has_no_overlaps() {
+        let desc = InstanceRaw::DESC;
+        assert_eq!(desc.array_stride, size_of::<InstanceRaw>() as BufferAddress);
+
+        let mut ranges = desc.attributes.iter().map(|attribute| (attribute.offset, attribute.offset + attribute.format.size())).collect::<Vec<_>>();
+        ranges.sort();
+        assert_eq!(ranges.first().unwrap().0, 0);
+        for pair in ranges.windows(2) {
+            assert!(pair[0].1 <= pair[1].0, "{pair:?} overlap");
+        }
+        assert_eq!(ranges.last().unwrap().1, desc.array_stride);
+
+        // the model matrix keeps 5 to 8, and nothing clashes with the vertex attributes
+        let mut locations = ModelVertex::DESC.attributes.iter().chain(desc.attributes).map(|attribute| attribute.shader_location).collect::<Vec<_>>();
+        let count = locations.len();
+        locations.sort();
+        locations.dedup();
+        assert_eq!(locations.len(), count);
+        let at = |location| desc.attributes.iter().find(|attribute| attribute.shader_location == location).unwrap();
+        for (column, location) in (5..=8).enumerate() {
+            assert_eq!(at(location).offset, (std::mem::offset_of!(InstanceRaw, model) + column * 16) as BufferAddress);
+        }
+        assert_eq!(at(9).offset, std::mem::offset_of!(InstanceRaw, normal) as BufferAddress);
+        let tint = at(12);
+        assert_eq!((tint.offset, tint.format), (std::mem::offset_of!(InstanceRaw, tint) as BufferAddress, wgpu::VertexFormat::Float32x4));
+    }
+
+    #[test]
+    fn shaders_compile() {
+        let Some((device, _queue)) = test_device() else {
+            return;
+        };
+
+        let main_shader = MAIN_SHADER;
+        let mut shaders = vec![
+            DrawDataMode::Uniform.shader("main_shader.wgsl", main_shader, DRAW_DATA_GROUP),
+            wgpu::include_wgsl!("./shaders/light.wgsl"),
+            wgpu::include_wgsl!("./shaders/particle.wgsl"),
+            wgpu::include_wgsl!("./shaders/occlusion.wgsl"),
+            chunk_mesh::shader(DrawDataMode::Uniform),
+        ];
+        if device.features().contains(wgpu::Features::PUSH_CONSTANTS) {
+            shaders.push(DrawDataMode::PushConstants.shader("main_shader.wgsl", main_shader, DRAW_DATA_GROUP));
+            shaders.push(chunk_mesh::shader(DrawDataMode::PushConstants));
+        }
+
+        for shader in shaders {
+            let label = shader.label;
+            device.push_error_scope(wgpu::ErrorFilter::Validation);
+            let _module = device.create_shader_module(shader);
+            let error = voxel_runtime::block_on(device.pop_error_scope());
+            assert!(error.is_none(), "{label:?} failed to compile: {error:?}");
+        }
+    }
+
+    /// Draws the model `build` makes with the single draw pipeline into a small target, returning any validation error
+    fn draw_single(device: &Device, queue: &Queue, build: impl FnOnce(&BindGroupLayout) -> Model) -> Option<wgpu::Error> {
+        draw_many(device, queue, 0..1, build).0
+    }
+
+    /// Draws all the `instances` of the model on top of each other, without an instance buffer
+    fn draw_many(
+        device: &Device,
+        queue: &Queue,
+        instances: std::ops::Range<u32>,
+        build: impl FnOnce(&BindGroupLayout) -> Model
+    ) -> (Option<wgpu::Error>, FrameStats) {
+        const FORMAT: TextureFormat = TextureFormat::Rgba8UnormSrgb;
+
+        let texture_layout = texture_bind_group_layout(device, TextureFilter::Nearest);
+        let camera_layout = camera_bind_group_layout(device);
+        let light_layout = light_bind_group_layout(device);
+        let mut draw_data = DrawDataBinding::new(device, DrawDataMode::Uniform);
+        let slot = draw_data.push(DrawData::IDENTITY);
+        let mut layouts = vec![&texture_layout, &camera_layout, &light_layout];
+        layouts.extend(draw_data.bind_group_layout());
+        let layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: None,
+            bind_group_layouts: &layouts,
+            push_constant_ranges: draw_data.push_constant_ranges(),
+        });
+
+        device.push_error_scope(wgpu::ErrorFilter::Validation);
+        let pipeline = single_render_pipeline(device, &layout, DrawDataMode::Uniform, FORMAT, DepthMode::default(), CullMode::default());
+
+        let model = build(&texture_layout);
+
+        let camera = Buffer::with_init(device, &[CameraUniform { view_position: Vec3::ZERO.into(), view_proj: Mat4::IDENTITY }], BufferUsages::UNIFORM, None);
+        let light = Buffer::with_init(device, &[LightUniform::new(&TimeOfDay::NOON.sun(), Vec3::ZERO, SUN_DISTANCE)], BufferUsages::UNIFORM, None);
+        let fog = Buffer::with_init(device, &[FogUniform::new(&FogSettings::default(), Vec3::ZERO, 1.0)], BufferUsages::UNIFORM, None);
+        let display = Buffer::with_init(device, &[DisplayUniform::new(1.0, false)], BufferUsages::UNIFORM, None);
+        let camera_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+            layout: &camera_layout,
+            entries: &[wgpu::BindGroupEntry { binding: 0, resource: camera.as_entire_binding() }],
+            label: None,
+        });
+        let light_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+            layout: &light_layout,
+            entries: &[
+                wgpu::BindGroupEntry { binding: 0, resource: light.as_entire_binding() },
+                wgpu::BindGroupEntry { binding: 1, resource: fog.as_entire_binding() },
+                wgpu::BindGroupEntry { binding: 2, resource: display.as_entire_binding() },
+            ],
+            label: None,
+        });
+
+        let config = SurfaceConfiguration {
+            usage: TextureUsages::RENDER_ATTACHMENT,
+            format: FORMAT,
+            width: 4,
+            height: 4,
+            present_mode: PresentMode::Fifo,
+            desired_maximum_frame_latency: 2,
+            alpha_mode: CompositeAlphaMode::Auto,
+            view_formats: vec![],
+        };
+        let target = device.create_texture(&wgpu::TextureDescriptor {
+            label: None,
+            size: wgpu::Extent3d { width: 4, height: 4, depth_or_array_layers: 1 },
+            mip_level_count: 1,
+            sample_count: 1,
+            dimension: wgpu::TextureDimension::D2,
+            format: FORMAT,
+            usage: TextureUsages::RENDER_ATTACHMENT,
+            view_formats: &[],
+        });
+        let target = target.create_view(&TextureViewDescriptor::default());
+        let depth = Texture::create_depth_texture(device, &config, DepthMode::default(), "depth");
+
+        let mut encoder = device.create_command_encoder(&Default::default());
+        let mut staging_belt = StagingBelt::new(1024);
+        draw_data.upload(&mut staging_belt, &mut encoder, device);
+        staging_belt.finish();
+        let stats = {
+            let mut pass = encoder.begin_render_pass(&RenderPassDescriptor {
+                label: None,
+                color_attachments: &[Some(RenderPassColorAttachment {
+                    view: &target,
+                    resolve_target: None,
+                    ops: Operations { load: LoadOp::Clear(Color::BLACK), store: StoreOp::Store },
+                })],
+                depth_stencil_attachment: Some(wgpu::RenderPassDepthStencilAttachment {
+                    view: &depth.view,
+                    depth_ops: Some(Operations { load: LoadOp::Clear(DepthMode::default().clear_value()), store: StoreOp::Store }),
+                    stencil_ops: None,
+                }),
+                timestamp_writes: None,
+                occlusion_query_set: None,
+            });
+
+            // nothing is bound to vertex buffer slot 1
+            pass.set_pipeline(&pipeline);
+            pass.set_bind_group(1, &camera_group, &[]);
+            pass.set_bind_group(2, &light_group, &[]);
+            draw_data.bind(&mut pass, DRAW_DATA_GROUP, slot);
+            pass.draw_obj_instanced(&model, instances)
+        };
+        queue.submit([encoder.finish()]);
+        staging_belt.recall();
+
+        (voxel_runtime::block_on(device.pop_error_scope()), stats)
+    }
+
+    fn white(device: &Device, layout: &BindGroupLayout, cache: &TextureCache) -> model::Material {
+        model::Material::solid_color(device, layout, cache, "white", model::MaterialUniform::WHITE.color)
+    }
+
+    #[test]
+    fn pipelines_cull_the_configured_faces() {
+        let modes = [
+            (CullMode::Back, Some(wgpu::Face::Back)),
+            (CullMode::Front, Some(wgpu::Face::Front)),
+            (CullMode::None, None),
+        ];
+        for (mode, face) in modes {
+            let primitive = primitive_state(mode);
+            assert_eq!(primitive.cull_mode, face);
+            // only which side gets culled changes
+            assert_eq!(primitive, wgpu::PrimitiveState { cull_mode: face, ..primitive_state(CullMode::Back) });
+        }
+
+        let Some((device, _queue)) = test_device() else {
+            return;
+        };
+
+        let camera_layout = camera_bind_group_layout(&device);
+        let light_layout = light_bind_group_layout(&device);
+        let layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: None,
+            bind_group_layouts: &[&camera_layout, &light_layout],
+            push_constant_ranges: &[],
+        });
+
+        device.push_error_scope(wgpu::ErrorFilter::Validation);
+        for (mode, _) in modes {
+            create_render_pipeline(
+                &device,
+                &layout,
+                "vs_main",
+                TextureFormat::Rgba8UnormSrgb,
+                Some((DepthMode::default(), DepthStage::Full)),
+                mode,
+                &[ModelVertex::DESC],
+                wgpu::include_wgsl!("./shaders/light.wgsl"),
+            );
+        }
+        let error = voxel_runtime::block_on(device.pop_error_scope());
+        assert!(error.is_none(), "{error:?}");
+    }
+
+    #[test]
+    fn the_color_pass_only_draws_what_won_the_prepass() {
+        assert_eq!(DepthStage::color_pass(false), DepthStage::Full);
+        assert_eq!(DepthStage::color_pass(true), DepthStage::AfterPrepass);
+
+        for mode in [DepthMode::Standard, DepthMode::Reversed] {
+            let full = DepthStage::Full.depth_stencil(mode);
+            assert!(full.depth_write_enabled);
+            assert_eq!(full.depth_compare, mode.compare());
+            // the pre-pass fills the depth in the same way drawing without it would
+            assert_eq!(DepthStage::Prepass.depth_stencil(mode), full);
+
+            let after = DepthStage::AfterPrepass.depth_stencil(mode);
+            assert!(!after.depth_write_enabled);
+            assert_eq!(after.depth_compare, wgpu::CompareFunction::Equal);
+            assert_eq!(after.format, full.format);
+        }
+
+        let Some((device, _queue)) = test_device() else {
+            return;
+        };
+
+        let texture_layout = texture_bind_group_layout(&device, TextureFilter::Nearest);
+        let camera_layout = camera_bind_group_layout(&device);
+        let light_layout = light_bind_group_layout(&device);
+        let draw_data = DrawDataBinding::new(&device, DrawDataMode::Uniform);
+        let mut layouts = vec![&texture_layout, &camera_layout, &light_layout];
+        layouts.extend(draw_data.bind_group_layout());
+        let layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: None,
+            bind_group_layouts: &layouts,
+            push_constant_ranges: draw_data.push_constant_ranges(),
+        });
+
+        device.push_error_scope(wgpu::ErrorFilter::Validation);
+        for stage in [DepthStage::Full, DepthStage::Prepass, DepthStage::AfterPrepass] {
+            create_render_pipeline(
+                &device,
+                &layout,
+                "vs_main",
+                TextureFormat::Rgba8UnormSrgb,
+                Some((DepthMode::default(), stage)),
+                CullMode::default(),
+                &[ModelVertex::DESC, InstanceRaw::DESC],
+                DrawDataMode::Uniform.shader("main_shader.wgsl", MAIN_SHADER, DRAW_DATA_GROUP),
+            );
+        }
+
+        for prepass in [false, true] {
+            ChunkMeshes::new(
+                &device,
+                &camera_layout,
+                &light_layout,
+                &draw_data,
+                TextureFormat::Rgba8UnormSrgb,
+                DepthMode::default(),
+                prepass,
+                CullMode::default()
+            );
+        }
+        let error = voxel_runtime::block_on(device.pop_error_scope());
+        assert!(error.is_none(), "{error:?}");
+    }
+
+    /// Draws a frame of the default scene with `settings` into a small texture, returning the scene and what went into the frame
+    fn draw_default_scene(device: &Device, queue: &Queue, settings: GameSettings) -> (SceneRenderer, FrameStats) {
+        let size = winit::dpi::PhysicalSize::new(64, 64);
+        let config = Renderer::make_config_with_settings(&settings, size, TextureFormat::Rgba8UnormSrgb);
+        let target = device.create_texture(&wgpu::TextureDescriptor {
+            label: None,
+            size: wgpu::Extent3d { width: config.width, height: config.height, depth_or_array_layers: 1 },
+            mip_level_count: 1,
+            sample_count: 1,
+            dimension: wgpu::TextureDimension::D2,
+            format: config.format,
+            usage: TextureUsages::RENDER_ATTACHMENT,
+            view_formats: &[],
+        });
+
+        device.push_error_scope(wgpu::ErrorFilter::Validation);
+        let assets = crate::assets::source(&settings);
+        let settings = GameSettingsHandle::in_memory(settings);
+        let mut scene = SceneRenderer::new(device.clone(), queue.clone(), settings, &*assets, &config);
+        let stats = scene.draw(&GameState::new(), &target.create_view(&Default::default()));
+        let error = voxel_runtime::block_on(device.pop_error_scope());
+        assert!(error.is_none(), "{error:?}");
+        (scene, stats)
+    }
+
+    #[test]
+    fn frame_stats_count_the_default_scene() {
+        let mut raw = Vec::new();
+        collect_instances(&GameState::new(), &mut raw);
+        assert_eq!(raw.len(), 100);
+
+        let Some((device, queue)) = test_device() else {
+            return;
+        };
+
+        let settings = GameSettings { anti_aliasing: AntiAliasing::Off, depth_prepass: false, ..GameSettings::default() };
+        let (scene, stats) = draw_default_scene(&device, &queue, settings.clone());
+        let meshes = scene.model.meshes.iter().map(|mesh| mesh.index_buffer.len_u32() / 3).collect::<Vec<_>>();
+        let sun = scene.sun.index_buffer.len_u32() / 3;
+        // one draw call for each of the cube's meshes drawing every instance, and the sun
+        assert_eq!(stats.draw_calls, meshes.len() as u32 + 1);
+        assert_eq!(stats.instances_drawn, 100 * meshes.len() as u32 + 1);
+        assert_eq!(stats.triangles, 100 * meshes.iter().map(|&triangles| u64::from(triangles)).sum::<u64>() + u64::from(sun));
+        assert_eq!(stats.prepass_draw_calls, 0);
+        assert_eq!(stats.cpu_time, Duration::ZERO);
+
+        // anti-aliasing draws one big triangle over the screen
+        let fxaa = GameSettings { anti_aliasing: AntiAliasing::Fxaa, ..settings.clone() };
+        let (_, with_fxaa) = draw_default_scene(&device, &queue, fxaa);
+        assert_eq!(with_fxaa, [stats, FrameStats::draw(1, 0..1)].into_iter().sum());
+
+        // the pre-pass draws the props again, but they're only counted once
+        let prepass = GameSettings { depth_prepass: true, ..settings };
+        let (_, with_prepass) = draw_default_scene(&device, &queue, prepass);
+        assert_eq!(with_prepass, FrameStats { prepass_draw_calls: meshes.len() as u32, ..stats });
+
+        let total = [stats, FrameStats::draw(2, 0..5), FrameStats::draw(0, 0..1)].into_iter().sum::<FrameStats>();
+        assert_eq!(total.draw_calls, stats.draw_calls + 2);
+        assert_eq!(total.instances_drawn, stats.instances_drawn + 6);
+        assert_eq!(total.triangles, stats.triangles + 10);
+    }
+
+    #[test]
+    fn single_draws_need_no_instance_buffer() {
+        let Some((device, queue)) = test_device() else {
+            return;
+        };
+        let cache = TextureCache::new(&device, &queue, TextureFilter::Nearest);
+
+        let error = draw_single(&device, &queue, |layout| {
+            let vertex = |x, y| ModelVertex { position: vec3(x, y, 0.0), tex_coords: glam::Vec2::ZERO, normal: Vec3::Z };
+            let vertices = [vertex(0.0, 0.0), vertex(1.0, 0.0), vertex(0.0, 1.0)];
+            let mesh = model::Mesh::new(&device, "triangle", &vertices, &[0, 1, 2], 0).unwrap();
+            Model::from_meshes(&device, layout, &cache, vec![mesh], vec![white(&device, layout, &cache)])
+        });
+        assert!(error.is_none(), "{error:?}");
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn procedural_models_draw() {
+        let Some((device, queue)) = test_device() else {
+            return;
+        };
+        let cache = TextureCache::new(&device, &queue, TextureFilter::Nearest);
+
+        let quad = draw_single(&device, &queue, |layout| {
+            let model = Model::quad(&device, layout, &cache, glam::Vec2::splat(2.0), white(&device, layout, &cache));
+            assert_eq!((model.meshes.len(), model.meshes[0].index_buffer.len()), (1, 6));
+            model
+        });
+        assert!(quad.is_none(), "{quad:?}");
+
+        let cube = draw_single(&device, &queue, |layout| {
+            let model = Model::cube(&device, layout, &cache, 1.0, white(&device, layout, &cache));
+            assert_eq!((model.meshes[0].vertex_buffer.len(), model.meshes[0].index_buffer.len()), (24, 36));
+            model
+        });
+        assert!(cube.is_none(), "{cube:?}");
+    }
+
+    #[test]
+    fn surface_config_follows_the_settings() {
+        let size = winit::dpi::PhysicalSize::new(800, 600);
+        let latency = |max_frame_latency| {
+            let settings = GameSettings { max_frame_latency, ..GameSettings::default() };
+            Renderer::make_config_with_settings(&settings, size, TextureFormat::Bgra8UnormSrgb).desired_maximum_frame_latency
+        };
+
+        assert_eq!(latency(GameSettings::default().max_frame_latency), 2);
+        assert_eq!(latency(1), 1);
+        assert_eq!(latency(3), 3);
+        assert_eq!(latency(0), 1);
+        assert_eq!(latency(10), 3);
+
+        let settings = GameSettings { vsync: Vsync::Off, ..GameSettings::default() };
+        let config = Renderer::make_config_with_settings(&settings, size, TextureFormat::Bgra8UnormSrgb);
+        assert_eq!(config.present_mode, PresentMode::AutoNoVsync);
+        assert_eq!((config.width, config.height), (800, 600));
+    }
+
+    #[test]
+    fn surface_format_priority() {
+        use TextureFormat::*;
+
+        let hdr_display = [Bgra8Unorm, Bgra8UnormSrgb, Rgb10a2Unorm, Rgba16Float];
+        assert_eq!(surface_format(&hdr_display, true), Rgba16Float);
+        assert_eq!(surface_format(&hdr_display, false), Bgra8UnormSrgb);
+
+        // falls back to what it picked before there was a choice
+        let sdr_display = [Bgra8Unorm, Rgba8UnormSrgb, Bgra8UnormSrgb];
+        assert_eq!(surface_format(&sdr_display, true), Rgba8UnormSrgb);
+        assert_eq!(surface_format(&sdr_display, false), Rgba8UnormSrgb);
+        assert_eq!(surface_format(&[Rgb10a2Unorm, Bgra8Unorm], true), Rgb10a2Unorm);
+        assert_eq!(surface_format(&[Bgra8Unorm], false), Bgra8Unorm);
+    }
+
+    #[test]
+    fn display_uniform_follows_the_surface() {
+        assert_eq!(size_of::<DisplayUniform>(), 16);
+
+        assert!(is_hdr(TextureFormat::Rgba16Float));
+        assert!(!is_hdr(TextureFormat::Bgra8UnormSrgb));
+        assert!(!is_hdr(TextureFormat::Rgb10a2Unorm));
+
+        assert_eq!(DisplayUniform::new(1.5, true).hdr, 1);
+        assert_eq!(DisplayUniform::new(1.5, false).exposure, 1.5);
+        // the exposure can't flip colors around or poison them
+        assert_eq!(DisplayUniform::new(-1.0, false).exposure, 0.0);
+        assert_eq!(DisplayUniform::new(f32::NAN, false).exposure, 1.0);
+        assert_eq!(DisplayUniform::new(f32::INFINITY, false).exposure, 1.0);
+    }
+
+    #[test]
+    fn fog_uniform_packing() {
+        assert_eq!(size_of::<FogUniform>(), 32);
+
+        let sky = vec3(0.2, 0.4, 0.8);
+        let off = FogUniform::new(&FogSettings::default(), sky, 100.0);
+        assert_eq!(off.enabled, 0);
+        assert_eq!(off.color, [0.2, 0.4, 0.8]);
+
+        let on = FogSettings { enabled: true, start: 0.5, end: 0.9 };
+        let fog = FogUniform::new(&on, sky, 200.0);
+        assert_eq!(fog.enabled, 1);
+        assert_eq!((fog.start, fog.end), (100.0, 180.0));
+
+        // the fog can't end before it starts, or the shader would divide by zero
+        let backwards = FogSettings { enabled: true, start: 0.8, end: 0.2 };
+        let fog = FogUniform::new(&backwards, sky, 100.0);
+        assert!(fog.end > fog.start);
+        let fog = FogUniform::new(&FogSettings { start: 2.0, end: 3.0, ..on }, sky, 100.0);
+        assert_eq!(fog.start, 100.0);
+        assert!(fog.end > fog.start);
+    }
+
+    #[test]
+    fn light_uniform_packing() {
+        assert_eq!(size_of::<LightUniform>(), 64);
+        // where the shaders expect each field to start
+        let words = |uniform: &LightUniform| bytemuck::cast::<_, [u32; 16]>(*uniform);
+        let float = |value: f32| value.to_bits();
+
+        let eye = vec3(1.0, 2.0, 3.0);
+        let sun = LightUniform::new(
+            &Light::Directional { direction: Vec3::Y * 2.0, color: vec3(1.0, 0.5, 0.25), intensity: 2.0 },
+            eye,
+            10.0
+        );
+        let words_of_sun = words(&sun);
+        assert_eq!(words_of_sun[0..4], [float(1.0), float(12.0), float(3.0), LightUniform::DIRECTIONAL]);
+        assert_eq!(words_of_sun[4..7], [float(0.0), float(1.0), float(0.0)]);
+        assert_eq!(words_of_sun[8..12], [float(1.0), float(0.5), float(0.25), float(2.0)]);
+
+        let point = LightUniform::new(
+            &Light::Point { position: vec3(4.0, 5.0, 6.0), range: 16.0, color: Vec3::ONE, intensity: 0.5 },
+            eye,
+            10.0
+        );
+        let words_of_point = words(&point);
+        assert_eq!(words_of_point[0..4], [float(4.0), float(5.0), float(6.0), LightUniform::POINT]);
+        assert_eq!(words_of_point[7], float(16.0));
+        assert_eq!(words_of_point[8..12], [float(1.0), float(1.0), float(1.0), float(0.5)]);
+
+        let cone = 30_f32.to_radians();
+        let spot = LightUniform::new(
+            &Light::Spot { position: Vec3::ZERO, direction: Vec3::NEG_Z * 3.0, range: 8.0, cone, color: Vec3::X, intensity: 1.0 },
+            eye,
+            10.0
+        );
+        let words_of_spot = words(&spot);
+        assert_eq!(words_of_spot[3], LightUniform::SPOT);
+        assert_eq!(words_of_spot[4..8], [float(0.0), float(0.0), float(-1.0), float(8.0)]);
+        assert_eq!(words_of_spot[12..14], [float((cone * LightUniform::SPOT_SOFTNESS).cos()), float(cone.cos())]);
+        // it fades out towards the edge of the cone, not past it
+        assert!(spot.cos_inner > spot.cos_outer);
+        assert_eq!(words_of_spot[14..], [0, 0]);
+    }
+
+    #[test]
+    fn visible_chunks_respect_the_far_plane() {
+        let game = GameState::new();
+        let camera = Camera::interpolated(game.player(), vec3(8.0, 64.0, 8.0));
+        let mut projection = Projection::new(1600, 900, Fov::default(), DepthMode::default());
+
+        let within = |projection: &Projection, coord: &ChunkCoord| {
+            let far = projection.far();
+            let min = vec3(coord.chunk_x() as f32, 0.0, coord.chunk_z() as f32) * CHUNK_SIZE as f32;
+            let closest = camera.eye().clamp(min, min + CHUNK_SIZE as f32);
+            closest.xz().distance(camera.eye().xz()) <= far
+        };
+
+        let visible = chunks_in_view(&camera, &projection);
+        assert!(visible.contains(&ChunkCoord::ZERO));
+        assert!(visible.iter().all(|coord| within(&projection, coord)));
+        // the chunks behind the camera get culled by the frustum
+        let around = (-8..=8)
+            .flat_map(|z| (-8..=8).map(move |x| ChunkCoord::from_xz(x, z)))
+            .filter(|coord| within(&projection, coord))
+            .count();
+        assert!(visible.len() < around, "{} of {around}", visible.len());
+
+        projection.set_far(40.0);
+        let nearby = chunks_in_view(&camera, &projection);
+        assert!(nearby.len() < visible.len());
+        assert!(nearby.iter().all(|coord| within(&projection, coord) && visible.contains(coord)));
+    }
+
+    #[test]
+    fn rescaling_keeps_the_logical_size() {
+        use winit::dpi::PhysicalSize;
+
+        let size = PhysicalSize::new(800, 600);
+        assert_eq!(rescaled_size(size, 1.0, 2.0), PhysicalSize::new(1600, 1200));
+        assert_eq!(rescaled_size(size, 2.0, 1.0), PhysicalSize::new(400, 300));
+        assert_eq!(rescaled_size(size, 1.0, 1.5), PhysicalSize::new(1200, 900));
+        assert_eq!(rescaled_size(size, 1.25, 1.25), size);
+        assert_eq!(rescaled_size(PhysicalSize::new(1, 1), 2.0, 1.0), PhysicalSize::new(1, 1));
+    }
+
+    #[test]
+    fn minimizing_or_covering_pauses_rendering() {
+        use winit::dpi::PhysicalSize;
+
+        let mut visibility = Visibility::default();
+        assert!(!visibility.is_paused());
+
+        visibility.resized(PhysicalSize::new(0, 0));
+        assert!(visibility.is_paused());
+        visibility.resized(PhysicalSize::new(800, 0));
+        assert!(visibility.is_paused());
+        visibility.resized(PhysicalSize::new(800, 600));
+        assert!(!visibility.is_paused());
+
+        visibility.set_occluded(true);
+        assert!(visibility.is_paused());
+        // coming back from being minimized doesn't uncover the window
+        visibility.resized(PhysicalSize::new(0, 0));
+        visibility.resized(PhysicalSize::new(800, 600));
+        assert!(visibility.is_paused());
+        visibility.set_occluded(false);
+        assert!(!visibility.is_paused());
+    }
+}
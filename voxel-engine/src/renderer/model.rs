@@ -1,11 +1,19 @@
+use std::borrow::Cow;
+use std::io::BufRead;
 use std::ops::Range;
 use std::path::Path;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::Duration;
 use glam::{Vec2, Vec3};
-use wgpu::{BindGroupLayout, BufferUsages, Device, IndexFormat, Queue, RenderPass};
+use wgpu::{BindGroupLayout, BufferUsages, CommandEncoder, Device, IndexFormat, Queue, RenderPass};
+use wgpu::util::StagingBelt;
+use crate::renderer::animation::{AnimationUniform, TextureAnimation};
 use crate::renderer::buffer::Buffer;
-use crate::renderer::buffer_size_of;
-use crate::renderer::texture::Texture;
-use anyhow::{ensure, Context, Result};
+use crate::renderer::{buffer_size_of, shapes};
+use crate::renderer::stats::FrameStats;
+use crate::renderer::texture::{ColorSpace, Texture, TextureCache};
+use anyhow::{bail, ensure, Context, Result};
+use crate::assets::AssetSource;
 
 // model.rs
 pub trait VertexComponent {
@@ -28,8 +36,115 @@ impl VertexComponent for ModelVertex {
     };
 }
 
+/// The color a material's texture gets multiplied with
+#[derive(Debug, Copy, Clone, PartialEq, bytemuck::Pod, bytemuck::Zeroable)]
+#[repr(C)]
+pub struct MaterialUniform {
+    pub color: [f32; 4],
+}
+
+impl MaterialUniform {
+    /// leaves the texture as it is
+    pub const WHITE: Self = Self { color: [1.0; 4] };
+}
+
 pub struct Material {
     pub bind_group: wgpu::BindGroup,
+    /// the material's `map_Bump`, if it has one
+    #[cfg_attr(not(test), expect(dead_code, reason = "the shaders don't do normal mapping yet"))]
+    pub normal_texture: Option<Texture>,
+    pub animation: Option<TextureAnimation>,
+    animation_buffer: Buffer<AnimationUniform>,
+    /// the material's [`MaterialUniform`], [`Buffer`] destroys itself on drop so it has to live as long as the bind group
+    #[expect(dead_code, reason = "only the bind group reads it, it's kept so it lives as long as the material")]
+    color_buffer: Buffer<MaterialUniform>,
+    current_frame: u32,
+}
+
+impl Material {
+    fn new(
+        device: &Device,
+        layout: &BindGroupLayout,
+        name: &str,
+        diffuse_texture: &Texture,
+        normal_texture: Option<Texture>,
+        animation: Option<TextureAnimation>,
+        color: MaterialUniform,
+    ) -> Self {
+        let uniform = match animation {
+            Some(animation) => AnimationUniform::new(0, animation.frames().get()),
+            None => AnimationUniform::STATIC
+        };
+        let animation_buffer = Buffer::with_init(
+            device,
+            &[uniform],
+            BufferUsages::UNIFORM | BufferUsages::COPY_DST,
+            Some(&format!("{name} animation buffer"))
+        );
+        let color_buffer = Buffer::with_init(device, &[color], BufferUsages::UNIFORM, Some(&format!("{name} color buffer")));
+
+        let bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+            layout,
+            entries: &[
+                wgpu::BindGroupEntry {
+                    binding: 0,
+                    resource: wgpu::BindingResource::TextureView(&diffuse_texture.view),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 1,
+                    resource: wgpu::BindingResource::Sampler(&diffuse_texture.sampler),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 2,
+                    resource: animation_buffer.as_entire_binding(),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 3,
+                    resource: color_buffer.as_entire_binding(),
+                },
+            ],
+            label: Some(name),
+        });
+
+        Self {
+            bind_group,
+            normal_texture,
+            animation,
+            animation_buffer,
+            color_buffer,
+            current_frame: 0,
+        }
+    }
+
+    /// A material that is `color` all over, it needs no texture file
+    pub fn solid_color(device: &Device, layout: &BindGroupLayout, cache: &TextureCache, name: &str, color: [f32; 4]) -> Self {
+        Self::new(device, layout, name, cache.white(), None, None, MaterialUniform { color })
+    }
+
+    /// Stands in for a material a mesh doesn't have
+    pub fn fallback(device: &Device, layout: &BindGroupLayout, cache: &TextureCache) -> Self {
+        Self::new(device, layout, "missing material", cache.missing(), None, None, MaterialUniform::WHITE)
+    }
+
+    /// Moves the material's animation (if it has one) to the frame for `elapsed` simulation time
+    pub fn animate(
+        &mut self,
+        staging_belt: &mut StagingBelt,
+        encoder: &mut CommandEncoder,
+        device: &Device,
+        elapsed: Duration
+    ) {
+        let Some(animation) = self.animation else {
+            return;
+        };
+
+        let frame = animation.frame_at(elapsed);
+        if frame != self.current_frame {
+            self.current_frame = frame;
+            let uniform = AnimationUniform::new(frame, animation.frames().get());
+            self.animation_buffer.write(staging_belt, encoder, device, std::slice::from_ref(&uniform));
+        }
+    }
 }
 
 pub struct Mesh {
@@ -38,49 +153,109 @@ pub struct Mesh {
     pub material: usize,
 }
 
+impl Mesh {
+    /// Uploads `vertices` and `indices` (triangles, counter clockwise facing out) drawn with `material`
+    pub fn new(
+        device: &Device,
+        label: &str,
+        vertices: &[ModelVertex],
+        indices: &[u32],
+        material: usize
+    ) -> Result<Self> {
+        check_indices(indices, vertices.len())?;
+
+        Ok(Self {
+            vertex_buffer: Buffer::with_init(device, vertices, BufferUsages::VERTEX, Some(&format!("{label} vertex buffer"))),
+            index_buffer: Buffer::with_init(device, indices, BufferUsages::INDEX, Some(&format!("{label} index buffer"))),
+            material,
+        })
+    }
+}
+
 pub struct Model {
     pub meshes: Box<[Mesh]>,
     pub materials: Box<[Material]>,
+    /// drawn for meshes whose material isn't one of `materials`
+    pub fallback: Material,
+    /// whether drawing has run into a mesh without a material yet, so it only gets logged once
+    pub warned_missing_material: AtomicBool,
 }
 
+/// Makes sure every index points at one of the `vertex_count` vertices,
+/// the GPU would read past the end of the vertex buffer otherwise
+fn check_indices(indices: &[u32], vertex_count: usize) -> Result<()> {
+    if let Some((position, &index)) = indices.iter().enumerate().find(|&(_, &index)| index as usize >= vertex_count) {
+        bail!("index {index} at {position} is out of bounds for {vertex_count} vertices, malformed obj file")
+    }
+
+    Ok(())
+}
+
+/// The cube every entity gets drawn with, built into the game so it runs without any asset files
+const CUBE_OBJ: &[u8] = include_bytes!("../../assets/cube/cube.obj");
+const CUBE_MTL: &[u8] = include_bytes!("../../assets/cube/cube.mtl");
+const CUBE_TEXTURES: &[(&str, &[u8])] = &[
+    ("cube-diffuse.jpg", include_bytes!("../../assets/cube/cube-diffuse.jpg")),
+    ("cube-normal.png", include_bytes!("../../assets/cube/cube-normal.png")),
+];
+
 impl Model {
-    fn load_inner(file_name: &Path, device: &Device, queue: &Queue, layout: &BindGroupLayout) -> Result<Self> {
-        let (models, materials) = tobj::load_obj(file_name, &tobj::GPU_LOAD_OPTIONS)?;
-        let parent_file = file_name.parent();
-        
-        let materials = materials?.into_iter().map(|material| {
-            let texture_file = material.diffuse_texture.context("no texture file found in material")?;
-            
-            let owned_path;
-            let path = match parent_file {
-                None => file_name,
-                Some(parent) => {
-                    owned_path = parent.join(texture_file);
-                    &owned_path
-                }
+    /// Parses the obj file read out of `obj`, with the mtl files it refers to read by `load_file`
+    fn parse_obj<'a>(label: &str, obj: &mut impl BufRead, load_file: impl Fn(&Path) -> Result<Cow<'a, [u8]>>) -> tobj::LoadResult {
+        tobj::load_obj_buf(obj, &tobj::GPU_LOAD_OPTIONS, |mtl_file| {
+            let mtl = load_file(mtl_file).map_err(|err| {
+                tracing::warn!("unable to read {} for {label}; {err}", mtl_file.display());
+                tobj::LoadError::OpenFileFailed
+            })?;
+            tobj::load_mtl_buf(&mut &*mtl)
+        })
+    }
+
+    /// Builds the model out of a parsed obj file, whose textures come from `load_texture`
+    /// and their animations from `load_animation`, both get the file name the mtl file refers to them by
+    fn from_obj(
+        label: &str,
+        (models, materials): (Vec<tobj::Model>, Result<Vec<tobj::Material>, tobj::LoadError>),
+        device: &Device,
+        layout: &BindGroupLayout,
+        cache: &mut TextureCache,
+        mut load_texture: impl FnMut(&mut TextureCache, &str, ColorSpace) -> Result<Texture>,
+        load_animation: impl Fn(&str, (u32, u32)) -> Result<Option<TextureAnimation>>,
+    ) -> Result<Self> {
+        // a missing mtl file only costs the materials, the meshes still load
+        let materials = materials.unwrap_or_else(|err| {
+            tracing::warn!("unable to load the materials of {label}; {err}");
+            Vec::new()
+        });
+
+        // only meshes drawn with a texture need texture coordinates
+        let textured = materials.iter().map(|material| material.diffuse_texture.is_some()).collect::<Vec<_>>();
+
+        let materials = materials.into_iter().map(|material| {
+            let Some(texture_file) = material.diffuse_texture.as_deref() else {
+                let [r, g, b] = material.diffuse.unwrap_or([1.0; 3]);
+                let alpha = material.dissolve.unwrap_or(1.0);
+                return Ok(Material::solid_color(device, layout, cache, &material.name, [r, g, b, alpha]));
             };
-            
-            let diffuse_texture = Texture::from_file(device, queue, path)?;
-            
-            let bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+
+            let diffuse_texture = load_texture(cache, texture_file, ColorSpace::Srgb)?;
+            let normal_texture = material.normal_texture
+                .as_deref()
+                .map(|normal_file| load_texture(cache, normal_file, ColorSpace::Linear))
+                .transpose()?;
+            let size = diffuse_texture.size;
+            let animation = load_animation(texture_file, (size.width, size.height))?;
+
+            Ok(Material::new(
+                device,
                 layout,
-                entries: &[
-                    wgpu::BindGroupEntry {
-                        binding: 0,
-                        resource: wgpu::BindingResource::TextureView(&diffuse_texture.view),
-                    },
-                    wgpu::BindGroupEntry {
-                        binding: 1,
-                        resource: wgpu::BindingResource::Sampler(&diffuse_texture.sampler),
-                    },
-                ],
-                label: Some(&material.name),
-            });
-
-            Ok(Material {
-                bind_group,
-            })
-        }).collect::<Result<Box<[_]>>>()?;
+                &material.name,
+                &diffuse_texture,
+                normal_texture,
+                animation,
+                MaterialUniform::WHITE
+            ))
+        }).collect::<Result<Vec<_>>>()?;
 
         let meshes = models
             .into_iter()
@@ -97,8 +272,18 @@ impl Model {
                     .ok()
                     .context("invalid mesh normals decoded, form needs to be in [x, y, z]")?;
                 
+                let material = match model.mesh.material_id.filter(|&id| id < materials.len()) {
+                    Some(id) => id,
+                    None => {
+                        tracing::warn!("{label} has no material for mesh {}, using a fallback", model.name);
+                        // past the real materials, see `Model::material`
+                        materials.len()
+                    }
+                };
+
+                let textured = textured.get(material).copied().unwrap_or(false);
                 ensure!(
-                    tex_coords.len() == positions.len(),
+                    tex_coords.len() == positions.len() || (tex_coords.is_empty() && !textured),
                     "expected {vertex_count} texture coordinates found {texture_count}, malformed obj file",
                     vertex_count = positions.len(),
                     texture_count = tex_coords.len()
@@ -111,7 +296,9 @@ impl Model {
                     normal_count = normals.len()
                 );
                 
-                let iter = positions.iter().copied().zip(tex_coords.iter().copied());
+                // untextured meshes don't have to come with texture coordinates
+                let tex_coords = tex_coords.iter().copied().chain(std::iter::repeat(Vec2::ZERO));
+                let iter = positions.iter().copied().zip(tex_coords);
                 
                 let vertices = match normals.is_empty() {
                     true => iter.map(|(position, tex_coords)| ModelVertex {
@@ -124,79 +311,431 @@ impl Model {
                         tex_coords,
                         normal,
                     }).collect::<Vec<_>>()
-                }; 
+                };
 
-                let vertex_buffer = Buffer::with_init(
-                    device,
-                    &vertices,
-                    BufferUsages::VERTEX,
-                    Some(&format!("{:?} vertex buffer", file_name))
-                );
-                
-                let index_buffer = Buffer::with_init(
-                    device,
-                    &model.mesh.indices,
-                    BufferUsages::INDEX,
-                    Some(&format!("{:?} index buffer", file_name))
-                );
-
-                Ok(Mesh {
-                    vertex_buffer,
-                    index_buffer,
-                    material: model.mesh.material_id.context("no material found for model")?,
-                })
+                Mesh::new(device, &format!("{label:?}"), &vertices, &model.mesh.indices, material)
             })
-            .collect::<Result<Box<[_]>>>()?;
-        
-        Ok(Self { meshes, materials })
+            .collect::<Result<Vec<_>>>()?;
+
+        Ok(Self::from_meshes(device, layout, cache, meshes, materials))
     }
     
-    pub fn load<P: AsRef<Path>>(file_name: P, device: &Device, queue: &Queue, layout: &BindGroupLayout) -> Result<Self> {
-        Self::load_inner(file_name.as_ref(), device, queue, layout)
+    /// Loads the obj file read out of `obj`, getting the files it refers to from `load_file` by the path they are referred to with,
+    /// its mtl files and the textures in those, so they can come from an archive or memory just as well as off the disk.
+    /// A file that isn't there has to fail with [`std::io::ErrorKind::NotFound`] like an [`AssetSource`] does.
+    /// Its textures don't have a path in the assets, so they are loaded for this model alone instead of going into `cache`
+    pub fn load_from_reader<'a>(
+        label: &str,
+        obj: &mut impl BufRead,
+        load_file: impl Fn(&Path) -> Result<Cow<'a, [u8]>>,
+        device: &Device,
+        queue: &Queue,
+        layout: &BindGroupLayout,
+        cache: &mut TextureCache
+    ) -> Result<Self> {
+        Self::from_obj(
+            label,
+            Self::parse_obj(label, obj, &load_file)?,
+            device,
+            layout,
+            cache,
+            |cache, texture_file, color_space| {
+                let bytes = load_file(Path::new(texture_file))?;
+                Texture::from_bytes(device, queue, &bytes, texture_file, color_space, cache.filter())
+            },
+            |texture_file, dimensions| TextureAnimation::load_with(Path::new(texture_file), dimensions, &load_file),
+        )
+    }
+
+    /// Loads the obj file at `path` out of `source`, the files it refers to are relative to it like they would be on disk,
+    /// its textures are shared through `cache` with every other model using them
+    pub fn load_from_source(
+        source: &dyn AssetSource,
+        path: &Path,
+        device: &Device,
+        queue: &Queue,
+        layout: &BindGroupLayout,
+        cache: &mut TextureCache
+    ) -> Result<Self> {
+        let obj = source.read(path)?;
+        let directory = path.parent().unwrap_or(Path::new(""));
+        let load_file = |file: &Path| source.read(&directory.join(file)).map(Cow::Owned);
+        let label = path.display().to_string();
+
+        Self::from_obj(
+            &label,
+            Self::parse_obj(&label, &mut &*obj, load_file)?,
+            device,
+            layout,
+            cache,
+            |cache, texture_file, color_space| cache.load(device, queue, source, &directory.join(texture_file), color_space),
+            |texture_file, dimensions| TextureAnimation::load_with(Path::new(texture_file), dimensions, load_file),
+        )
+    }
+
+    /// Loads an obj file out of memory, every mtl file it refers to being `mtl` and `textures` the files those refer to by name
+    pub fn load_from_memory(
+        obj: &[u8],
+        mtl: &[u8],
+        textures: &[(&str, &[u8])],
+        device: &Device,
+        queue: &Queue,
+        layout: &BindGroupLayout,
+        cache: &mut TextureCache
+    ) -> Result<Self> {
+        let load_file = |path: &Path| {
+            if path.extension().is_some_and(|extension| extension == "mtl") {
+                return Ok(Cow::Borrowed(mtl));
+            }
+
+            textures
+                .iter()
+                .find(|&&(name, _)| Path::new(name) == path)
+                .map(|&(_, bytes)| Cow::Borrowed(bytes))
+                .ok_or_else(|| {
+                    let err = std::io::Error::new(std::io::ErrorKind::NotFound, format!("there is no file named {}", path.display()));
+                    anyhow::Error::from(err)
+                })
+        };
+
+        Self::load_from_reader("model in memory", &mut &*obj, load_file, device, queue, layout, cache)
+    }
+
+    /// The cube built into the game, for when there is none on disk
+    pub fn builtin_cube(
+        device: &Device,
+        queue: &Queue,
+        layout: &BindGroupLayout,
+        cache: &mut TextureCache
+    ) -> Result<Self> {
+        Self::load_from_memory(CUBE_OBJ, CUBE_MTL, CUBE_TEXTURES, device, queue, layout, cache)
+    }
+
+    /// A model out of meshes built some other way than loading a file,
+    /// the meshes' material indices point into `materials`
+    pub fn from_meshes(
+        device: &Device,
+        layout: &BindGroupLayout,
+        cache: &TextureCache,
+        meshes: Vec<Mesh>,
+        materials: Vec<Material>
+    ) -> Self {
+        Self {
+            meshes: meshes.into_boxed_slice(),
+            materials: materials.into_boxed_slice(),
+            fallback: Material::fallback(device, layout, cache),
+            warned_missing_material: AtomicBool::new(false),
+        }
+    }
+
+    /// A single [quad](shapes::quad) drawn with `material`
+    #[cfg_attr(not(test), expect(dead_code, reason = "nothing procedural gets drawn yet"))]
+    pub fn quad(device: &Device, layout: &BindGroupLayout, cache: &TextureCache, size: Vec2, material: Material) -> Self {
+        let (vertices, indices) = shapes::quad(size);
+        let mesh = Mesh::new(device, "quad", &vertices, &indices, 0).expect("the quad's indices are in bounds");
+        Self::from_meshes(device, layout, cache, vec![mesh], vec![material])
+    }
+
+    /// A single [cube](shapes::cube) drawn with `material`
+    #[cfg_attr(not(test), expect(dead_code, reason = "nothing procedural gets drawn yet"))]
+    pub fn cube(device: &Device, layout: &BindGroupLayout, cache: &TextureCache, size: f32, material: Material) -> Self {
+        let (vertices, indices) = shapes::cube(size);
+        let mesh = Mesh::new(device, "cube", &vertices, &indices, 0).expect("the cube's indices are in bounds");
+        Self::from_meshes(device, layout, cache, vec![mesh], vec![material])
+    }
+
+    /// The material `mesh` is drawn with, the fallback if it points past the end of the materials
+    pub fn material(&self, mesh: &Mesh) -> &Material {
+        self.materials.get(mesh.material).unwrap_or_else(|| {
+            if !self.warned_missing_material.swap(true, Ordering::Relaxed) {
+                tracing::error!(
+                    "a mesh uses material {} of only {}, drawing it with the fallback",
+                    mesh.material,
+                    self.materials.len()
+                );
+            }
+            &self.fallback
+        })
+    }
+
+    /// Advances every animated material, see [`Material::animate`]
+    pub fn animate(
+        &mut self,
+        staging_belt: &mut StagingBelt,
+        encoder: &mut CommandEncoder,
+        device: &Device,
+        elapsed: Duration
+    ) {
+        for material in &mut self.materials {
+            material.animate(staging_belt, encoder, device, elapsed)
+        }
     }
 }
 
 
 pub trait DrawObjExt<T> {
-    fn draw_obj_instanced(&mut self, obj: &T, range: Range<u32>);
+    fn draw_obj_instanced(&mut self, obj: &T, range: Range<u32>) -> FrameStats;
 }
 
 
 pub trait DrawLightExt<T> {
-    fn draw_light_instanced(&mut self, obj: &T, range: Range<u32>);
+    fn draw_light_instanced(&mut self, obj: &T, range: Range<u32>) -> FrameStats;
+}
+
+impl Mesh {
+    fn triangles(&self) -> u32 {
+        self.index_buffer.len_u32() / 3
+    }
 }
 
 impl DrawObjExt<(&Mesh, &Material)> for RenderPass<'_> {
-    fn draw_obj_instanced(&mut self, &(mesh, material): &(&Mesh, &Material), range: Range<u32>) {
+    fn draw_obj_instanced(&mut self, &(mesh, material): &(&Mesh, &Material), range: Range<u32>) -> FrameStats {
         self.set_vertex_buffer(0, mesh.vertex_buffer.slice(..));
         self.set_bind_group(0, &material.bind_group, &[]);
         self.set_index_buffer(mesh.index_buffer.slice(..), IndexFormat::Uint32);
-        self.draw_indexed(0..mesh.index_buffer.len_u32(), 0, range);
+        self.draw_indexed(0..mesh.index_buffer.len_u32(), 0, range.clone());
+        FrameStats::draw(mesh.triangles(), range)
     }
 }
 
 
 impl DrawLightExt<Mesh> for RenderPass<'_> {
-    fn draw_light_instanced(&mut self, mesh: &Mesh, range: Range<u32>) {
+    fn draw_light_instanced(&mut self, mesh: &Mesh, range: Range<u32>) -> FrameStats {
         self.set_vertex_buffer(0, mesh.vertex_buffer.slice(..));
         self.set_index_buffer(mesh.index_buffer.slice(..), IndexFormat::Uint32);
-        self.draw_indexed(0..mesh.index_buffer.len_u32(), 0, range);
+        self.draw_indexed(0..mesh.index_buffer.len_u32(), 0, range.clone());
+        FrameStats::draw(mesh.triangles(), range)
     }
 }
 
 impl DrawObjExt<Model> for RenderPass<'_> {
-    fn draw_obj_instanced(&mut self, model: &Model, range: Range<u32>) {
-        for mesh in &model.meshes {
-            let material = &model.materials[mesh.material];
-            self.draw_obj_instanced(&(mesh, material), range.clone())
-        }
+    fn draw_obj_instanced(&mut self, model: &Model, range: Range<u32>) -> FrameStats {
+        model.meshes
+            .iter()
+            .map(|mesh| self.draw_obj_instanced(&(mesh, model.material(mesh)), range.clone()))
+            .sum()
     }
 }
 
 impl DrawLightExt<Model> for RenderPass<'_> {
-    fn draw_light_instanced(&mut self, model: &Model, range: Range<u32>) {
-        for mesh in &model.meshes {
-            self.draw_light_instanced(mesh, range.clone())
+    fn draw_light_instanced(&mut self, model: &Model, range: Range<u32>) -> FrameStats {
+        model.meshes
+            .iter()
+            .map(|mesh| self.draw_light_instanced(mesh, range.clone()))
+            .sum()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::PathBuf;
+    use crate::assets::AssetPaths;
+    use crate::settings::{GameSettings, TextureFilter};
+    use super::*;
+
+    /// Writes `files` into a fresh directory, returning where the first one ended up
+    fn write_files(test: &str, files: &[(&str, &[u8])]) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("voxel-model-test-{test}-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        for (name, contents) in files {
+            std::fs::write(dir.join(name), contents).unwrap();
         }
+        dir.join(files[0].0)
+    }
+
+    /// Loads the obj file at the absolute `path` off the disk, through the assets directory like the game does
+    fn load(path: impl AsRef<Path>, device: &Device, queue: &Queue, layout: &BindGroupLayout, filter: TextureFilter) -> Result<Model> {
+        let assets = AssetPaths::from_settings(&GameSettings::default());
+        let mut cache = TextureCache::new(device, queue, filter);
+        Model::load_from_source(&assets, path.as_ref(), device, queue, layout, &mut cache)
+    }
+
+    fn png() -> Vec<u8> {
+        let mut bytes = std::io::Cursor::new(Vec::new());
+        image::RgbaImage::from_pixel(2, 2, image::Rgba([0, 255, 0, 255]))
+            .write_to(&mut bytes, image::ImageFormat::Png)
+            .unwrap();
+        bytes.into_inner()
+    }
+
+    #[test]
+    fn indices_must_be_in_bounds() {
+        assert!(check_indices(&[], 0).is_ok());
+        assert!(check_indices(&[0, 1, 2, 2, 1, 0], 3).is_ok());
+
+        let err = check_indices(&[0, 1, 2, 0, 2, 3], 3).unwrap_err();
+        assert!(err.to_string().contains("index 3 at 5"), "{err}");
+        assert!(check_indices(&[0], 0).is_err());
+        assert!(check_indices(&[u32::MAX], 3).is_err());
+    }
+
+    #[test]
+    fn meshes_without_texture_coordinates() {
+        let Some((device, queue)) = crate::renderer::test_device() else {
+            return;
+        };
+        let layout = crate::renderer::texture_bind_group_layout(&device, TextureFilter::Nearest);
+
+        let path = write_files("no-uvs", &[
+            ("test.obj", b"mtllib test.mtl\nv 0 0 0\nv 1 0 0\nv 0 1 0\nvn 0 0 1\nf 1//1 2//1 3//1\nusemtl green\nf 3//1 2//1 1//1\n"),
+            ("test.mtl", b"newmtl green\nmap_Kd green.png\n"),
+            ("green.png", &png()),
+        ]);
+
+        // fine while untextured, the textured triangle has nothing to map its texture with
+        let err = load(&path, &device, &queue, &layout, TextureFilter::Nearest).err().unwrap();
+        assert!(err.to_string().contains("texture coordinates"), "{err}");
+
+        std::fs::write(path.with_extension("obj"), b"v 0 0 0\nv 1 0 0\nv 0 1 0\nf 1 2 3\n").unwrap();
+        let model = load(&path, &device, &queue, &layout, TextureFilter::Nearest).unwrap();
+        assert_eq!(model.meshes.len(), 1);
+        assert_eq!(model.meshes[0].vertex_buffer.len_u32(), 3);
+        std::fs::remove_dir_all(path.parent().unwrap()).unwrap();
+    }
+
+    #[test]
+    fn solid_color_materials_need_no_texture() {
+        let Some((device, queue)) = crate::renderer::test_device() else {
+            return;
+        };
+
+        for filter in [TextureFilter::Nearest, TextureFilter::Linear] {
+            let layout = crate::renderer::texture_bind_group_layout(&device, filter);
+            device.push_error_scope(wgpu::ErrorFilter::Validation);
+            let cache = TextureCache::new(&device, &queue, filter);
+            let material = Material::solid_color(&device, &layout, &cache, "red", [1.0, 0.0, 0.0, 1.0]);
+            let error = voxel_runtime::block_on(device.pop_error_scope());
+            assert!(error.is_none(), "{filter:?}: {error:?}");
+            assert!(material.animation.is_none());
+        }
+
+        // an mtl material without a texture map is drawn in its diffuse color
+        let layout = crate::renderer::texture_bind_group_layout(&device, TextureFilter::Linear);
+        let path = write_files("solid", &[
+            ("test.obj", b"mtllib test.mtl\nv 0 0 0\nv 1 0 0\nv 0 1 0\nusemtl blue\nf 1 2 3\n"),
+            ("test.mtl", b"newmtl blue\nKd 0 0 1\n"),
+        ]);
+        let model = load(&path, &device, &queue, &layout, TextureFilter::Linear).unwrap();
+        assert_eq!(model.materials.len(), 1);
+        assert_eq!(model.meshes[0].material, 0);
+        std::fs::remove_dir_all(path.parent().unwrap()).unwrap();
+    }
+
+    const TRIANGLE: &str = "v 0 0 0\nv 1 0 0\nv 0 1 0\nvt 0 0\nvt 1 0\nvt 0 1\n";
+
+    #[test]
+    fn meshes_without_a_material_use_the_fallback() {
+        let Some((device, queue)) = crate::renderer::test_device() else {
+            return;
+        };
+        let layout = crate::renderer::texture_bind_group_layout(&device, TextureFilter::Nearest);
+
+        let obj = format!(
+            "mtllib test.mtl\n{TRIANGLE}o bare\nf 1/1 2/2 3/3\no textured\nusemtl green\nf 1/1 2/2 3/3\no unknown\nusemtl nothing\nf 1/1 2/2 3/3\n"
+        );
+        let path = write_files("fallback", &[
+            ("test.obj", obj.as_bytes()),
+            ("test.mtl", b"newmtl green\nmap_Kd green.png\n"),
+            ("green.png", &png()),
+        ]);
+
+        let model = load(&path, &device, &queue, &layout, TextureFilter::Nearest).unwrap();
+        assert_eq!(model.meshes.len(), 3);
+        // one real material, the meshes without one share the fallback,
+        // a `usemtl` carries over to the objects after it so the bare one goes first
+        assert_eq!(model.materials.len(), 1);
+        assert_eq!(model.meshes.iter().map(|mesh| mesh.material).collect::<Vec<_>>(), [1, 0, 1]);
+        let fallback = |mesh| std::ptr::eq(model.material(mesh), &model.fallback);
+        assert_eq!(model.meshes.iter().map(fallback).collect::<Vec<_>>(), [true, false, true]);
+        std::fs::remove_dir_all(path.parent().unwrap()).unwrap();
+
+        // without an mtl file at all
+        let path = write_files("no-mtl", &[("test.obj", format!("{TRIANGLE}f 1/1 2/2 3/3\n").as_bytes())]);
+        let model = load(&path, &device, &queue, &layout, TextureFilter::Nearest).unwrap();
+        assert_eq!(model.materials.len(), 0);
+        assert!(std::ptr::eq(model.material(&model.meshes[0]), &model.fallback));
+        std::fs::remove_dir_all(path.parent().unwrap()).unwrap();
+    }
+
+    #[test]
+    fn drawing_a_mesh_with_a_bad_material_uses_the_fallback() {
+        let Some((device, queue)) = crate::renderer::test_device() else {
+            return;
+        };
+        let layout = crate::renderer::texture_bind_group_layout(&device, TextureFilter::Nearest);
+
+        let path = write_files("bad-material", &[("test.obj", format!("{TRIANGLE}f 1/1 2/2 3/3
+").as_bytes())]);
+        let mut model = load(&path, &device, &queue, &layout, TextureFilter::Nearest).unwrap();
+        std::fs::remove_dir_all(path.parent().unwrap()).unwrap();
+
+        model.meshes[0].material = 42;
+        assert!(std::ptr::eq(model.material(&model.meshes[0]), &model.fallback));
+        assert!(model.warned_missing_material.load(Ordering::Relaxed));
+        // and again without logging it twice
+        assert!(std::ptr::eq(model.material(&model.meshes[0]), &model.fallback));
+    }
+
+    #[test]
+    fn the_builtin_cube_matches_the_one_on_disk() {
+        let Some((device, queue)) = crate::renderer::test_device() else {
+            return;
+        };
+        let layout = crate::renderer::texture_bind_group_layout(&device, TextureFilter::Nearest);
+
+        let mut cache = TextureCache::new(&device, &queue, TextureFilter::Nearest);
+        let builtin = Model::builtin_cube(&device, &queue, &layout, &mut cache).unwrap();
+        let path = Path::new(env!("CARGO_MANIFEST_DIR")).join("assets/cube/cube.obj");
+        let on_disk = load(path, &device, &queue, &layout, TextureFilter::Nearest).unwrap();
+
+        let shape = |model: &Model| model.meshes
+            .iter()
+            .map(|mesh| (mesh.vertex_buffer.len_u32(), mesh.index_buffer.len_u32(), mesh.material))
+            .collect::<Vec<_>>();
+        // a single mesh with the cube's one material
+        assert_eq!(shape(&builtin), [(277, 1284, 0)]);
+        assert_eq!(shape(&builtin), shape(&on_disk));
+        assert_eq!(builtin.materials.len(), 1);
+        assert!(builtin.materials[0].normal_texture.is_some());
+
+        // textures have to be there under the name the mtl file uses
+        let err = Model::load_from_memory(CUBE_OBJ, CUBE_MTL, &[], &device, &queue, &layout, &mut cache)
+            .err()
+            .unwrap();
+        assert!(err.to_string().contains("cube-diffuse.jpg"), "{err}");
+    }
+
+    #[test]
+    fn objs_load_from_anywhere() {
+        let Some((device, queue)) = crate::renderer::test_device() else {
+            return;
+        };
+        let layout = crate::renderer::texture_bind_group_layout(&device, TextureFilter::Nearest);
+
+        let obj = format!("mtllib shapes/test.mtl\n{TRIANGLE}usemtl green\nf 1/1 2/2 3/3\nusemtl blue\nf 3/3 2/2 1/1\n");
+        let mtl = "newmtl green\nmap_Kd green.png\nnewmtl blue\nKd 0 0 1\n";
+        let png = png();
+        let asked = std::cell::RefCell::new(vec![]);
+        let load_file = |path: &Path| {
+            asked.borrow_mut().push(path.to_path_buf());
+            match path.to_str() {
+                Some("shapes/test.mtl") => Ok(Cow::Borrowed(mtl.as_bytes())),
+                Some("green.png") => Ok(Cow::Owned(png.clone())),
+                _ => Err(std::io::Error::from(std::io::ErrorKind::NotFound).into()),
+            }
+        };
+
+        let mut cache = TextureCache::new(&device, &queue, TextureFilter::Nearest);
+        let model = Model::load_from_reader("test", &mut obj.as_bytes(), load_file, &device, &queue, &layout, &mut cache).unwrap();
+        // the texture doesn't have to come with an animation
+        assert_eq!(asked.take(), ["shapes/test.mtl", "green.png", "green.png.anim.toml"].map(PathBuf::from));
+        assert_eq!(model.materials.len(), 2);
+        assert_eq!(model.meshes.iter().map(|mesh| mesh.material).collect::<Vec<_>>(), [0, 1]);
+
+        // the meshes still load without their materials
+        let model = Model::load_from_reader("test", &mut obj.as_bytes(), |_| bail!("no files"), &device, &queue, &layout, &mut cache).unwrap();
+        assert_eq!(model.materials.len(), 0);
+        assert!(!model.meshes.is_empty());
+        assert!(model.meshes.iter().all(|mesh| std::ptr::eq(model.material(mesh), &model.fallback)));
     }
 }
@@ -0,0 +1,422 @@
+use std::hash::Hash;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicU8, Ordering};
+use ahash::{HashMap, HashMapExt};
+use bytemuck::{Pod, Zeroable};
+use glam::Vec3;
+use wgpu::{BindGroup, BindGroupLayout, BufferAddress, BufferUsages, CommandEncoder, Device, QuerySet, RenderPass, VertexBufferLayout};
+use crate::renderer::buffer::Buffer;
+use crate::renderer::buffer_size_of;
+use crate::renderer::model::VertexComponent;
+use crate::settings::DepthMode;
+
+/// An axis aligned box that gets rasterized (without writing color or depth)
+/// while an occlusion query is active
+#[derive(Debug, Copy, Clone, PartialEq, Pod, Zeroable)]
+#[repr(C)]
+pub struct BoundingBox {
+    pub min: Vec3,
+    pub max: Vec3,
+}
+
+impl VertexComponent for BoundingBox {
+    const DESC: VertexBufferLayout<'static> = VertexBufferLayout {
+        array_stride: buffer_size_of::<BoundingBox>(),
+        step_mode: wgpu::VertexStepMode::Instance,
+        attributes: &const { wgpu::vertex_attr_array![0 => Float32x3, 1 => Float32x3] },
+    };
+}
+
+/// the amount of vertices needed to draw the 12 triangles of a box
+const BOX_VERTICES: u32 = 36;
+
+const READBACK_PENDING: u8 = 0;
+const READBACK_MAPPED: u8 = 1;
+const READBACK_FAILED: u8 = 2;
+
+/// Maps the samples a query produced to whether what it was guarding is visible,
+/// anything that passed the depth test at least once has to be drawn
+#[inline]
+pub fn is_visible(samples_passed: u64) -> bool {
+    samples_passed != 0
+}
+
+/// Pairs every queried key with the result of its query,
+/// `keys[i]` is expected to have been drawn with query index `i`
+pub fn map_visibility<K: Hash + Eq + Copy>(keys: &[K], samples: &[u64]) -> HashMap<K, bool> {
+    debug_assert_eq!(keys.len(), samples.len(), "every query needs a result");
+
+    let mut visibility = HashMap::with_capacity(keys.len());
+    visibility.extend(
+        keys.iter()
+            .copied()
+            .zip(samples.iter().copied().map(is_visible))
+    );
+    visibility
+}
+
+/// Hardware occlusion culling with a single frame of latency.
+///
+/// Every frame the bounding boxes of what could be drawn are rendered into an occlusion query set,
+/// the results are copied back to the cpu and used on the *next* frame to skip
+/// anything that produced zero samples. Anything that has never been queried is treated as visible,
+/// so new geometry pops in instead of out.
+pub struct OcclusionCuller<K> {
+    pipeline: wgpu::RenderPipeline,
+    query_set: QuerySet,
+    capacity: u32,
+    boxes: Buffer<BoundingBox>,
+    resolve_buffer: wgpu::Buffer,
+    readback_buffer: wgpu::Buffer,
+    readback_state: Arc<AtomicU8>,
+    readback_in_flight: bool,
+
+    /// the keys queried this frame in query order
+    queried: Vec<K>,
+    /// the keys whose results are being read back
+    in_flight: Vec<K>,
+    visibility: HashMap<K, bool>,
+}
+
+impl<K: Hash + Eq + Copy> OcclusionCuller<K> {
+    pub fn new(
+        device: &Device,
+        camera_layout: &BindGroupLayout,
+        depth: DepthMode,
+        capacity: u32,
+    ) -> Self {
+        let query_set = device.create_query_set(&wgpu::QuerySetDescriptor {
+            label: Some("occlusion query set"),
+            ty: wgpu::QueryType::Occlusion,
+            count: capacity,
+        });
+
+        let results_size = (capacity as BufferAddress * wgpu::QUERY_SIZE as BufferAddress)
+            .next_multiple_of(wgpu::QUERY_RESOLVE_BUFFER_ALIGNMENT);
+
+        let resolve_buffer = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("occlusion resolve buffer"),
+            size: results_size,
+            usage: BufferUsages::QUERY_RESOLVE | BufferUsages::COPY_SRC,
+            mapped_at_creation: false,
+        });
+
+        let readback_buffer = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("occlusion readback buffer"),
+            size: results_size,
+            usage: BufferUsages::MAP_READ | BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        });
+
+        let boxes = Buffer::new(
+            device,
+            capacity as BufferAddress * buffer_size_of::<BoundingBox>(),
+            BufferUsages::VERTEX | BufferUsages::COPY_DST,
+            Some("occlusion bounding boxes")
+        );
+
+        let layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: Some("Occlusion Pipeline Layout"),
+            bind_group_layouts: &[camera_layout],
+            push_constant_ranges: &[],
+        });
+
+        let shader = device.create_shader_module(wgpu::include_wgsl!("./shaders/occlusion.wgsl"));
+        let pipeline = device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+            label: Some("Occlusion Pipeline"),
+            layout: Some(&layout),
+            vertex: wgpu::VertexState {
+                module: &shader,
+                entry_point: Some("vs_main"),
+                buffers: &[BoundingBox::DESC],
+                compilation_options: Default::default(),
+            },
+            // only the depth test matters, there is nothing to shade
+            fragment: None,
+            primitive: wgpu::PrimitiveState {
+                topology: wgpu::PrimitiveTopology::TriangleList,
+                // the camera can be inside a box, and that box is very much visible
+                cull_mode: None,
+                ..Default::default()
+            },
+            depth_stencil: Some(wgpu::DepthStencilState {
+                format: depth.format(),
+                // the boxes are proxies, they must never occlude anything themselves
+                depth_write_enabled: false,
+                depth_compare: depth.compare_or_equal(),
+                stencil: wgpu::StencilState::default(),
+                bias: wgpu::DepthBiasState::default(),
+            }),
+            multisample: wgpu::MultisampleState::default(),
+            multiview: None,
+            cache: None,
+        });
+
+        Self {
+            pipeline,
+            query_set,
+            capacity,
+            boxes,
+            resolve_buffer,
+            readback_buffer,
+            readback_state: Arc::new(AtomicU8::new(READBACK_PENDING)),
+            readback_in_flight: false,
+            queried: Vec::new(),
+            in_flight: Vec::new(),
+            visibility: HashMap::new(),
+        }
+    }
+
+    /// The query set that has to be passed as the `occlusion_query_set` of the pass
+    /// [`OcclusionCuller::draw_queries`] records into
+    pub fn query_set(&self) -> &QuerySet {
+        &self.query_set
+    }
+
+    /// Whether `key` should be drawn this frame based on last frame's queries
+    pub fn is_visible(&self, key: &K) -> bool {
+        self.visibility.get(key).copied().unwrap_or(true)
+    }
+
+    /// Drops the keys last frame's queries found to be hidden
+    pub fn retain_visible(&self, keys: &mut Vec<K>) {
+        keys.retain(|key| self.is_visible(key));
+    }
+
+    /// Picks up last frame's results if the gpu is done with them
+    pub fn begin_frame(&mut self, device: &Device) {
+        if !self.readback_in_flight {
+            return;
+        }
+
+        // this never blocks, if the results aren't in yet last frame's visibility stays around
+        let _ = device.poll(wgpu::PollType::Poll);
+        match self.readback_state.swap(READBACK_PENDING, Ordering::Acquire) {
+            READBACK_PENDING => return,
+            READBACK_FAILED => {
+                // keep the old results and try again with this frame's queries
+                self.readback_in_flight = false;
+                return;
+            }
+            _ => {}
+        }
+
+        {
+            let len = self.in_flight.len() as BufferAddress * wgpu::QUERY_SIZE as BufferAddress;
+            let view = self.readback_buffer.slice(..len).get_mapped_range();
+            let samples = bytemuck::cast_slice::<u8, u64>(&view);
+            self.visibility = map_visibility(&self.in_flight, samples);
+        }
+
+        self.readback_buffer.unmap();
+        self.readback_in_flight = false;
+    }
+
+    /// Uploads the bounding boxes that are going to be tested this frame,
+    /// anything past the capacity of the query set is left untested and so stays visible
+    pub fn prepare(
+        &mut self,
+        staging_belt: &mut wgpu::util::StagingBelt,
+        encoder: &mut CommandEncoder,
+        device: &Device,
+        boxes: impl IntoIterator<Item = (K, BoundingBox)>
+    ) {
+        self.queried.clear();
+        if self.readback_in_flight {
+            // we can't write into the readback buffer while it's mapped or about to be mapped
+            return;
+        }
+
+        let mut uploaded = Vec::with_capacity(self.capacity as usize);
+        for (key, bounding_box) in boxes.into_iter().take(self.capacity as usize) {
+            self.queried.push(key);
+            uploaded.push(bounding_box);
+        }
+
+        uploaded.resize(self.capacity as usize, BoundingBox::zeroed());
+        self.boxes.write(staging_belt, encoder, device, &uploaded);
+    }
+
+    /// Records a query per prepared bounding box,
+    /// this should happen after the opaque geometry has filled the depth buffer
+    pub fn draw_queries(&self, render_pass: &mut RenderPass, camera_bind_group: &BindGroup) {
+        if self.queried.is_empty() {
+            return;
+        }
+
+        render_pass.set_pipeline(&self.pipeline);
+        render_pass.set_bind_group(0, camera_bind_group, &[]);
+        render_pass.set_vertex_buffer(0, self.boxes.slice(..));
+        for index in 0..self.queried.len() as u32 {
+            render_pass.begin_occlusion_query(index);
+            render_pass.draw(0..BOX_VERTICES, index..index + 1);
+            render_pass.end_occlusion_query();
+        }
+    }
+
+    /// Copies this frame's query results somewhere the cpu can read them
+    pub fn resolve(&mut self, encoder: &mut CommandEncoder) {
+        if self.queried.is_empty() {
+            return;
+        }
+
+        let len = self.queried.len() as u32;
+        let size = len as BufferAddress * wgpu::QUERY_SIZE as BufferAddress;
+        encoder.resolve_query_set(&self.query_set, 0..len, &self.resolve_buffer, 0);
+        encoder.copy_buffer_to_buffer(&self.resolve_buffer, 0, &self.readback_buffer, 0, size);
+    }
+
+    /// Starts mapping the results, has to be called after the resolve was submitted
+    pub fn after_submit(&mut self) {
+        if self.queried.is_empty() {
+            return;
+        }
+
+        let size = self.queried.len() as BufferAddress * wgpu::QUERY_SIZE as BufferAddress;
+        std::mem::swap(&mut self.queried, &mut self.in_flight);
+        self.queried.clear();
+
+        let state = Arc::clone(&self.readback_state);
+        self.readback_in_flight = true;
+        self.readback_buffer.slice(..size).map_async(wgpu::MapMode::Read, move |res| {
+            let new_state = match res {
+                Ok(()) => READBACK_MAPPED,
+                Err(err) => {
+                    tracing::error!("unable to read back occlusion queries; {err}");
+                    READBACK_FAILED
+                }
+            };
+
+            state.store(new_state, Ordering::Release)
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn zero_samples_is_hidden() {
+        assert!(!is_visible(0));
+        assert!(is_visible(1));
+        assert!(is_visible(u64::MAX));
+    }
+
+    #[test]
+    fn results_map_to_their_keys() {
+        let keys = ['a', 'b', 'c'];
+        let visibility = map_visibility(&keys, &[0, 12, 0]);
+
+        assert_eq!(visibility.len(), 3);
+        assert!(!visibility[&'a']);
+        assert!(visibility[&'b']);
+        assert!(!visibility[&'c']);
+    }
+
+    fn camera_layout(device: &Device) -> BindGroupLayout {
+        device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            entries: &[wgpu::BindGroupLayoutEntry {
+                binding: 0,
+                visibility: wgpu::ShaderStages::VERTEX,
+                ty: wgpu::BindingType::Buffer {
+                    ty: wgpu::BufferBindingType::Uniform,
+                    has_dynamic_offset: false,
+                    min_binding_size: None,
+                },
+                count: None,
+            }],
+            label: None,
+        })
+    }
+
+    #[test]
+    fn unqueried_keys_are_visible() {
+        let Some((device, _queue)) = crate::renderer::test_device() else {
+            return;
+        };
+
+        let culler = OcclusionCuller::<u32>::new(
+            &device,
+            &camera_layout(&device),
+            DepthMode::default(),
+            16
+        );
+
+        // nothing has been read back yet, so nothing can be culled
+        assert!(culler.is_visible(&0));
+        assert!(culler.is_visible(&15));
+    }
+
+    #[test]
+    fn queries_cull_what_is_hidden() {
+        let Some((device, queue)) = crate::renderer::test_device() else {
+            return;
+        };
+
+        let depth = DepthMode::default();
+        let layout = camera_layout(&device);
+        let mut culler = OcclusionCuller::<u32>::new(&device, &layout, depth, 16);
+
+        // the boxes are already in clip space, the camera leaves them where they are
+        let mut camera = [0.0_f32; 20];
+        camera[4..].copy_from_slice(&glam::Mat4::IDENTITY.to_cols_array());
+        let camera = Buffer::with_init(&device, &[camera], BufferUsages::UNIFORM, None);
+        let camera = device.create_bind_group(&wgpu::BindGroupDescriptor {
+            layout: &layout,
+            entries: &[wgpu::BindGroupEntry { binding: 0, resource: camera.as_entire_binding() }],
+            label: None,
+        });
+        let target = device.create_texture(&wgpu::TextureDescriptor {
+            label: None,
+            size: wgpu::Extent3d { width: 4, height: 4, depth_or_array_layers: 1 },
+            mip_level_count: 1,
+            sample_count: 1,
+            dimension: wgpu::TextureDimension::D2,
+            format: depth.format(),
+            usage: wgpu::TextureUsages::RENDER_ATTACHMENT,
+            view_formats: &[],
+        });
+        let target = target.create_view(&Default::default());
+
+        // a frame with the depth buffer cleared to `cleared`, standing in for what was drawn before the queries
+        let frame = |culler: &mut OcclusionCuller<u32>, cleared: f32| {
+            let mut staging_belt = wgpu::util::StagingBelt::new(1024);
+            let mut encoder = device.create_command_encoder(&Default::default());
+            culler.begin_frame(&device);
+            let bounds = BoundingBox { min: Vec3::new(-0.5, -0.5, 0.4), max: Vec3::new(0.5, 0.5, 0.6) };
+            culler.prepare(&mut staging_belt, &mut encoder, &device, [(7, bounds)]);
+            {
+                let mut render_pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+                    label: None,
+                    color_attachments: &[],
+                    depth_stencil_attachment: Some(wgpu::RenderPassDepthStencilAttachment {
+                        view: &target,
+                        depth_ops: Some(wgpu::Operations { load: wgpu::LoadOp::Clear(cleared), store: wgpu::StoreOp::Store }),
+                        stencil_ops: None,
+                    }),
+                    timestamp_writes: None,
+                    occlusion_query_set: Some(culler.query_set()),
+                });
+                culler.draw_queries(&mut render_pass, &camera);
+            }
+            culler.resolve(&mut encoder);
+            staging_belt.finish();
+            queue.submit([encoder.finish()]);
+            culler.after_submit();
+            device.poll(wgpu::PollType::Wait).unwrap();
+            culler.begin_frame(&device);
+        };
+
+        // everything in front of the box hides it, and it's dropped from what gets drawn
+        frame(&mut culler, 1.0 - depth.clear_value());
+        assert!(!culler.is_visible(&7));
+        let mut keys = vec![3, 7, 9];
+        culler.retain_visible(&mut keys);
+        assert_eq!(keys, [3, 9]);
+
+        // and once nothing is in front of it anymore, it comes back
+        frame(&mut culler, depth.clear_value());
+        assert!(culler.is_visible(&7));
+    }
+}
@@ -0,0 +1,202 @@
+use bytemuck::{Pod, Zeroable};
+use glam::{Vec3, Vec4};
+use wgpu::{BindGroup, BindGroupLayout, BufferUsages, CommandEncoder, Device, RenderPass, TextureFormat, VertexBufferLayout};
+use wgpu::util::StagingBelt;
+use crate::game_state::GameState;
+use crate::renderer::buffer::GrowableBuffer;
+use crate::renderer::buffer_size_of;
+use crate::renderer::stats::FrameStats;
+use crate::renderer::model::VertexComponent;
+use crate::settings::DepthMode;
+
+/// the width and height of a particle in blocks
+const PARTICLE_SIZE: f32 = 0.1;
+
+/// the two triangles of a quad
+const QUAD_VERTICES: u32 = 6;
+
+#[derive(Debug, Copy, Clone, PartialEq, Pod, Zeroable)]
+#[repr(C)]
+pub struct ParticleInstance {
+    pub position: Vec3,
+    pub size: f32,
+    pub color: Vec4,
+}
+
+impl VertexComponent for ParticleInstance {
+    const DESC: VertexBufferLayout<'static> = VertexBufferLayout {
+        array_stride: buffer_size_of::<ParticleInstance>(),
+        step_mode: wgpu::VertexStepMode::Instance,
+        attributes: &const { wgpu::vertex_attr_array![0 => Float32x3, 1 => Float32, 2 => Float32x4] },
+    };
+}
+
+/// Converts the live particles into instances, `raw` is cleared first so its allocation can be reused
+fn collect_particles(game: &GameState, raw: &mut Vec<ParticleInstance>) {
+    raw.clear();
+    raw.reserve(game.particles().len());
+    raw.extend(game.particles().iter().map(|particle| ParticleInstance {
+        position: particle.position.xyz().as_f32(),
+        size: PARTICLE_SIZE,
+        color: particle.faded_color(),
+    }));
+}
+
+/// Draws every particle as a camera facing quad,
+/// they are blended on top of the opaque geometry without writing depth
+pub struct ParticleRenderer {
+    pipeline: wgpu::RenderPipeline,
+    instances: Vec<ParticleInstance>,
+    instance_buffer: GrowableBuffer<ParticleInstance>,
+}
+
+impl ParticleRenderer {
+    pub fn new(
+        device: &Device,
+        camera_layout: &BindGroupLayout,
+        color_format: TextureFormat,
+        depth: DepthMode,
+    ) -> Self {
+        let layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: Some("Particle Pipeline Layout"),
+            bind_group_layouts: &[camera_layout],
+            push_constant_ranges: &[],
+        });
+
+        let shader = device.create_shader_module(wgpu::include_wgsl!("./shaders/particle.wgsl"));
+        let pipeline = device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+            label: Some("Particle Pipeline"),
+            layout: Some(&layout),
+            vertex: wgpu::VertexState {
+                module: &shader,
+                entry_point: Some("vs_main"),
+                buffers: &[ParticleInstance::DESC],
+                compilation_options: Default::default(),
+            },
+            fragment: Some(wgpu::FragmentState {
+                module: &shader,
+                entry_point: Some("fs_main"),
+                targets: &[Some(wgpu::ColorTargetState {
+                    format: color_format,
+                    blend: Some(wgpu::BlendState::ALPHA_BLENDING),
+                    write_mask: wgpu::ColorWrites::ALL,
+                })],
+                compilation_options: Default::default(),
+            }),
+            primitive: wgpu::PrimitiveState {
+                topology: wgpu::PrimitiveTopology::TriangleList,
+                // quads always face the camera anyway
+                cull_mode: None,
+                ..Default::default()
+            },
+            depth_stencil: Some(wgpu::DepthStencilState {
+                format: depth.format(),
+                // particles are see through, they get hidden by the world but never hide each other
+                depth_write_enabled: false,
+                depth_compare: depth.compare(),
+                stencil: wgpu::StencilState::default(),
+                bias: wgpu::DepthBiasState::default(),
+            }),
+            multisample: wgpu::MultisampleState::default(),
+            multiview: None,
+            cache: None,
+        });
+
+        Self {
+            pipeline,
+            instances: Vec::new(),
+            instance_buffer: GrowableBuffer::new(
+                device,
+                0,
+                BufferUsages::VERTEX,
+                Some("particle instance buffer")
+            ),
+        }
+    }
+
+    /// Uploads this frame's particles, has to be called before the render pass starts
+    pub fn prepare(
+        &mut self,
+        staging_belt: &mut StagingBelt,
+        encoder: &mut CommandEncoder,
+        device: &Device,
+        game: &GameState,
+    ) {
+        collect_particles(game, &mut self.instances);
+        self.instance_buffer.write(staging_belt, encoder, device, &self.instances);
+    }
+
+    /// Draws the particles uploaded in [`ParticleRenderer::prepare`],
+    /// should come after everything opaque so blending sees what's behind
+    pub fn draw(&self, pass: &mut RenderPass, camera_bind_group: &BindGroup) -> FrameStats {
+        if self.instance_buffer.len() == 0 {
+            return FrameStats::default();
+        }
+
+        pass.set_pipeline(&self.pipeline);
+        pass.set_bind_group(0, camera_bind_group, &[]);
+        pass.set_vertex_buffer(0, self.instance_buffer.slice());
+        pass.draw(0..QUAD_VERTICES, 0..self.instance_buffer.len_u32());
+        FrameStats::draw(QUAD_VERTICES / 3, 0..self.instance_buffer.len_u32())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use glam::vec4;
+    use voxel_maths::fixed_point::FixedPoint;
+    use crate::game_state::coords::AbsoluteCoord;
+    use crate::game_state::particles::ParticleEmitter;
+    use super::*;
+
+    #[test]
+    fn particles_become_instances() {
+        let mut game = GameState::new();
+        let mut raw = Vec::new();
+
+        collect_particles(&game, &mut raw);
+        assert!(raw.is_empty());
+
+        let color = vec4(1.0, 0.5, 0.0, 1.0);
+        game.spawn_particles(&ParticleEmitter {
+            position: AbsoluteCoord::ZERO,
+            count: 16,
+            speed: 1.0,
+            lifetime: FixedPoint::from_f32(1.0),
+            color,
+        });
+
+        collect_particles(&game, &mut raw);
+        assert_eq!(raw.len(), 16);
+        assert!(raw.iter().all(|instance| instance.position == Vec3::ZERO && instance.color == color));
+    }
+
+    #[test]
+    fn pipeline_builds() {
+        let Some((device, _queue)) = crate::renderer::test_device() else {
+            return;
+        };
+
+        let camera_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            entries: &[wgpu::BindGroupLayoutEntry {
+                binding: 0,
+                visibility: wgpu::ShaderStages::VERTEX_FRAGMENT,
+                ty: wgpu::BindingType::Buffer {
+                    ty: wgpu::BufferBindingType::Uniform,
+                    has_dynamic_offset: false,
+                    min_binding_size: None,
+                },
+                count: None,
+            }],
+            label: None,
+        });
+
+        let renderer = ParticleRenderer::new(
+            &device,
+            &camera_layout,
+            TextureFormat::Rgba8UnormSrgb,
+            DepthMode::default()
+        );
+        assert_eq!(renderer.instance_buffer.len(), 0);
+    }
+}
@@ -0,0 +1,79 @@
+// chunk.wgsl
+// Draws the meshed blocks of the world
+
+struct CameraUniform {
+    view_pos: vec4<f32>,
+    view_proj: mat4x4<f32>,
+}
+
+@group(0) @binding(0)
+var<uniform> camera: CameraUniform;
+
+// `Light`, `Display` and the functions using them come from lighting.wgsl
+@group(1) @binding(0)
+var<uniform> light: Light;
+
+// distance fog, fades into the sky between `start` and `end` units of view space depth
+struct Fog {
+    color: vec3<f32>,
+    enabled: u32,
+    start: f32,
+    end: f32,
+}
+@group(1) @binding(1)
+var<uniform> fog: Fog;
+
+@group(1) @binding(2)
+var<uniform> display: Display;
+
+fn apply_fog(color: vec3<f32>, view_depth: f32) -> vec3<f32> {
+    let amount = clamp((view_depth - fog.start) / (fog.end - fog.start), 0.0, 1.0) * f32(fog.enabled);
+    return mix(color, fog.color, amount);
+}
+
+struct VertexInput {
+    @location(0) position: vec3<f32>,
+    @location(1) normal: vec3<f32>,
+    @location(2) color: vec3<f32>,
+    // ambient occlusion in the low 2 bits, the light level in the 4 above
+    @location(3) lighting: u32,
+};
+
+struct VertexOutput {
+    // the depth pre-pass draws the same vertices again, they have to land on exactly the same depth
+    @invariant @builtin(position) clip_position: vec4<f32>,
+    @location(0) normal: vec3<f32>,
+    @location(1) color: vec3<f32>,
+    @location(2) occlusion: f32,
+    @location(3) light: f32,
+    @location(4) world_position: vec3<f32>,
+    @location(5) view_depth: f32,
+};
+
+@vertex
+fn vs_main(in: VertexInput) -> VertexOutput {
+    var out: VertexOutput;
+    // the mesh is built around the chunk's corner, `draw` is declared by the prelude the renderer prepends,
+    // it only moves the chunk so the normals stay as they are
+    out.world_position = (draw.model * vec4<f32>(in.position, 1.0)).xyz;
+    out.clip_position = camera.view_proj * vec4<f32>(out.world_position, 1.0);
+    out.view_depth = out.clip_position.w;
+    out.normal = in.normal;
+    out.color = in.color;
+    // interpolated across the face so the shading fades smoothly between the corners
+    out.occlusion = f32(in.lighting & 3u) / 3.0;
+    out.light = f32((in.lighting >> 2u) & 15u) / 15.0;
+    return out;
+}
+
+@fragment
+fn fs_main(in: VertexOutput) -> @location(0) vec4<f32> {
+    let sample = light_at(light, in.world_position);
+    let ambient = light.color * light.intensity * 0.35;
+    let diffuse = sample.color * max(dot(in.normal, sample.direction), 0.0) * 0.65;
+    let ao = mix(0.4, 1.0, in.occlusion);
+    let light_level = mix(0.05, 1.0, in.light);
+    let lit = (ambient + diffuse) * ao * light_level;
+    // the fog fades into the sky, which is drawn as it is, so it goes on after the tone mapping
+    return vec4<f32>(apply_fog(tone_map(in.color * lit, display), in.view_depth), 1.0);
+}
@@ -0,0 +1,77 @@
+// fxaa.wgsl
+// Fast approximate anti-aliasing, blurs the drawn scene along the edges it finds in it
+
+@group(0) @binding(0)
+var t_scene: texture_2d<f32>;
+@group(0) @binding(1)
+var s_scene: sampler;
+
+struct VertexOutput {
+    @builtin(position) clip_position: vec4<f32>,
+    @location(0) uv: vec2<f32>,
+};
+
+// a single triangle covering the whole screen, the parts past the edges get clipped
+@vertex
+fn vs_main(@builtin(vertex_index) vertex_index: u32) -> VertexOutput {
+    let position = vec2<f32>(f32((vertex_index << 1u) & 2u), f32(vertex_index & 2u)) * 2.0 - 1.0;
+
+    var out: VertexOutput;
+    out.clip_position = vec4<f32>(position, 0.0, 1.0);
+    out.uv = vec2<f32>(position.x * 0.5 + 0.5, 0.5 - position.y * 0.5);
+    return out;
+}
+
+// how far along an edge the blur reaches, in texels
+const SPAN_MAX: f32 = 8.0;
+const REDUCE_MUL: f32 = 1.0 / 8.0;
+const REDUCE_MIN: f32 = 1.0 / 128.0;
+
+fn sample(uv: vec2<f32>) -> vec3<f32> {
+    return textureSampleLevel(t_scene, s_scene, uv, 0.0).rgb;
+}
+
+// the scene is in linear color, the square root gets close enough to how bright it looks
+fn luma(color: vec3<f32>) -> f32 {
+    return sqrt(dot(color, vec3<f32>(0.299, 0.587, 0.114)));
+}
+
+@fragment
+fn fs_main(in: VertexOutput) -> @location(0) vec4<f32> {
+    let texel = 1.0 / vec2<f32>(textureDimensions(t_scene));
+
+    let luma_nw = luma(sample(in.uv + vec2<f32>(-1.0, -1.0) * texel));
+    let luma_ne = luma(sample(in.uv + vec2<f32>(1.0, -1.0) * texel));
+    let luma_sw = luma(sample(in.uv + vec2<f32>(-1.0, 1.0) * texel));
+    let luma_se = luma(sample(in.uv + vec2<f32>(1.0, 1.0) * texel));
+    let middle = sample(in.uv);
+    let luma_m = luma(middle);
+
+    let luma_min = min(luma_m, min(min(luma_nw, luma_ne), min(luma_sw, luma_se)));
+    let luma_max = max(luma_m, max(max(luma_nw, luma_ne), max(luma_sw, luma_se)));
+
+    // the direction along the edge, across the way the brightness changes
+    var direction = vec2<f32>(
+        -((luma_nw + luma_ne) - (luma_sw + luma_se)),
+        (luma_nw + luma_sw) - (luma_ne + luma_se),
+    );
+    let reduce = max((luma_nw + luma_ne + luma_sw + luma_se) * 0.25 * REDUCE_MUL, REDUCE_MIN);
+    let scale = 1.0 / (min(abs(direction.x), abs(direction.y)) + reduce);
+    direction = clamp(direction * scale, vec2<f32>(-SPAN_MAX), vec2<f32>(SPAN_MAX)) * texel;
+
+    let near = 0.5 * (
+        sample(in.uv + direction * (1.0 / 3.0 - 0.5)) +
+        sample(in.uv + direction * (2.0 / 3.0 - 0.5))
+    );
+    let far = near * 0.5 + 0.25 * (
+        sample(in.uv + direction * -0.5) +
+        sample(in.uv + direction * 0.5)
+    );
+
+    // reaching further picked up something from past the edge, the shorter blur is safer
+    let luma_far = luma(far);
+    if luma_far < luma_min || luma_far > luma_max {
+        return vec4<f32>(near, 1.0);
+    }
+    return vec4<f32>(far, 1.0);
+}
@@ -7,9 +7,16 @@ struct Camera {
 @group(0) @binding(0)
 var<uniform> camera: Camera;
 
+// only the position and color are needed here, the rest keeps the layout the same as the other shaders
 struct Light {
     position: vec3<f32>,
+    kind: u32,
+    direction: vec3<f32>,
+    range: f32,
     color: vec3<f32>,
+    intensity: f32,
+    cos_inner: f32,
+    cos_outer: f32,
 }
 @group(1) @binding(0)
 var<uniform> light: Light;
@@ -0,0 +1,69 @@
+// lighting.wgsl
+// The lighting shared by everything lit, put in front of the shaders that use it
+// and each of those binds its own `var<uniform> light: Light`
+
+// `kind` is 0 for a directional light, 1 for a point light and 2 for a spot light,
+// `direction` points towards a directional light and the way a spot light shines
+struct Light {
+    position: vec3<f32>,
+    kind: u32,
+    direction: vec3<f32>,
+    range: f32,
+    color: vec3<f32>,
+    intensity: f32,
+    cos_inner: f32,
+    cos_outer: f32,
+}
+
+// where the light comes from and how much of it arrives at `world_position`
+struct LightSample {
+    direction: vec3<f32>,
+    color: vec3<f32>,
+}
+
+// how close to a light something counts as being, right on top of one there is no direction to divide by
+const MIN_LIGHT_DISTANCE: f32 = 1e-4;
+
+fn light_at(light: Light, world_position: vec3<f32>) -> LightSample {
+    var sample: LightSample;
+    if light.kind == 0u {
+        sample.direction = light.direction;
+        sample.color = light.color * light.intensity;
+        return sample;
+    }
+
+    let to_light = light.position - world_position;
+    let distance = max(length(to_light), MIN_LIGHT_DISTANCE);
+    sample.direction = to_light / distance;
+    // fades out smoothly, reaching nothing right at the range
+    let falloff = clamp(1.0 - pow(distance / light.range, 4.0), 0.0, 1.0);
+    var amount = falloff * falloff / (distance * distance + 1.0);
+    if light.kind == 2u {
+        amount *= smoothstep(light.cos_outer, light.cos_inner, dot(-sample.direction, light.direction));
+    }
+    sample.color = light.color * light.intensity * amount;
+    return sample;
+}
+
+// how the lit scene is mapped onto the surface
+struct Display {
+    exposure: f32,
+    hdr: u32,
+}
+
+// where a surface that clamps at white starts squeezing brighter colors in under it
+const TONE_MAP_KNEE: f32 = 0.8;
+
+// scales the lit color by the exposure, a float surface shows what's past white as brighter,
+// anything else gets what's above the knee rolled off towards white instead of clipped at it
+fn tone_map(color: vec3<f32>, display: Display) -> vec3<f32> {
+    let exposed = max(color * display.exposure, vec3<f32>(0.0));
+    if display.hdr != 0u {
+        return exposed;
+    }
+
+    let headroom = 1.0 - TONE_MAP_KNEE;
+    let rolled_off = TONE_MAP_KNEE + headroom * (1.0 - exp((TONE_MAP_KNEE - exposed) / headroom));
+    return select(exposed, rolled_off, exposed > vec3<f32>(TONE_MAP_KNEE));
+}
+
@@ -19,14 +19,23 @@ struct InstanceInput {
     @location(6) model_matrix_1: vec4<f32>,
     @location(7) model_matrix_2: vec4<f32>,
     @location(8) model_matrix_3: vec4<f32>,
+    // the inverse transpose of the model matrix, so normals stay right under non uniform scaling
+    @location(9) normal_matrix_0: vec3<f32>,
+    @location(10) normal_matrix_1: vec3<f32>,
+    @location(11) normal_matrix_2: vec3<f32>,
+    // multiplied into the color
+    @location(12) tint: vec4<f32>,
 };
 
 
 struct VertexOutput {
-    @builtin(position) clip_position: vec4<f32>,
+    // the depth pre-pass draws the same vertices again, they have to land on exactly the same depth
+    @invariant @builtin(position) clip_position: vec4<f32>,
     @location(0) tex_coords: vec2<f32>,
     @location(1) world_normal: vec3<f32>,
     @location(2) world_position: vec3<f32>,
+    @location(3) view_depth: f32,
+    @location(4) tint: vec4<f32>,
 }
 
 @vertex
@@ -40,15 +49,46 @@ fn vs_main(
         instance.model_matrix_2,
         instance.model_matrix_3,
     );
+    let normal_matrix = mat3x3<f32>(
+        instance.normal_matrix_0,
+        instance.normal_matrix_1,
+        instance.normal_matrix_2,
+    );
+
+    return transform(model, model_matrix, normal_matrix, instance.tint);
+}
+
+// one-off draws without an instance buffer, the model is only placed by `draw.model`
+@vertex
+fn vs_single(model: VertexInput) -> VertexOutput {
+    let identity = mat4x4<f32>(
+        vec4<f32>(1.0, 0.0, 0.0, 0.0),
+        vec4<f32>(0.0, 1.0, 0.0, 0.0),
+        vec4<f32>(0.0, 0.0, 1.0, 0.0),
+        vec4<f32>(0.0, 0.0, 0.0, 1.0),
+    );
+    let normal_identity = mat3x3<f32>(
+        vec3<f32>(1.0, 0.0, 0.0),
+        vec3<f32>(0.0, 1.0, 0.0),
+        vec3<f32>(0.0, 0.0, 1.0),
+    );
 
+    return transform(model, identity, normal_identity, vec4<f32>(1.0));
+}
+
+fn transform(model: VertexInput, model_matrix: mat4x4<f32>, normal_matrix: mat3x3<f32>, tint: vec4<f32>) -> VertexOutput {
     var out: VertexOutput;
 
+    out.tint = tint;
     out.tex_coords = model.tex_coords;
-    out.world_normal = normalize((model_matrix * vec4<f32>(model.normal, 0.0)).xyz);
+    // `draw` is declared by the prelude the renderer prepends, see draw_data.rs, it goes on top of the instance
+    out.world_normal = normalize(draw.normal * normal_matrix * model.normal);
 
-    let world_position: vec4<f32> = model_matrix * vec4<f32>(model.position, 1.0);
+    let world_position: vec4<f32> = draw.model * model_matrix * vec4<f32>(model.position, 1.0);
     out.world_position = world_position.xyz;
     out.clip_position = camera.view_proj * world_position;
+    // for a perspective projection w is the depth in view space
+    out.view_depth = out.clip_position.w;
     return out;
 }
 
@@ -57,35 +97,70 @@ var t_diffuse: texture_2d<f32>;
 @group(0) @binding(1)
 var s_diffuse: sampler;
 
-struct Light {
-    position: vec3<f32>,
-    color: vec3<f32>,
+// animated textures are frames stacked top to bottom, static ones are a single frame
+struct Animation {
+    frame: u32,
+    frame_count: u32,
 }
+@group(0) @binding(2)
+var<uniform> animation: Animation;
+
+// tints the texture, materials without a texture sample a white pixel so this is their whole color
+struct Material {
+    color: vec4<f32>,
+}
+@group(0) @binding(3)
+var<uniform> material: Material;
+
+// `Light`, `Display` and the functions using them come from lighting.wgsl
 @group(2) @binding(0)
 var<uniform> light: Light;
 
+// distance fog, fades into the sky between `start` and `end` units of view space depth
+struct Fog {
+    color: vec3<f32>,
+    enabled: u32,
+    start: f32,
+    end: f32,
+}
+@group(2) @binding(1)
+var<uniform> fog: Fog;
+
+@group(2) @binding(2)
+var<uniform> display: Display;
+
+fn apply_fog(color: vec3<f32>, view_depth: f32) -> vec3<f32> {
+    let amount = clamp((view_depth - fog.start) / (fog.end - fog.start), 0.0, 1.0) * f32(fog.enabled);
+    return mix(color, fog.color, amount);
+}
+
 @fragment
 fn fs_main(in: VertexOutput) -> @location(0) vec4<f32> {
-    let object_color: vec4<f32> = textureSample(t_diffuse, s_diffuse, in.tex_coords);
+    let frame_coords = vec2<f32>(
+        in.tex_coords.x,
+        (in.tex_coords.y + f32(animation.frame)) / f32(animation.frame_count)
+    );
+    let object_color: vec4<f32> = textureSample(t_diffuse, s_diffuse, frame_coords) * material.color * in.tint;
 
 
-    let light_dir = normalize(light.position - in.world_position);
+    let sample = light_at(light, in.world_position);
+    let light_dir = sample.direction;
 
     let view_dir = normalize(camera.view_pos.xyz - in.world_position);
     let reflect_dir = reflect(-light_dir, in.world_normal);
     let specular_strength = pow(max(dot(view_dir, reflect_dir), 0.0), 32.0);
-    let specular_color = specular_strength * light.color;
+    let specular_color = specular_strength * sample.color;
 
 
     // We don't need (or want) much ambient light, so 0.1 is fine
     let ambient_strength = 0.15;
-    let ambient_color = light.color * ambient_strength;
+    let ambient_color = light.color * light.intensity * ambient_strength;
 
     let diffuse_strength = max(dot(in.world_normal, light_dir), 0.0);
-    let diffuse_color = light.color * diffuse_strength;
+    let diffuse_color = sample.color * diffuse_strength;
 
 
     let result = (ambient_color + diffuse_color + specular_color) * object_color.xyz;
 
-    return vec4<f32>(result, object_color.a);
+    return vec4<f32>(apply_fog(tone_map(result, display), in.view_depth), object_color.a);
 }
\ No newline at end of file
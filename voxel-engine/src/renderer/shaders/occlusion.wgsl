@@ -0,0 +1,40 @@
+// occlusion.wgsl
+// Rasterizes bounding boxes so occlusion queries can count the samples that pass the depth test
+
+struct CameraUniform {
+    view_pos: vec4<f32>,
+    view_proj: mat4x4<f32>,
+}
+
+@group(0) @binding(0)
+var<uniform> camera: CameraUniform;
+
+struct BoxInput {
+    @location(0) min: vec3<f32>,
+    @location(1) max: vec3<f32>,
+};
+
+// corner i picks max on the axes whose bits are set (x = 1, y = 2, z = 4)
+const BOX_INDICES = array<u32, 36>(
+    0u, 2u, 1u, 1u, 2u, 3u, // -z
+    4u, 5u, 6u, 5u, 7u, 6u, // +z
+    0u, 4u, 2u, 2u, 4u, 6u, // -x
+    1u, 3u, 5u, 3u, 7u, 5u, // +x
+    0u, 1u, 4u, 1u, 5u, 4u, // -y
+    2u, 6u, 3u, 3u, 6u, 7u, // +y
+);
+
+@vertex
+fn vs_main(
+    @builtin(vertex_index) vertex_index: u32,
+    bounds: BoxInput,
+) -> @builtin(position) vec4<f32> {
+    let corner = BOX_INDICES[vertex_index];
+    let position = select(bounds.min, bounds.max, vec3<bool>(
+        (corner & 1u) != 0u,
+        (corner & 2u) != 0u,
+        (corner & 4u) != 0u,
+    ));
+
+    return camera.view_proj * vec4<f32>(position, 1.0);
+}
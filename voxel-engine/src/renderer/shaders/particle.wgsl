@@ -0,0 +1,58 @@
+// particle.wgsl
+// Expands every particle instance into a quad that faces the camera
+
+struct CameraUniform {
+    view_pos: vec4<f32>,
+    view_proj: mat4x4<f32>,
+}
+
+@group(0) @binding(0)
+var<uniform> camera: CameraUniform;
+
+struct ParticleInput {
+    @location(0) position: vec3<f32>,
+    @location(1) size: f32,
+    @location(2) color: vec4<f32>,
+};
+
+struct VertexOutput {
+    @builtin(position) clip_position: vec4<f32>,
+    @location(0) color: vec4<f32>,
+};
+
+const QUAD_CORNERS = array<vec2<f32>, 6>(
+    vec2<f32>(-0.5, -0.5),
+    vec2<f32>( 0.5, -0.5),
+    vec2<f32>( 0.5,  0.5),
+    vec2<f32>(-0.5, -0.5),
+    vec2<f32>( 0.5,  0.5),
+    vec2<f32>(-0.5,  0.5),
+);
+
+@vertex
+fn vs_main(
+    @builtin(vertex_index) vertex_index: u32,
+    particle: ParticleInput,
+) -> VertexOutput {
+    let to_eye = camera.view_pos.xyz - particle.position;
+    var right = cross(vec3<f32>(0.0, 1.0, 0.0), to_eye);
+    // straight above or below the camera any right works
+    if dot(right, right) < 1e-8 {
+        right = vec3<f32>(1.0, 0.0, 0.0);
+    }
+    right = normalize(right);
+    let up = normalize(cross(to_eye, right));
+
+    let corner = QUAD_CORNERS[vertex_index] * particle.size;
+    let position = particle.position + right * corner.x + up * corner.y;
+
+    var out: VertexOutput;
+    out.clip_position = camera.view_proj * vec4<f32>(position, 1.0);
+    out.color = particle.color;
+    return out;
+}
+
+@fragment
+fn fs_main(in: VertexOutput) -> @location(0) vec4<f32> {
+    return in.color;
+}
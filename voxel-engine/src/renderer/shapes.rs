@@ -0,0 +1,196 @@
+//! Geometry for simple shapes, as vertices and the indices of their triangles
+//! wound counter clockwise seen from the outside, ready for [`Mesh::new`](crate::renderer::model::Mesh::new)
+
+use std::f32::consts::{PI, TAU};
+use glam::{Vec2, Vec3};
+use crate::renderer::model::ModelVertex;
+
+/// The corners of a face going counter clockwise from the bottom left,
+/// as where they sit along the face's right and up directions and their texture coordinates
+const FACE_CORNERS: [(f32, f32, Vec2); 4] = [
+    (-1.0, -1.0, Vec2::new(0.0, 1.0)),
+    (1.0, -1.0, Vec2::new(1.0, 1.0)),
+    (1.0, 1.0, Vec2::new(1.0, 0.0)),
+    (-1.0, 1.0, Vec2::new(0.0, 0.0)),
+];
+
+/// The two triangles of a face made out of [`FACE_CORNERS`] starting at `first`
+fn face_indices(first: u32) -> [u32; 6] {
+    [0, 1, 2, 0, 2, 3].map(|index| first + index)
+}
+
+/// A `size` wide and tall quad in the xy plane around the origin, facing +z
+pub fn quad(size: Vec2) -> (Vec<ModelVertex>, Vec<u32>) {
+    let half = size / 2.0;
+    let vertices = FACE_CORNERS
+        .map(|(x, y, tex_coords)| ModelVertex {
+            position: Vec3::new(x * half.x, y * half.y, 0.0),
+            tex_coords,
+            normal: Vec3::Z,
+        })
+        .to_vec();
+
+    (vertices, face_indices(0).to_vec())
+}
+
+/// A cube `size` along each edge around the origin, each face with its own vertices so the normals stay flat
+pub fn cube(size: f32) -> (Vec<ModelVertex>, Vec<u32>) {
+    // the normal, and the directions right and up on the face looking at it from the outside
+    const FACES: [(Vec3, Vec3, Vec3); 6] = [
+        (Vec3::X, Vec3::NEG_Z, Vec3::Y),
+        (Vec3::NEG_X, Vec3::Z, Vec3::Y),
+        (Vec3::Y, Vec3::X, Vec3::NEG_Z),
+        (Vec3::NEG_Y, Vec3::X, Vec3::Z),
+        (Vec3::Z, Vec3::X, Vec3::Y),
+        (Vec3::NEG_Z, Vec3::NEG_X, Vec3::Y),
+    ];
+
+    let half = size / 2.0;
+    let mut vertices = Vec::with_capacity(FACES.len() * 4);
+    let mut indices = Vec::with_capacity(FACES.len() * 6);
+    for (normal, right, up) in FACES {
+        indices.extend(face_indices(vertices.len() as u32));
+        vertices.extend(FACE_CORNERS.map(|(x, y, tex_coords)| ModelVertex {
+            position: (normal + right * x + up * y) * half,
+            tex_coords,
+            normal,
+        }));
+    }
+
+    (vertices, indices)
+}
+
+/// A `size` wide and deep plane in the xz plane around the origin, facing +y,
+/// split into `subdivisions` by `subdivisions` squares so it can be lit per vertex or bent afterwards
+#[cfg_attr(not(test), expect(dead_code, reason = "nothing draws a plane yet"))]
+pub fn plane(size: Vec2, subdivisions: u32) -> (Vec<ModelVertex>, Vec<u32>) {
+    assert!(subdivisions > 0, "a plane needs at least one square");
+
+    let row = subdivisions + 1;
+    let vertices = (0..row)
+        .flat_map(|z| (0..row).map(move |x| Vec2::new(x as f32, z as f32) / subdivisions as f32))
+        .map(|along| ModelVertex {
+            position: Vec3::new(along.x - 0.5, 0.0, along.y - 0.5) * Vec3::new(size.x, 1.0, size.y),
+            tex_coords: along,
+            normal: Vec3::Y,
+        })
+        .collect();
+
+    let indices = (0..subdivisions)
+        .flat_map(|z| (0..subdivisions).map(move |x| z * row + x))
+        .flat_map(|top_left| {
+            // seen from above +x is right and -z is up
+            let (top_right, bottom_left) = (top_left + 1, top_left + row);
+            let bottom_right = bottom_left + 1;
+            [bottom_left, bottom_right, top_right, bottom_left, top_right, top_left]
+        })
+        .collect();
+
+    (vertices, indices)
+}
+
+/// A sphere of `radius` around the origin, split into `rings` bands from pole to pole
+/// and `sectors` slices around the y axis
+///
+/// the seam where the texture wraps around gets its own column of vertices,
+/// and the triangles that would collapse at the poles are left out
+pub fn sphere(radius: f32, rings: u32, sectors: u32) -> (Vec<ModelVertex>, Vec<u32>) {
+    assert!(rings >= 2 && sectors >= 3, "a sphere needs at least 2 rings and 3 sectors");
+
+    let row = sectors + 1;
+    let vertices = (0..=rings)
+        .flat_map(|ring| (0..=sectors).map(move |sector| Vec2::new(sector as f32 / sectors as f32, ring as f32 / rings as f32)))
+        .map(|tex_coords| {
+            // from the north pole down, and around from +z towards +x
+            let (polar, around) = (tex_coords.y * PI, tex_coords.x * TAU);
+            let normal = Vec3::new(polar.sin() * around.sin(), polar.cos(), polar.sin() * around.cos());
+            ModelVertex { position: normal * radius, tex_coords, normal }
+        })
+        .collect();
+
+    let mut indices = Vec::with_capacity((6 * sectors * (rings - 1)) as usize);
+    for ring in 0..rings {
+        for sector in 0..sectors {
+            let top_left = ring * row + sector;
+            let (top_right, bottom_left) = (top_left + 1, top_left + row);
+            let bottom_right = bottom_left + 1;
+            if ring != rings - 1 {
+                indices.extend([bottom_left, bottom_right, top_right]);
+            }
+            if ring != 0 {
+                indices.extend([bottom_left, top_right, top_left]);
+            }
+        }
+    }
+
+    (vertices, indices)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Every triangle has some area and faces the same way as its vertices' normals
+    fn assert_wound_outwards((vertices, indices): &(Vec<ModelVertex>, Vec<u32>)) {
+        for triangle in indices.chunks_exact(3) {
+            let [a, b, c] = [0, 1, 2].map(|i| vertices[triangle[i] as usize]);
+            let facing = (b.position - a.position).cross(c.position - a.position);
+            assert!(facing.length() > 1e-6, "{triangle:?} has no area");
+            for vertex in [a, b, c] {
+                assert!(facing.dot(vertex.normal) > 0.0, "{triangle:?} faces away from its normals");
+            }
+        }
+    }
+
+    #[test]
+    fn counts() {
+        let counts = |(vertices, indices): (Vec<ModelVertex>, Vec<u32>)| {
+            assert!(indices.iter().all(|&index| (index as usize) < vertices.len()));
+            (vertices.len(), indices.len())
+        };
+
+        assert_eq!(counts(quad(Vec2::ONE)), (4, 6));
+        assert_eq!(counts(cube(1.0)), (24, 36));
+        assert_eq!(counts(plane(Vec2::ONE, 1)), (4, 6));
+        assert_eq!(counts(plane(Vec2::ONE, 4)), (25, 96));
+        assert_eq!(counts(sphere(1.0, 2, 3)), (12, 18));
+        assert_eq!(counts(sphere(1.0, 8, 16)), (153, 672));
+    }
+
+    #[test]
+    fn cube_normals_point_out() {
+        let size = 2.0;
+        let shape = cube(size);
+        for vertex in &shape.0 {
+            assert!((vertex.normal.length() - 1.0).abs() < 1e-6);
+            // every vertex sits on the face its normal points out of
+            assert!((vertex.position.dot(vertex.normal) - size / 2.0).abs() < 1e-6, "{vertex:?}");
+            assert!(vertex.position.abs().max_element() <= size / 2.0);
+        }
+        assert_wound_outwards(&shape);
+
+        // one face per direction
+        let mut normals = shape.0.iter().map(|vertex| vertex.normal.to_array().map(f32::to_bits)).collect::<Vec<_>>();
+        normals.sort();
+        normals.dedup();
+        assert_eq!(normals.len(), 6);
+    }
+
+    #[test]
+    fn shapes_face_outwards() {
+        assert_wound_outwards(&quad(Vec2::new(2.0, 1.0)));
+        assert_wound_outwards(&plane(Vec2::new(3.0, 2.0), 3));
+
+        let radius = 1.5;
+        let shape = sphere(radius, 6, 12);
+        for vertex in &shape.0 {
+            assert!((vertex.normal.length() - 1.0).abs() < 1e-5);
+            assert!((vertex.position - vertex.normal * radius).length() < 1e-5);
+        }
+        assert_wound_outwards(&shape);
+
+        let (vertices, _) = plane(Vec2::new(3.0, 2.0), 3);
+        let (min, max) = vertices.iter().fold((Vec3::MAX, Vec3::MIN), |(min, max), vertex| (min.min(vertex.position), max.max(vertex.position)));
+        assert_eq!((min, max), (Vec3::new(-1.5, 0.0, -1.0), Vec3::new(1.5, 0.0, 1.0)));
+    }
+}
@@ -0,0 +1,60 @@
+use std::iter::Sum;
+use std::ops::{AddAssign, Range};
+use std::time::Duration;
+
+/// What went into drawing a frame, for profiling
+///
+/// more gets counted as the renderer draws more things, so only read the fields that are needed
+#[derive(Debug, Copy, Clone, Default, Eq, PartialEq)]
+pub struct FrameStats {
+    pub draw_calls: u32,
+    /// draws made by the depth pre-pass, kept out of the other counts so they add up the same with it on or off
+    pub prepass_draw_calls: u32,
+    /// added up over every draw call, drawing something without instancing counts as one instance
+    pub instances_drawn: u32,
+    /// lines and points don't count
+    pub triangles: u64,
+    /// how long the frame took to record and submit, not counting the time the GPU spends on it
+    pub cpu_time: Duration,
+}
+
+impl FrameStats {
+    /// A single draw call of the `instances`, each made of `triangles` triangles
+    pub fn draw(triangles: u32, instances: Range<u32>) -> Self {
+        let instances = instances.end.saturating_sub(instances.start);
+        Self {
+            draw_calls: 1,
+            prepass_draw_calls: 0,
+            instances_drawn: instances,
+            triangles: u64::from(triangles) * u64::from(instances),
+            cpu_time: Duration::ZERO,
+        }
+    }
+
+    /// The same draws made by the depth pre-pass, only counted in [`FrameStats::prepass_draw_calls`]
+    pub fn in_prepass(self) -> Self {
+        Self {
+            prepass_draw_calls: self.prepass_draw_calls + self.draw_calls,
+            ..Self::default()
+        }
+    }
+}
+
+impl AddAssign for FrameStats {
+    fn add_assign(&mut self, rhs: Self) {
+        self.draw_calls += rhs.draw_calls;
+        self.prepass_draw_calls += rhs.prepass_draw_calls;
+        self.instances_drawn += rhs.instances_drawn;
+        self.triangles += rhs.triangles;
+        self.cpu_time += rhs.cpu_time;
+    }
+}
+
+impl Sum for FrameStats {
+    fn sum<I: Iterator<Item = Self>>(iter: I) -> Self {
+        iter.fold(Self::default(), |mut total, stats| {
+            total += stats;
+            total
+        })
+    }
+}
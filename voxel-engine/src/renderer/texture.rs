@@ -1,42 +1,327 @@
-use std::ffi::OsStr;
-use std::path::Path;
-use anyhow::Result;
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use anyhow::{bail, ensure, Context, Result};
+use image::RgbaImage;
+use crate::assets::AssetSource;
+use crate::settings::{DepthMode, TextureFilter};
 
+impl TextureFilter {
+    fn filter_mode(self) -> wgpu::FilterMode {
+        match self {
+            TextureFilter::Nearest => wgpu::FilterMode::Nearest,
+            TextureFilter::Linear => wgpu::FilterMode::Linear,
+        }
+    }
+
+    /// What the layout of a bind group has to declare for a sampler using this filter
+    pub fn sampler_binding_type(self) -> wgpu::SamplerBindingType {
+        match self {
+            TextureFilter::Nearest => wgpu::SamplerBindingType::NonFiltering,
+            TextureFilter::Linear => wgpu::SamplerBindingType::Filtering,
+        }
+    }
+}
+
+/// How the values in an image relate to the colors they stand for
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Hash)]
+pub enum ColorSpace {
+    /// colors meant to be looked at, like diffuse textures, the GPU linearizes them when sampling
+    Srgb,
+    /// data that just happens to be stored in an image, like normal maps
+    Linear,
+}
+
+impl ColorSpace {
+    pub fn format(self) -> wgpu::TextureFormat {
+        match self {
+            ColorSpace::Srgb => wgpu::TextureFormat::Rgba8UnormSrgb,
+            ColorSpace::Linear => wgpu::TextureFormat::Rgba8Unorm,
+        }
+    }
+}
+
+/// An image decoded into memory, not yet on the GPU
+pub struct DecodedImage {
+    image: RgbaImage,
+    label: Option<String>,
+}
+
+impl DecodedImage {
+    fn decode(bytes: &[u8], label: Option<String>) -> Result<Self> {
+        let image = image::load_from_memory(bytes)?.into_rgba8();
+        Ok(Self { image, label })
+    }
+
+    #[cfg_attr(not(test), expect(dead_code, reason = "nothing decodes textures ahead of time yet"))]
+    pub fn dimensions(&self) -> (u32, u32) {
+        self.image.dimensions()
+    }
+
+    /// Puts the image on the GPU, this is cheap next to decoding it
+    #[cfg_attr(not(test), expect(dead_code, reason = "nothing decodes textures ahead of time yet"))]
+    pub fn upload(
+        self,
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        color_space: ColorSpace,
+        filter: TextureFilter
+    ) -> Result<Texture> {
+        let image = image::DynamicImage::ImageRgba8(self.image);
+        Texture::from_image(device, queue, &image, self.label.as_deref(), color_space, filter)
+    }
+}
+
+#[derive(Clone)]
 pub struct Texture {
     pub view: wgpu::TextureView,
     pub sampler: wgpu::Sampler,
+    pub size: wgpu::Extent3d,
+    #[cfg_attr(not(test), expect(dead_code, reason = "nothing needs to know a loaded texture's format yet"))]
+    pub format: wgpu::TextureFormat,
+}
+
+impl DepthMode {
+    /// Reversed depth needs a float format to be any more precise
+    pub fn format(self) -> wgpu::TextureFormat {
+        Texture::DEPTH_FORMAT
+    }
+
+    /// Passes whatever is in front of what's already drawn
+    pub fn compare(self) -> wgpu::CompareFunction {
+        match self {
+            DepthMode::Standard => wgpu::CompareFunction::Less,
+            DepthMode::Reversed => wgpu::CompareFunction::Greater,
+        }
+    }
+
+    /// Passes whatever is in front of or at the same depth as what's already drawn
+    pub fn compare_or_equal(self) -> wgpu::CompareFunction {
+        match self {
+            DepthMode::Standard => wgpu::CompareFunction::LessEqual,
+            DepthMode::Reversed => wgpu::CompareFunction::GreaterEqual,
+        }
+    }
+
+    /// The depth of nothing having been drawn, as far away as it gets
+    pub fn clear_value(self) -> f32 {
+        match self {
+            DepthMode::Standard => 1.0,
+            DepthMode::Reversed => 0.0,
+        }
+    }
 }
 
 impl Texture {
     pub const DEPTH_FORMAT: wgpu::TextureFormat = wgpu::TextureFormat::Depth32Float;
     
-    fn from_file_inner(
+    /// Reads and decodes the image at `path` without holding up the calling thread,
+    /// so only the [upload](DecodedImage::upload) is left for the render thread
+    #[cfg_attr(not(test), expect(dead_code, reason = "nothing decodes textures ahead of time yet"))]
+    pub async fn from_file_async<P: Into<PathBuf>>(path: P) -> Result<DecodedImage> {
+        let path = path.into();
+        let bytes = voxel_runtime::fs::read(&*path)
+            .await
+            .with_context(|| format!("failed to read {}", path.display()))?;
+
+        let label = path.file_name().map(|name| name.to_string_lossy().into_owned());
+        voxel_runtime::spawn(move || DecodedImage::decode(&bytes, label))
+            .await
+            .with_context(|| format!("failed to decode {}", path.display()))
+    }
+
+    /// Decodes an image file that was already read into memory, `label` being its name
+    pub fn from_bytes(
         device: &wgpu::Device,
         queue: &wgpu::Queue,
-        path: &Path
+        bytes: &[u8],
+        label: &str,
+        color_space: ColorSpace,
+        filter: TextureFilter
     ) -> Result<Self> {
-        let image = image::open(path)?;
+        let image = image::load_from_memory(bytes).with_context(|| format!("failed to decode {label}"))?;
+        Self::from_image(device, queue, &image, Some(label), color_space, filter)
+    }
+
+    /// A single white texel, sampling it leaves a color as it is
+    fn white_image() -> RgbaImage {
+        RgbaImage::from_pixel(1, 1, image::Rgba([u8::MAX; 4]))
+    }
+
+    /// A magenta and black checkerboard, hard to mistake for a real texture
+    fn missing_image() -> RgbaImage {
+        const MAGENTA: image::Rgba<u8> = image::Rgba([255, 0, 255, 255]);
+        const BLACK: image::Rgba<u8> = image::Rgba([0, 0, 0, 255]);
+
+        RgbaImage::from_fn(2, 2, |x, y| match (x + y) % 2 {
+            0 => MAGENTA,
+            _ => BLACK,
+        })
+    }
+
+    fn from_generated(device: &wgpu::Device, queue: &wgpu::Queue, image: RgbaImage, label: &str) -> Self {
+        // the texels are meant to stay crisp, and a nearest sampler fits a layout made for any filter
         Self::from_image(
             device,
             queue,
-            &image,
-            path.file_name().map(OsStr::to_string_lossy).as_deref()
-        )
+            &image::DynamicImage::ImageRgba8(image),
+            Some(label),
+            ColorSpace::Srgb,
+            TextureFilter::Nearest
+        ).expect("a tiny texture always fits on the device")
     }
-    
-    pub fn from_file<P: AsRef<Path>>(
+
+    /// Stands in for the texture of materials that are only a color, [`TextureCache::white`] is one to share
+    pub fn default_white(device: &wgpu::Device, queue: &wgpu::Queue) -> Self {
+        Self::from_generated(device, queue, Self::white_image(), "default white texture")
+    }
+
+    /// Stands in for a texture that couldn't be found, [`TextureCache::missing`] is one to share
+    pub fn missing(device: &wgpu::Device, queue: &wgpu::Queue) -> Self {
+        Self::from_generated(device, queue, Self::missing_image(), "missing texture")
+    }
+
+    /// Loads every image into its own layer of a single `D2Array` texture,
+    /// the layer index is the image's index in `paths`
+    #[expect(dead_code, reason = "chunk meshes are colored by block id, nothing loads the block texture atlas yet")]
+    pub fn from_files_array<P: AsRef<Path>>(
         device: &wgpu::Device,
         queue: &wgpu::Queue,
-        path: P
+        paths: &[P],
+        label: Option<&str>
     ) -> Result<Self> {
-        Self::from_file_inner(device, queue, path.as_ref())
+        let images = paths
+            .iter()
+            .map(|path| {
+                let path = path.as_ref();
+                image::open(path)
+                    .map(|image| image.into_rgba8())
+                    .with_context(|| format!("failed to load array layer {}", path.display()))
+            })
+            .collect::<Result<Vec<_>>>()?;
+
+        Self::from_images_array(device, queue, &images, label)
+    }
+
+    /// The size every layer of an array texture has,
+    /// erroring if the images don't all share one
+    fn array_layer_size(images: &[RgbaImage]) -> Result<(u32, u32)> {
+        let Some((first, rest)) = images.split_first() else {
+            bail!("an array texture needs at least one layer")
+        };
+
+        let dimensions = first.dimensions();
+        for (layer, image) in rest.iter().enumerate() {
+            ensure!(
+                image.dimensions() == dimensions,
+                "array layer {layer} is {actual:?} but every layer has to be {dimensions:?}",
+                layer = layer + 1,
+                actual = image.dimensions(),
+            );
+        }
+
+        Ok(dimensions)
+    }
+
+    pub fn from_images_array(
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        images: &[RgbaImage],
+        label: Option<&str>
+    ) -> Result<Self> {
+        let (width, height) = Self::array_layer_size(images)?;
+        let layers = u32::try_from(images.len())
+            .ok()
+            .filter(|&layers| layers <= device.limits().max_texture_array_layers)
+            .with_context(|| format!("{} layers is more than the device supports", images.len()))?;
+
+        let size = wgpu::Extent3d {
+            width,
+            height,
+            depth_or_array_layers: layers,
+        };
+        let texture = device.create_texture(
+            &wgpu::TextureDescriptor {
+                label,
+                size,
+                mip_level_count: 1,
+                sample_count: 1,
+                dimension: wgpu::TextureDimension::D2,
+                format: ColorSpace::Srgb.format(),
+                usage: wgpu::TextureUsages::TEXTURE_BINDING | wgpu::TextureUsages::COPY_DST,
+                view_formats: &[],
+            }
+        );
+
+        for (layer, image) in (0..).zip(images) {
+            queue.write_texture(
+                wgpu::TexelCopyTextureInfo {
+                    aspect: wgpu::TextureAspect::All,
+                    texture: &texture,
+                    mip_level: 0,
+                    origin: wgpu::Origin3d { x: 0, y: 0, z: layer },
+                },
+                image,
+                wgpu::TexelCopyBufferLayout {
+                    offset: 0,
+                    bytes_per_row: Some(4 * width),
+                    rows_per_image: Some(height),
+                },
+                wgpu::Extent3d { depth_or_array_layers: 1, ..size },
+            );
+        }
+
+        let view = texture.create_view(&wgpu::TextureViewDescriptor {
+            dimension: Some(wgpu::TextureViewDimension::D2Array),
+            ..Default::default()
+        });
+        let sampler = device.create_sampler(
+            &wgpu::SamplerDescriptor {
+                address_mode_u: wgpu::AddressMode::Repeat,
+                address_mode_v: wgpu::AddressMode::Repeat,
+                address_mode_w: wgpu::AddressMode::ClampToEdge,
+                mag_filter: wgpu::FilterMode::Nearest,
+                min_filter: wgpu::FilterMode::Nearest,
+                mipmap_filter: wgpu::FilterMode::Nearest,
+                ..Default::default()
+            }
+        );
+
+        Ok(Self { view, sampler, size, format: ColorSpace::Srgb.format() })
+    }
+
+    /// The layout of a bind group holding an array texture at binding 0 and its sampler at binding 1
+    #[cfg_attr(not(test), expect(dead_code, reason = "the chunk pipeline colors blocks by id, it doesn't bind the block texture atlas yet"))]
+    pub fn array_bind_group_layout(device: &wgpu::Device) -> wgpu::BindGroupLayout {
+        device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            entries: &[
+                wgpu::BindGroupLayoutEntry {
+                    binding: 0,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Texture {
+                        multisampled: false,
+                        view_dimension: wgpu::TextureViewDimension::D2Array,
+                        sample_type: wgpu::TextureSampleType::Float { filterable: true },
+                    },
+                    count: None,
+                },
+                wgpu::BindGroupLayoutEntry {
+                    binding: 1,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Sampler(wgpu::SamplerBindingType::Filtering),
+                    count: None,
+                },
+            ],
+            label: Some("texture_array_bind_group_layout"),
+        })
     }
 
     pub fn from_image(
         device: &wgpu::Device,
         queue: &wgpu::Queue,
         img: &image::DynamicImage,
-        label: Option<&str>
+        label: Option<&str>,
+        color_space: ColorSpace,
+        filter: TextureFilter
     ) -> Result<Self> {
         let rgba = match img {
             image::DynamicImage::ImageRgba8(x) => x,
@@ -57,7 +342,7 @@ impl Texture {
                 mip_level_count: 1,
                 sample_count: 1,
                 dimension: wgpu::TextureDimension::D2,
-                format: wgpu::TextureFormat::Rgba8UnormSrgb,
+                format: color_space.format(),
                 usage: wgpu::TextureUsages::TEXTURE_BINDING | wgpu::TextureUsages::COPY_DST,
                 view_formats: &[],
             }
@@ -70,7 +355,7 @@ impl Texture {
                 mip_level: 0,
                 origin: wgpu::Origin3d::ZERO,
             },
-            &rgba,
+            rgba,
             wgpu::TexelCopyBufferLayout {
                 offset: 0,
                 bytes_per_row: Some(4 * dimensions.0),
@@ -85,18 +370,23 @@ impl Texture {
                 address_mode_u: wgpu::AddressMode::ClampToEdge,
                 address_mode_v: wgpu::AddressMode::ClampToEdge,
                 address_mode_w: wgpu::AddressMode::ClampToEdge,
-                mag_filter: wgpu::FilterMode::Nearest,
-                min_filter: wgpu::FilterMode::Nearest,
+                mag_filter: filter.filter_mode(),
+                min_filter: filter.filter_mode(),
                 mipmap_filter: wgpu::FilterMode::Nearest,
                 ..Default::default()
             }
         );
 
-        Ok(Self { view, sampler })
+        Ok(Self { view, sampler, size, format: color_space.format() })
     }
 
 
-    pub fn create_depth_texture(device: &wgpu::Device, config: &wgpu::SurfaceConfiguration, label: &str) -> Self {
+    pub fn create_depth_texture(
+        device: &wgpu::Device,
+        config: &wgpu::SurfaceConfiguration,
+        mode: DepthMode,
+        label: &str
+    ) -> Self {
         let size = wgpu::Extent3d { // 2.
             width: config.width.max(1),
             height: config.height.max(1),
@@ -109,7 +399,7 @@ impl Texture {
             mip_level_count: 1,
             sample_count: 1,
             dimension: wgpu::TextureDimension::D2,
-            format: Self::DEPTH_FORMAT,
+            format: mode.format(),
             usage: wgpu::TextureUsages::RENDER_ATTACHMENT // 3.
                 | wgpu::TextureUsages::TEXTURE_BINDING,
             view_formats: &[],
@@ -125,13 +415,268 @@ impl Texture {
                 mag_filter: wgpu::FilterMode::Linear,
                 min_filter: wgpu::FilterMode::Linear,
                 mipmap_filter: wgpu::FilterMode::Nearest,
-                compare: Some(wgpu::CompareFunction::LessEqual), // 5.
+                compare: Some(mode.compare_or_equal()), // 5.
                 lod_min_clamp: 0.0,
                 lod_max_clamp: 100.0,
                 ..Default::default()
             }
         );
 
-        Self { view, sampler }
+        Self { view, sampler, size, format: mode.format() }
+    }
+}
+
+/// The textures every model gets drawn with, each file in the assets is only loaded once however many materials use it,
+/// along with the placeholders for materials without a texture
+pub struct TextureCache {
+    filter: TextureFilter,
+    white: Texture,
+    missing: Texture,
+    loaded: HashMap<(PathBuf, ColorSpace), Texture>,
+}
+
+impl TextureCache {
+    /// The textures loaded through it get sampled with `filter`
+    pub fn new(device: &wgpu::Device, queue: &wgpu::Queue, filter: TextureFilter) -> Self {
+        Self {
+            filter,
+            white: Texture::default_white(device, queue),
+            missing: Texture::missing(device, queue),
+            loaded: HashMap::new(),
+        }
+    }
+
+    pub fn filter(&self) -> TextureFilter {
+        self.filter
+    }
+
+    /// See [`Texture::default_white`]
+    pub fn white(&self) -> &Texture {
+        &self.white
+    }
+
+    /// See [`Texture::missing`]
+    pub fn missing(&self) -> &Texture {
+        &self.missing
+    }
+
+    /// The texture at `path` in `source`, only read the first time it's asked for,
+    /// a texture that fails to load is tried again the next time
+    pub fn load(
+        &mut self,
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        source: &dyn AssetSource,
+        path: &Path,
+        color_space: ColorSpace,
+    ) -> Result<Texture> {
+        let key = (path.to_path_buf(), color_space);
+        if let Some(texture) = self.loaded.get(&key) {
+            return Ok(texture.clone());
+        }
+
+        let bytes = source.read(path)?;
+        let texture = Texture::from_bytes(device, queue, &bytes, &path.display().to_string(), color_space, self.filter)?;
+        self.loaded.insert(key, texture.clone());
+        Ok(texture)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn solid(width: u32, height: u32, value: u8) -> RgbaImage {
+        RgbaImage::from_pixel(width, height, image::Rgba([value; 4]))
+    }
+
+    fn png(image: &RgbaImage) -> Vec<u8> {
+        let mut bytes = std::io::Cursor::new(Vec::new());
+        image.write_to(&mut bytes, image::ImageFormat::Png).unwrap();
+        bytes.into_inner()
+    }
+
+    #[test]
+    fn decode_images() {
+        let decoded = DecodedImage::decode(&png(&solid(3, 5, 9)), None).unwrap();
+        assert_eq!(decoded.dimensions(), (3, 5));
+        assert_eq!(decoded.image, solid(3, 5, 9));
+        assert!(DecodedImage::decode(b"not an image", None).is_err());
+
+        let dir = std::env::temp_dir().join(format!("voxel-texture-test-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("decode.png");
+        std::fs::write(&path, png(&solid(7, 2, 100))).unwrap();
+
+        let decoded = voxel_runtime::block_on(Texture::from_file_async(&path)).unwrap();
+        assert_eq!(decoded.dimensions(), (7, 2));
+        assert_eq!(decoded.label.as_deref(), Some("decode.png"));
+        assert!(voxel_runtime::block_on(Texture::from_file_async(dir.join("missing.png"))).is_err());
+        std::fs::remove_dir_all(&dir).unwrap();
+
+        let Some((device, queue)) = crate::renderer::test_device() else {
+            return;
+        };
+        let texture = decoded.upload(&device, &queue, ColorSpace::Srgb, TextureFilter::Nearest).unwrap();
+        assert_eq!((texture.size.width, texture.size.height), (7, 2));
+    }
+
+    #[test]
+    fn default_textures() {
+        let white = Texture::white_image();
+        assert_eq!(white.dimensions(), (1, 1));
+        assert_eq!(white.as_raw(), &[255; 4]);
+
+        let missing = Texture::missing_image();
+        assert_eq!(missing.dimensions(), (2, 2));
+        assert_eq!(missing.get_pixel(0, 0), missing.get_pixel(1, 1));
+        assert_eq!(missing.get_pixel(0, 0).0, [255, 0, 255, 255]);
+        assert_eq!(missing.get_pixel(1, 0).0, [0, 0, 0, 255]);
+        assert_eq!(missing.get_pixel(0, 1), missing.get_pixel(1, 0));
+
+        let Some((device, queue)) = crate::renderer::test_device() else {
+            return;
+        };
+        let white = Texture::default_white(&device, &queue);
+        assert_eq!((white.size.width, white.size.height), (1, 1));
+        let missing = Texture::missing(&device, &queue);
+        assert_eq!((missing.size.width, missing.size.height), (2, 2));
+        assert_eq!(missing.format, wgpu::TextureFormat::Rgba8UnormSrgb);
+    }
+
+    /// Hands out the same image for every path, counting how many times it was asked
+    struct CountingSource(std::sync::atomic::AtomicUsize);
+
+    impl AssetSource for CountingSource {
+        fn read(&self, path: &Path) -> Result<Vec<u8>> {
+            self.0.fetch_add(1, std::sync::atomic::Ordering::Relaxed);
+            ensure!(path != Path::new("missing.png"), "there is no {}", path.display());
+            let mut bytes = std::io::Cursor::new(Vec::new());
+            solid(2, 2, 255).write_to(&mut bytes, image::ImageFormat::Png)?;
+            Ok(bytes.into_inner())
+        }
+    }
+
+    #[test]
+    fn cached_textures_load_once_per_path() {
+        let Some((device, queue)) = crate::renderer::test_device() else {
+            return;
+        };
+        let source = CountingSource(Default::default());
+        let reads = || source.0.load(std::sync::atomic::Ordering::Relaxed);
+        let mut cache = TextureCache::new(&device, &queue, TextureFilter::Nearest);
+
+        let first = cache.load(&device, &queue, &source, Path::new("cube/a.png"), ColorSpace::Srgb).unwrap();
+        let again = cache.load(&device, &queue, &source, Path::new("cube/a.png"), ColorSpace::Srgb).unwrap();
+        assert_eq!(reads(), 1);
+        assert_eq!(first.view, again.view);
+
+        // a different file, or the same one read as data, is a different texture
+        cache.load(&device, &queue, &source, Path::new("other/a.png"), ColorSpace::Srgb).unwrap();
+        cache.load(&device, &queue, &source, Path::new("cube/a.png"), ColorSpace::Linear).unwrap();
+        assert_eq!(reads(), 3);
+        assert_eq!(cache.loaded.len(), 3);
+
+        // failures aren't remembered
+        assert!(cache.load(&device, &queue, &source, Path::new("missing.png"), ColorSpace::Srgb).is_err());
+        assert!(cache.load(&device, &queue, &source, Path::new("missing.png"), ColorSpace::Srgb).is_err());
+        assert_eq!(reads(), 5);
+        assert_eq!(cache.loaded.len(), 3);
+    }
+
+    #[test]
+    fn array_layers_must_match() {
+        assert!(Texture::array_layer_size(&[]).is_err());
+        assert_eq!(Texture::array_layer_size(&[solid(4, 4, 0), solid(4, 4, 1)]).unwrap(), (4, 4));
+        assert!(Texture::array_layer_size(&[solid(4, 4, 0), solid(4, 8, 1)]).is_err());
+    }
+
+    #[test]
+    fn array_texture_has_a_layer_per_image() {
+        let Some((device, queue)) = crate::renderer::test_device() else {
+            return;
+        };
+
+        let images = [solid(16, 16, 0), solid(16, 16, 127), solid(16, 16, 255)];
+        let texture = Texture::from_images_array(&device, &queue, &images, Some("test array")).unwrap();
+        assert_eq!(texture.size.depth_or_array_layers, 3);
+        assert_eq!((texture.size.width, texture.size.height), (16, 16));
+
+        let layout = Texture::array_bind_group_layout(&device);
+        device.push_error_scope(wgpu::ErrorFilter::Validation);
+        let _bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+            layout: &layout,
+            entries: &[
+                wgpu::BindGroupEntry {
+                    binding: 0,
+                    resource: wgpu::BindingResource::TextureView(&texture.view),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 1,
+                    resource: wgpu::BindingResource::Sampler(&texture.sampler),
+                },
+            ],
+            label: None,
+        });
+        let error = voxel_runtime::block_on(device.pop_error_scope());
+        assert!(error.is_none(), "{error:?}");
+
+        let mismatched = [solid(16, 16, 0), solid(8, 8, 0)];
+        assert!(Texture::from_images_array(&device, &queue, &mismatched, None).is_err());
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn textures_use_the_format_of_their_color_space() {
+        let Some((device, queue)) = crate::renderer::test_device() else {
+            return;
+        };
+
+        let image = image::DynamicImage::ImageRgba8(solid(4, 4, 128));
+        for (color_space, format) in [
+            (ColorSpace::Srgb, wgpu::TextureFormat::Rgba8UnormSrgb),
+            (ColorSpace::Linear, wgpu::TextureFormat::Rgba8Unorm),
+        ] {
+            let texture = Texture::from_image(&device, &queue, &image, None, color_space, TextureFilter::Nearest).unwrap();
+            assert_eq!(texture.format, format);
+        }
+    }
+
+    #[test]
+    fn samplers_match_the_layout_for_their_filter() {
+        let Some((device, queue)) = crate::renderer::test_device() else {
+            return;
+        };
+
+        let image = image::DynamicImage::ImageRgba8(solid(4, 4, 200));
+        let bind = |texture_filter, layout_filter| {
+            let texture = Texture::from_image(&device, &queue, &image, None, ColorSpace::Srgb, texture_filter).unwrap();
+            let layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+                entries: &[wgpu::BindGroupLayoutEntry {
+                    binding: 0,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Sampler(TextureFilter::sampler_binding_type(layout_filter)),
+                    count: None,
+                }],
+                label: None,
+            });
+
+            device.push_error_scope(wgpu::ErrorFilter::Validation);
+            let _bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+                layout: &layout,
+                entries: &[wgpu::BindGroupEntry {
+                    binding: 0,
+                    resource: wgpu::BindingResource::Sampler(&texture.sampler),
+                }],
+                label: None,
+            });
+            voxel_runtime::block_on(device.pop_error_scope())
+        };
+
+        for filter in [TextureFilter::Nearest, TextureFilter::Linear] {
+            let error = bind(filter, filter);
+            assert!(error.is_none(), "{filter:?}: {error:?}");
+        }
+        // a linear sampler can't go where the layout promised there would be no filtering
+        assert!(bind(TextureFilter::Linear, TextureFilter::Nearest).is_some());
+    }
+}
@@ -2,12 +2,14 @@ use std::convert::Infallible;
 use std::marker::PhantomData;
 use std::num::NonZero;
 use std::ops::Deref;
-use std::sync::Arc;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex, PoisonError};
 use std::time::Duration;
 use arc_swap::{ArcSwap, Guard};
 use serde::{Deserialize, Deserializer, Serialize, Serializer};
 use winit::window::Icon;
-use voxel_runtime::sync::Unparker;
+use voxel_runtime::sync::{mpsc, Parker, Unparker};
+use crate::assets::AssetSource;
 
 #[derive(Debug, Copy, Clone, Serialize, Deserialize, Eq, PartialEq, Default)]
 pub enum FullscreenMode {
@@ -77,9 +79,68 @@ pub enum Vsync {
     Off,
 }
 
+/// A size of the window in logical pixels, so it's the same size on screen no matter the monitor's scale factor
+#[derive(Debug, Copy, Clone, Serialize, Deserialize, Eq, PartialEq)]
+pub struct WindowSize {
+    pub width: u32,
+    pub height: u32,
+}
+
+impl WindowSize {
+    pub const fn new(width: u32, height: u32) -> Self {
+        Self { width, height }
+    }
+
+    /// At least as wide and as tall as `min`
+    pub fn at_least(self, min: Self) -> Self {
+        Self::new(self.width.max(min.width), self.height.max(min.height))
+    }
+}
+
+impl From<WindowSize> for winit::dpi::Size {
+    fn from(size: WindowSize) -> Self {
+        winit::dpi::LogicalSize::new(size.width, size.height).into()
+    }
+}
+
+/// Which monitor the window opens on
+#[derive(Debug, Clone, Serialize, Deserialize, Eq, PartialEq)]
+#[serde(untagged)]
+pub enum MonitorChoice {
+    /// where it is in the system's list of monitors, counting from 0, which can change when they get plugged in differently
+    Index(usize),
+    /// the name the system gives the monitor
+    Name(String),
+}
+
+/// Where the window's top left corner is on the desktop in physical pixels, the same ones monitors are placed in
+#[derive(Debug, Copy, Clone, Serialize, Deserialize, Eq, PartialEq)]
+pub struct WindowPosition {
+    pub x: i32,
+    pub y: i32,
+}
+
+impl From<winit::dpi::PhysicalPosition<i32>> for WindowPosition {
+    fn from(position: winit::dpi::PhysicalPosition<i32>) -> Self {
+        Self { x: position.x, y: position.y }
+    }
+}
+
+impl From<WindowPosition> for winit::dpi::PhysicalPosition<i32> {
+    fn from(position: WindowPosition) -> Self {
+        Self::new(position.x, position.y)
+    }
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize, Eq, PartialEq)]
 pub struct GameTitle(Box<str>);
 
+impl GameTitle {
+    pub fn new(title: impl Into<Box<str>>) -> Self {
+        Self(title.into())
+    }
+}
+
 impl Default for GameTitle {
     fn default() -> Self {
         GameTitle("Game of Voxels".into())
@@ -97,18 +158,288 @@ impl Deref for GameTitle {
 
 
 
-#[derive(Default, Debug, Clone, Serialize, Deserialize, PartialEq)]
+/// How textures are sampled between texels
+#[derive(Debug, Copy, Clone, Serialize, Deserialize, Eq, PartialEq, Default)]
+pub enum TextureFilter {
+    /// crisp texels, for the pixel-art look
+    #[default]
+    Nearest,
+    /// blends neighboring texels
+    Linear,
+}
+
+/// How the mouse motion of a frame turns into turning the camera
+#[derive(Debug, Copy, Clone, Serialize, Deserialize, PartialEq, Default)]
+pub enum AimCurve {
+    /// turns exactly as far as the mouse moved
+    #[default]
+    Linear,
+    /// the motion is scaled by its length to the power of `exponent - 1`, so fast flicks turn further,
+    /// but never by more than `cap`
+    Power {
+        exponent: f32,
+        cap: f32,
+    },
+}
+
+/// How an analog stick's position turns into motion
+#[derive(Debug, Copy, Clone, Serialize, Deserialize, PartialEq)]
+#[serde(default)]
+pub struct StickSettings {
+    /// how far from the center the stick has to be pushed to count, as a fraction of all the way
+    pub deadzone: f32,
+    /// what pushing the stick all the way gets scaled to
+    pub sensitivity: f32,
+}
+
+impl Default for StickSettings {
+    fn default() -> Self {
+        Self {
+            deadzone: 0.15,
+            sensitivity: 1.0,
+        }
+    }
+}
+
+/// How jagged edges get smoothed out
+#[derive(Debug, Copy, Clone, Serialize, Deserialize, Eq, PartialEq, Default)]
+pub enum AntiAliasing {
+    #[default]
+    Off,
+    /// blurs along the edges found in the finished frame, cheap but softens textures a little
+    Fxaa,
+}
+
+/// Which faces of the scene's triangles don't get drawn
+#[derive(Debug, Copy, Clone, Serialize, Deserialize, Eq, PartialEq, Default)]
+pub enum CullMode {
+    /// the ones facing away from the camera, which are always behind something solid
+    #[default]
+    Back,
+    /// the ones facing the camera, showing the inside of things
+    Front,
+    /// draws both sides, for meshes that are inside out or only one sided
+    None,
+}
+
+/// Which way depth runs in the depth buffer
+#[derive(Debug, Copy, Clone, Serialize, Deserialize, Eq, PartialEq, Default)]
+pub enum DepthMode {
+    /// 0 at the near plane and 1 at the far plane
+    #[default]
+    Standard,
+    /// 1 at the near plane and 0 at the far plane, floats are much more precise close to 0
+    /// so this keeps far away things from fighting over the same depth
+    Reversed,
+}
+
+#[derive(Debug, Copy, Clone, Serialize, Deserialize, Eq, PartialEq)]
+#[serde(default)]
+pub struct DayNightSettings {
+    /// how many seconds a whole day and night takes
+    pub day_length: NonZero<u32>,
+    /// stops the sun where it is
+    pub paused: bool,
+}
+
+impl Default for DayNightSettings {
+    fn default() -> Self {
+        Self {
+            day_length: const { NonZero::new(20 * 60).unwrap() },
+            paused: false,
+        }
+    }
+}
+
+#[derive(Debug, Copy, Clone, Serialize, Deserialize, PartialEq)]
+#[serde(default)]
+pub struct FogSettings {
+    pub enabled: bool,
+    /// where the fog starts, as a fraction of the view distance
+    pub start: f32,
+    /// where the fog hides everything, as a fraction of the view distance
+    pub end: f32,
+}
+
+impl Default for FogSettings {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            start: 0.6,
+            end: 1.0,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
 #[non_exhaustive]
 pub struct GameSettings {
     pub game_title: GameTitle,
     pub vsync: Vsync,
     pub fov: Fov,
     pub fullscreen: FullscreenMode,
+    // settings files from before this was added don't have it
+    #[serde(default)]
+    pub day_night: DayNightSettings,
+    #[serde(default)]
+    pub fog: FogSettings,
+    /// the most frames to draw in a second, as many as possible if there is no cap
+    #[serde(default)]
+    pub frame_cap: Option<NonZero<u32>>,
+    /// only read when the renderer starts
+    #[serde(default)]
+    pub texture_filter: TextureFilter,
+    /// only read when the renderer starts
+    #[serde(default)]
+    pub depth_mode: DepthMode,
+    #[serde(default)]
+    pub aim_curve: AimCurve,
+    #[serde(default)]
+    pub stick: StickSettings,
+    /// how many frames can be queued up ahead of the one on screen, between 1 and 3,
+    /// fewer is less input lag and more is smoother
+    #[serde(default = "default_max_frame_latency")]
+    pub max_frame_latency: u32,
+    /// draws to a floating point surface when the display takes one, only read when the renderer starts
+    #[serde(default)]
+    pub hdr: bool,
+    /// how much the lit scene gets brightened (or darkened) before it's tone mapped onto the display
+    #[serde(default = "default_exposure")]
+    pub exposure: f32,
+    /// the color of the outline around the block under the crosshair, as red, green, blue and alpha
+    #[serde(default = "default_outline_color")]
+    pub outline_color: [f32; 4],
+    /// only read when the renderer starts
+    #[serde(default)]
+    pub anti_aliasing: AntiAliasing,
+    /// draws the depth of the scene before its color so every pixel only gets shaded once,
+    /// only read when the renderer starts
+    #[serde(default)]
+    pub depth_prepass: bool,
+    /// only read when the renderer starts
+    #[serde(default)]
+    pub cull_mode: CullMode,
+    /// the window can't be made any smaller than this, only read when the window is created
+    #[serde(default = "default_min_window_size")]
+    pub min_window_size: WindowSize,
+    /// the window can't be made any bigger than this, if it's set, only read when the window is created
+    #[serde(default)]
+    pub max_window_size: Option<WindowSize>,
+    /// whether the window can be resized by dragging its edges, only read when the window is created
+    #[serde(default = "default_resizable")]
+    pub resizable: bool,
+    /// where the window was last left, it opens there again
+    #[serde(default)]
+    pub window_position: Option<WindowPosition>,
+    /// how big the window was last left, it opens at that size again
+    #[serde(default)]
+    pub window_size: Option<WindowSize>,
+    /// the monitor the window opens and goes fullscreen on, the primary one if it's not set or isn't there anymore,
+    /// only read when the window is created or toggled fullscreen
+    #[serde(default)]
+    pub monitor: Option<MonitorChoice>,
+    /// an image in the assets to use as the window's icon instead of the game's own
+    #[serde(default)]
+    pub icon: Option<PathBuf>,
+    /// where the game's assets are, relative to the executable, if not set the `assets` directory next to it
+    /// or in the working directory, only read when the renderer starts
+    #[serde(default)]
+    pub assets_dir: Option<PathBuf>,
+}
+
+fn default_max_frame_latency() -> u32 {
+    2
+}
+
+fn default_exposure() -> f32 {
+    1.0
+}
+
+fn default_outline_color() -> [f32; 4] {
+    [0.0, 0.0, 0.0, 0.6]
+}
+
+fn default_min_window_size() -> WindowSize {
+    WindowSize::new(320, 180)
+}
+
+fn default_resizable() -> bool {
+    true
+}
+
+impl Default for GameSettings {
+    fn default() -> Self {
+        Self {
+            game_title: GameTitle::default(),
+            vsync: Vsync::default(),
+            fov: Fov::default(),
+            fullscreen: FullscreenMode::default(),
+            day_night: DayNightSettings::default(),
+            fog: FogSettings::default(),
+            frame_cap: None,
+            texture_filter: TextureFilter::default(),
+            depth_mode: DepthMode::default(),
+            aim_curve: AimCurve::default(),
+            stick: StickSettings::default(),
+            max_frame_latency: default_max_frame_latency(),
+            hdr: false,
+            exposure: default_exposure(),
+            outline_color: default_outline_color(),
+            anti_aliasing: AntiAliasing::default(),
+            depth_prepass: false,
+            cull_mode: CullMode::default(),
+            min_window_size: default_min_window_size(),
+            max_window_size: None,
+            resizable: default_resizable(),
+            window_position: None,
+            window_size: None,
+            monitor: None,
+            icon: None,
+            assets_dir: None,
+        }
+    }
+}
+
+/// Which of the settings the window was created with differ between two versions of the settings,
+/// the window doesn't follow the settings by itself so these have to be set on it again
+#[derive(Debug, Copy, Clone, Default, Eq, PartialEq)]
+pub struct WindowChanges {
+    pub title: bool,
+    pub icon: bool,
+}
+
+impl WindowChanges {
+    pub fn between(old: &GameSettings, new: &GameSettings) -> Self {
+        Self {
+            title: old.game_title != new.game_title,
+            icon: old.icon != new.icon,
+        }
+    }
+}
+
+/// What the settings saver gets told to do
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+enum SaverCommand {
+    /// the settings changed, they should get saved soon
+    Save,
+    /// the game is closing, save right away and stop
+    Shutdown,
 }
 
 struct GameSettingsHandleInner {
     data: ArcSwap<GameSettings>,
-    modified: Unparker 
+    saver: mpsc::Sender<SaverCommand>,
+    /// held while the settings get replaced, so edits made at the same time don't undo each other
+    writing: Mutex<()>,
+    /// wakes up when whatever saves the settings is done
+    finished: Mutex<Option<Parker>>,
+}
+
+/// What the settings saver waits on, it stops once every handle is gone
+struct SaverSignals {
+    commands: mpsc::Receiver<SaverCommand>,
+    /// dropped when the saver is done
+    finished: Unparker,
 }
 
 #[derive(Clone)]
@@ -128,13 +459,39 @@ impl Deref for LoadedSettings {
 }
 
 impl LoadedSettings {
-    fn load_full(self) -> Arc<GameSettings> {
+    /// Keeps hold of these settings for as long as needed, the [`Arc`] can be shared with other threads
+    pub fn into_arc(self) -> Arc<GameSettings> {
         Guard::into_inner(self.guard)
     }
 }
 
 
 impl GameSettingsHandle {
+    /// A handle to `settings`, and what gets woken up whenever they change or the game closes
+    fn new(settings: GameSettings) -> (Self, SaverSignals) {
+        let (saver, commands) = mpsc::channel();
+        let (finished_parker, finished) = voxel_runtime::sync::make_parker();
+
+        let inner = GameSettingsHandleInner {
+            data: ArcSwap::new(Arc::new(settings)),
+            saver,
+            writing: Mutex::new(()),
+            finished: Mutex::new(Some(finished_parker)),
+        };
+
+        (Self(Arc::new(inner)), SaverSignals { commands, finished })
+    }
+
+    /// Settings that only live in memory, nothing saves them
+    pub fn in_memory(settings: GameSettings) -> Self {
+        Self::new(settings).0
+    }
+
+    /// The settings as they are now, stores made after this don't change it
+    pub fn snapshot(&self) -> Arc<GameSettings> {
+        self.0.data.load_full()
+    }
+
     pub fn load(&self) -> LoadedSettings {
         LoadedSettings {
             guard: self.0.data.load(),
@@ -143,49 +500,118 @@ impl GameSettingsHandle {
     }
 
 
-    #[expect(dead_code, reason = "UI not implemented yet")]
+    #[cfg_attr(not(test), expect(dead_code, reason = "everything changes the settings through edit"))]
     pub fn store(&self, settings: GameSettings) {
+        let _writing = self.0.writing.lock().unwrap_or_else(PoisonError::into_inner);
+        self.store_locked(settings)
+    }
+
+    /// Changes the settings in place, like toggling a single option,
+    /// no other change can land between reading the settings and storing the edited ones
+    ///
+    /// the settings can't be stored from inside `edit`, that would never finish
+    pub fn edit(&self, edit: impl FnOnce(&mut GameSettings)) {
+        let _writing = self.0.writing.lock().unwrap_or_else(PoisonError::into_inner);
+        let mut settings = GameSettings::clone(&self.load());
+        edit(&mut settings);
+        self.store_locked(settings)
+    }
+
+    fn store_locked(&self, settings: GameSettings) {
         if *self.load() != settings {
             self.0.data.store(Arc::new(settings));
-            self.0.modified.unpark();
+            // nothing saves settings that only live in memory
+            let _ = self.0.saver.send(SaverCommand::Save);
         }
     }
+
+    /// Saves the settings right away and stops saving them after that,
+    /// waits at most `wait` for the save, returning whether it finished
+    pub fn shutdown(&self, wait: Duration) -> bool {
+        let _ = self.0.saver.send(SaverCommand::Shutdown);
+
+        // only the first shutdown waits
+        let Some(mut finished) = self.0.finished.lock().unwrap().take() else {
+            return true
+        };
+        voxel_runtime::block_on(voxel_runtime::time::timeout(wait, finished.park())).is_some()
+    }
 }
 
 const SETTINGS_PATH: &str = "./settings.toml";
 
-fn load_icon_inner() -> anyhow::Result<Icon> {
-    let image = image::load_from_memory(include_bytes!("../assets/icon/voxel-engine256.png"))?.into_rgba8();
-    let (width, height) = image.dimensions();
+/// The sizes the game's icon comes in
+const EMBEDDED_ICONS: [(u32, &[u8]); 2] = [
+    (128, include_bytes!("../assets/icon/voxel-engine128.png")),
+    (256, include_bytes!("../assets/icon/voxel-engine256.png")),
+];
+
+/// How big the window icon gets shown, windows only ever shows it small in the title bar and taskbar
+const ICON_SIZE: u32 = if cfg!(windows) { 32 } else { 256 };
+
+/// Which of the `available` sizes makes the best icon `wanted` pixels across,
+/// the smallest that only has to be scaled down, or the biggest if they are all too small
+fn pick_icon_size(available: impl IntoIterator<Item = u32>, wanted: u32) -> Option<u32> {
+    let (big_enough, too_small) = available.into_iter().partition::<Vec<_>, _>(|&size| size >= wanted);
+    big_enough.into_iter().min().or_else(|| too_small.into_iter().max())
+}
+
+fn icon_from_image(image: image::DynamicImage) -> anyhow::Result<Icon> {
+    let image = match image.width().max(image.height()) > ICON_SIZE {
+        true => image.resize(ICON_SIZE, ICON_SIZE, image::imageops::FilterType::Triangle),
+        false => image,
+    };
 
+    let image = image.into_rgba8();
+    let (width, height) = image.dimensions();
     Ok(Icon::from_rgba(image.into_raw(), width, height)?)
 }
 
-pub fn load_icon() -> Option<Icon> {
+fn load_icon_inner() -> anyhow::Result<Icon> {
+    let size = pick_icon_size(EMBEDDED_ICONS.map(|(size, _)| size), ICON_SIZE).expect("there are embedded icons");
+    let (_, bytes) = EMBEDDED_ICONS.into_iter().find(|&(embedded, _)| embedded == size).unwrap();
+    icon_from_image(image::load_from_memory(bytes)?)
+}
+
+/// The window icon, from the image at `custom` in the `assets` if there is one and it loads
+pub fn load_icon(assets: &dyn AssetSource, custom: Option<&Path>) -> Option<Icon> {
+    if let Some(path) = custom {
+        let image = assets.read(path).and_then(|bytes| Ok(image::load_from_memory(&bytes)?));
+        match image.and_then(icon_from_image) {
+            Ok(icon) => return Some(icon),
+            Err(err) => tracing::warn!("unable to load the icon at {}, using the default; {err}", path.display()),
+        }
+    }
+
     load_icon_inner().inspect_err(|err| tracing::error!("unable to load game icon; {err}")).ok()
 }
 
-pub fn load() -> GameSettingsHandle {
-    let game_settings = std::fs::read_to_string(SETTINGS_PATH)
+/// The settings saved on disk, or the defaults if there are none
+pub fn read() -> GameSettings {
+    std::fs::read_to_string(SETTINGS_PATH)
         .ok()
         .and_then(|s| toml::from_str::<GameSettings>(&s).ok())
-        .unwrap_or_default();
-    
-    let swap = ArcSwap::new(Arc::new(game_settings));
-    let (mut parker, unparker) = voxel_runtime::sync::make_parker();
-    
-    let inner = GameSettingsHandleInner {
-        data: swap,
-        modified: unparker
-    };
-    
-    let settings = GameSettingsHandle(Arc::new(inner));
+        .unwrap_or_default()
+}
+
+/// A handle to `game_settings` that saves them to disk whenever they change
+pub fn persisted(game_settings: GameSettings) -> GameSettingsHandle {
+    persisted_to(PathBuf::from(SETTINGS_PATH), game_settings)
+}
+
+/// How long to wait after saving the settings before saving them again
+const SAVE_INTERVAL: Duration = Duration::from_secs(10);
+
+fn persisted_to(path: PathBuf, game_settings: GameSettings) -> GameSettingsHandle {
+    let (settings, signals) = GameSettingsHandle::new(game_settings);
+    let SaverSignals { mut commands, finished } = signals;
 
     let settings_handle = Arc::downgrade(&settings.0);
 
     // spawn non async because these operations (serialization, file writing)
     // and this will live for a long time, don't put this in the blocking pool
     voxel_runtime::rt::spawn_long_lived(move || -> Option<Infallible> {
+        let _finished = finished;
         let mut prev = {
             let handle = settings_handle.upgrade()?;
             handle.data.load_full()
@@ -195,7 +621,7 @@ pub fn load() -> GameSettingsHandle {
             let bytes = toml::to_string_pretty(settings)
                 .expect("should always be able to serialize");
 
-            let res = std::fs::write(SETTINGS_PATH, bytes);
+            let res = std::fs::write(&path, bytes);
             if let Err(err) = res.as_ref() {
                 tracing::error!("Failed to save settings; {err}")
             }
@@ -207,29 +633,192 @@ pub fn load() -> GameSettingsHandle {
 
         loop {
             // join the execution poll and wait
-            voxel_runtime::block_on(async {
-                // Save only at most every 10 seconds
-                voxel_runtime::time::sleep(Duration::from_secs(10)).await;
-                parker.park().await;
-                Some(())
-            })?;
-
-            
+            let command = voxel_runtime::block_on(async {
+                // Save only at most every 10 seconds, unless the game is closing,
+                // changes made in the meantime get saved once the time is up
+                let mut modified = false;
+                let until_closing = async {
+                    loop {
+                        match commands.recv().await {
+                            Some(SaverCommand::Save) => modified = true,
+                            command => return command,
+                        }
+                    }
+                };
+
+                match voxel_runtime::time::timeout(SAVE_INTERVAL, until_closing).await {
+                    Some(command) => command,
+                    None if modified => Some(SaverCommand::Save),
+                    None => commands.recv().await,
+                }
+            });
+
+            // every handle being gone leaves nothing to save
             let handle = GameSettingsHandle(settings_handle.upgrade()?);
+            let closing = command != Some(SaverCommand::Save);
             let current = handle.load();
             let changed = (!Arc::ptr_eq(&current.guard, &prev))
                 || (*current) != *prev;
 
             if last_save_err || changed {
                 match changed {
-                    true => prev = current.load_full(),
+                    true => prev = current.into_arc(),
                     false => drop(current)
                 }
 
                 last_save_err = save(&prev);
             }
+
+            if closing {
+                return None
+            }
         }
-    });
+    }).detach();
 
     settings
-}
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn settings_round_trip() {
+        let settings = GameSettings {
+            fog: FogSettings { enabled: true, start: 0.25, end: 0.9 },
+            day_night: DayNightSettings { paused: true, ..DayNightSettings::default() },
+            frame_cap: NonZero::new(144),
+            texture_filter: TextureFilter::Linear,
+            depth_mode: DepthMode::Reversed,
+            aim_curve: AimCurve::Power { exponent: 1.5, cap: 4.0 },
+            stick: StickSettings { deadzone: 0.2, sensitivity: 2.0 },
+            max_frame_latency: 1,
+            hdr: true,
+            exposure: 1.5,
+            outline_color: [1.0, 1.0, 1.0, 1.0],
+            anti_aliasing: AntiAliasing::Fxaa,
+            depth_prepass: true,
+            cull_mode: CullMode::None,
+            min_window_size: WindowSize::new(640, 480),
+            max_window_size: Some(WindowSize::new(1920, 1080)),
+            resizable: false,
+            window_position: Some(WindowPosition { x: -1920, y: 40 }),
+            window_size: Some(WindowSize::new(1280, 720)),
+            monitor: Some(MonitorChoice::Name("DP-1".to_string())),
+            icon: Some(PathBuf::from("./my icon.png")),
+            assets_dir: Some(PathBuf::from("/usr/share/voxels")),
+            ..GameSettings::default()
+        };
+
+        let toml = toml::to_string_pretty(&settings).unwrap();
+        assert_eq!(toml::from_str::<GameSettings>(&toml).unwrap(), settings);
+    }
+
+    #[test]
+    fn missing_sections_use_defaults() {
+        // a settings file saved before fog and the day cycle existed
+        let old = "game_title = \"Game of Voxels\"\nvsync = \"On\"\nfov = 45\nfullscreen = \"Off\"\n";
+        let settings = toml::from_str::<GameSettings>(old).unwrap();
+        assert_eq!(settings, GameSettings::default());
+        assert!(!settings.fog.enabled);
+
+        let partial = toml::from_str::<FogSettings>("enabled = true").unwrap();
+        assert_eq!(partial, FogSettings { enabled: true, ..FogSettings::default() });
+    }
+
+    #[test]
+    fn shutting_down_saves_right_away() {
+        let dir = std::env::temp_dir().join(format!("voxel-settings-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("settings.toml");
+
+        let settings = persisted_to(path.clone(), GameSettings::default());
+        // well within the wait between saves
+        let changed = GameSettings { fov: Fov::MAX, ..GameSettings::default() };
+        settings.store(changed.clone());
+
+        assert!(settings.shutdown(Duration::from_secs(5)));
+        let saved = std::fs::read_to_string(&path).unwrap();
+        assert_eq!(toml::from_str::<GameSettings>(&saved).unwrap(), changed);
+        // the saver is gone, shutting down again has nothing to wait for
+        assert!(settings.shutdown(Duration::ZERO));
+
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn edits_dont_undo_each_other() {
+        let settings = GameSettingsHandle::in_memory(GameSettings::default());
+        settings.edit(|settings| settings.fov = Fov::MAX);
+        settings.edit(|settings| settings.hdr = true);
+        assert_eq!(*settings.load(), GameSettings { fov: Fov::MAX, hdr: true, ..GameSettings::default() });
+
+        // edits from all over land one after another, none of them get lost
+        let frame_caps = (1..=8).map(|i| {
+            let settings = settings.clone();
+            std::thread::spawn(move || {
+                for _ in 0..100 {
+                    settings.edit(|settings| {
+                        let cap = settings.frame_cap.map_or(0, NonZero::get);
+                        settings.frame_cap = NonZero::new(cap + i);
+                    });
+                }
+            })
+        });
+        for thread in frame_caps.collect::<Vec<_>>() {
+            thread.join().unwrap();
+        }
+        assert_eq!(settings.load().frame_cap, NonZero::new(100 * (1..=8).sum::<u32>()));
+        assert_eq!(settings.load().fov, Fov::MAX);
+    }
+
+    #[test]
+    fn snapshots_keep_their_value() {
+        let settings = GameSettingsHandle::in_memory(GameSettings::default());
+        let before = settings.snapshot();
+        let loaded = settings.load().into_arc();
+        settings.edit(|settings| settings.fov = Fov::MAX);
+
+        assert_eq!(*before, GameSettings::default());
+        assert_eq!(*loaded, GameSettings::default());
+        assert_eq!(settings.snapshot().fov, Fov::MAX);
+
+        // and can go to other threads
+        let fov = std::thread::spawn(move || before.fov).join().unwrap();
+        assert_eq!(fov, GameSettings::default().fov);
+    }
+
+    #[test]
+    fn icons_only_get_scaled_down() {
+        let available = [16, 32, 48, 256];
+        assert_eq!(pick_icon_size(available, 32), Some(32));
+        assert_eq!(pick_icon_size(available, 40), Some(48));
+        assert_eq!(pick_icon_size(available, 8), Some(16));
+        // nothing is big enough, the biggest loses the least
+        assert_eq!(pick_icon_size(available, 512), Some(256));
+        assert_eq!(pick_icon_size([], 32), None);
+
+        let assets = crate::assets::source(&GameSettings::default());
+        assert!(load_icon(&*assets, None).is_some());
+        assert!(load_icon(&*assets, Some(Path::new("icon/voxel-engine128.png"))).is_some());
+        // a missing file falls back to the game's icon
+        assert!(load_icon(&*assets, Some(Path::new("./there/is/no/icon.png"))).is_some());
+    }
+
+    #[test]
+    fn window_changes_only_track_window_settings() {
+        let old = GameSettings::default();
+        assert_eq!(WindowChanges::between(&old, &old.clone()), WindowChanges::default());
+
+        let renamed = GameSettings { game_title: GameTitle("Voxels".into()), ..old.clone() };
+        assert_eq!(WindowChanges::between(&old, &renamed), WindowChanges { title: true, ..WindowChanges::default() });
+        assert!(WindowChanges::between(&renamed, &old).title);
+
+        let icon = GameSettings { icon: Some(PathBuf::from("icon.png")), ..old.clone() };
+        assert_eq!(WindowChanges::between(&old, &icon), WindowChanges { icon: true, ..WindowChanges::default() });
+
+        // the renderer picks these up by itself
+        let other = GameSettings { fov: Fov::MAX, vsync: Vsync::Off, ..old.clone() };
+        assert_eq!(WindowChanges::between(&old, &other), WindowChanges::default());
+    }
+}
@@ -0,0 +1,98 @@
+//! Compares `i48` against `i64` and `FixedPoint` against `f32`.
+//!
+//! Run with `cargo bench -p voxel-maths`. Every bench runs its operation over the same
+//! 1024 operands, small enough that nothing overflows or saturates.
+//!
+//! Baselines for all 1024 operations, measured with `--quick` on one core of a shared x86_64 VM:
+//!
+//! | op   | i48     | i64     | FixedPoint | f32     |
+//! |------|---------|---------|------------|---------|
+//! | add  | 810 ns  | 811 ns  | 1.50 µs    | 746 ns  |
+//! | mul  | 432 ns  | 582 ns  | 2.71 µs    | 763 ns  |
+//! | div  | 3.39 µs | 3.27 µs | 11.8 µs    | 1.25 µs |
+//! | sqrt | 17.0 µs | 13.8 µs |            | 1.23 µs |
+//! | sin  |         |         | 265 µs     | 8.85 µs |
+//! | cos  |         |         | 270 µs     | 9.28 µs |
+//!
+//! `FixedPoint` has no square root yet, so only the integer and float ones are measured.
+
+use std::hint::black_box;
+use criterion::{criterion_group, criterion_main, Criterion};
+use voxel_maths::fixed_point::FixedPoint;
+use voxel_maths::i48_int::i48;
+
+const OPERANDS: usize = 1024;
+
+/// The same pairs of operands every run, the right hand side is never zero
+fn operands() -> Vec<(i64, i64)> {
+    let mut state = 0x2545_f491_4f6c_dd1d_u64;
+    let mut next = move || {
+        state ^= state << 13;
+        state ^= state >> 7;
+        state ^= state << 17;
+        state
+    };
+
+    (0..OPERANDS)
+        .map(|_| {
+            let lhs = (next() % 2_000_000) as i64 - 1_000_000;
+            let rhs = (next() % 2_000) as i64 + 1;
+            (lhs, rhs)
+        })
+        .collect()
+}
+
+/// Benches `op` over every one of `operands` as `name` in `group`
+fn bench<T: Copy, R>(c: &mut Criterion, group: &str, name: &str, operands: &[T], op: impl Fn(T) -> R) {
+    c.benchmark_group(group).bench_function(name, |b| {
+        b.iter(|| {
+            for &operand in black_box(operands) {
+                black_box(op(operand));
+            }
+        })
+    });
+}
+
+fn integers(c: &mut Criterion) {
+    let wide = operands();
+    let narrow = wide
+        .iter()
+        .map(|&(lhs, rhs)| (i48::new(lhs).unwrap(), i48::new(rhs).unwrap()))
+        .collect::<Vec<_>>();
+
+    bench(c, "add", "i64", &wide, |(lhs, rhs)| lhs + rhs);
+    bench(c, "add", "i48", &narrow, |(lhs, rhs)| lhs + rhs);
+    bench(c, "mul", "i64", &wide, |(lhs, rhs)| lhs * rhs);
+    bench(c, "mul", "i48", &narrow, |(lhs, rhs)| lhs * rhs);
+    bench(c, "div", "i64", &wide, |(lhs, rhs)| lhs / rhs);
+    bench(c, "div", "i48", &narrow, |(lhs, rhs)| lhs / rhs);
+    bench(c, "sqrt", "i64", &wide, |(lhs, _)| lhs.unsigned_abs().isqrt());
+    bench(c, "sqrt", "i48", &narrow, |(lhs, _)| lhs.wrapping_abs().checked_isqrt());
+}
+
+fn fractions(c: &mut Criterion) {
+    // the same pairs, scaled down so they have fractional parts
+    let floats = operands()
+        .into_iter()
+        .map(|(lhs, rhs)| (lhs as f32 / 1000.0, rhs as f32 / 100.0))
+        .collect::<Vec<_>>();
+    let fixed = floats
+        .iter()
+        .map(|&(lhs, rhs)| (FixedPoint::from_f32(lhs), FixedPoint::from_f32(rhs)))
+        .collect::<Vec<_>>();
+
+    bench(c, "add", "f32", &floats, |(lhs, rhs)| lhs + rhs);
+    bench(c, "add", "FixedPoint", &fixed, |(lhs, rhs)| lhs + rhs);
+    bench(c, "mul", "f32", &floats, |(lhs, rhs)| lhs * rhs);
+    bench(c, "mul", "FixedPoint", &fixed, |(lhs, rhs)| lhs * rhs);
+    bench(c, "div", "f32", &floats, |(lhs, rhs)| lhs / rhs);
+    bench(c, "div", "FixedPoint", &fixed, |(lhs, rhs)| lhs / rhs);
+    bench(c, "sqrt", "f32", &floats, |(lhs, _)| lhs.abs().sqrt());
+    bench(c, "sin", "f32", &floats, |(lhs, _)| lhs.sin());
+    bench(c, "sin", "FixedPoint", &fixed, |(lhs, _)| lhs.sin());
+    bench(c, "cos", "f32", &floats, |(lhs, _)| lhs.cos());
+    bench(c, "cos", "FixedPoint", &fixed, |(lhs, _)| lhs.cos());
+}
+
+criterion_group!(benches, integers, fractions);
+criterion_main!(benches);
@@ -1,6 +1,7 @@
 use std::cmp::Ordering;
 use std::fmt::{Binary, Debug, Display, Formatter, LowerHex, Octal, UpperHex, Write};
-use std::ops::{Add, AddAssign, Div, DivAssign, Mul, MulAssign, Sub, SubAssign};
+use std::str::FromStr;
+use std::ops::{Add, AddAssign, Div, DivAssign, Mul, MulAssign, Neg, Sub, SubAssign};
 use bytemuck::{Pod, Zeroable};
 use crate::i48_int::i48;
 
@@ -23,6 +24,17 @@ impl Fract {
     pub const MAX: Self = Self(u16::MAX);
 
     pub const HALF: Self = Self::recip(2);
+
+    /// The fraction `bits / 2^16`
+    #[inline(always)]
+    pub const fn from_bits(bits: u16) -> Self {
+        Self(bits)
+    }
+
+    #[inline(always)]
+    pub const fn to_bits(self) -> u16 {
+        self.0
+    }
     
     fn fmt_fractional<const RADIX: u32, const UPPERCASE: bool>(self, f: &mut Formatter) -> std::fmt::Result {
         const { assert!(2 <= RADIX && RADIX <= 16, "radix must be in range 2..=16") }
@@ -230,6 +242,17 @@ impl FixedPoint {
         self.0 > 0
     }
 
+    /// The fixed point number `bits / 2^16`
+    #[inline(always)]
+    pub const fn from_bits(bits: i64) -> Self {
+        Self(bits)
+    }
+
+    #[inline(always)]
+    pub const fn to_bits(self) -> i64 {
+        self.0
+    }
+
     #[inline(always)]
     pub const fn from_raw(integer: i48, fractional: Fract) -> Self {
         let bits = (integer.to_bits() << 16) | fractional.0 as u64;
@@ -317,6 +340,14 @@ impl SubAssign for FixedPoint {
     }
 }
 
+impl Neg for FixedPoint {
+    type Output = FixedPoint;
+
+    fn neg(self) -> Self::Output {
+        Self(self.0.saturating_neg())
+    }
+}
+
 const fn clamp_computation(result: i128) -> FixedPoint {
     if result > const { FixedPoint::MAX.0 as i128 } {
         return FixedPoint::MAX
@@ -370,6 +401,127 @@ impl DivAssign for FixedPoint {
     }
 }
 
+// Transcendental functions
+// These work with 32 fractional bits internally so the rounding errors of each step
+// stay well below what the 16 bits of the result can show, the results are within
+// a couple of steps (2^-16) of the exact value, plus a relative error of about 1e-8
+//
+// Being built from integer operations only, they give the same bits on every platform,
+// unlike the float functions whose last bits can differ between libms
+
+/// the number of fractional bits used while computing
+const WORK_BITS: u32 = 32;
+const WORK_ONE: i128 = 1 << WORK_BITS;
+/// ln(2) * 2^32
+const WORK_LN_2: i128 = 2_977_044_472;
+
+/// Turns a number with [`WORK_BITS`] fractional bits into a [`FixedPoint`], rounding to the nearest
+const fn from_work(value: i128) -> FixedPoint {
+    const SHIFT: u32 = WORK_BITS - 16;
+    clamp_computation((value + (1 << (SHIFT - 1))) >> SHIFT)
+}
+
+impl FixedPoint {
+    /// `e^self`, saturating at [`FixedPoint::MAX`] and rounding down to zero for very negative inputs
+    pub fn exp(self) -> Self {
+        // e^33 is past the largest fixed point number and e^-16 rounds to 0
+        if self.0 >= 33 << 16 {
+            return Self::MAX;
+        }
+        if self.0 <= -16 << 16 {
+            return Self::ZERO;
+        }
+
+        // e^x = 2^k * e^r, where x = k * ln(2) + r and |r| <= ln(2)/2
+        let x = (self.0 as i128) << (WORK_BITS - 16);
+        let k = (x + WORK_LN_2 / 2).div_euclid(WORK_LN_2);
+        let r = x - k * WORK_LN_2;
+
+        // the taylor series 1 + r(1 + r/2(1 + r/3(...))), by the 11th term it is below 2^-32
+        let mut exp_r = WORK_ONE;
+        for n in (1..=11).rev() {
+            exp_r = WORK_ONE + ((r * exp_r) >> WORK_BITS) / n;
+        }
+
+        // k is in -23..=48, the shift stays well inside an i128
+        let k = k as i32;
+        match k >= 0 {
+            true => from_work(exp_r << k),
+            false => from_work(exp_r >> -k),
+        }
+    }
+
+    /// The natural logarithm, `None` unless `self` is positive
+    pub fn ln(self) -> Option<Self> {
+        if !self.is_positive() {
+            return None;
+        }
+
+        // self = m * 2^e, with m in [1, 2)
+        let top_bit = 63 - self.0.leading_zeros() as i128;
+        let e = top_bit - 16;
+        let m = ((self.0 as i128) << WORK_BITS) >> top_bit;
+
+        // ln(m) = 2 * atanh(s) = 2(s + s^3/3 + s^5/5 + ...), where s = (m - 1)/(m + 1) is in [0, 1/3)
+        let s = ((m - WORK_ONE) << WORK_BITS) / (m + WORK_ONE);
+        let s_squared = (s * s) >> WORK_BITS;
+        let mut series = 0;
+        for n in (0..=10).rev() {
+            series = WORK_ONE / (2 * n + 1) + ((s_squared * series) >> WORK_BITS);
+        }
+        let ln_m = (2 * s * series) >> WORK_BITS;
+
+        Some(from_work(e * WORK_LN_2 + ln_m))
+    }
+
+    /// `self^exponent`, `None` unless `self` is positive
+    pub fn powf(self, exponent: Self) -> Option<Self> {
+        self.ln().map(|ln| (ln * exponent).exp())
+    }
+
+    /// The sine and cosine of `self` in radians, each within 2^-16 of the exact value
+    pub fn sin_cos(self) -> (Self, Self) {
+        // π/2 * 2^64, reducing with 64 fractional bits keeps even huge angles accurate
+        const FRAC_PI_2: i128 = 28_976_077_832_308_491_370;
+
+        // self = quadrant * π/2 + r, with |r| <= π/4
+        let x = (self.0 as i128) << 48;
+        let quadrant = (x + FRAC_PI_2 / 2).div_euclid(FRAC_PI_2);
+        let r = (x - quadrant * FRAC_PI_2) >> (64 - WORK_BITS);
+        let r_squared = (r * r) >> WORK_BITS;
+
+        // the taylor series of both, by the 8th term they are below 2^-32
+        // sin(r) = r(1 - r^2/(2*3)(1 - r^2/(4*5)(...)))
+        // cos(r) = 1 - r^2/(1*2)(1 - r^2/(3*4)(...))
+        let mut sin = WORK_ONE;
+        let mut cos = WORK_ONE;
+        for n in (1..=8).rev() {
+            sin = WORK_ONE - ((r_squared * sin) >> WORK_BITS) / ((2 * n) * (2 * n + 1));
+            cos = WORK_ONE - ((r_squared * cos) >> WORK_BITS) / ((2 * n - 1) * (2 * n));
+        }
+        let sin = (r * sin) >> WORK_BITS;
+
+        let (sin, cos) = match quadrant.rem_euclid(4) {
+            0 => (sin, cos),
+            1 => (cos, -sin),
+            2 => (-sin, -cos),
+            _ => (-cos, sin),
+        };
+
+        (from_work(sin), from_work(cos))
+    }
+
+    /// The sine of `self` in radians, see [`FixedPoint::sin_cos`]
+    pub fn sin(self) -> Self {
+        self.sin_cos().0
+    }
+
+    /// The cosine of `self` in radians, see [`FixedPoint::sin_cos`]
+    pub fn cos(self) -> Self {
+        self.sin_cos().1
+    }
+}
+
 impl From<Fract> for FixedPoint {
     #[inline]
     fn from(value: Fract) -> Self {
@@ -377,6 +529,66 @@ impl From<Fract> for FixedPoint {
     }
 }
 
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub enum ParseFixedPointError {
+    Empty,
+    InvalidDigit,
+    Overflow,
+}
+
+impl Display for ParseFixedPointError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        f.write_str(match self {
+            Self::Empty => "cannot parse a fixed point number from an empty string",
+            Self::InvalidDigit => "invalid digit found in string",
+            Self::Overflow => "number too large to fit in a fixed point number",
+        })
+    }
+}
+
+impl std::error::Error for ParseFixedPointError {}
+
+/// Parses decimals like `-12.375`, rounding to the nearest fixed point number
+impl FromStr for FixedPoint {
+    type Err = ParseFixedPointError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (negative, digits) = match s.strip_prefix('-') {
+            Some(rest) => (true, rest),
+            None => (false, s.strip_prefix('+').unwrap_or(s)),
+        };
+        let (int, fract) = digits.split_once('.').unwrap_or((digits, ""));
+
+        if int.is_empty() && fract.is_empty() {
+            return Err(ParseFixedPointError::Empty);
+        }
+        if !int.bytes().chain(fract.bytes()).all(|b| b.is_ascii_digit()) {
+            return Err(ParseFixedPointError::InvalidDigit);
+        }
+
+        let mut bits = 0_i128;
+        for digit in int.bytes() {
+            bits = bits * 10 + i128::from(digit - b'0');
+            if bits > const { i64::MAX as i128 } {
+                return Err(ParseFixedPointError::Overflow);
+            }
+        }
+        bits *= const { FRACTIONAL_SCALE as i128 };
+
+        // past 20 digits the rest can't change the rounding of 16 bits
+        let (numerator, denominator) = fract.bytes().take(20).fold((0_i128, 1_i128), |(num, den), digit| {
+            (num * 10 + i128::from(digit - b'0'), den * 10)
+        });
+        bits += (numerator * const { FRACTIONAL_SCALE as i128 } + denominator / 2) / denominator;
+
+        let bits = match negative {
+            true => -bits,
+            false => bits,
+        };
+        i64::try_from(bits).map(Self).map_err(|_| ParseFixedPointError::Overflow)
+    }
+}
+
 macro_rules! impl_cmp {
     ($ty: ty) => {
         impl $ty {
@@ -404,7 +616,7 @@ macro_rules! impl_cmp {
         impl PartialOrd for $ty {
             #[inline(always)]
             fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
-                PartialOrd::partial_cmp(&self.0, &other.0)
+                Some(self.cmp(other))
             }
             
             #[inline(always)]
@@ -476,11 +688,54 @@ impl_fmt! { LowerHex; base: 0x10 }
 impl_fmt! { UpperHex; base: 0x10 uppercase }
 
 
+/// both serialize as their raw bits, which round trip exactly unlike a float would
+#[cfg(feature = "serde")]
+mod serde_impls {
+    use serde::{Deserialize, Deserializer, Serialize, Serializer};
+    use super::{FixedPoint, Fract};
+
+    impl Serialize for Fract {
+        fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+            serializer.serialize_u16(self.to_bits())
+        }
+    }
+
+    impl<'de> Deserialize<'de> for Fract {
+        fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+            u16::deserialize(deserializer).map(Fract::from_bits)
+        }
+    }
+
+    impl Serialize for FixedPoint {
+        fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+            serializer.serialize_i64(self.to_bits())
+        }
+    }
+
+    impl<'de> Deserialize<'de> for FixedPoint {
+        fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+            i64::deserialize(deserializer).map(FixedPoint::from_bits)
+        }
+    }
+}
+
 #[cfg(test)]
 mod tests {
+    use proptest::proptest;
     use crate::i48;
     use super::*;
 
+    /// Bits of numbers small enough that adding or subtracting two never saturates
+    const SUM_RANGE: std::ops::RangeInclusive<i64> = -(1 << 61)..=(1 << 61);
+    /// Bits of numbers small enough that multiplying two never saturates, up to about ±2^23
+    const PRODUCT_RANGE: std::ops::RangeInclusive<i64> = -(1 << 39)..=(1 << 39);
+    /// One step of the fixed point grid
+    const EPSILON: f64 = 1.0 / FRACTIONAL_SCALE as f64;
+
+    fn as_f64(x: FixedPoint) -> f64 {
+        x.to_bits() as f64 * EPSILON
+    }
+
     #[test]
     fn test_fmt() {
         assert_eq!(Fract(8000).to_string(), "0.1220703125");
@@ -531,6 +786,158 @@ mod tests {
         )
     }
 
+    #[test]
+    fn test_exp() {
+        // 2^-16 for the rounding of the result, and a little more for f32's own rounding
+        let within = |fixed: FixedPoint, expected: f32| {
+            let error = (fixed.as_f32() - expected).abs();
+            assert!(error <= 2e-5 + expected * 1e-6, "{fixed} != {expected}, off by {error}");
+        };
+
+        for i in -16 * 64..=20 * 64 {
+            let x = FixedPoint::from_f32(i as f32 / 64.0);
+            within(x.exp(), x.as_f32().exp());
+        }
+
+        assert_eq!(FixedPoint::ZERO.exp(), FixedPoint::from_int(i48!(1)));
+        assert_eq!(FixedPoint::from_int(i48!(40)).exp(), FixedPoint::MAX);
+        assert_eq!(FixedPoint::from_int(i48!(-40)).exp(), FixedPoint::ZERO);
+        assert_eq!(FixedPoint::MIN.exp(), FixedPoint::ZERO);
+    }
+
+    #[test]
+    fn test_ln_and_powf() {
+        for i in 1..=4096 {
+            let x = FixedPoint::from_f32(i as f32 / 16.0);
+            let ln = x.ln().unwrap().as_f32();
+            assert!((ln - x.as_f32().ln()).abs() <= 2e-5, "ln({x}) = {ln}");
+        }
+
+        assert_eq!(FixedPoint::from_int(i48!(1)).ln(), Some(FixedPoint::ZERO));
+        assert_eq!(FixedPoint::ZERO.ln(), None);
+        assert_eq!(FixedPoint::from_int(i48!(-2)).ln(), None);
+
+        let two = FixedPoint::from_int(i48!(2));
+        let eight = two.powf(FixedPoint::from_int(i48!(3))).unwrap();
+        assert!((eight.as_f32() - 8.0).abs() <= 1e-4, "{eight}");
+        let root = two.powf(FixedPoint::from_fract(Fract::HALF)).unwrap();
+        assert!((root.as_f32() - std::f32::consts::SQRT_2).abs() <= 2e-5, "{root}");
+    }
+
+    #[test]
+    fn test_sin_cos() {
+        use std::f32::consts::PI;
+
+        for i in -4096..=4096 {
+            let x = FixedPoint::from_f32(i as f32 / 4096.0 * PI);
+            let (sin, cos) = x.sin_cos();
+            let (expected_sin, expected_cos) = x.as_f32().sin_cos();
+            assert!((sin.as_f32() - expected_sin).abs() <= 2e-5, "sin({x}) = {sin}, not {expected_sin}");
+            assert!((cos.as_f32() - expected_cos).abs() <= 2e-5, "cos({x}) = {cos}, not {expected_cos}");
+            assert_eq!((x.sin(), x.cos()), (sin, cos));
+        }
+
+        // far from zero the angle still gets reduced accurately
+        let big = FixedPoint::from_int(i48!(1_000_000));
+        assert!((big.sin().as_f32() - (1_000_000f64).sin() as f32).abs() <= 2e-5);
+
+        let one = FixedPoint::from_int(i48!(1));
+        assert_eq!(FixedPoint::ZERO.sin_cos(), (FixedPoint::ZERO, one));
+    }
+
+    #[test]
+    fn test_trig_is_deterministic() {
+        // integer only, so these bits are the same everywhere, about 0.9077 and 0.4196
+        let angle = FixedPoint::from_bits(0x1_2345);
+        assert_eq!(angle.sin_cos(), angle.sin_cos());
+        assert_eq!(angle.sin().to_bits(), 59487);
+        assert_eq!(angle.cos().to_bits(), 27500);
+    }
+
+    #[test]
+    fn test_from_str() {
+        let parse = |s: &str| s.parse::<FixedPoint>();
+
+        assert_eq!(parse("12"), Ok(FixedPoint::from_int(i48!(12))));
+        assert_eq!(parse("-1.5"), Ok(FixedPoint::from_f32(-1.5)));
+        assert_eq!(parse("+.25"), Ok(FixedPoint::from_f32(0.25)));
+        assert_eq!(parse("3."), Ok(FixedPoint::from_int(i48!(3))));
+        // 0.1 has no exact fixed point form, it rounds to the closest one
+        assert_eq!(parse("0.1"), Ok(FixedPoint::from_bits(6554)));
+        assert_eq!(parse("0.00000000000000000000000001"), Ok(FixedPoint::ZERO));
+        assert_eq!(parse(&FixedPoint::MAX.int().to_string()), Ok(FixedPoint::from_int(FixedPoint::MAX.int())));
+
+        assert_eq!(parse(""), Err(ParseFixedPointError::Empty));
+        assert_eq!(parse("-."), Err(ParseFixedPointError::Empty));
+        assert_eq!(parse("1.2.3"), Err(ParseFixedPointError::InvalidDigit));
+        assert_eq!(parse("1e5"), Err(ParseFixedPointError::InvalidDigit));
+        assert_eq!(parse("--1"), Err(ParseFixedPointError::InvalidDigit));
+        assert_eq!(parse("140737488355328"), Err(ParseFixedPointError::Overflow));
+    }
+
+    proptest! {
+        #[test]
+        fn test_add_sub_identities(a in SUM_RANGE, b in SUM_RANGE) {
+            let (a, b) = (FixedPoint::from_bits(a), FixedPoint::from_bits(b));
+            assert_eq!((a + b) - b, a);
+            assert_eq!(a + b, b + a);
+            assert_eq!(a - a, FixedPoint::ZERO);
+        }
+
+        #[test]
+        fn test_mul_identities(a in PRODUCT_RANGE, b in PRODUCT_RANGE) {
+            let (a, b) = (FixedPoint::from_bits(a), FixedPoint::from_bits(b));
+            let one = FixedPoint::from_int(i48!(1));
+            assert_eq!(a * one, a);
+            assert_eq!(a * b, b * a);
+            assert_eq!(a * FixedPoint::ZERO, FixedPoint::ZERO);
+
+            // the exact product, rounded towards zero onto the grid
+            let exact = a.to_bits() as i128 * b.to_bits() as i128;
+            let error = exact - ((a * b).to_bits() as i128) * FRACTIONAL_SCALE as i128;
+            assert!(error.abs() < FRACTIONAL_SCALE as i128, "{a} * {b} = {}", a * b);
+        }
+
+        #[test]
+        fn test_div_identities(a in PRODUCT_RANGE, b in PRODUCT_RANGE) {
+            let (a, b) = (FixedPoint::from_bits(a), FixedPoint::from_bits(b));
+            let one = FixedPoint::from_int(i48!(1));
+            assert_eq!(a / one, a);
+            if a != FixedPoint::ZERO {
+                assert_eq!(a / a, one);
+            }
+
+            // the exact quotient, rounded towards zero onto the grid,
+            // these never saturate since dividing by the smallest fraction only takes it up to about 2^55
+            if b != FixedPoint::ZERO {
+                let exact = a.to_bits() as i128 * FRACTIONAL_SCALE as i128;
+                let error = exact - (a / b).to_bits() as i128 * b.to_bits() as i128;
+                assert!(error.abs() < (b.to_bits() as i128).abs(), "{a} / {b} = {}", a / b);
+            }
+        }
+
+        #[test]
+        fn test_fract_mul_div(a in 0..=u16::MAX, b in 1..=u16::MAX) {
+            let (a, b) = (Fract(a), Fract(b));
+            let (x, y) = (a.as_f32() as f64, b.as_f32() as f64);
+
+            let product = (a * b).as_f32() as f64;
+            assert!((product - x * y).abs() < EPSILON, "{a} * {b} = {product}");
+            assert_eq!(a * b, b * a);
+
+            let quotient = as_f64(a / b);
+            assert!((quotient - x / y).abs() < EPSILON, "{a} / {b} = {quotient}");
+        }
+
+        #[test]
+        fn test_f32_round_trip(float in -1e9f32..1e9f32) {
+            let out = FixedPoint::from_f32(float).as_f32();
+            // truncated onto the grid, then rounded to the closest f32 on the way back
+            let within = EPSILON as f32 + float.abs() * f32::EPSILON;
+            assert!((out - float).abs() <= within, "{float} came back as {out}");
+        }
+    }
+
     #[test]
     fn test_fixed_float_to_from_raw() {
         let integer = i48::from_bits(123456).unwrap();
@@ -2,6 +2,8 @@ use std::cmp::Ordering;
 use std::fmt::{Binary, Debug, Display, Formatter, LowerHex, Octal, UpperHex};
 use std::hash::{Hash, Hasher};
 use std::hint::assert_unchecked;
+use std::num::ParseIntError;
+use std::str::FromStr;
 use std::ops::{Add, AddAssign, Div, DivAssign, Mul, MulAssign, Neg, Not, Rem, RemAssign, Sub, SubAssign};
 use bytemuck::{NoUninit, Zeroable};
 use cfg_if::cfg_if;
@@ -186,6 +188,8 @@ impl i48 {
         }
     }
 
+    /// # Safety
+    /// `x`'s two most significant bytes must be zero
     #[inline(always)]
     pub const unsafe fn from_bits_unchecked(x: u64) -> Self {
         match cfg!(debug_assertions) {
@@ -213,6 +217,8 @@ impl i48 {
         Some(unsafe { Self::new_unchecked(x) })
     }
 
+    /// # Safety
+    /// `x` must be within `i48::MIN..=i48::MAX`
     #[inline(always)]
     pub const unsafe fn new_unchecked(x: i64) -> Self {
         // Safety: up to caller
@@ -470,12 +476,6 @@ impl PartialEq for i48 {
     fn eq(&self, other: &Self) -> bool {
         self.to_bits() == other.to_bits()
     }
-
-
-    #[inline(always)]
-    fn ne(&self, other: &Self) -> bool {
-        self.to_bits() != other.to_bits()
-    }
 }
 
 impl Eq for i48 {}
@@ -514,13 +514,60 @@ impl Ord for i48 {
     }
 }
 
+impl FromStr for i48 {
+    type Err = ParseIntError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let x = s.parse::<i64>()?;
+        i48::new(x).ok_or_else(|| {
+            // ParseIntError can't be built by hand, borrow the overflow error of a smaller type
+            let out_of_range = match x < 0 {
+                true => "-129",
+                false => "128"
+            };
+            out_of_range.parse::<i8>().unwrap_err()
+        })
+    }
+}
+
+#[cfg(feature = "serde")]
+impl serde::Serialize for i48 {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_i64(self.as_i64())
+    }
+}
+
+#[cfg(feature = "serde")]
+impl<'de> serde::Deserialize<'de> for i48 {
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let x = <i64 as serde::Deserialize>::deserialize(deserializer)?;
+        i48::new(x).ok_or_else(|| {
+            serde::de::Error::custom(format_args!("{x} is out of range for an i48"))
+        })
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
     use std::cmp::Ordering;
     use std::mem::{size_of, align_of};
+    use std::num::IntErrorKind;
     use proptest::proptest;
 
+    #[test]
+    fn test_from_str() {
+        assert_eq!("-42".parse::<i48>(), Ok(i48::new(-42).unwrap()));
+        assert_eq!(i48::MAX.to_string().parse::<i48>(), Ok(i48::MAX));
+        assert_eq!(i48::MIN.to_string().parse::<i48>(), Ok(i48::MIN));
+
+        let error = |s: &str| *s.parse::<i48>().unwrap_err().kind();
+        assert_eq!(error(&(i48::MAX.as_i64() + 1).to_string()), IntErrorKind::PosOverflow);
+        assert_eq!(error(&(i48::MIN.as_i64() - 1).to_string()), IntErrorKind::NegOverflow);
+        assert_eq!(error("1.5"), IntErrorKind::InvalidDigit);
+        assert_eq!(error(""), IntErrorKind::Empty);
+    }
+
     #[test]
     fn test_size_and_alignment() {
         assert_eq!(size_of::<i48>(), size_of::<i64>());
@@ -787,4 +834,16 @@ mod tests {
         const B: i48 = i48!(-42);
         assert_eq!(B.as_i64(), -42);
     }
-}
\ No newline at end of file
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn test_serde() {
+        let a = i48::new(-123_456_789).unwrap();
+        let json = serde_json::to_string(&a).unwrap();
+        assert_eq!(json, "-123456789");
+        assert_eq!(serde_json::from_str::<i48>(&json).unwrap(), a);
+
+        assert!(serde_json::from_str::<i48>(&i64::MAX.to_string()).is_err());
+        assert!(serde_json::from_str::<i48>(&(i48::MIN.as_i64() - 1).to_string()).is_err());
+    }
+}
@@ -6,9 +6,11 @@ use crate::i48_int::i48;
 
 pub mod i48_int;
 pub mod fixed_point;
+pub mod rng;
+pub mod noise;
 
 
-#[derive(Copy, Clone, PartialEq)]
+#[derive(Copy, Clone, PartialEq, Debug)]
 pub struct Transform {
     // storing the Quaternion means were paying for the padding,
     // since we already pay for padding might as well use it for speed
@@ -16,6 +18,23 @@ pub struct Transform {
     pub rotation: Quat,
 }
 
+impl Transform {
+    pub const IDENTITY: Self = Self {
+        position: Vec3A::ZERO,
+        rotation: Quat::IDENTITY,
+    };
+
+    /// Interpolates between `self` at `t = 0` and `end` at `t = 1`,
+    /// the position is interpolated linearly and the rotation spherically
+    #[inline]
+    pub fn lerp(self, end: Self, t: f32) -> Self {
+        Self {
+            position: self.position.lerp(end.position, t),
+            rotation: self.rotation.slerp(end.rotation, t),
+        }
+    }
+}
+
 
 
 #[derive(Copy, Clone, Hash, PartialEq, Eq, Zeroable, Debug)]
@@ -147,3 +166,27 @@ impl SubAssign for FixedPointVec3 {
         *self = (*self) - rhs
     }
 }
+
+
+#[cfg(test)]
+mod tests {
+    use glam::vec3a;
+    use super::*;
+
+    #[test]
+    fn transform_lerp() {
+        let start = Transform::IDENTITY;
+        let end = Transform {
+            position: vec3a(2.0, 4.0, -8.0),
+            rotation: Quat::from_rotation_y(std::f32::consts::FRAC_PI_2),
+        };
+
+        assert_eq!(start.lerp(end, 0.0), start);
+        assert!(start.lerp(end, 1.0).position.abs_diff_eq(end.position, 1e-6));
+        assert!(start.lerp(end, 1.0).rotation.abs_diff_eq(end.rotation, 1e-6));
+
+        let mid = start.lerp(end, 0.5);
+        assert!(mid.position.abs_diff_eq(vec3a(1.0, 2.0, -4.0), 1e-6));
+        assert!(mid.rotation.abs_diff_eq(Quat::from_rotation_y(std::f32::consts::FRAC_PI_4), 1e-6));
+    }
+}
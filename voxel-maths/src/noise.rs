@@ -0,0 +1,242 @@
+//! Coherent noise computed entirely in integers
+//!
+//! Float noise can differ in the last few bits between platforms and compilers,
+//! which is enough to grow a different world from the same seed.
+//! Everything here works on the raw bits of [`FixedPoint`] instead, so the same seed
+//! and coordinates give bit for bit the same result everywhere.
+
+use crate::fixed_point::{FixedPoint, Fract};
+use crate::i48;
+use crate::rng::splitmix64;
+
+/// one in fixed point bits
+const ONE: i64 = 1 << 16;
+
+/// Hashes a lattice point into 64 random looking bits
+fn hash_lattice(seed: u64, lattice: &[i48]) -> u64 {
+    let mut state = seed;
+    let mut hash = splitmix64(&mut state);
+    for coordinate in lattice {
+        state ^= hash ^ coordinate.to_bits();
+        hash = splitmix64(&mut state);
+    }
+    hash
+}
+
+/// The quintic `6t^5 - 15t^4 + 10t^3`, eases `t` in `[0, 1]` so the noise
+/// has no visible creases along the lattice lines
+const fn fade(t: i64) -> i64 {
+    let t3 = (((t * t) >> 16) * t) >> 16;
+    let inner = ((t * (6 * t - 15 * ONE)) >> 16) + 10 * ONE;
+    (t3 * inner) >> 16
+}
+
+const fn lerp(a: i64, b: i64, t: i64) -> i64 {
+    a + (((b - a) * t) >> 16)
+}
+
+/// Splits a coordinate into the lattice cell it's in and how far into the cell it is
+fn cell(coordinate: FixedPoint) -> (i48, i64) {
+    (coordinate.int(), coordinate.fract().to_bits() as i64)
+}
+
+fn value_at(seed: u64, lattice: &[i48]) -> i64 {
+    (hash_lattice(seed, lattice) >> 48) as i64
+}
+
+/// Smoothly interpolated random values placed on the integer lattice, in `[0, 1)`
+pub fn value_noise_2d(seed: u64, x: FixedPoint, z: FixedPoint) -> Fract {
+    let (x0, tx) = cell(x);
+    let (z0, tz) = cell(z);
+    let (x1, z1) = (x0.wrapping_add(i48!(1)), z0.wrapping_add(i48!(1)));
+    let (fx, fz) = (fade(tx), fade(tz));
+
+    let near = lerp(value_at(seed, &[x0, z0]), value_at(seed, &[x1, z0]), fx);
+    let far = lerp(value_at(seed, &[x0, z1]), value_at(seed, &[x1, z1]), fx);
+
+    // interpolating never leaves the range of the corners, which are all 16 bit
+    Fract::from_bits(lerp(near, far, fz) as u16)
+}
+
+/// Smoothly interpolated random values placed on the integer lattice, in `[0, 1)`
+pub fn value_noise_3d(seed: u64, x: FixedPoint, y: FixedPoint, z: FixedPoint) -> Fract {
+    let (x0, tx) = cell(x);
+    let (y0, ty) = cell(y);
+    let (z0, tz) = cell(z);
+    let [x1, y1, z1] = [x0, y0, z0].map(|c| c.wrapping_add(i48!(1)));
+    let (fx, fy, fz) = (fade(tx), fade(ty), fade(tz));
+
+    let layer = |y| {
+        let near = lerp(value_at(seed, &[x0, y, z0]), value_at(seed, &[x1, y, z0]), fx);
+        let far = lerp(value_at(seed, &[x0, y, z1]), value_at(seed, &[x1, y, z1]), fx);
+        lerp(near, far, fz)
+    };
+
+    Fract::from_bits(lerp(layer(y0), layer(y1), fy) as u16)
+}
+
+/// The dot product of one of 8 gradients (the axes and diagonals) picked by `hash`
+/// with the offset `(dx, dz)` from the lattice point
+fn gradient_2d(hash: u64, dx: i64, dz: i64) -> i64 {
+    match hash >> 61 {
+        0 => dx,
+        1 => -dx,
+        2 => dz,
+        3 => -dz,
+        4 => dx + dz,
+        5 => -dx + dz,
+        6 => dx - dz,
+        _ => -dx - dz,
+    }
+}
+
+/// The dot product of one of the 12 cube edge gradients from improved Perlin noise
+fn gradient_3d(hash: u64, dx: i64, dy: i64, dz: i64) -> i64 {
+    // 4 extra slots repeat gradients so the pick stays a cheap shift
+    match hash >> 60 {
+        0 | 12 => dx + dy,
+        1 | 13 => -dx + dy,
+        2 => dx - dy,
+        3 => -dx - dy,
+        4 => dx + dz,
+        5 => -dx + dz,
+        6 => dx - dz,
+        7 => -dx - dz,
+        8 => dy + dz,
+        9 | 14 => -dy + dz,
+        10 => dy - dz,
+        _ => -dy - dz,
+    }
+}
+
+/// Perlin style gradient noise in `[-1, 1]`, zero on every lattice point
+pub fn gradient_noise_2d(seed: u64, x: FixedPoint, z: FixedPoint) -> FixedPoint {
+    let (x0, tx) = cell(x);
+    let (z0, tz) = cell(z);
+    let (x1, z1) = (x0.wrapping_add(i48!(1)), z0.wrapping_add(i48!(1)));
+
+    let corner = |x, z, dx, dz| gradient_2d(hash_lattice(seed, &[x, z]), dx, dz);
+
+    let (fx, fz) = (fade(tx), fade(tz));
+    let near = lerp(corner(x0, z0, tx, tz), corner(x1, z0, tx - ONE, tz), fx);
+    let far = lerp(corner(x0, z1, tx, tz - ONE), corner(x1, z1, tx - ONE, tz - ONE), fx);
+
+    FixedPoint::from_bits(lerp(near, far, fz).clamp(-ONE, ONE))
+}
+
+/// Perlin style gradient noise in `[-1, 1]`, zero on every lattice point
+pub fn gradient_noise_3d(seed: u64, x: FixedPoint, y: FixedPoint, z: FixedPoint) -> FixedPoint {
+    let (x0, tx) = cell(x);
+    let (y0, ty) = cell(y);
+    let (z0, tz) = cell(z);
+    let [x1, y1, z1] = [x0, y0, z0].map(|c| c.wrapping_add(i48!(1)));
+
+    let corner = |x, y, z, dx, dy, dz| gradient_3d(hash_lattice(seed, &[x, y, z]), dx, dy, dz);
+
+    let (fx, fy, fz) = (fade(tx), fade(ty), fade(tz));
+    let layer = |y, dy| {
+        let near = lerp(corner(x0, y, z0, tx, dy, tz), corner(x1, y, z0, tx - ONE, dy, tz), fx);
+        let far = lerp(
+            corner(x0, y, z1, tx, dy, tz - ONE),
+            corner(x1, y, z1, tx - ONE, dy, tz - ONE),
+            fx
+        );
+        lerp(near, far, fz)
+    };
+
+    FixedPoint::from_bits(lerp(layer(y0, ty), layer(y1, ty - ONE), fy).clamp(-ONE, ONE))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn fixed(bits: i64) -> FixedPoint {
+        FixedPoint::from_bits(bits)
+    }
+
+    /// a spread of coordinates, including negative ones and ones far from the origin
+    fn sample_points() -> impl Iterator<Item = (FixedPoint, FixedPoint, FixedPoint)> {
+        (0..20_000_i64).map(|i| {
+            let x = (i * 7919 - 50_000) * 37;
+            let y = (i * 104_729) % 4_000_000 - 2_000_000;
+            let z = -(i * 15_485_863) % 9_000_000_000;
+            (fixed(x), fixed(y), fixed(z))
+        })
+    }
+
+    #[test]
+    fn fade_hits_the_ends() {
+        assert_eq!(fade(0), 0);
+        assert_eq!(fade(ONE), ONE);
+        assert_eq!(fade(ONE / 2), ONE / 2);
+    }
+
+    #[test]
+    fn golden_values() {
+        // these are the values every platform has to produce,
+        // changing them changes every world generated from a seed
+        let (x, y, z) = (fixed(123_456), fixed(-654_321), fixed(42 << 16 | 0x8000));
+
+        assert_eq!(value_noise_2d(1, x, z).to_bits(), 0x7349);
+        assert_eq!(value_noise_3d(1, x, y, z).to_bits(), 0xE732);
+        assert_eq!(gradient_noise_2d(1, x, z).to_bits(), -3759);
+        assert_eq!(gradient_noise_3d(1, x, y, z).to_bits(), -1040);
+    }
+
+    #[test]
+    fn seeds_change_the_output() {
+        let differs = sample_points().take(100).any(|(x, _, z)| {
+            value_noise_2d(1, x, z) != value_noise_2d(2, x, z)
+        });
+        assert!(differs);
+    }
+
+    #[test]
+    fn output_stays_in_range() {
+        let (mut min, mut max) = (i64::MAX, i64::MIN);
+        let (mut gradient_min, mut gradient_max) = (i64::MAX, i64::MIN);
+
+        for (x, y, z) in sample_points() {
+            // fract is in range by construction, but it has to actually use the range
+            let value = value_noise_2d(7, x, z).to_bits();
+            min = min.min(value as i64);
+            max = max.max(value as i64);
+            let _ = value_noise_3d(7, x, y, z);
+
+            for noise in [gradient_noise_2d(7, x, z), gradient_noise_3d(7, x, y, z)] {
+                assert!((-ONE..=ONE).contains(&noise.to_bits()), "{}", noise.as_f32());
+                gradient_min = gradient_min.min(noise.to_bits());
+                gradient_max = gradient_max.max(noise.to_bits());
+            }
+        }
+
+        assert!(min < 0x2000 && max > 0xE000, "{min:#x}..{max:#x}");
+        assert!(gradient_min < -ONE / 2 && gradient_max > ONE / 2, "{gradient_min}..{gradient_max}");
+    }
+
+    #[test]
+    fn gradient_noise_is_zero_on_the_lattice() {
+        for i in -50..50 {
+            let x = FixedPoint::from_int(i48::new(i * 3).unwrap());
+            let z = FixedPoint::from_int(i48::new(i * -5 + 1).unwrap());
+            assert_eq!(gradient_noise_2d(99, x, z), FixedPoint::ZERO);
+            assert_eq!(gradient_noise_3d(99, x, z, x), FixedPoint::ZERO);
+        }
+    }
+
+    #[test]
+    fn noise_is_continuous() {
+        // one fixed point step can only move the noise a tiny bit
+        let step = fixed(1);
+        for (x, y, z) in sample_points().take(2000) {
+            let a = gradient_noise_3d(3, x, y, z).to_bits();
+            let b = gradient_noise_3d(3, x + step, y, z).to_bits();
+            assert!((a - b).abs() < 16, "{a} {b}");
+
+            let a = value_noise_2d(3, x, z).to_bits() as i64;
+            let b = value_noise_2d(3, x, z + step).to_bits() as i64;
+            assert!((a - b).abs() < 16, "{a} {b}");
+        }
+    }
+}
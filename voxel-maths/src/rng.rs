@@ -0,0 +1,203 @@
+use std::ops::Range;
+use crate::fixed_point::{FixedPoint, Fract};
+use crate::i48_int::i48;
+use crate::FixedPointVec3;
+
+/// one in fixed point bits
+const ONE_BITS: i64 = 1 << 16;
+
+/// A small, fast pseudo random number generator (xoshiro256**)
+///
+/// the output only ever depends on the seed, never on the platform, so anything generated
+/// from it (terrain, particles, ...) comes out exactly the same everywhere.
+/// Not suitable for anything that needs to be unpredictable.
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct Rng {
+    state: [u64; 4],
+}
+
+/// Spreads a single seed over the whole state, as the xoshiro authors recommend
+pub(crate) const fn splitmix64(state: &mut u64) -> u64 {
+    *state = state.wrapping_add(0x9E37_79B9_7F4A_7C15);
+    let mut z = *state;
+    z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+    z ^ (z >> 31)
+}
+
+impl Rng {
+    pub const fn new(seed: u64) -> Self {
+        let mut seed = seed;
+        // splitmix64 never outputs four zeroes in a row, so the state is never all zero
+        let state = [
+            splitmix64(&mut seed),
+            splitmix64(&mut seed),
+            splitmix64(&mut seed),
+            splitmix64(&mut seed),
+        ];
+
+        Self { state }
+    }
+
+    pub const fn next_u64(&mut self) -> u64 {
+        let [s0, s1, s2, s3] = &mut self.state;
+
+        let result = s1.wrapping_mul(5).rotate_left(7).wrapping_mul(9);
+        let t = *s1 << 17;
+
+        *s2 ^= *s0;
+        *s3 ^= *s1;
+        *s1 ^= *s2;
+        *s0 ^= *s3;
+
+        *s2 ^= t;
+        *s3 = s3.rotate_left(45);
+
+        result
+    }
+
+    pub const fn next_u32(&mut self) -> u32 {
+        // the high bits are the better ones
+        (self.next_u64() >> 32) as u32
+    }
+
+    /// A uniformly distributed number in `0..bound` without modulo bias
+    ///
+    /// # Panics
+    /// when `bound` is zero
+    pub fn below(&mut self, bound: u64) -> u64 {
+        assert_ne!(bound, 0, "can't generate a number below zero");
+
+        // Lemire's nearly divisionless method
+        let mut product = self.next_u64() as u128 * bound as u128;
+        if (product as u64) < bound {
+            let threshold = bound.wrapping_neg() % bound;
+            while (product as u64) < threshold {
+                product = self.next_u64() as u128 * bound as u128;
+            }
+        }
+
+        (product >> 64) as u64
+    }
+
+    /// A uniformly distributed number in `range`
+    ///
+    /// # Panics
+    /// when `range` is empty
+    pub fn i48_in(&mut self, range: Range<i48>) -> i48 {
+        assert!(range.start < range.end, "can't generate a number in an empty range");
+
+        // an i48 range is at most 2^48 wide, so this can't overflow an i64
+        let span = range.end.as_i64() - range.start.as_i64();
+        let offset = self.below(span as u64) as i64;
+        i48::new(range.start.as_i64() + offset).expect("start + offset < end")
+    }
+
+    /// A uniformly distributed fraction in `[0, 1)`
+    pub const fn fract(&mut self) -> Fract {
+        Fract::from_bits((self.next_u64() >> 48) as u16)
+    }
+
+    /// A uniformly distributed direction, with a length as close to one as fixed point allows
+    pub fn unit_vector(&mut self) -> FixedPointVec3 {
+        // pick points in the cube around the unit sphere until one lands inside it,
+        // then push it out to the surface, all in integers so it is the same on every platform
+        loop {
+            let mut component = || self.below(2 * ONE_BITS as u64 + 1) as i64 - ONE_BITS;
+            let [x, y, z] = [component(), component(), component()];
+
+            let length_squared = (x * x + y * y + z * z) as u64;
+            // outside the sphere or too close to the center to have a useful direction
+            if length_squared > (ONE_BITS * ONE_BITS) as u64 || length_squared < (ONE_BITS as u64) {
+                continue;
+            }
+
+            let length = length_squared.isqrt() as i64;
+            let scale = |bits: i64| FixedPoint::from_bits(bits * ONE_BITS / length);
+            return FixedPointVec3::new(scale(x), scale(y), scale(z));
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::i48;
+    use super::*;
+
+    #[test]
+    fn same_seed_same_sequence() {
+        let mut a = Rng::new(0xDEAD_BEEF);
+        let mut b = Rng::new(0xDEAD_BEEF);
+
+        for _ in 0..1000 {
+            assert_eq!(a.next_u64(), b.next_u64());
+            assert_eq!(a.i48_in(i48!(-50)..i48!(50)), b.i48_in(i48!(-50)..i48!(50)));
+            assert_eq!(a.fract(), b.fract());
+            assert_eq!(a.unit_vector(), b.unit_vector());
+        }
+
+        let mut c = Rng::new(0xDEAD_BEF0);
+        let a = (0..4).map(|_| a.next_u64()).collect::<Vec<_>>();
+        let c = (0..4).map(|_| c.next_u64()).collect::<Vec<_>>();
+        assert_ne!(a, c);
+    }
+
+    #[test]
+    fn sequence_is_stable() {
+        // worlds are generated from this, changing the output changes every world
+        let mut rng = Rng::new(0);
+        let first = [rng.next_u64(), rng.next_u64(), rng.next_u64()];
+        assert_eq!(first, [0x99EC_5F36_CB75_F2B4, 0xBF6E_1F78_4956_452A, 0x1A5F_849D_4933_E6E0]);
+    }
+
+    #[test]
+    fn ranges_stay_in_bounds() {
+        let mut rng = Rng::new(42);
+
+        let ranges = [
+            i48!(0)..i48!(1),
+            i48!(-3)..i48!(4),
+            i48!(-1_000_000)..i48!(-999_990),
+            i48::MIN..i48::MAX,
+        ];
+
+        for range in ranges {
+            let mut seen_start = false;
+            for _ in 0..2000 {
+                let value = rng.i48_in(range.clone());
+                assert!(range.contains(&value), "{value} not in {range:?}");
+                seen_start |= value == range.start;
+            }
+
+            if range.end.as_i64() - range.start.as_i64() <= 10 {
+                assert!(seen_start, "{range:?} never generated its start");
+            }
+        }
+
+        for _ in 0..2000 {
+            assert!(rng.below(7) < 7);
+        }
+    }
+
+    #[test]
+    fn fractions_cover_the_unit_interval() {
+        let mut rng = Rng::new(7);
+        let mut buckets = [0u32; 4];
+        for _ in 0..4000 {
+            let fract = rng.fract().as_f32();
+            assert!((0.0..1.0).contains(&fract));
+            buckets[(fract * 4.0) as usize] += 1;
+        }
+
+        assert!(buckets.iter().all(|&count| count > 800), "{buckets:?}");
+    }
+
+    #[test]
+    fn unit_vectors_have_unit_length() {
+        let mut rng = Rng::new(1234);
+        for _ in 0..1000 {
+            let vector = rng.unit_vector().as_f32();
+            assert!((vector.length() - 1.0).abs() < 1e-3, "{vector}");
+        }
+    }
+}
@@ -1,5 +1,6 @@
 pub mod time;
 pub mod rt;
 pub mod sync;
+pub mod fs;
 
-pub use rt::{block_on, spawn, spawn_async};
\ No newline at end of file
+pub use rt::{block_on, is_cancelled, spawn, spawn_async};
\ No newline at end of file
@@ -1,9 +1,13 @@
 use std::convert::Infallible;
+use std::num::NonZero;
 use std::panic::AssertUnwindSafe;
 use std::pin::Pin;
-use std::sync::LazyLock;
+use std::cell::RefCell;
+use std::sync::{Arc, LazyLock};
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
 use std::task::{Context, Poll};
 use tokio::runtime::Handle; 
+use tokio::sync::Semaphore;
 use tokio::task::{JoinError, JoinHandle};
 
 static RUNTIME: LazyLock<Handle> = LazyLock::new(|| {
@@ -32,17 +36,52 @@ pub fn poll<F: Future>(mut future: Pin<&mut F>) -> Poll<F::Output> {
     block_on(std::future::poll_fn(|cx| Poll::Ready(future.as_mut().poll(cx))))
 }
 
-pub struct JobHandle<T>(JoinHandle<T>);
+/// A running job, dropping it cancels the job unless it was [detached](JobHandle::detach)
+pub struct JobHandle<T> {
+    task: JoinHandle<T>,
+    abort_on_drop: bool,
+}
 
 impl<T> JobHandle<T> {
-    /// This doesnt always stop the task from executing but it will try its best to cancel it
+    fn new(task: JoinHandle<T>) -> Self {
+        Self { task, abort_on_drop: true }
+    }
+
+    /// This doesnt always stop the task from executing but it will try its best to cancel it,
+    /// blocking jobs only stop early if they check [`is_cancelled`]
     pub fn abort(self) {
-        self.0.abort()
+        self.task.abort()
+    }
+
+    /// Lets the job run to the end with nothing waiting on it
+    pub fn detach(mut self) {
+        self.abort_on_drop = false
     }
 
     pub fn join(self) -> T {
         block_on(self)
     }
+
+    /// Takes the result if the job is already done, without waiting on it,
+    /// otherwise hands the handle back to try again later
+    pub fn try_join(mut self) -> Result<T, Self> {
+        if !self.task.is_finished() {
+            return Err(self)
+        }
+
+        match poll(Pin::new(&mut self)) {
+            Poll::Ready(res) => Ok(res),
+            Poll::Pending => Err(self),
+        }
+    }
+}
+
+impl<T> Drop for JobHandle<T> {
+    fn drop(&mut self) {
+        if self.abort_on_drop {
+            self.task.abort()
+        }
+    }
 }
 
 #[cold]
@@ -61,7 +100,7 @@ impl<T> Future for JobHandle<T> {
 
     #[track_caller]
     fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
-        Pin::new(&mut Pin::into_inner(self).0).poll(cx).map(|res| {
+        Pin::new(&mut Pin::into_inner(self).task).poll(cx).map(|res| {
             match res {
                 Ok(res) => res,
                 Err(payload) => hit_join_error(payload),
@@ -70,16 +109,205 @@ impl<T> Future for JobHandle<T> {
     }
 }
 
-fn blocking_to_join_handle<T: Send + 'static>(func: impl FnOnce() -> T + 'static + Send) -> (impl FnOnce() + Send, JobHandle<T>) {
+/// Drives every handle at once, stopping at the first panic when `short_circuit` is set,
+/// otherwise the first panic cancels the rest of the tasks and waits for them to stop
+async fn join_handles<T>(handles: Vec<JobHandle<T>>, short_circuit: bool) -> Result<Vec<T>, JoinError> {
+    let mut pending = handles.into_iter().map(Some).collect::<Vec<_>>();
+    let mut results = pending.iter().map(|_| None).collect::<Vec<_>>();
+    let mut first_error = None;
+
+    std::future::poll_fn(|cx| {
+        let mut done = true;
+        for (slot, result) in pending.iter_mut().zip(&mut results) {
+            let Some(handle) = slot else {
+                continue
+            };
+
+            match Pin::new(&mut handle.task).poll(cx) {
+                Poll::Pending => {
+                    done = false;
+                    continue
+                },
+                Poll::Ready(Ok(value)) => *result = Some(value),
+                Poll::Ready(Err(err)) if short_circuit => return Poll::Ready(Err(err)),
+                // the tasks cancelled below finish with errors of their own, only the first one counts
+                Poll::Ready(Err(err)) => {
+                    first_error.get_or_insert(err);
+                }
+            }
+
+            *slot = None
+        }
+
+        // a task that never finishes would keep the panic from ever getting passed on
+        if first_error.is_some() {
+            pending.iter().flatten().for_each(|handle| handle.task.abort());
+        }
+
+        match done {
+            true => Poll::Ready(Ok(())),
+            false => Poll::Pending,
+        }
+    }).await?;
+
+    match first_error {
+        Some(err) => Err(err),
+        None => Ok(results.into_iter().map(|result| result.expect("every task finished")).collect())
+    }
+}
+
+/// Waits on every handle at once and returns their results in the same order
+///
+/// # Panics
+/// with the first panic of any of the tasks, the ones still running then get cancelled
+/// and waited on before the panic is passed on
+pub fn join_all<T>(handles: Vec<JobHandle<T>>) -> Vec<T> {
+    block_on(join_handles(handles, false)).unwrap_or_else(|err| hit_join_error(err))
+}
+
+/// Like [`join_all`], but the first panic is passed on as soon as it happens,
+/// the rest of the tasks get cancelled instead of waited on
+pub fn try_join_all<T>(handles: Vec<JobHandle<T>>) -> Vec<T> {
+    block_on(join_handles(handles, true)).unwrap_or_else(|err| hit_join_error(err))
+}
+
+/// How many tasks a pool has been given and what became of them
+struct PoolCounters {
+    spawned: AtomicU64,
+    completed: AtomicU64,
+    panicked: AtomicU64,
+}
+
+impl PoolCounters {
+    const fn new() -> Self {
+        Self {
+            spawned: AtomicU64::new(0),
+            completed: AtomicU64::new(0),
+            panicked: AtomicU64::new(0),
+        }
+    }
+
+    fn spawn(&'static self) -> TaskGuard {
+        self.spawned.fetch_add(1, Ordering::Relaxed);
+        TaskGuard(self)
+    }
+
+    fn load(&self) -> PoolMetrics {
+        // load the finished counts first so a task finishing in between can't make active negative
+        let completed = self.completed.load(Ordering::Relaxed);
+        let panicked = self.panicked.load(Ordering::Relaxed);
+        let spawned = self.spawned.load(Ordering::Relaxed);
+
+        PoolMetrics {
+            active: spawned.saturating_sub(completed + panicked),
+            completed,
+            panicked,
+        }
+    }
+}
+
+/// Lives as long as the task it was made for, counting it as finished once dropped
+struct TaskGuard(&'static PoolCounters);
+
+impl Drop for TaskGuard {
+    fn drop(&mut self) {
+        let counter = match std::thread::panicking() {
+            true => &self.0.panicked,
+            false => &self.0.completed,
+        };
+        counter.fetch_add(1, Ordering::Relaxed);
+    }
+}
+
+static BLOCKING: PoolCounters = PoolCounters::new();
+static LONG_LIVED: PoolCounters = PoolCounters::new();
+static ASYNC: PoolCounters = PoolCounters::new();
+
+#[derive(Debug, Copy, Clone, Default, Eq, PartialEq)]
+pub struct PoolMetrics {
+    /// waiting to run or running
+    pub active: u64,
+    /// returned, or were cancelled
+    pub completed: u64,
+    pub panicked: u64,
+}
+
+#[derive(Debug, Copy, Clone, Default, Eq, PartialEq)]
+pub struct RuntimeMetrics {
+    /// tasks from [`spawn`]
+    pub blocking: PoolMetrics,
+    /// tasks from [`spawn_long_lived`]
+    pub long_lived: PoolMetrics,
+    /// tasks from [`spawn_async`]
+    pub async_tasks: PoolMetrics,
+}
+
+/// A snapshot of how many tasks each pool has handled since the program started
+pub fn metrics() -> RuntimeMetrics {
+    RuntimeMetrics {
+        blocking: BLOCKING.load(),
+        long_lived: LONG_LIVED.load(),
+        async_tasks: ASYNC.load(),
+    }
+}
+
+thread_local! {
+    static CURRENT_JOB: RefCell<Option<Arc<AtomicBool>>> = const { RefCell::new(None) };
+}
+
+/// Whether the handle of the blocking job running on this thread was aborted or dropped,
+/// long jobs should check this every so often and give up once it's set
+pub fn is_cancelled() -> bool {
+    CURRENT_JOB.with_borrow(|job| job.as_ref().is_some_and(|cancelled| cancelled.load(Ordering::Relaxed)))
+}
+
+/// Marks a job as the one running on this thread until dropped,
+/// a job may be run while waiting on another one so the previous job is put back after
+struct CurrentJob(Option<Arc<AtomicBool>>);
+
+impl CurrentJob {
+    fn enter(cancelled: Arc<AtomicBool>) -> Self {
+        Self(CURRENT_JOB.replace(Some(cancelled)))
+    }
+}
+
+impl Drop for CurrentJob {
+    fn drop(&mut self) {
+        CURRENT_JOB.set(self.0.take())
+    }
+}
+
+/// Sets the flag once the task waiting on a blocking job goes away
+struct CancelOnDrop(Arc<AtomicBool>);
+
+impl Drop for CancelOnDrop {
+    fn drop(&mut self) {
+        self.0.store(true, Ordering::Relaxed)
+    }
+}
+
+fn blocking_to_join_handle<T: Send + 'static>(
+    pool: &'static PoolCounters,
+    func: impl FnOnce() -> T + 'static + Send
+) -> (impl FnOnce() + Send, JobHandle<T>) {
     let (send, rcv) = tokio::sync::oneshot::channel();
+    let task = pool.spawn();
+    let cancelled = Arc::new(AtomicBool::new(false));
+    let cancel_on_drop = CancelOnDrop(Arc::clone(&cancelled));
 
     let func = move || {
-        let res = std::panic::catch_unwind(AssertUnwindSafe(func));
+        let _job = CurrentJob::enter(cancelled);
+        // the guard drops inside catch_unwind, while it can still tell if the task panicked
+        let res = std::panic::catch_unwind(AssertUnwindSafe(move || {
+            let _task = task;
+            func()
+        }));
         // we don't care if there is no receiver
         let _ = send.send(res);
     };
 
-    let handle = JobHandle(RUNTIME.spawn(async move {
+    let handle = JobHandle::new(RUNTIME.spawn(async move {
+        let _cancel_on_drop = cancel_on_drop;
         match rcv.await {
             Ok(Ok(data)) => data,
             Ok(Err(payload)) => std::panic::resume_unwind(payload),
@@ -91,13 +319,13 @@ fn blocking_to_join_handle<T: Send + 'static>(func: impl FnOnce() -> T + 'static
 }
 
 pub fn spawn<T: Send + 'static>(func: impl FnOnce() -> T + 'static + Send) -> JobHandle<T> {
-    let (task, handle) = blocking_to_join_handle(func);
+    let (task, handle) = blocking_to_join_handle(&BLOCKING, func);
     rayon::spawn(task);
     handle
 }
 
 pub fn spawn_long_lived<T: Send + 'static>(func: impl FnOnce() -> T + 'static + Send) -> JobHandle<T> {
-    let (task, handle) = blocking_to_join_handle(func);
+    let (task, handle) = blocking_to_join_handle(&LONG_LIVED, func);
     std::thread::spawn(task);
     handle
 }
@@ -107,5 +335,252 @@ where
     F: Future + Send + 'static,
     F::Output: Send + 'static
 {
-    JobHandle(RUNTIME.spawn(future))
+    spawn_async_in(&ASYNC, future)
+}
+
+fn spawn_async_in<F>(pool: &'static PoolCounters, future: F) -> JobHandle<F::Output>
+where
+    F: Future + Send + 'static,
+    F::Output: Send + 'static
+{
+    let task = pool.spawn();
+    JobHandle::new(RUNTIME.spawn(async move {
+        let _task = task;
+        future.await
+    }))
+}
+
+/// Caps how many jobs given to [`spawn_bounded`] with it can run at once
+#[derive(Debug, Clone)]
+pub struct JobLimit(Arc<Semaphore>);
+
+impl JobLimit {
+    pub fn new(max_in_flight: NonZero<usize>) -> Self {
+        Self(Arc::new(Semaphore::new(max_in_flight.get())))
+    }
+}
+
+/// Like [`spawn`], but waits for one of the jobs sharing `limit` to finish
+/// if too many are running, so a flood of jobs can't take over the blocking pool.
+/// A job that gets cancelled while it's waiting never runs
+pub fn spawn_bounded<T: Send + 'static>(
+    limit: &JobLimit,
+    func: impl FnOnce() -> T + 'static + Send
+) -> JobHandle<T> {
+    let semaphore = Arc::clone(&limit.0);
+    spawn_async(async move {
+        let permit = semaphore.acquire_owned().await.expect("the semaphore is never closed");
+        spawn(move || {
+            let _permit = permit;
+            func()
+        }).await
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::RwLock;
+    use std::sync::atomic::AtomicUsize;
+    use std::sync::{mpsc, Mutex, MutexGuard};
+    use std::time::Duration;
+    use super::*;
+
+    /// The pools are shared by every test, so the ones counting what's on them take turns
+    fn exclusive_pools() -> MutexGuard<'static, ()> {
+        static POOLS: Mutex<()> = Mutex::new(());
+        POOLS.lock().unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+
+    #[test]
+    fn dropping_a_handle_cancels_the_job() {
+        let _pools = exclusive_pools();
+        let (started_send, started) = mpsc::channel();
+        let (stopped_send, stopped) = mpsc::channel();
+        let job = spawn_long_lived(move || {
+            started_send.send(()).unwrap();
+            while !is_cancelled() {
+                std::thread::sleep(Duration::from_millis(1));
+            }
+            stopped_send.send(()).unwrap();
+        });
+
+        started.recv().unwrap();
+        assert!(stopped.recv_timeout(Duration::from_millis(20)).is_err());
+        drop(job);
+        stopped.recv_timeout(Duration::from_secs(5)).expect("the job should see it was cancelled");
+        assert!(!is_cancelled());
+    }
+
+    #[test]
+    fn detached_jobs_keep_running() {
+        let _pools = exclusive_pools();
+        let (send, rcv) = mpsc::channel();
+        let gate = Arc::new(RwLock::new(()));
+        let closed = gate.write().unwrap();
+        let job = spawn({
+            let gate = Arc::clone(&gate);
+            move || {
+                drop(gate.read().unwrap());
+                send.send(is_cancelled()).unwrap();
+            }
+        });
+
+        job.detach();
+        drop(closed);
+        assert_eq!(rcv.recv_timeout(Duration::from_secs(5)), Ok(false));
+    }
+
+    #[test]
+    fn bounded_jobs_stay_under_the_limit() {
+        let _pools = exclusive_pools();
+        const LIMIT: usize = 2;
+
+        let limit = JobLimit::new(NonZero::new(LIMIT).unwrap());
+        let running = Arc::new(AtomicUsize::new(0));
+        let peak = Arc::new(AtomicUsize::new(0));
+        let handles = (0..12)
+            .map(|i| {
+                let (running, peak) = (Arc::clone(&running), Arc::clone(&peak));
+                spawn_bounded(&limit, move || {
+                    let now = running.fetch_add(1, Ordering::SeqCst) + 1;
+                    peak.fetch_max(now, Ordering::SeqCst);
+                    std::thread::sleep(Duration::from_millis(5));
+                    running.fetch_sub(1, Ordering::SeqCst);
+                    i
+                })
+            })
+            .collect();
+
+        assert_eq!(join_all(handles), (0..12).collect::<Vec<_>>());
+        assert!((1..=LIMIT).contains(&peak.load(Ordering::SeqCst)));
+        assert_eq!(running.load(Ordering::SeqCst), 0);
+    }
+
+    #[test]
+    fn try_join_never_waits() {
+        let _pools = exclusive_pools();
+        let gate = Arc::new(RwLock::new(()));
+        let closed = gate.write().unwrap();
+        let job = spawn({
+            let gate = Arc::clone(&gate);
+            move || {
+                drop(gate.read().unwrap());
+                42
+            }
+        });
+
+        let mut job = job.try_join().expect_err("the job can't have finished yet");
+        drop(closed);
+
+        let start = std::time::Instant::now();
+        let res = loop {
+            match job.try_join() {
+                Ok(res) => break res,
+                Err(handle) => job = handle,
+            }
+            assert!(start.elapsed() < Duration::from_secs(5), "the job never finished");
+            std::thread::yield_now();
+        };
+        assert_eq!(res, 42);
+    }
+
+    #[test]
+    fn join_all_keeps_the_order() {
+        let _pools = exclusive_pools();
+        // the later jobs finish first
+        let handles = (0..8_u64)
+            .map(|i| spawn(move || {
+                std::thread::sleep(Duration::from_millis(8 - i));
+                i * i
+            }))
+            .collect();
+
+        assert_eq!(join_all(handles), [0, 1, 4, 9, 16, 25, 36, 49]);
+        assert_eq!(join_all(Vec::<JobHandle<()>>::new()), []);
+    }
+
+    #[test]
+    fn try_join_all_passes_on_panics() {
+        let _pools = exclusive_pools();
+        let slow = spawn_long_lived(|| {
+            std::thread::sleep(Duration::from_secs(2));
+            0
+        });
+        let failing = spawn(|| -> i32 { panic!("broken job") });
+
+        let start = std::time::Instant::now();
+        let panic = std::panic::catch_unwind(AssertUnwindSafe(|| try_join_all(vec![slow, failing]))).unwrap_err();
+        assert_eq!(panic.downcast_ref::<&str>(), Some(&"broken job"));
+        // it didn't wait on the slow job
+        assert!(start.elapsed() < Duration::from_secs(2));
+
+        let handles = vec![spawn(|| 1), spawn(|| -> i32 { panic!("also broken") })];
+        assert!(std::panic::catch_unwind(AssertUnwindSafe(|| join_all(handles))).is_err());
+    }
+
+    #[test]
+    fn join_all_cancels_the_rest_after_a_panic() {
+        let _pools = exclusive_pools();
+        let (stopped_send, stopped) = mpsc::channel();
+        let failing = spawn(|| -> i32 { panic!("broken job") });
+        let stuck = spawn_async(std::future::pending::<i32>());
+        let cancelled = spawn_long_lived(move || {
+            while !is_cancelled() {
+                std::thread::sleep(Duration::from_millis(1));
+            }
+            stopped_send.send(()).unwrap();
+            0
+        });
+
+        // joined on another thread so a hang fails the test instead of stalling it
+        let (joined_send, joined) = mpsc::channel();
+        std::thread::spawn(move || {
+            let res = std::panic::catch_unwind(AssertUnwindSafe(|| join_all(vec![failing, stuck, cancelled])));
+            joined_send.send(res.map_err(|panic| panic.downcast_ref::<&str>().copied())).unwrap();
+        });
+
+        let res = joined.recv_timeout(Duration::from_secs(5)).expect("join_all should give up on the stuck task");
+        assert_eq!(res, Err(Some("broken job")));
+        stopped.recv_timeout(Duration::from_secs(5)).expect("the blocking job should see it was cancelled");
+    }
+
+    #[test]
+    fn metrics_follow_jobs() {
+        // pools of the test's own, jobs other tests leave behind can't throw off the counts
+        static BLOCKING: PoolCounters = PoolCounters::new();
+        static ASYNC: PoolCounters = PoolCounters::new();
+        fn spawn<T: Send + 'static>(func: impl FnOnce() -> T + 'static + Send) -> JobHandle<T> {
+            let (task, handle) = blocking_to_join_handle(&BLOCKING, func);
+            rayon::spawn(task);
+            handle
+        }
+        const JOBS: u64 = 4;
+
+        // none of the jobs can finish until the gate opens
+        let gate = Arc::new(RwLock::new(()));
+        let closed = gate.write().unwrap();
+        let handles = (0..JOBS)
+            .map(|i| {
+                let gate = Arc::clone(&gate);
+                spawn(move || {
+                    drop(gate.read().unwrap());
+                    i
+                })
+            })
+            .collect::<Vec<_>>();
+
+        assert_eq!(BLOCKING.load(), PoolMetrics { active: JOBS, completed: 0, panicked: 0 });
+
+        drop(closed);
+        let results = handles.into_iter().map(JobHandle::join).collect::<Vec<_>>();
+        assert_eq!(results, (0..JOBS).collect::<Vec<_>>());
+        assert_eq!(BLOCKING.load(), PoolMetrics { active: 0, completed: JOBS, panicked: 0 });
+
+        let panicking = spawn(|| panic!("job failed"));
+        assert!(std::panic::catch_unwind(AssertUnwindSafe(|| panicking.join())).is_err());
+        assert_eq!(BLOCKING.load(), PoolMetrics { active: 0, completed: JOBS, panicked: 1 });
+
+        assert_eq!(spawn_async_in(&ASYNC, async { 7 }).join(), 7);
+        assert_eq!(ASYNC.load(), PoolMetrics { active: 0, completed: 1, panicked: 0 });
+    }
 }
@@ -0,0 +1,164 @@
+//! Fans events out to everything subscribed to them, every receiver sees every event sent after it subscribed
+
+use std::fmt::{Display, Formatter};
+use tokio::sync::broadcast;
+
+/// Makes a channel holding up to `capacity` events that not every receiver has seen yet,
+/// past that the oldest get dropped and whoever didn't see them gets told they lagged behind
+///
+/// # Panics
+/// if `capacity` is zero
+pub fn channel<T: Clone>(capacity: usize) -> (Sender<T>, Receiver<T>) {
+    let (sender, receiver) = broadcast::channel(capacity);
+    (Sender(sender), Receiver(receiver))
+}
+
+#[derive(Debug)]
+pub struct Sender<T>(broadcast::Sender<T>);
+
+impl<T> Clone for Sender<T> {
+    fn clone(&self) -> Self {
+        Self(self.0.clone())
+    }
+}
+
+impl<T: Clone> Sender<T> {
+    /// Sends `event` to every receiver, returning how many there are
+    ///
+    /// this never waits, receivers that fall too far behind lose the oldest events instead
+    pub fn send(&self, event: T) -> usize {
+        // with nobody subscribed the event just goes nowhere
+        self.0.send(event).unwrap_or(0)
+    }
+
+    /// A receiver that gets every event sent from now on
+    pub fn subscribe(&self) -> Receiver<T> {
+        Receiver(self.0.subscribe())
+    }
+
+    pub fn receiver_count(&self) -> usize {
+        self.0.receiver_count()
+    }
+}
+
+#[derive(Debug)]
+pub struct Receiver<T>(broadcast::Receiver<T>);
+
+impl<T: Clone> Receiver<T> {
+    /// Another receiver on the same channel, like [`Sender::subscribe`]
+    ///
+    /// it only gets events sent from now on, the ones this receiver hasn't gotten to yet are left out
+    pub fn resubscribe(&self) -> Self {
+        Self(self.0.resubscribe())
+    }
+
+    /// Waits for the next event
+    ///
+    /// after a [`RecvError::Lagged`] this carries on from the oldest event still around
+    pub async fn recv(&mut self) -> Result<T, RecvError> {
+        self.0.recv().await.map_err(|err| match err {
+            broadcast::error::RecvError::Lagged(missed) => RecvError::Lagged(missed),
+            broadcast::error::RecvError::Closed => RecvError::Closed,
+        })
+    }
+
+    /// The next event if one was already sent, for polling once a frame
+    pub fn try_recv(&mut self) -> Result<T, TryRecvError> {
+        self.0.try_recv().map_err(|err| match err {
+            broadcast::error::TryRecvError::Empty => TryRecvError::Empty,
+            broadcast::error::TryRecvError::Lagged(missed) => TryRecvError::Lagged(missed),
+            broadcast::error::TryRecvError::Closed => TryRecvError::Closed,
+        })
+    }
+}
+
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub enum RecvError {
+    /// the receiver fell behind and missed this many events
+    Lagged(u64),
+    /// every sender is gone and every event was received
+    Closed,
+}
+
+impl Display for RecvError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Lagged(missed) => write!(f, "receiver lagged behind and missed {missed} events"),
+            Self::Closed => f.write_str("channel closed"),
+        }
+    }
+}
+
+impl std::error::Error for RecvError {}
+
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub enum TryRecvError {
+    /// nothing new was sent yet
+    Empty,
+    /// the receiver fell behind and missed this many events
+    Lagged(u64),
+    /// every sender is gone and every event was received
+    Closed,
+}
+
+impl Display for TryRecvError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Empty => f.write_str("channel empty"),
+            Self::Lagged(missed) => write!(f, "receiver lagged behind and missed {missed} events"),
+            Self::Closed => f.write_str("channel closed"),
+        }
+    }
+}
+
+impl std::error::Error for TryRecvError {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn every_subscriber_gets_every_event() {
+        let (sender, mut first) = channel(4);
+        let mut second = sender.subscribe();
+        assert_eq!(sender.receiver_count(), 2);
+
+        assert_eq!(sender.send("chunk loaded"), 2);
+        assert_eq!(crate::block_on(first.recv()), Ok("chunk loaded"));
+        assert_eq!(crate::block_on(second.recv()), Ok("chunk loaded"));
+        assert_eq!(first.try_recv(), Err(TryRecvError::Empty));
+
+        // a late subscriber only sees what comes after, even the ones
+        // the receiver it came from still has waiting
+        let mut late = sender.subscribe();
+        sender.send("block changed");
+        let mut later = late.resubscribe();
+        assert_eq!(later.try_recv(), Err(TryRecvError::Empty));
+        assert_eq!(late.try_recv(), Ok("block changed"));
+        assert_eq!(late.try_recv(), Err(TryRecvError::Empty));
+
+        drop(sender);
+        assert_eq!(first.try_recv(), Ok("block changed"));
+        assert_eq!(crate::block_on(first.recv()), Err(RecvError::Closed));
+    }
+
+    #[test]
+    fn slow_subscribers_lag_instead_of_blocking() {
+        let (sender, mut slow) = channel(2);
+        let mut fast = sender.subscribe();
+        for event in 0..5 {
+            sender.send(event);
+            assert_eq!(fast.try_recv(), Ok(event));
+        }
+
+        // only the last two are left, the slow one picks up from there
+        assert_eq!(crate::block_on(slow.recv()), Err(RecvError::Lagged(3)));
+        assert_eq!(slow.try_recv(), Ok(3));
+        assert_eq!(slow.try_recv(), Ok(4));
+        assert_eq!(slow.try_recv(), Err(TryRecvError::Empty));
+
+        // nobody listening isn't an error
+        drop((slow, fast));
+        assert_eq!(sender.send(5), 0);
+    }
+}
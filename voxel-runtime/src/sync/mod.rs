@@ -1,6 +1,9 @@
 use std::sync::Arc;
 use tokio::sync::Notify;
 
+pub mod broadcast;
+pub mod mpsc;
+
 #[derive(Debug)]
 pub struct Unparker(Arc<Notify>);
 
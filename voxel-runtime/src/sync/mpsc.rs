@@ -0,0 +1,144 @@
+//! Queues commands up for a single worker, like the main thread handing work to a background task
+
+use std::fmt::{Debug, Display, Formatter};
+use tokio::sync::mpsc;
+
+/// Makes a queue that any number of senders can push commands onto and one receiver takes them off of,
+/// in the order they were sent
+///
+/// sending never waits, so the main thread can send without stalling a frame
+pub fn channel<T>() -> (Sender<T>, Receiver<T>) {
+    let (sender, receiver) = mpsc::unbounded_channel();
+    (Sender(sender), Receiver(receiver))
+}
+
+#[derive(Debug)]
+pub struct Sender<T>(mpsc::UnboundedSender<T>);
+
+impl<T> Clone for Sender<T> {
+    fn clone(&self) -> Self {
+        Self(self.0.clone())
+    }
+}
+
+impl<T> Sender<T> {
+    /// Queues `command` up, handing it back if the receiver is gone
+    pub fn send(&self, command: T) -> Result<(), SendError<T>> {
+        self.0.send(command).map_err(|mpsc::error::SendError(command)| SendError(command))
+    }
+
+    /// Whether the receiver is gone, nothing sent will be received anymore
+    pub fn is_closed(&self) -> bool {
+        self.0.is_closed()
+    }
+}
+
+#[derive(Debug)]
+pub struct Receiver<T>(mpsc::UnboundedReceiver<T>);
+
+impl<T> Receiver<T> {
+    /// Waits for the next command, `None` once every sender is gone and every command was received,
+    /// so a worker looping on this stops by itself
+    pub async fn recv(&mut self) -> Option<T> {
+        self.0.recv().await
+    }
+
+    pub fn try_recv(&mut self) -> Result<T, TryRecvError> {
+        self.0.try_recv().map_err(|err| match err {
+            mpsc::error::TryRecvError::Empty => TryRecvError::Empty,
+            mpsc::error::TryRecvError::Disconnected => TryRecvError::Closed,
+        })
+    }
+}
+
+/// The receiver was gone, so the command that was sent never will be received
+#[derive(Copy, Clone, Eq, PartialEq)]
+pub struct SendError<T>(pub T);
+
+impl<T> Debug for SendError<T> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("SendError").finish_non_exhaustive()
+    }
+}
+
+impl<T> Display for SendError<T> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        f.write_str("channel closed")
+    }
+}
+
+impl<T> std::error::Error for SendError<T> {}
+
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub enum TryRecvError {
+    /// nothing was sent that wasn't received already
+    Empty,
+    /// every sender is gone and every command was received
+    Closed,
+}
+
+impl Display for TryRecvError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        f.write_str(match self {
+            Self::Empty => "channel empty",
+            Self::Closed => "channel closed",
+        })
+    }
+}
+
+impl std::error::Error for TryRecvError {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[derive(Debug, Eq, PartialEq)]
+    enum Command {
+        Load(i32),
+        Save,
+    }
+
+    #[test]
+    fn commands_arrive_in_order() {
+        let (sender, mut receiver) = channel();
+        let other = sender.clone();
+        sender.send(Command::Load(1)).unwrap();
+        other.send(Command::Load(2)).unwrap();
+        sender.send(Command::Save).unwrap();
+
+        assert_eq!(crate::block_on(receiver.recv()), Some(Command::Load(1)));
+        assert_eq!(receiver.try_recv(), Ok(Command::Load(2)));
+        assert_eq!(receiver.try_recv(), Ok(Command::Save));
+        assert_eq!(receiver.try_recv(), Err(TryRecvError::Empty));
+
+        // with the receiver gone the command comes back
+        drop(receiver);
+        assert!(sender.is_closed());
+        assert_eq!(sender.send(Command::Save).map_err(|SendError(command)| command), Err(Command::Save));
+    }
+
+    #[test]
+    fn dropping_the_senders_stops_the_worker() {
+        let (sender, mut receiver) = channel();
+        let main = std::thread::spawn(move || {
+            let other = sender.clone();
+            sender.send(Command::Load(1)).unwrap();
+            drop(sender);
+            other.send(Command::Save).unwrap();
+        });
+
+        let worker = async move {
+            let mut received = vec![];
+            while let Some(command) = receiver.recv().await {
+                received.push(command);
+            }
+            received
+        };
+
+        // everything sent before the last sender went still gets received
+        let received = crate::block_on(crate::time::timeout(std::time::Duration::from_secs(5), worker))
+            .expect("the worker should stop once every sender is gone");
+        assert_eq!(received, vec![Command::Load(1), Command::Save]);
+        main.join().unwrap();
+    }
+}
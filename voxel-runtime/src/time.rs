@@ -1,5 +1,219 @@
-use std::time::Duration;
+use std::cell::Cell;
+use std::time::{Duration, Instant};
 
 pub async fn sleep(duration: Duration) {
     tokio::time::sleep(duration).await
-}
\ No newline at end of file
+}
+
+/// Sleeps until `deadline`, right away if it already passed
+///
+/// waiting on a series of deadlines keeps to them, where the error of each [`sleep`] would add up
+pub async fn sleep_until(deadline: Instant) {
+    tokio::time::sleep_until(deadline.into()).await
+}
+
+/// Runs `future` for at most `duration`, `None` if it didn't finish in time
+pub async fn timeout<F: Future>(duration: Duration, future: F) -> Option<F::Output> {
+    tokio::time::timeout(duration, future).await.ok()
+}
+
+/// Where a [`Clock`] reads the time from, so tests can move it along by hand
+pub trait TimeSource {
+    fn now(&self) -> Instant;
+}
+
+impl<S: TimeSource + ?Sized> TimeSource for &S {
+    fn now(&self) -> Instant {
+        S::now(self)
+    }
+}
+
+/// The actual time, from [`Instant::now`]
+#[derive(Debug, Copy, Clone, Default)]
+pub struct RealTime;
+
+impl TimeSource for RealTime {
+    fn now(&self) -> Instant {
+        Instant::now()
+    }
+}
+
+/// A time that only moves when it's told to, for testing things that depend on time without waiting on it
+#[derive(Debug, Clone)]
+pub struct ManualTime {
+    now: Cell<Instant>,
+}
+
+impl ManualTime {
+    pub fn new() -> Self {
+        Self { now: Cell::new(Instant::now()) }
+    }
+
+    pub fn advance(&self, by: Duration) {
+        self.now.set(self.now.get() + by)
+    }
+}
+
+impl Default for ManualTime {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl TimeSource for ManualTime {
+    fn now(&self) -> Instant {
+        self.now.get()
+    }
+}
+
+/// Measures how much time passes between ticks, like between frames
+///
+/// the first tick, and the first after a pause or reset, only starts measuring so it's always zero;
+/// time spent paused never shows up in a tick
+#[derive(Debug, Clone, Default)]
+pub struct Clock<S = RealTime> {
+    source: S,
+    /// when the last tick was, `None` until the next one starts measuring again
+    last: Option<Instant>,
+    paused: bool,
+}
+
+impl Clock {
+    pub const fn new() -> Self {
+        Self::with_source(RealTime)
+    }
+}
+
+impl<S: TimeSource> Clock<S> {
+    pub const fn with_source(source: S) -> Self {
+        Self { source, last: None, paused: false }
+    }
+
+    /// The time since the last tick, zero while paused
+    pub fn tick(&mut self) -> Duration {
+        if self.paused {
+            return Duration::ZERO;
+        }
+
+        let now = self.source.now();
+        self.last
+            .replace(now)
+            .map_or(Duration::ZERO, |last| now.saturating_duration_since(last))
+    }
+
+    pub fn pause(&mut self) {
+        self.paused = true
+    }
+
+    /// Picks back up after [`Clock::pause`], the time spent paused gets skipped
+    pub fn resume(&mut self) {
+        if self.paused {
+            self.paused = false;
+            self.last = None;
+        }
+    }
+
+    pub fn is_paused(&self) -> bool {
+        self.paused
+    }
+
+    /// Forgets the last tick, so the time up to the next one gets skipped
+    pub fn reset(&mut self) {
+        self.last = None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const STEP: Duration = Duration::from_millis(16);
+
+    #[test]
+    fn ticks_measure_the_time_between_them() {
+        let time = ManualTime::new();
+        let mut clock = Clock::with_source(&time);
+        assert_eq!(clock.tick(), Duration::ZERO);
+        for _ in 0..10 {
+            time.advance(STEP);
+            assert_eq!(clock.tick(), STEP);
+        }
+
+        time.advance(2 * STEP);
+        assert_eq!(clock.tick(), 2 * STEP);
+        // no time passed since
+        assert_eq!(clock.tick(), Duration::ZERO);
+    }
+
+    #[test]
+    fn paused_clocks_stand_still() {
+        let time = ManualTime::new();
+        let mut clock = Clock::with_source(&time);
+        clock.tick();
+        clock.pause();
+        assert!(clock.is_paused());
+        time.advance(STEP);
+        assert_eq!(clock.tick(), Duration::ZERO);
+        time.advance(10 * STEP);
+        assert_eq!(clock.tick(), Duration::ZERO);
+
+        // the time spent paused doesn't land on the first tick after
+        clock.resume();
+        assert!(!clock.is_paused());
+        time.advance(STEP);
+        assert_eq!(clock.tick(), Duration::ZERO);
+        time.advance(STEP);
+        assert_eq!(clock.tick(), STEP);
+
+        // resuming a running clock changes nothing
+        clock.resume();
+        time.advance(STEP);
+        assert_eq!(clock.tick(), STEP);
+    }
+
+    #[test]
+    fn resets_skip_to_the_next_tick() {
+        let time = ManualTime::new();
+        let mut clock = Clock::with_source(&time);
+        clock.tick();
+        clock.reset();
+        time.advance(5 * STEP);
+        assert_eq!(clock.tick(), Duration::ZERO);
+        time.advance(STEP);
+        assert_eq!(clock.tick(), STEP);
+
+        // a reset doesn't unpause
+        clock.pause();
+        clock.reset();
+        time.advance(STEP);
+        assert_eq!(clock.tick(), Duration::ZERO);
+        assert!(clock.is_paused());
+    }
+
+    #[test]
+    fn deadlines_dont_drift() {
+        let runtime = tokio::runtime::Builder::new_current_thread()
+            .enable_time()
+            .start_paused(true)
+            .build()
+            .unwrap();
+
+        runtime.block_on(async {
+            // the paused time jumps straight to each deadline, doing some work in between
+            // takes time the way a frame would
+            let start = tokio::time::Instant::now();
+            for frame in 1..=100 {
+                tokio::time::advance(Duration::from_millis(u64::from(frame % 7))).await;
+                let deadline = start + STEP * frame;
+                sleep_until(deadline.into_std()).await;
+                let late = tokio::time::Instant::now() - deadline;
+                assert!(late < Duration::from_millis(1), "frame {frame} was {late:?} late");
+            }
+
+            // a deadline that passed already doesn't wait
+            let now = tokio::time::Instant::now();
+            sleep_until((now - STEP).into_std()).await;
+            assert_eq!(tokio::time::Instant::now(), now);
+        });
+    }
+}